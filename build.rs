@@ -0,0 +1,52 @@
+//! Record the versions of the crates we were built against, so they can be reported at runtime.
+
+use std::path::Path;
+
+/// The crates whose versions are recorded, and the environment variables they are recorded in.
+const CRATES: &[(&str, &str)] = &[
+    ("kcl-lib", "KCL_LIB_VERSION"),
+    ("kittycad-modeling-cmds", "KITTYCAD_MODELING_CMDS_VERSION"),
+];
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    record_versions(Path::new(&manifest_dir));
+}
+
+/// Ask cargo which versions of our dependencies it resolved, so they are right whether or not there's a lock file.
+fn record_versions(manifest_dir: &Path) {
+    let manifest = manifest_dir.join("Cargo.toml");
+    println!("cargo:rerun-if-changed={}", manifest.display());
+    // Updating the lock file changes the versions without touching the manifest.
+    println!("cargo:rerun-if-changed={}", manifest_dir.join("Cargo.lock").display());
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let metadata = std::process::Command::new(cargo)
+        .args(["metadata", "--format-version", "1", "--manifest-path"])
+        .arg(&manifest)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| serde_json::from_slice::<serde_json::Value>(&output.stdout).ok())
+        .unwrap_or_default();
+
+    // The versions of the packages this crate depends on directly, by name.
+    let root = metadata["resolve"]["root"].as_str().unwrap_or_default();
+    let dependencies: Vec<&str> = metadata["resolve"]["nodes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|node| node["id"] == root)
+        .flat_map(|node| node["dependencies"].as_array().into_iter().flatten())
+        .filter_map(|id| id.as_str())
+        .collect();
+    for (name, variable) in CRATES {
+        let version = metadata["packages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|package| package["name"] == *name && dependencies.contains(&package["id"].as_str().unwrap_or("")))
+            .and_then(|package| package["version"].as_str())
+            .unwrap_or("unknown");
+        println!("cargo:rustc-env={}={}", variable, version);
+    }
+}
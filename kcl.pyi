@@ -0,0 +1,821 @@
+# Generated by `kcl.generate_stubs()`, do not edit.
+
+from os import PathLike
+from typing import Any, Awaitable, Callable, ClassVar, Optional, Union
+
+class ImageFormat:
+    Png: ClassVar[ImageFormat]
+    Jpeg: ClassVar[ImageFormat]
+    def __repr__(self, /) -> str: ...
+    def __hash__(self, /) -> int: ...
+    def __str__(self, /) -> str: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __int__(self, /) -> int: ...
+    @staticmethod
+    def from_str(name: str) -> ImageFormat: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ImageFormat: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ImageFormat: ...
+
+class RenderMode:
+    Shaded: ClassVar[RenderMode]
+    ShadedWithEdges: ClassVar[RenderMode]
+    Wireframe: ClassVar[RenderMode]
+    def __repr__(self, /) -> str: ...
+    def __hash__(self, /) -> int: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __int__(self, /) -> int: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> RenderMode: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> RenderMode: ...
+
+class ExportFile:
+    contents: memoryview
+    format: Optional[str]
+    name: str
+    units: Optional[UnitLength]
+    def __init__(self, contents: bytes, name: str, units: Optional[Union[UnitLength, str]] = ..., format: Optional[str] = ...) -> None: ...
+    def save(self, path: Union[str, PathLike[str]]) -> None: ...
+    def scale_to(self, units: Union[UnitLength, str]) -> ExportFile: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ExportFile: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ExportFile: ...
+
+class FileExportFormat:
+    Fbx: ClassVar[FileExportFormat]
+    Glb: ClassVar[FileExportFormat]
+    Gltf: ClassVar[FileExportFormat]
+    Obj: ClassVar[FileExportFormat]
+    Ply: ClassVar[FileExportFormat]
+    Step: ClassVar[FileExportFormat]
+    Stl: ClassVar[FileExportFormat]
+    def __repr__(self, /) -> str: ...
+    def __hash__(self, /) -> int: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __int__(self, /) -> int: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> FileExportFormat: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> FileExportFormat: ...
+
+class ExportSelectionKind:
+    DefaultScene: ClassVar[ExportSelectionKind]
+    SceneByIndex: ClassVar[ExportSelectionKind]
+    SceneByName: ClassVar[ExportSelectionKind]
+    MeshByIndex: ClassVar[ExportSelectionKind]
+    MeshByName: ClassVar[ExportSelectionKind]
+    Objects: ClassVar[ExportSelectionKind]
+    def __repr__(self, /) -> str: ...
+    def __hash__(self, /) -> int: ...
+    def __str__(self, /) -> str: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __int__(self, /) -> int: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ExportSelectionKind: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ExportSelectionKind: ...
+
+class ExportSelection:
+    index: Optional[int]
+    kind: ExportSelectionKind
+    name: Optional[str]
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    @staticmethod
+    def default_scene() -> ExportSelection: ...
+    @staticmethod
+    def scene_by_index(index: int) -> ExportSelection: ...
+    @staticmethod
+    def scene_by_name(name: str) -> ExportSelection: ...
+    @staticmethod
+    def mesh_by_index(index: int) -> ExportSelection: ...
+    @staticmethod
+    def mesh_by_name(name: str) -> ExportSelection: ...
+    @staticmethod
+    def objects() -> ExportSelection: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ExportSelection: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ExportSelection: ...
+
+class FbxOptions:
+    storage: str
+    def __init__(self, storage: str = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+
+class StlOptions:
+    selection: Optional[ExportSelection]
+    storage: str
+    def __init__(self, storage: str = ..., selection: Optional[ExportSelection] = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+
+class PlyOptions:
+    selection: Optional[ExportSelection]
+    storage: str
+    def __init__(self, storage: str = ..., selection: Optional[ExportSelection] = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+
+class GltfOptions:
+    presentation: Optional[str]
+    storage: Optional[str]
+    def __init__(self, storage: Optional[str] = ..., presentation: Optional[str] = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+
+class FormatOptions:
+    insert_final_newline: bool
+    tab_size: int
+    use_tabs: bool
+    def __init__(self, tab_size: Optional[int] = ..., use_tabs: Optional[bool] = ..., insert_final_newline: Optional[bool] = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> FormatOptions: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> FormatOptions: ...
+
+class RenderSettings:
+    enable_ssao: bool
+    highlight_edges: bool
+    settle_frames: int
+    show_grid: bool
+    def __init__(self, highlight_edges: Optional[bool] = ..., enable_ssao: Optional[bool] = ..., show_grid: Optional[bool] = ..., settle_frames: Optional[int] = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    @staticmethod
+    def deterministic() -> RenderSettings: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> RenderSettings: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> RenderSettings: ...
+
+class Appearance:
+    color: str
+    metalness: float
+    roughness: float
+    def __init__(self, color: str, metalness: float = ..., roughness: float = ...) -> None: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> Appearance: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> Appearance: ...
+
+class CameraPose:
+    center: tuple[float, float, float]
+    eye: tuple[float, float, float]
+    fov_degrees: Optional[float]
+    up: tuple[float, float, float]
+    def __init__(self, eye: tuple[float, float, float], center: tuple[float, float, float], up: tuple[float, float, float] = ..., fov_degrees: Optional[float] = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> CameraPose: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> CameraPose: ...
+
+class RetrySettings:
+    connect_attempts: int
+    initial_delay: float
+    max_delay: float
+    rerun_on_disconnect: bool
+    def __init__(self, connect_attempts: Optional[int] = ..., initial_delay: Optional[float] = ..., max_delay: Optional[float] = ..., rerun_on_disconnect: Optional[bool] = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    @staticmethod
+    def disabled() -> RetrySettings: ...
+
+class Defaults:
+    client_config: Optional[ClientConfig]
+    export_format: Optional[FileExportFormat]
+    image_format: Optional[ImageFormat]
+    timeout_seconds: Optional[float]
+    units: Optional[UnitLength]
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+
+class Diagnostic:
+    end: int
+    message: str
+    severity: str
+    start: int
+    def __str__(self, /) -> str: ...
+    def snippet(self, code: str, context_lines: int = ...) -> Snippet: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> Diagnostic: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> Diagnostic: ...
+
+class UnitLength:
+    Mm: ClassVar[UnitLength]
+    Cm: ClassVar[UnitLength]
+    M: ClassVar[UnitLength]
+    In: ClassVar[UnitLength]
+    Ft: ClassVar[UnitLength]
+    Yd: ClassVar[UnitLength]
+    def __repr__(self, /) -> str: ...
+    def __hash__(self, /) -> int: ...
+    def __str__(self, /) -> str: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __int__(self, /) -> int: ...
+    @staticmethod
+    def from_str(s: str) -> UnitLength: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> UnitLength: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> UnitLength: ...
+
+class Discovered:
+    description: str
+    finding: Finding
+    overridden: bool
+    pos: tuple[int, int]
+
+class Finding:
+    code: str
+    description: str
+    experimental: bool
+    title: str
+
+class LintFinding:
+    description: str
+    finding: Finding
+    overridden: bool
+    pos: tuple[int, int]
+    severity: LintSeverity
+    def __copy__(self) -> LintFinding: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> LintFinding: ...
+
+class LintSeverity:
+    Info: ClassVar[LintSeverity]
+    Warning: ClassVar[LintSeverity]
+    Error: ClassVar[LintSeverity]
+    def __repr__(self, /) -> str: ...
+    def __hash__(self, /) -> int: ...
+    def __str__(self, /) -> str: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __int__(self, /) -> int: ...
+    @staticmethod
+    def from_str(name: str) -> LintSeverity: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> LintSeverity: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> LintSeverity: ...
+
+class AppliedFix:
+    end: int
+    replacement: str
+    rule: str
+    start: int
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> AppliedFix: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> AppliedFix: ...
+
+class ReportEntry:
+    duration_ms: float
+    label: str
+    source_range: Optional[tuple[int, int]]
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ReportEntry: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ReportEntry: ...
+
+class ExecutionReport:
+    entries: list[ReportEntry]
+    files: list[ExportFile]
+    image: Optional[bytes]
+    total_ms: float
+    def to_dict(self) -> dict[str, Any]: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ExecutionReport: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ExecutionReport: ...
+
+class Session:
+    pool_size: int
+    use_cache: bool
+    def __init__(self, units: Union[UnitLength, str], render_settings: Optional[RenderSettings] = ..., use_cache: bool = ..., config: Optional[ClientConfig] = ..., create_default_planes: bool = ..., pool_size: int = ...) -> None: ...
+    async def execute(self, code: Union[str, Program]) -> SessionOutcome: ...
+    async def snapshot(self, image_format: Optional[ImageFormat] = ..., camera: Optional[CameraPose] = ..., frame_objects: Optional[list[ObjectHandle]] = ..., background_color: Optional[Union[str, tuple[float, float, float, float]]] = ..., transparent: bool = ..., deterministic: bool = ..., allow_empty: bool = ...) -> bytes: ...
+    async def export(self, export_format: Optional[FileExportFormat] = ..., objects: Optional[list[ObjectHandle]] = ..., selection: Optional[ExportSelection] = ..., format_options: Optional[Union[FbxOptions, StlOptions, PlyOptions, GltfOptions]] = ..., allow_empty: bool = ...) -> list[ExportFile]: ...
+    async def reset(self) -> None: ...
+    async def close(self) -> None: ...
+    def bust_cache(self) -> None: ...
+
+class SessionOutcome:
+    cache_hit: bool
+    default_planes: dict[str, str]
+    execution_ms: float
+    objects: list[ObjectHandle]
+    trace: list[TraceEntry]
+    warnings: list[Diagnostic]
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> SessionOutcome: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> SessionOutcome: ...
+
+class TraceEntry:
+    name: str
+    source_range: tuple[int, int]
+    value: str
+    def __str__(self, /) -> str: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> TraceEntry: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> TraceEntry: ...
+
+class FileReport:
+    error: Optional[Diagnostic]
+    findings: list[LintFinding]
+    formatted: Optional[bool]
+    ok: bool
+    path: str
+    def __copy__(self) -> FileReport: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> FileReport: ...
+
+class PathsReport:
+    files: list[FileReport]
+    ok: bool
+    def __len__(self, /) -> int: ...
+    def __getitem__(self, key: str, /) -> FileReport: ...
+    def failed(self) -> list[FileReport]: ...
+    def __copy__(self) -> PathsReport: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> PathsReport: ...
+
+class CheckReport:
+    format_diff: str
+    lint_findings: list[LintFinding]
+    ok: bool
+    parse_errors: list[Diagnostic]
+    def __copy__(self) -> CheckReport: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> CheckReport: ...
+
+class FormatDirResult:
+    changed: list[str]
+    errors: dict[str, Diagnostic]
+    ok: bool
+    def __copy__(self) -> FormatDirResult: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> FormatDirResult: ...
+
+class Program:
+    @staticmethod
+    def parse(code: str) -> Program: ...
+    def source(self) -> str: ...
+    def recast(self, options: Optional[FormatOptions] = ...) -> str: ...
+    def digest(self) -> str: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> Program: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> Program: ...
+
+class Variable:
+    name: str
+    source_range: tuple[int, int]
+    type_name: str
+    value: Any
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> Variable: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> Variable: ...
+
+class Variables:
+    missing: list[str]
+    variables: list[Variable]
+    def get(self, name: str) -> Optional[Variable]: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> Variables: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> Variables: ...
+
+class Tag:
+    area: Optional[float]
+    center: Optional[tuple[float, float, float]]
+    entity_id: str
+    index: int
+    kind: str
+    length: Optional[float]
+    name: str
+    normal: Optional[tuple[float, float, float]]
+    source_range: tuple[int, int]
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> Tag: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> Tag: ...
+
+class SceneObject:
+    children: list[SceneObject]
+    id: Optional[str]
+    kind: str
+    name: str
+    source_range: Optional[tuple[int, int]]
+    def __str__(self, /) -> str: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> SceneObject: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> SceneObject: ...
+
+class Scene:
+    objects: list[SceneObject]
+    def __str__(self, /) -> str: ...
+    def find(self, name: str) -> Optional[SceneObject]: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> Scene: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> Scene: ...
+
+class ObjectHandle:
+    end: int
+    id: str
+    kind: str
+    name: Optional[str]
+    start: int
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ObjectHandle: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ObjectHandle: ...
+
+class StdlibFunction:
+    deprecated: bool
+    description: str
+    name: str
+    parameters: list[StdlibParameter]
+    return_type: Optional[str]
+    summary: str
+    tags: list[str]
+    def to_dict(self) -> dict[str, Any]: ...
+
+class StdlibParameter:
+    name: str
+    required: bool
+    type: str
+
+class CompletionItem:
+    detail: Optional[str]
+    end: int
+    kind: str
+    label: str
+    start: int
+
+class Hover:
+    contents: str
+    end: int
+    start: int
+
+class SignatureHelp:
+    active_parameter: int
+    label: str
+    parameters: list[str]
+
+class Snippet:
+    end_column: int
+    end_line: int
+    first_line: int
+    lines: list[str]
+    start_column: int
+    start_line: int
+    underline: str
+    def __str__(self, /) -> str: ...
+
+class Token:
+    end: int
+    is_error: bool
+    kind: str
+    start: int
+    value: str
+
+class BatchError:
+    index: int
+    kind: str
+    message: str
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> BatchError: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> BatchError: ...
+
+class BatchIterator:
+    def __aiter__(self, /) -> BatchIterator: ...
+    def __anext__(self, /) -> Awaitable[tuple[int, Union[bytes, BatchError]]]: ...
+    async def next(self) -> tuple[int, Union[bytes, BatchError]]: ...
+
+class ClientConfig:
+    api_token: Optional[str]
+    headers: Optional[dict[str, str]]
+    host: Optional[str]
+    token_provider: Optional[Callable[[], str]]
+    user_agent: Optional[str]
+    def __init__(self, api_token: Optional[str] = ..., host: Optional[str] = ..., token_provider: Optional[Callable[[], str]] = ..., user_agent: Optional[str] = ..., headers: Optional[dict[str, str]] = ...) -> None: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+
+class ConnectionStatus:
+    latency_ms: float
+    ok: bool
+    org: Optional[str]
+    user: Optional[str]
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ConnectionStatus: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ConnectionStatus: ...
+
+class ProgressPhase:
+    Parsing: ClassVar[ProgressPhase]
+    Connecting: ClassVar[ProgressPhase]
+    Executing: ClassVar[ProgressPhase]
+    Snapshotting: ClassVar[ProgressPhase]
+    Exporting: ClassVar[ProgressPhase]
+    def __repr__(self, /) -> str: ...
+    def __hash__(self, /) -> int: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __int__(self, /) -> int: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ProgressPhase: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ProgressPhase: ...
+
+class ProgressEvent:
+    message: Optional[str]
+    operation_index: Optional[int]
+    operation_total: Optional[int]
+    phase: ProgressPhase
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> ProgressEvent: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> ProgressEvent: ...
+
+class SnapshotResult:
+    camera: CameraPose
+    data: bytes
+    execution_ms: float
+    format: ImageFormat
+    height: int
+    stats: RunStats
+    width: int
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> SnapshotResult: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> SnapshotResult: ...
+
+class SnapshotOptions:
+    allow_empty: bool
+    appearance: Optional[Appearance]
+    camera: Optional[CameraPose]
+    deterministic: bool
+    entity_names: Optional[list[str]]
+    files: Optional[dict[str, str]]
+    frame_objects: Optional[list[str]]
+    image_format: Optional[ImageFormat]
+    render_mode: Optional[RenderMode]
+    render_settings: Optional[RenderSettings]
+    transparent: bool
+    units: Optional[UnitLength]
+    warnings_as_errors: bool
+    def __init__(self, units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., render_settings: Optional[RenderSettings] = ..., background_color: Optional[Union[str, tuple[float, float, float, float]]] = ..., transparent: bool = ..., appearance: Optional[Appearance] = ..., warnings_as_errors: bool = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., entity_names: Optional[list[str]] = ..., render_mode: Optional[RenderMode] = ..., camera: Optional[CameraPose] = ..., deterministic: bool = ..., attachments: Optional[dict[str, bytes]] = ..., frame_objects: Optional[list[str]] = ..., allow_empty: bool = ...) -> None: ...
+
+class SketchExportFormat:
+    Svg: ClassVar[SketchExportFormat]
+    Dxf: ClassVar[SketchExportFormat]
+    def __repr__(self, /) -> str: ...
+    def __hash__(self, /) -> int: ...
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __int__(self, /) -> int: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> SketchExportFormat: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> SketchExportFormat: ...
+
+class MeshStats:
+    byte_size: int
+    name: str
+    triangle_count: int
+    vertex_count: int
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> MeshStats: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> MeshStats: ...
+
+class GeometryCheck:
+    details: list[str]
+    name: str
+    reason: Optional[str]
+    status: str
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> GeometryCheck: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> GeometryCheck: ...
+
+class SolidReport:
+    checks: list[GeometryCheck]
+    id: str
+    name: str
+    passed: bool
+    source_range: Optional[tuple[int, int]]
+    def check(self, name: str) -> Optional[GeometryCheck]: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> SolidReport: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> SolidReport: ...
+
+class GeometryReport:
+    passed: bool
+    solids: list[SolidReport]
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> GeometryReport: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> GeometryReport: ...
+
+class RunStats:
+    command_count: int
+    connect_ms: float
+    execution_ms: float
+    parse_ms: float
+    def __lt__(self, value: object, /) -> bool: ...
+    def __le__(self, value: object, /) -> bool: ...
+    def __eq__(self, value: object, /) -> bool: ...
+    def __ne__(self, value: object, /) -> bool: ...
+    def __gt__(self, value: object, /) -> bool: ...
+    def __ge__(self, value: object, /) -> bool: ...
+    def __reduce__(self) -> tuple[Any, ...]: ...
+    def __copy__(self) -> RunStats: ...
+    def __deepcopy__(self, _memo: dict[int, Any]) -> RunStats: ...
+
+__version__: str
+
+class EngineConnectionError(Exception): ...
+
+class HostNotFoundError(EngineConnectionError): ...
+
+class KclAuthError(Exception): ...
+
+class KclEngineError(Exception): ...
+
+class UnexpectedResponseError(KclEngineError): ...
+
+class KclWarningError(Exception): ...
+
+class KclEmptySceneError(Exception): ...
+
+class InvalidAstError(ValueError): ...
+
+class KclLintError(Exception): ...
+
+class InvalidExportError(Exception): ...
+
+async def execute(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., warnings_as_errors: bool = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., attachments: Optional[dict[str, bytes]] = ..., return_stats: bool = ...) -> Union[list[Diagnostic], tuple[list[Diagnostic], RunStats]]: ...
+
+async def execute_and_get_variables(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., names: Optional[list[str]] = ...) -> Variables: ...
+
+async def execute_and_get_tags(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ...) -> dict[str, Tag]: ...
+
+async def validate(code: str, units: Optional[Union[UnitLength, str]] = ...) -> list[Diagnostic]: ...
+
+async def execute_and_snapshot(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., render_settings: Optional[RenderSettings] = ..., background_color: Optional[Union[str, tuple[float, float, float, float]]] = ..., transparent: bool = ..., appearance: Optional[Appearance] = ..., warnings_as_errors: bool = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., entity_names: Optional[list[str]] = ..., render_mode: Optional[RenderMode] = ..., camera: Optional[CameraPose] = ..., deterministic: bool = ..., attachments: Optional[dict[str, bytes]] = ..., return_stats: bool = ..., frame_objects: Optional[list[str]] = ..., allow_empty: bool = ...) -> Union[bytes, tuple[bytes, RunStats]]: ...
+
+async def execute_and_snapshot_ex(code: Union[str, Program], options: Optional[SnapshotOptions] = ...) -> SnapshotResult: ...
+
+async def execute_and_export(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., export_format: Optional[FileExportFormat] = ..., warnings_as_errors: bool = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., selection: Optional[ExportSelection] = ..., format_options: Optional[Union[FbxOptions, StlOptions, PlyOptions, GltfOptions]] = ..., attachments: Optional[dict[str, bytes]] = ..., return_stats: bool = ..., base_name: Optional[str] = ..., allow_empty: bool = ..., validate: bool = ...) -> Union[list[ExportFile], tuple[list[ExportFile], RunStats]]: ...
+
+async def execute_and_export_sketch(code: Union[str, Program], units: Optional[Union[UnitLength, str]], export_format: SketchExportFormat, warnings_as_errors: bool = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., attachments: Optional[dict[str, bytes]] = ...) -> list[ExportFile]: ...
+
+async def execute_and_get_mesh_stats(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., export_format: FileExportFormat = ..., warnings_as_errors: bool = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., attachments: Optional[dict[str, bytes]] = ...) -> list[MeshStats]: ...
+
+def validate_export(file: ExportFile) -> list[str]: ...
+
+async def execute_and_check_geometry(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ...) -> GeometryReport: ...
+
+async def execute_and_snapshot_and_export(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., export_format: Optional[FileExportFormat] = ..., render_settings: Optional[RenderSettings] = ..., warnings_as_errors: bool = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., attachments: Optional[dict[str, bytes]] = ..., allow_empty: bool = ...) -> tuple[bytes, list[ExportFile]]: ...
+
+async def execute_and_snapshot_turntable(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., frames: int = ..., render_settings: Optional[RenderSettings] = ..., background_color: Optional[Union[str, tuple[float, float, float, float]]] = ..., transparent: bool = ..., warnings_as_errors: bool = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., attachments: Optional[dict[str, bytes]] = ...) -> list[bytes]: ...
+
+async def execute_and_snapshot_steps(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., render_settings: Optional[RenderSettings] = ..., background_color: Optional[Union[str, tuple[float, float, float, float]]] = ..., transparent: bool = ..., warnings_as_errors: bool = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ...) -> list[tuple[tuple[int, int], bytes]]: ...
+
+async def execute_and_get_artifact_graph(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., files: Optional[dict[str, str]] = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ..., attachments: Optional[dict[str, bytes]] = ...) -> dict[str, Any]: ...
+
+async def execute_and_get_scene(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ...) -> Scene: ...
+
+async def execute_with_report(code: Union[str, Program], units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., export_format: Optional[FileExportFormat] = ...) -> ExecutionReport: ...
+
+async def execute_and_snapshot_batch(items: list[Union[str, Program, tuple[str]]], units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., max_concurrency: int = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ...) -> list[Union[bytes, BatchError]]: ...
+
+def execute_and_snapshot_iter(items: list[Union[str, Program, tuple[str]]], units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., max_concurrency: int = ...) -> BatchIterator: ...
+
+async def execute_project(path: Union[str, PathLike[str]], entry: Optional[str] = ..., warnings_as_errors: bool = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ...) -> list[Diagnostic]: ...
+
+async def execute_project_and_snapshot(path: Union[str, PathLike[str]], image_format: Optional[ImageFormat] = ..., entry: Optional[str] = ..., render_settings: Optional[RenderSettings] = ..., background_color: Optional[Union[str, tuple[float, float, float, float]]] = ..., transparent: bool = ..., warnings_as_errors: bool = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ...) -> bytes: ...
+
+async def execute_project_and_export(path: Union[str, PathLike[str]], export_format: Optional[FileExportFormat] = ..., entry: Optional[str] = ..., warnings_as_errors: bool = ..., on_progress: Optional[Callable[[ProgressEvent], Any]] = ...) -> list[ExportFile]: ...
+
+def format(code: Union[str, Program], options: Optional[FormatOptions] = ...) -> str: ...
+
+def format_dir(path: Union[str, PathLike[str]], check: bool = ..., options: Optional[FormatOptions] = ...) -> FormatDirResult: ...
+
+def format_check_paths(paths: list[str], options: Optional[FormatOptions] = ...) -> PathsReport: ...
+
+def lint_paths(paths: list[str], min_severity: Optional[Union[LintSeverity, str]] = ...) -> PathsReport: ...
+
+def check(code: str, rules: Optional[list[str]] = ..., format_options: Optional[FormatOptions] = ...) -> CheckReport: ...
+
+def lint_sarif(paths_or_code: Union[list[str], str, Program], rules: Optional[list[str]] = ...) -> str: ...
+
+def lint(code: Union[str, Program], min_severity: Optional[Union[LintSeverity, str]] = ...) -> list[LintFinding]: ...
+
+def lint_and_fix(code: str, rules: Optional[list[str]] = ...) -> tuple[str, list[LintFinding], list[AppliedFix]]: ...
+
+def lint_strict(code: Union[str, Program], fail_on: Union[LintSeverity, str] = ...) -> list[LintFinding]: ...
+
+def parse(code: str) -> dict[str, Any]: ...
+
+def parse_to_json(code: str) -> str: ...
+
+def recast(ast: dict[str, Any], options: Optional[FormatOptions] = ...) -> str: ...
+
+def program_digest(code: Union[str, Program]) -> str: ...
+
+def set_retry_settings(settings: RetrySettings) -> None: ...
+
+def get_retry_settings() -> RetrySettings: ...
+
+def set_defaults(units: Optional[Union[UnitLength, str]] = ..., image_format: Optional[ImageFormat] = ..., export_format: Optional[FileExportFormat] = ..., timeout_seconds: Optional[float] = ..., client_config: Optional[ClientConfig] = ...) -> None: ...
+
+def get_defaults() -> Defaults: ...
+
+def reset_defaults() -> None: ...
+
+def enable_logging(level: str = ...) -> None: ...
+
+def disable_logging() -> None: ...
+
+def version_info() -> dict[str, str]: ...
+
+async def engine_version() -> dict[str, str]: ...
+
+async def check_connection(config: Optional[ClientConfig] = ..., timeout_seconds: Optional[float] = ...) -> ConnectionStatus: ...
+
+def stdlib_docs() -> list[StdlibFunction]: ...
+
+def stdlib_function(name: str) -> StdlibFunction: ...
+
+def completions(code: str, line: int, column: int) -> list[CompletionItem]: ...
+
+def hover(code: str, line: int, column: int) -> Optional[Hover]: ...
+
+def signature_help(code: str, line: int, column: int) -> Optional[SignatureHelp]: ...
+
+def semantic_tokens(code: str) -> list[tuple[int, int, str, list[str]]]: ...
+
+def tokenize(code: str) -> list[Token]: ...
+
+def snippet(code: str, source_range: tuple[int, int], context_lines: int = ...) -> Snippet: ...
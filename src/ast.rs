@@ -0,0 +1,98 @@
+//! Turning an AST from `parse`, perhaps edited, back into kcl code.
+
+use pyo3::{prelude::PyAnyMethods, pyfunction, types::PyDict, Bound, PyErr, PyResult, Python};
+use serde_json::Value;
+
+use crate::FormatOptions;
+
+pyo3::create_exception!(
+    kcl,
+    InvalidAstError,
+    pyo3::exceptions::PyValueError,
+    "An AST could not be read back as a kcl program. `path` is where in the AST the problem is, e.g. \
+     `body[0].declaration.init`, and `reason` is what is wrong there."
+);
+
+/// Read an AST as a program, or the reason it can't be.
+fn read_program(ast: Value) -> Result<kcl_lib::Program, String> {
+    serde_json::from_value(ast).map_err(|err| err.to_string())
+}
+
+/// Whether a node on its own can't be read as a program for the same reason as the whole AST, when put in the
+/// program `x` in place of either its only statement or the expression in it.
+fn fails_for(node: &Value, template: &Value, reason: &str) -> bool {
+    ["/body/0", "/body/0/expression"].iter().any(|pointer| {
+        let mut program = template.clone();
+        if let Some(slot) = program.pointer_mut(pointer) {
+            *slot = node.clone();
+        }
+        read_program(program).err().as_deref() == Some(reason)
+    })
+}
+
+/// The path of the innermost node within `node` that can't be read for `reason`, if one can be found.
+fn find_invalid(node: &Value, path: &str, template: &Value, reason: &str) -> Option<String> {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    let children: Vec<(String, &Value)> = match node {
+        Value::Object(fields) => fields.iter().map(|(key, child)| (join(key), child)).collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, child)| (format!("{}[{}]", path, index), child))
+            .collect(),
+        _ => return None,
+    };
+
+    children
+        .into_iter()
+        .find_map(|(path, child)| find_invalid(child, &path, template, reason))
+        .or_else(|| (node.get("type").is_some() && fails_for(node, template, reason)).then(|| path.to_string()))
+}
+
+/// The error for an AST that can't be read as a program, naming the node that is wrong.
+fn invalid_ast(ast: &Value, reason: String) -> PyErr {
+    let template = kcl_lib::Program::parse_no_errs("x")
+        .ok()
+        .and_then(|program| serde_json::to_value(&program).ok())
+        .unwrap_or_default();
+    let path = find_invalid(ast, "", &template, &reason).unwrap_or_default();
+    let err = InvalidAstError::new_err(if path.is_empty() {
+        format!("Invalid AST: {}", reason)
+    } else {
+        format!("Invalid AST at {}: {}", path, reason)
+    });
+
+    Python::with_gil(|py| {
+        let value = err.value_bound(py);
+        match value
+            .setattr("path", path)
+            .and_then(|()| value.setattr("reason", reason))
+        {
+            Ok(()) => err,
+            Err(set_err) => set_err,
+        }
+    })
+}
+
+/// Recast an AST from `parse` back into kcl code, e.g. after editing it.
+///
+/// Raises `InvalidAstError` naming where the problem is if the AST isn't a valid program.
+#[pyfunction]
+#[pyo3(name = "recast", signature = (ast, options = None))]
+pub(crate) fn recast_ast(py: Python<'_>, ast: &Bound<'_, PyDict>, options: Option<FormatOptions>) -> PyResult<String> {
+    let json: String = py
+        .import_bound("json")?
+        .call_method1("dumps", (ast.as_any(),))?
+        .extract()?;
+    let ast: Value = serde_json::from_str(&json).map_err(|err| InvalidAstError::new_err(err.to_string()))?;
+    let program = read_program(ast.clone()).map_err(|reason| invalid_ast(&ast, reason))?;
+    let options: kcl_lib::FormatOptions = options.unwrap_or_default().into();
+
+    Ok(program.ast.recast(&options, 0))
+}
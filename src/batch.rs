@@ -0,0 +1,327 @@
+//! Executing many kcl programs at once, sharing connections to the engine between them.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use kcl_lib::ExecutorContext;
+use pyo3::{
+    prelude::{PyAnyMethods, PyTypeMethods},
+    pyclass, pyfunction, pymethods,
+    types::PyAny,
+    Bound, FromPyObject, IntoPy, PyErr, PyObject, PyRef, PyResult, Python,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{
+    connection, defaults, get_background_color, join_error, new_context, pickle, program::Code, run_code_in, snapshot,
+    spawn, tokio, watch_disconnects, ImageFormat, RetrySettings, RunOptions, UnitLength, Units,
+};
+
+/// A program in a batch: either the kcl code or a `Program`, or a tuple holding just the kcl code.
+#[derive(FromPyObject)]
+pub(crate) enum BatchItem {
+    Code(Code),
+    Tuple(Vec<String>),
+}
+
+impl BatchItem {
+    fn into_code(self, index: usize) -> PyResult<Code> {
+        match self {
+            BatchItem::Code(code) => Ok(code),
+            BatchItem::Tuple(values) => match <[String; 1]>::try_from(values) {
+                Ok([code]) => Ok(Code::Text(code)),
+                Err(values) if values.is_empty() => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Batch item {} is empty, it needs the kcl code",
+                    index
+                ))),
+                Err(values) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Batch item {} has {} elements, but only the kcl code is used, give just that",
+                    index,
+                    values.len()
+                ))),
+            },
+        }
+    }
+}
+
+/// Why a program in a batch failed.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct BatchError {
+    /// The position of the program in the batch.
+    #[pyo3(get)]
+    pub index: usize,
+    /// The name of the exception that running the program on its own would have raised, e.g. `KclError`.
+    #[pyo3(get)]
+    pub kind: String,
+    /// The error message.
+    #[pyo3(get)]
+    pub message: String,
+}
+
+#[pymethods]
+impl BatchError {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+impl BatchError {
+    fn new(index: usize, err: PyErr) -> Self {
+        Python::with_gil(|py| BatchError {
+            index,
+            kind: err
+                .get_type_bound(py)
+                .name()
+                .map(|name| name.to_string())
+                .unwrap_or_default(),
+            message: err.value_bound(py).to_string(),
+        })
+    }
+}
+
+/// The outcome of one program in a batch: the image, or why it failed.
+pub(crate) enum BatchResult {
+    Image(Vec<u8>),
+    Error(BatchError),
+}
+
+impl IntoPy<PyObject> for BatchResult {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            BatchResult::Image(image) => image.into_py(py),
+            BatchResult::Error(err) => err.into_py(py),
+        }
+    }
+}
+
+/// Tell the progress callback, if there is one, what happened to a program.
+fn report(on_progress: &Option<PyObject>, index: usize, status: &str) -> PyResult<()> {
+    if let Some(on_progress) = on_progress {
+        Python::with_gil(|py| on_progress.call1(py, (index, status)))?;
+    }
+    Ok(())
+}
+
+/// Execute and snapshot one program using the worker's connection, which is made or remade as needed.
+async fn snapshot_item(
+    connection: &mut Option<ExecutorContext>,
+    code: &Code,
+    options: &RunOptions,
+    image_format: ImageFormat,
+    background_color: Option<kittycad_modeling_cmds::shared::Color>,
+) -> PyResult<Vec<u8>> {
+    let mut rerun = RetrySettings::current().rerun_on_disconnect;
+    loop {
+        let (result, disconnected) = watch_disconnects(async {
+            if connection.is_none() {
+                let units = options.units.unwrap_or(UnitLength::Mm);
+                *connection = Some(new_context(units, &options.render_settings, None).await?);
+            }
+            let ctx = connection.as_ref().expect("the connection was just made");
+            let executed = run_code_in(Some(ctx), code, options).await?;
+            snapshot(
+                &executed.ctx,
+                image_format,
+                background_color,
+                options.render_settings.settle(true),
+            )
+            .await
+        })
+        .await;
+        match result {
+            Err(err) if disconnected => {
+                // The connection is no good any more, so the next program gets a new one.
+                *connection = None;
+                if !rerun {
+                    return Err(err);
+                }
+                rerun = false;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The kcl code of each item in a batch.
+fn batch_codes(items: Vec<BatchItem>) -> PyResult<Vec<Code>> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| item.into_code(index))
+        .collect()
+}
+
+/// Execute and snapshot many kcl programs, with at most `max_concurrency` running at once.
+///
+/// Each concurrent worker keeps its connection to the engine for all the programs it runs. Results are in the same
+/// order as the items: the image for each program that succeeded, or a `BatchError` for each that failed, so one bad
+/// program doesn't fail the rest. If given, `on_progress` is called with the index of a program and its status
+/// (`started`, `succeeded` or `failed`); an exception raised by it stops the whole batch.
+#[pyfunction]
+#[pyo3(signature = (items, units = None, image_format = None, max_concurrency = 4, on_progress = None))]
+pub(crate) async fn execute_and_snapshot_batch(
+    items: Vec<BatchItem>,
+    units: Option<Units>,
+    image_format: Option<ImageFormat>,
+    max_concurrency: usize,
+    on_progress: Option<PyObject>,
+) -> PyResult<Vec<BatchResult>> {
+    connection::require_token(None)?;
+    let image_format = defaults::image_format(image_format)?;
+    if max_concurrency == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "max_concurrency must be at least 1",
+        ));
+    }
+    let codes = batch_codes(items)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        ..Default::default()
+    };
+    let background_color = get_background_color(image_format, None, false)?;
+
+    spawn(async move {
+        let count = codes.len();
+        let (codes, options, on_progress) = (Arc::new(codes), Arc::new(options), Arc::new(on_progress));
+        let next = Arc::new(AtomicUsize::new(0));
+        let mut workers = tokio::task::JoinSet::new();
+        for _ in 0..max_concurrency.min(count) {
+            let (codes, options, on_progress, next) =
+                (codes.clone(), options.clone(), on_progress.clone(), next.clone());
+            workers.spawn(async move {
+                let mut connection = None;
+                let mut results = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(code) = codes.get(index) else {
+                        return Ok(results);
+                    };
+                    report(&on_progress, index, "started")?;
+                    let result = snapshot_item(&mut connection, code, &options, image_format, background_color).await;
+                    report(&on_progress, index, if result.is_ok() { "succeeded" } else { "failed" })?;
+                    results.push((index, result));
+                }
+            });
+        }
+
+        let mut results: Vec<Option<BatchResult>> = (0..count).map(|_| None).collect();
+        while let Some(worker) = workers.join_next().await {
+            let worker: PyResult<Vec<(usize, PyResult<Vec<u8>>)>> = worker.map_err(join_error)?;
+            for (index, result) in worker? {
+                results[index] = Some(match result {
+                    Ok(image) => BatchResult::Image(image),
+                    Err(err) => BatchResult::Error(BatchError::new(index, err)),
+                });
+            }
+        }
+
+        Ok(results
+            .into_iter()
+            .map(|result| result.expect("every item in the batch was run"))
+            .collect())
+    })
+    .await
+}
+
+/// The results of programs in a batch, by their index, as they finish.
+type BatchResults = mpsc::UnboundedReceiver<(usize, PyResult<Vec<u8>>)>;
+
+/// The results of a batch, as an async iterator of `(index, result)` in the order the programs finish.
+///
+/// The programs run in the background whether or not the iterator is being awaited. Once it is dropped, any that are
+/// still running are stopped and their connections to the engine closed.
+#[pyclass(module = "kcl")]
+pub struct BatchIterator {
+    results: Arc<tokio::sync::Mutex<BatchResults>>,
+    /// The workers running the programs, which are aborted when this is dropped.
+    _workers: tokio::task::JoinSet<()>,
+}
+
+#[pymethods]
+impl BatchIterator {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        slf.call_method0("next")
+    }
+
+    /// Wait for the next program to finish, raising `StopAsyncIteration` once they all have.
+    async fn next(&self) -> PyResult<(usize, BatchResult)> {
+        let results = self.results.clone();
+        let next = spawn(async move { Ok(results.lock().await.recv().await) }).await?;
+        match next {
+            Some((index, Ok(image))) => Ok((index, BatchResult::Image(image))),
+            Some((index, Err(err))) => Ok((index, BatchResult::Error(BatchError::new(index, err)))),
+            None => Err(pyo3::exceptions::PyStopAsyncIteration::new_err(())),
+        }
+    }
+}
+
+/// Execute and snapshot many kcl programs like `execute_and_snapshot_batch`, but return an async iterator that gives
+/// each result as soon as it's ready, e.g. `async for index, result in execute_and_snapshot_iter(...)`.
+///
+/// Results are `(index, image)` for programs that succeeded and `(index, BatchError)` for those that failed, in the
+/// order they finish. Breaking out of the loop early and dropping the iterator stops the programs still running.
+#[pyfunction]
+#[pyo3(signature = (items, units = None, image_format = None, max_concurrency = 4))]
+pub(crate) fn execute_and_snapshot_iter(
+    items: Vec<BatchItem>,
+    units: Option<Units>,
+    image_format: Option<ImageFormat>,
+    max_concurrency: usize,
+) -> PyResult<BatchIterator> {
+    connection::require_token(None)?;
+    let image_format = defaults::image_format(image_format)?;
+    if max_concurrency == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "max_concurrency must be at least 1",
+        ));
+    }
+    let codes = Arc::new(batch_codes(items)?);
+    let options = Arc::new(RunOptions {
+        units: defaults::units(units)?,
+        ..Default::default()
+    });
+    let background_color = get_background_color(image_format, None, false)?;
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let next = Arc::new(AtomicUsize::new(0));
+    let mut workers = tokio::task::JoinSet::new();
+    let _runtime = tokio().enter();
+    for _ in 0..max_concurrency.min(codes.len()) {
+        let (codes, options, next, sender) = (codes.clone(), options.clone(), next.clone(), sender.clone());
+        workers.spawn(async move {
+            let mut connection = None;
+            loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(code) = codes.get(index) else {
+                    return;
+                };
+                let result = snapshot_item(&mut connection, code, &options, image_format, background_color).await;
+                if sender.send((index, result)).is_err() {
+                    // Nobody is listening for results any more.
+                    return;
+                }
+            }
+        });
+    }
+
+    Ok(BatchIterator {
+        results: Arc::new(tokio::sync::Mutex::new(receiver)),
+        _workers: workers,
+    })
+}
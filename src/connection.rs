@@ -0,0 +1,406 @@
+//! How to reach the API, and checking that it can be reached before doing any real work.
+
+use std::collections::BTreeMap;
+
+use kcl_lib::ExecutorContext;
+use pyo3::{
+    prelude::PyAnyMethods, pyclass, pyfunction, pymethods, types::PyAny, Bound, FromPyObject, IntoPy, PyErr, PyObject,
+    PyResult, Python,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    defaults::{self, Defaults},
+    get_camera, pickle, spawn, EngineConnectionError, HostNotFoundError, KclAuthError,
+};
+
+/// How to give the API a token that it will accept.
+const AUTH_HINT: &str =
+    "Set KITTYCAD_API_TOKEN or ZOO_API_TOKEN, or pass a ClientConfig with an api_token or token_provider.";
+
+/// A `KclAuthError` with what went wrong, e.g. the server's message, followed by how to fix it.
+///
+/// `status` is the HTTP status the token was rejected with, or None if there was no token to send.
+pub(crate) fn auth_error(message: &str, status: Option<u16>) -> PyErr {
+    let err = KclAuthError::new_err(format!("{} {}", message, AUTH_HINT));
+
+    Python::with_gil(|py| {
+        let value = err.value_bound(py);
+        match value
+            .setattr("status", status)
+            .and_then(|()| value.setattr("hint", AUTH_HINT))
+        {
+            Ok(()) => err,
+            Err(attr_err) => attr_err,
+        }
+    })
+}
+
+/// The HTTP status the API answered with, if it was rejecting the token.
+fn auth_status(err: &kittycad::types::error::Error) -> Option<u16> {
+    err.status()
+        .map(|status| status.as_u16())
+        .filter(|status| matches!(status, 401 | 403))
+}
+
+/// The HTTP status the API rejected the token with, if that's why connecting to the engine failed, so retrying won't
+/// help. The websocket handshake is an HTTP request, which kcl-lib passes the API's error for on unchanged.
+pub(crate) fn engine_auth_status(err: &anyhow::Error) -> Option<u16> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<kittycad::types::error::Error>())
+        .and_then(auth_status)
+}
+
+/// Check that there is a token to connect with, so a missing one is reported before any work starts rather than
+/// when connecting to the engine.
+///
+/// Without a config the default one is used, or else the environment. A token provider isn't called until
+/// connecting.
+pub(crate) fn require_token(config: Option<&ClientConfig>) -> PyResult<()> {
+    match defaults::client_config(config) {
+        Some(config) if config.token_provider.is_some() => Ok(()),
+        config => config.unwrap_or_default().token().map(|_| ()),
+    }
+}
+
+/// A python callable that returns a fresh API token, e.g. from a sidecar that mints short-lived ones.
+pub struct TokenProvider(PyObject);
+
+impl TokenProvider {
+    /// Call the provider for a token, with the GIL.
+    fn token(&self) -> PyResult<String> {
+        Python::with_gil(|py| {
+            self.0.call0(py).and_then(|token| token.extract(py)).map_err(|err| {
+                let auth_err = auth_error(&format!("The token provider failed to give a token: {}.", err), None);
+                auth_err.set_cause(py, Some(err));
+                auth_err
+            })
+        })
+    }
+}
+
+impl Clone for TokenProvider {
+    fn clone(&self) -> Self {
+        Python::with_gil(|py| TokenProvider(self.0.clone_ref(py)))
+    }
+}
+
+impl PartialEq for TokenProvider {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.is(&other.0)
+    }
+}
+
+impl std::fmt::Debug for TokenProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenProvider")
+    }
+}
+
+impl<'py> FromPyObject<'py> for TokenProvider {
+    fn extract_bound(provider: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if !provider.is_callable() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "token_provider must be a callable that returns a token",
+            ));
+        }
+        Ok(TokenProvider(provider.clone().unbind()))
+    }
+}
+
+impl IntoPy<PyObject> for TokenProvider {
+    fn into_py(self, _py: Python<'_>) -> PyObject {
+        self.0
+    }
+}
+
+/// How to connect to the API, for anything that shouldn't come from the environment.
+///
+/// The token is never serialized or printed with `{:?}`, so a config can be logged without leaking it.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Default)]
+#[pyclass(eq)]
+pub struct ClientConfig {
+    /// The API token, used instead of `KITTYCAD_API_TOKEN` or `ZOO_API_TOKEN`.
+    #[pyo3(get, set)]
+    #[serde(skip_serializing)]
+    pub api_token: Option<String>,
+    /// The address of the API, used instead of `KITTYCAD_HOST`, `ZOO_HOST` or the default.
+    #[pyo3(get, set)]
+    pub host: Option<String>,
+    /// Called with no arguments for a fresh token each time a connection is made, and once more if the token it gave
+    /// is rejected. Used instead of `api_token`.
+    #[pyo3(get, set)]
+    #[serde(skip)]
+    pub token_provider: Option<TokenProvider>,
+    /// The `User-Agent` to send instead of the client's own.
+    #[pyo3(get)]
+    pub user_agent: Option<String>,
+    /// Extra headers to send with every request, including the one that opens the connection to the engine, e.g.
+    /// for a proxy that needs them. `Authorization` and `Host` can't be set, as they carry the token and address.
+    #[pyo3(get)]
+    pub headers: Option<BTreeMap<String, String>>,
+}
+
+impl std::fmt::Debug for ClientConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientConfig")
+            .field("api_token", &self.api_token.as_ref().map(|_| "<redacted>"))
+            .field("host", &self.host)
+            .field("token_provider", &self.token_provider)
+            .field("user_agent", &self.user_agent)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+/// The headers the client sets itself, from the token and host, which a config can't replace.
+const RESERVED_HEADERS: [&str; 2] = ["authorization", "host"];
+
+/// Check a `User-Agent` can be sent as a header.
+fn check_user_agent(user_agent: &str) -> PyResult<HeaderValue> {
+    HeaderValue::from_str(user_agent).map_err(|err| {
+        pyo3::exceptions::PyValueError::new_err(format!("{:?} isn't a valid user agent: {}", user_agent, err))
+    })
+}
+
+/// Check headers can be sent and don't replace any the client sets itself.
+fn check_headers(headers: &BTreeMap<String, String>) -> PyResult<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        if RESERVED_HEADERS.contains(&name.to_lowercase().as_str()) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "The {} header can't be set in a ClientConfig, as it is set from the token and host",
+                name
+            )));
+        }
+        let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|err| {
+            pyo3::exceptions::PyValueError::new_err(format!("{:?} isn't a valid header name: {}", name, err))
+        })?;
+        let header_value = HeaderValue::from_str(value).map_err(|err| {
+            pyo3::exceptions::PyValueError::new_err(format!("The value of the {} header isn't valid: {}", name, err))
+        })?;
+        map.insert(header_name, header_value);
+    }
+
+    Ok(map)
+}
+
+impl ClientConfig {
+    /// The API token to use, from the provider, the config or else the environment.
+    fn token(&self) -> PyResult<String> {
+        if let Some(provider) = &self.token_provider {
+            return provider.token();
+        }
+        self.api_token
+            .clone()
+            .or_else(|| std::env::var("KITTYCAD_API_TOKEN").ok())
+            .or_else(|| std::env::var("ZOO_API_TOKEN").ok())
+            .ok_or_else(|| auth_error("No API token found.", None))
+    }
+
+    /// The address of the API to use, from the config or else the environment, if either sets one.
+    fn host(&self) -> Option<String> {
+        self.host
+            .clone()
+            .or_else(|| std::env::var("KITTYCAD_HOST").ok())
+            .or_else(|| std::env::var("ZOO_HOST").ok())
+    }
+
+    /// Make an API client from the config.
+    pub(crate) fn client(&self) -> PyResult<kittycad::Client> {
+        let token = self.token()?;
+        let mut client = if self.user_agent.is_none() && self.headers.is_none() {
+            kittycad::Client::new(token)
+        } else {
+            let headers = check_headers(&self.headers.clone().unwrap_or_default())?;
+            let user_agent = self.user_agent.as_deref().map(check_user_agent).transpose()?;
+            // The same timeouts `kittycad::Client::new` uses, with the engine's connection on HTTP/1 to be upgraded.
+            let builder = || {
+                let builder = reqwest::Client::builder()
+                    .timeout(std::time::Duration::from_secs(600))
+                    .connect_timeout(std::time::Duration::from_secs(60))
+                    .default_headers(headers.clone());
+                match &user_agent {
+                    Some(user_agent) => builder.user_agent(user_agent.clone()),
+                    None => builder,
+                }
+            };
+            kittycad::Client::new_from_reqwest(token, builder(), builder().http1_only())
+        };
+        if let Some(host) = self.host() {
+            client.set_base_url(host);
+        }
+
+        Ok(client)
+    }
+}
+
+#[pymethods]
+impl ClientConfig {
+    #[new]
+    #[pyo3(signature = (api_token = None, host = None, token_provider = None, user_agent = None, headers = None))]
+    fn new(
+        api_token: Option<String>,
+        host: Option<String>,
+        token_provider: Option<TokenProvider>,
+        user_agent: Option<String>,
+        headers: Option<BTreeMap<String, String>>,
+    ) -> PyResult<Self> {
+        let mut config = ClientConfig {
+            api_token,
+            host,
+            token_provider,
+            ..Default::default()
+        };
+        config.set_user_agent(user_agent)?;
+        config.set_headers(headers)?;
+
+        Ok(config)
+    }
+
+    #[setter]
+    fn set_user_agent(&mut self, user_agent: Option<String>) -> PyResult<()> {
+        user_agent.as_deref().map(check_user_agent).transpose()?;
+        self.user_agent = user_agent;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_headers(&mut self, headers: Option<BTreeMap<String, String>>) -> PyResult<()> {
+        headers.as_ref().map(check_headers).transpose()?;
+        self.headers = headers;
+        Ok(())
+    }
+}
+
+/// The result of checking the connection to the engine.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct ConnectionStatus {
+    /// Whether the engine could be reached and answered a command.
+    #[pyo3(get)]
+    pub ok: bool,
+    /// How long a round trip of a command to the engine took, in milliseconds.
+    #[pyo3(get)]
+    pub latency_ms: f64,
+    /// The email, or else the name, of the user the token belongs to.
+    #[pyo3(get)]
+    pub user: Option<String>,
+    /// The name of the user's org, if they have one and are allowed to see it.
+    #[pyo3(get)]
+    pub org: Option<String>,
+}
+
+#[pymethods]
+impl ConnectionStatus {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// How long `check_connection` waits by default, in seconds.
+const CHECK_TIMEOUT_SECONDS: f64 = 10.0;
+
+/// Whether a request failed because the name of the host couldn't be resolved.
+fn is_dns_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        let message = err.to_string().to_lowercase();
+        if ["dns error", "failed to lookup address", "name or service not known"]
+            .iter()
+            .any(|pattern| message.contains(pattern))
+        {
+            return true;
+        }
+        source = err.source();
+    }
+
+    false
+}
+
+/// Convert an error from the API into the exception for what went wrong.
+fn api_error(err: kittycad::types::error::Error) -> PyErr {
+    if let Some(status) = auth_status(&err) {
+        return auth_error(&format!("The API rejected the token: {}.", err), Some(status));
+    }
+    if let kittycad::types::error::Error::RequestError(request) = &err {
+        if request.is_timeout() {
+            return pyo3::exceptions::PyTimeoutError::new_err(err.to_string());
+        }
+        if is_dns_error(request) {
+            return HostNotFoundError::new_err(err.to_string());
+        }
+    }
+
+    EngineConnectionError::new_err(err.to_string())
+}
+
+/// Check that the API and engine can be reached with the given credentials, or those from the environment.
+///
+/// A connection to the engine is made and a command that changes nothing is sent over it. Raises `KclAuthError` if
+/// the token is missing or rejected, `HostNotFoundError` if the API's address can't be resolved, `TimeoutError` if
+/// it all takes longer than `timeout_seconds`, and `EngineConnectionError` for anything else. The config and timeout
+/// default to those set with `set_defaults`, or else the environment and 10 seconds.
+#[pyfunction]
+#[pyo3(signature = (config = None, timeout_seconds = None))]
+pub(crate) async fn check_connection(
+    config: Option<ClientConfig>,
+    timeout_seconds: Option<f64>,
+) -> PyResult<ConnectionStatus> {
+    let timeout_seconds = timeout_seconds
+        .or(Defaults::current().timeout_seconds)
+        .unwrap_or(CHECK_TIMEOUT_SECONDS);
+    if !timeout_seconds.is_finite() || timeout_seconds <= 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "timeout_seconds must be positive, got {}",
+            timeout_seconds
+        )));
+    }
+    let config = defaults::client_config(config.as_ref());
+    require_token(config.as_ref())?;
+    let client = config.unwrap_or_default().client()?;
+    let check = async move {
+        let user = client.users().get_self().await.map_err(api_error)?;
+        // Only org admins can look at the org, so not being able to is no reason to fail.
+        let org = client.orgs().get().await.ok().and_then(|org| org.name);
+
+        let ctx = ExecutorContext::new(&client, Default::default()).await.map_err(|err| {
+            if let Some(status) = engine_auth_status(&err) {
+                auth_error(&format!("The engine rejected the token: {}.", err), Some(status))
+            } else {
+                EngineConnectionError::new_err(err.to_string())
+            }
+        })?;
+        let start = std::time::Instant::now();
+        get_camera(&ctx).await?;
+        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        Ok(ConnectionStatus {
+            ok: true,
+            latency_ms,
+            user: user.email.or(user.name),
+            org,
+        })
+    };
+
+    spawn(async move {
+        tokio::time::timeout(std::time::Duration::from_secs_f64(timeout_seconds), check)
+            .await
+            .map_err(|_| {
+                pyo3::exceptions::PyTimeoutError::new_err(format!(
+                    "The engine did not respond within {} seconds",
+                    timeout_seconds
+                ))
+            })?
+    })
+    .await
+}
@@ -0,0 +1,107 @@
+//! The default planes, `XY`, `XZ`, `YZ` and their negatives, that kcl-lib makes for programs to sketch on.
+
+use std::collections::BTreeMap;
+
+use kcl_lib::ExecutorContext;
+use pyo3::{PyErr, PyResult};
+use serde_json::Value;
+
+use crate::{kcl_error, send_modeling_cmd};
+
+/// The names the default planes are referred to by in kcl code.
+const NAMES: [&str; 6] = ["XY", "XZ", "YZ", "-XY", "-XZ", "-YZ"];
+
+/// Find the first reference to a default plane in the program, as a name and the byte offsets of the reference.
+///
+/// Planes are referred to by strings, e.g. `startSketchOn('XY')`, or by name, e.g. `startSketchOn(XY)`.
+fn find_reference(program: &kcl_lib::Program) -> PyResult<Option<(String, usize, usize)>> {
+    fn find(node: &Value) -> Option<(String, usize, usize)> {
+        match node {
+            Value::Object(object) => {
+                let name = match object.get("type").and_then(|kind| kind.as_str()) {
+                    Some("Literal") => object
+                        .get("raw")
+                        .and_then(|raw| raw.as_str())
+                        .map(|raw| raw.trim_matches(['\'', '"'])),
+                    Some("Identifier") => object.get("name").and_then(|name| name.as_str()),
+                    _ => None,
+                };
+                let offset =
+                    |key: &str| object.get(key).and_then(|offset| offset.as_u64()).unwrap_or_default() as usize;
+                match name.filter(|name| NAMES.contains(name)) {
+                    Some(name) => Some((name.to_string(), offset("start"), offset("end"))),
+                    None => object.values().find_map(find),
+                }
+            }
+            Value::Array(array) => array.iter().find_map(find),
+            _ => None,
+        }
+    }
+
+    let ast =
+        serde_json::to_value(&program.ast).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+
+    Ok(find(&ast))
+}
+
+/// A semantic error from kcl-lib pointing at some kcl code.
+///
+/// kcl-lib doesn't export a way to make one, so it is read from how kcl-lib serializes them.
+fn semantic_error(message: String, start: usize, end: usize) -> PyErr {
+    let err = serde_json::json!({ "kind": "semantic", "sourceRanges": [[start, end, 0]], "msg": message });
+    match serde_json::from_value::<kcl_lib::KclError>(err) {
+        Ok(err) => PyErr::from(err),
+        Err(_) => pyo3::exceptions::PyValueError::new_err(message),
+    }
+}
+
+/// Check a program doesn't use the default planes, for when they aren't created.
+pub(crate) fn check_unused(program: &kcl_lib::Program) -> PyResult<()> {
+    match find_reference(program)? {
+        Some((name, start, end)) => Err(semantic_error(
+            format!(
+                "The default plane {} is used, but default planes are disabled with create_default_planes=False",
+                name
+            ),
+            start,
+            end,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The ids of the default planes in the scene, by name, e.g. `XY`.
+pub(crate) async fn ids(ctx: &ExecutorContext) -> PyResult<BTreeMap<String, uuid::Uuid>> {
+    // The planes are made once per scene, so asking for them again gives the ones already made.
+    let planes = ctx
+        .engine
+        .default_planes(&mut Default::default(), kcl_lib::SourceRange::default())
+        .await
+        .map_err(kcl_error)?;
+    let ids = [
+        planes.xy,
+        planes.xz,
+        planes.yz,
+        planes.neg_xy,
+        planes.neg_xz,
+        planes.neg_yz,
+    ];
+
+    Ok(NAMES.iter().zip(ids).map(|(name, id)| (name.to_string(), id)).collect())
+}
+
+/// Remove the default planes from the scene, so they aren't in exports or framed by zoom-to-fit.
+///
+/// kcl-lib always makes them when the scene is set up, so they are removed once the program has run instead.
+pub(crate) async fn remove(ctx: &ExecutorContext) -> PyResult<()> {
+    let object_ids = ids(ctx).await?.into_values().collect();
+    send_modeling_cmd(
+        ctx,
+        kittycad_modeling_cmds::ModelingCmd::RemoveSceneObjects(kittycad_modeling_cmds::RemoveSceneObjects {
+            object_ids,
+        }),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! Defaults for the parameters most calls repeat, used whenever a call leaves them out.
+
+use std::sync::{Arc, RwLock};
+
+use pyo3::{pyclass, pyfunction, PyResult};
+
+use crate::{connection::ClientConfig, FileExportFormat, ImageFormat, UnitLength, Units};
+
+/// The defaults used for parameters a call leaves out. An argument given to the call always wins over its default,
+/// and a default that isn't set leaves the parameter as it was.
+#[derive(PartialEq, Debug, Clone)]
+#[pyclass(eq, module = "kcl")]
+pub struct Defaults {
+    /// The units to execute in when a call doesn't give any.
+    #[pyo3(get)]
+    pub units: Option<UnitLength>,
+    /// The format to snapshot in when a call doesn't give one.
+    #[pyo3(get)]
+    pub image_format: Option<ImageFormat>,
+    /// The format to export to when a call doesn't give one.
+    #[pyo3(get)]
+    pub export_format: Option<FileExportFormat>,
+    /// The most seconds an execution, including any retries, or `check_connection` may take.
+    #[pyo3(get)]
+    pub timeout_seconds: Option<f64>,
+    /// How to connect to the API when a call doesn't give a config. Its token is redacted when the defaults are
+    /// printed with `{:?}`, like any `ClientConfig`'s.
+    #[pyo3(get)]
+    pub client_config: Option<ClientConfig>,
+}
+
+/// The defaults, or None before any are set. They're kept behind an `Arc` so that reading them never clones a token
+/// provider, which needs the GIL, while the lock is held.
+static DEFAULTS: RwLock<Option<Arc<Defaults>>> = RwLock::new(None);
+
+impl Defaults {
+    const FACTORY: Defaults = Defaults {
+        units: None,
+        image_format: None,
+        export_format: None,
+        timeout_seconds: None,
+        client_config: None,
+    };
+
+    /// The defaults currently in use.
+    pub(crate) fn current() -> Arc<Defaults> {
+        let defaults = DEFAULTS.read().unwrap_or_else(|err| err.into_inner()).clone();
+        defaults.unwrap_or_else(|| Arc::new(Defaults::FACTORY))
+    }
+}
+
+/// The units a call gave, or else the default units if there are any.
+pub(crate) fn units(units: Option<Units>) -> PyResult<Option<UnitLength>> {
+    match units {
+        Some(units) => Ok(Some(units.try_into()?)),
+        None => Ok(Defaults::current().units),
+    }
+}
+
+/// The image format a call gave, or else the default one.
+pub(crate) fn image_format(image_format: Option<ImageFormat>) -> PyResult<ImageFormat> {
+    image_format.or(Defaults::current().image_format).ok_or_else(|| {
+        pyo3::exceptions::PyTypeError::new_err("No image_format given, and no default set with set_defaults")
+    })
+}
+
+/// The export format a call gave, or else the default one.
+pub(crate) fn export_format(export_format: Option<FileExportFormat>) -> PyResult<FileExportFormat> {
+    export_format
+        .or_else(|| Defaults::current().export_format.clone())
+        .ok_or_else(|| {
+            pyo3::exceptions::PyTypeError::new_err("No export_format given, and no default set with set_defaults")
+        })
+}
+
+/// The config a call gave, or else the default one, if either is set.
+pub(crate) fn client_config(config: Option<&ClientConfig>) -> Option<ClientConfig> {
+    config.cloned().or_else(|| Defaults::current().client_config.clone())
+}
+
+/// Set the defaults for parameters that calls leave out, for every call from now on.
+///
+/// Only the defaults given are changed, the rest keep their current values; `reset_defaults` clears them all.
+/// `timeout_seconds` bounds each execution, retries included, and `check_connection`, raising `TimeoutError` when it
+/// runs out.
+#[pyfunction]
+#[pyo3(signature = (units = None, image_format = None, export_format = None, timeout_seconds = None, client_config = None))]
+pub(crate) fn set_defaults(
+    units: Option<Units>,
+    image_format: Option<ImageFormat>,
+    export_format: Option<FileExportFormat>,
+    timeout_seconds: Option<f64>,
+    client_config: Option<ClientConfig>,
+) -> PyResult<()> {
+    if let Some(timeout_seconds) = timeout_seconds.filter(|seconds| !seconds.is_finite() || *seconds <= 0.0) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "timeout_seconds must be positive, got {}",
+            timeout_seconds
+        )));
+    }
+    let units = units.map(UnitLength::try_from).transpose()?;
+
+    // Everything is changed under the one lock, so no call sees only some of the new defaults.
+    let mut defaults = DEFAULTS.write().unwrap_or_else(|err| err.into_inner());
+    let current = defaults.as_deref();
+    let updated = Defaults {
+        units: units.or(current.and_then(|current| current.units)),
+        image_format: image_format.or(current.and_then(|current| current.image_format)),
+        export_format: export_format.or_else(|| current.and_then(|current| current.export_format.clone())),
+        timeout_seconds: timeout_seconds.or(current.and_then(|current| current.timeout_seconds)),
+        client_config: client_config.or_else(|| current.and_then(|current| current.client_config.clone())),
+    };
+    *defaults = Some(Arc::new(updated));
+
+    Ok(())
+}
+
+/// Get the defaults currently used for parameters that calls leave out.
+#[pyfunction]
+pub(crate) fn get_defaults() -> Defaults {
+    Defaults::current().as_ref().clone()
+}
+
+/// Clear every default, so calls behave as if `set_defaults` was never called.
+#[pyfunction]
+pub(crate) fn reset_defaults() {
+    *DEFAULTS.write().unwrap_or_else(|err| err.into_inner()) = None;
+}
@@ -0,0 +1,194 @@
+//! Line diffs between two texts, written as a unified diff like `diff -u` writes them.
+
+/// How many unchanged lines are shown around each change.
+const CONTEXT: usize = 3;
+
+/// The most lines found to differ before giving up on the shortest diff, and replacing every line between the first
+/// and last difference instead. This keeps the time and memory of a diff of two very different texts in bounds.
+const MAX_EDITS: usize = 2000;
+
+/// What happens to a line, by its index in the old and new text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Same(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// The shortest edits turning the `old` lines into the `new` ones, by Myers' algorithm, or None if there are more
+/// than `MAX_EDITS`.
+fn shortest_edits(old: &[&str], new: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m).min(MAX_EDITS as isize);
+    let offset = max + 1;
+    // The furthest x reached on each diagonal k = x - y, and what that was after each number of edits d, keeping
+    // only the diagonals -d..=d that can have been reached.
+    let mut furthest = vec![0isize; 2 * max as usize + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && furthest[i - 1] < furthest[i + 1]) {
+                furthest[i + 1]
+            } else {
+                furthest[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[i] = x;
+            if x >= n && y >= m {
+                trace.push(furthest[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+        trace.push(furthest[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+
+    None
+}
+
+/// Follow the furthest points reached back from the end, to the edits that reached it.
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+    let (mut x, mut y) = (n, m);
+    let mut edits = Vec::new();
+    for d in (1..trace.len()).rev() {
+        let (d, k) = (d as isize, x - y);
+        let previous = |k: isize| trace[d as usize - 1][(k + d - 1) as usize];
+        let previous_k = if k == -d || (k != d && previous(k - 1) < previous(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = previous(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Same(x as usize, y as usize));
+        }
+        if x == previous_x {
+            y -= 1;
+            edits.push(Edit::Insert(y as usize));
+        } else {
+            x -= 1;
+            edits.push(Edit::Delete(x as usize));
+        }
+    }
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        edits.push(Edit::Same(x as usize, y as usize));
+    }
+    edits.reverse();
+
+    edits
+}
+
+/// The edits turning the `old` lines into the `new` ones, keeping the lines they start and end with in common out of
+/// the search for the shortest edits.
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(old, new)| old == new).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let middle = shortest_edits(old_middle, new_middle).unwrap_or_else(|| {
+        let deletes = (0..old_middle.len()).map(Edit::Delete);
+        deletes.chain((0..new_middle.len()).map(Edit::Insert)).collect()
+    });
+    let shift = |edit: Edit| match edit {
+        Edit::Same(x, y) => Edit::Same(x + prefix, y + prefix),
+        Edit::Delete(x) => Edit::Delete(x + prefix),
+        Edit::Insert(y) => Edit::Insert(y + prefix),
+    };
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+
+    (0..prefix)
+        .map(|i| Edit::Same(i, i))
+        .chain(middle.into_iter().map(shift))
+        .chain((0..suffix).map(|i| Edit::Same(old_end + i, new_end + i)))
+        .collect()
+}
+
+/// A hunk header's range of lines, where `start` is the index of its first line.
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        // An empty range is written as the line before it.
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// Write a line of a hunk, marking one without a newline at the end like `diff` does.
+fn push_line(diff: &mut String, prefix: char, line: &str) {
+    diff.push(prefix);
+    diff.push_str(line);
+    if !line.ends_with('\n') {
+        diff.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// The unified diff from `old` to `new`, with the file names given for each, or an empty string if they are the same.
+pub(crate) fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    let (old_lines, new_lines): (Vec<&str>, Vec<&str>) =
+        (old.split_inclusive('\n').collect(), new.split_inclusive('\n').collect());
+    let edits = edits(&old_lines, &new_lines);
+
+    // Each hunk is a run of changes, with any changes close enough that their context would overlap in the same one.
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&i| !matches!(edits[i], Edit::Same(..)))
+        .collect();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &change in &changes {
+        let start = change.saturating_sub(CONTEXT);
+        let end = (change + CONTEXT + 1).min(edits.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut diff = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for (start, end) in hunks {
+        let hunk = &edits[start..end];
+        // Where the hunk starts in each text is where its first line is, or would be if it has none from that text.
+        let (mut old_start, mut new_start) = (0, 0);
+        for edit in &edits[..start] {
+            match edit {
+                Edit::Same(..) => {
+                    old_start += 1;
+                    new_start += 1;
+                }
+                Edit::Delete(_) => old_start += 1,
+                Edit::Insert(_) => new_start += 1,
+            }
+        }
+        let old_count = hunk.iter().filter(|edit| !matches!(edit, Edit::Insert(_))).count();
+        let new_count = hunk.iter().filter(|edit| !matches!(edit, Edit::Delete(_))).count();
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        ));
+        for edit in hunk {
+            match *edit {
+                Edit::Same(x, _) => push_line(&mut diff, ' ', old_lines[x]),
+                Edit::Delete(x) => push_line(&mut diff, '-', old_lines[x]),
+                Edit::Insert(y) => push_line(&mut diff, '+', new_lines[y]),
+            }
+        }
+    }
+
+    diff
+}
@@ -0,0 +1,722 @@
+//! Editor features for kcl code, from kcl-lib's language server without running a server.
+
+use std::sync::{Mutex, OnceLock};
+
+use pyo3::{pyclass, pyfunction, pymethods, PyResult, Python};
+use serde::{Deserialize, Serialize};
+use tower_lsp::{lsp_types, LanguageServer};
+
+use crate::tokio;
+
+/// The name the code is opened as in the language server.
+fn document() -> lsp_types::Url {
+    lsp_types::Url::parse("file:///main.kcl").expect("the document name is a valid URL")
+}
+
+/// kcl-lib's language server, made once as making it loads the documentation of the whole standard library.
+static SERVER: OnceLock<Mutex<kcl_lib::KclLspBackend>> = OnceLock::new();
+
+/// Ask kcl-lib's language server something without holding the GIL, as parsing the code can take a while.
+///
+/// Calls take turns with the server, since it only keeps the code it was opened with last.
+fn with_server<T: Send>(
+    py: Python<'_>,
+    ask: impl FnOnce(&kcl_lib::KclLspBackend) -> PyResult<T> + Send,
+) -> PyResult<T> {
+    py.allow_threads(|| {
+        let server = SERVER.get_or_init(|| {
+            // The API client is only used to execute the code, which the server isn't allowed to do.
+            let (service, _) = tower_lsp::LspService::new(|client| {
+                kcl_lib::KclLspBackend::new(client, None, kittycad::Client::new(""), false)
+                    .expect("kcl-lib's standard library documentation is valid")
+            });
+            Mutex::new(service.inner().clone())
+        });
+        let server = server.lock().unwrap_or_else(|err| err.into_inner());
+        ask(&server)
+    })
+}
+
+/// Open the code in kcl-lib's language server, which parses it, without connecting to the engine.
+///
+/// The server works out some things, like the modifiers of semantic tokens, from the code it had before, so it is
+/// given empty code first to forget the code of the last call.
+fn open(server: &kcl_lib::KclLspBackend, code: &str) {
+    for text in ["", code] {
+        tokio().block_on(server.did_open(lsp_types::DidOpenTextDocumentParams {
+            text_document: lsp_types::TextDocumentItem {
+                uri: document(),
+                language_id: "kcl".to_string(),
+                version: 0,
+                text: text.to_string(),
+            },
+        }));
+    }
+}
+
+/// The position of a byte offset into the code, as kcl-lib's language server counts it: columns are in bytes.
+fn lsp_position(code: &str, offset: usize) -> lsp_types::TextDocumentPositionParams {
+    let line_start = code[..offset].rfind('\n').map(|i| i + 1).unwrap_or_default();
+    lsp_types::TextDocumentPositionParams {
+        text_document: lsp_types::TextDocumentIdentifier { uri: document() },
+        position: lsp_types::Position::new(
+            code[..offset].matches('\n').count() as u32,
+            (offset - line_start) as u32,
+        ),
+    }
+}
+
+/// A suggestion for the text at the cursor.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass]
+pub struct CompletionItem {
+    /// The text to insert.
+    #[pyo3(get)]
+    pub label: String,
+    /// What the suggestion is, e.g. `function`, `variable`, `keyword` or `operator`.
+    #[pyo3(get)]
+    pub kind: String,
+    /// Extra information to show next to the suggestion, e.g. a function's signature.
+    #[pyo3(get)]
+    pub detail: Option<String>,
+    /// Byte offset of the start of the text the suggestion replaces.
+    #[pyo3(get)]
+    pub start: usize,
+    /// Byte offset of the end of the text the suggestion replaces.
+    #[pyo3(get)]
+    pub end: usize,
+}
+
+/// Convert a zero-based line and column (in characters) into a byte offset into the code.
+pub(crate) fn offset_at(code: &str, line: usize, column: usize) -> PyResult<usize> {
+    let out_of_range = || {
+        pyo3::exceptions::PyValueError::new_err(format!(
+            "Position {}:{} is outside of the code, which has {} lines",
+            line,
+            column,
+            code.split('\n').count()
+        ))
+    };
+    let line_start = if line == 0 {
+        0
+    } else {
+        code.match_indices('\n').nth(line - 1).ok_or_else(out_of_range)?.0 + 1
+    };
+    let text = &code[line_start..];
+    let text = &text[..text.find('\n').unwrap_or(text.len())];
+    if column == text.chars().count() {
+        return Ok(line_start + text.len());
+    }
+    let (offset, _) = text.char_indices().nth(column).ok_or_else(out_of_range)?;
+
+    Ok(line_start + offset)
+}
+
+/// Convert a byte offset into the code into a zero-based line and column (in characters).
+///
+/// An offset inside a character counts as the start of it, and the `\r` of a `\r\n` is part of the line ending.
+pub(crate) fn position_at(code: &str, offset: usize) -> PyResult<(usize, usize)> {
+    if offset > code.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Offset {} is outside of the code, which is {} bytes long",
+            offset,
+            code.len()
+        )));
+    }
+    let offset = (0..=offset).rev().find(|i| code.is_char_boundary(*i)).unwrap_or(0);
+    let before = &code[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    Ok((
+        before.matches('\n').count(),
+        before[line_start..].trim_end_matches('\r').chars().count(),
+    ))
+}
+
+/// The lines of code around a source range, for showing where a problem is.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass]
+pub struct Snippet {
+    /// The lines, without their line endings.
+    #[pyo3(get)]
+    pub lines: Vec<String>,
+    /// The zero-based line number of the first of the lines.
+    #[pyo3(get)]
+    pub first_line: usize,
+    /// The zero-based line of the start of the range.
+    #[pyo3(get)]
+    pub start_line: usize,
+    /// The zero-based column (in characters) of the start of the range.
+    #[pyo3(get)]
+    pub start_column: usize,
+    /// The zero-based line of the end of the range.
+    #[pyo3(get)]
+    pub end_line: usize,
+    /// The zero-based column (in characters) of the end of the range.
+    #[pyo3(get)]
+    pub end_column: usize,
+    /// Carets under the range on its first line, e.g. `    ^^^`, to print below that line.
+    #[pyo3(get)]
+    pub underline: String,
+}
+
+#[pymethods]
+impl Snippet {
+    /// The lines with their one-based line numbers, and the underline below the first line of the range.
+    fn __str__(&self) -> String {
+        let width = (self.first_line + self.lines.len()).to_string().len();
+        let mut text = String::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            let number = self.first_line + i;
+            text.push_str(&format!("{:>width$} | {}\n", number + 1, line));
+            if number == self.start_line {
+                text.push_str(&format!("{:>width$} | {}\n", "", self.underline));
+            }
+        }
+
+        text.trim_end().to_string()
+    }
+}
+
+/// Get the lines of the code a source range covers, along with `context_lines` lines either side of them.
+pub(crate) fn snippet_at(code: &str, start: usize, end: usize, context_lines: usize) -> PyResult<Snippet> {
+    if end < start {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "The end of the source range, {}, is before its start, {}",
+            end, start
+        )));
+    }
+    let (start_line, start_column) = position_at(code, start)?;
+    let (end_line, end_column) = position_at(code, end)?;
+    let all_lines: Vec<&str> = code.split('\n').map(|line| line.trim_end_matches('\r')).collect();
+    let first_line = start_line.saturating_sub(context_lines);
+    let last_line = (end_line + context_lines).min(all_lines.len() - 1);
+
+    // Keep tabs in the indent, so the carets line up however wide tabs are shown.
+    let line = all_lines[start_line];
+    let indent: String = line
+        .chars()
+        .take(start_column)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let range_end = if end_line == start_line {
+        end_column
+    } else {
+        line.chars().count()
+    };
+    let underline = format!(
+        "{}{}",
+        indent,
+        "^".repeat(range_end.saturating_sub(start_column).max(1))
+    );
+
+    Ok(Snippet {
+        lines: all_lines[first_line..=last_line]
+            .iter()
+            .map(|line| line.to_string())
+            .collect(),
+        first_line,
+        start_line,
+        start_column,
+        end_line,
+        end_column,
+        underline,
+    })
+}
+
+/// Get the lines of the code a `(start, end)` source range of byte offsets covers, to show where a problem is.
+///
+/// Lines and columns are zero-based, and columns count characters rather than bytes. `context_lines` lines either
+/// side of the range are included too.
+#[pyfunction]
+#[pyo3(signature = (code, source_range, context_lines = 1))]
+pub(crate) fn snippet(code: &str, source_range: (usize, usize), context_lines: usize) -> PyResult<Snippet> {
+    snippet_at(code, source_range.0, source_range.1, context_lines)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The byte range of the identifier at the offset, which is empty if there isn't one.
+pub(crate) fn word_at(code: &str, offset: usize) -> (usize, usize) {
+    let start = code[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier_char(*c))
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(offset);
+    let end = code[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_identifier_char(*c))
+        .map(|(i, _)| offset + i)
+        .unwrap_or(code.len());
+
+    (start, end)
+}
+
+/// Get completions for the kcl code at a zero-based line and column.
+///
+/// Suggestions are kcl-lib's: the names declared in the code, standard library functions, keywords and the pipe
+/// operator, filtered to those starting with the identifier being typed and sorted by label. If the code doesn't
+/// parse, only the names declared before the line of the cursor are suggested, as the line being edited is often
+/// incomplete. Positions outside of the code raise a `ValueError`.
+#[pyfunction]
+pub(crate) fn completions(py: Python<'_>, code: &str, line: usize, column: usize) -> PyResult<Vec<CompletionItem>> {
+    let offset = offset_at(code, line, column)?;
+    let (start, _) = word_at(code, offset);
+    let prefix = &code[start..offset];
+
+    let line_start = code[..offset].rfind('\n').map(|i| i + 1).unwrap_or_default();
+    let parsed = if kcl_lib::Program::parse_no_errs(code).is_ok() {
+        code
+    } else {
+        &code[..line_start]
+    };
+    let response = with_server(py, |server| {
+        open(server, parsed);
+        tokio()
+            .block_on(server.completion(lsp_types::CompletionParams {
+                text_document_position: lsp_position(code, offset),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: None,
+            }))
+            .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))
+    })?;
+    let completions = match response {
+        Some(lsp_types::CompletionResponse::Array(items)) => items,
+        Some(lsp_types::CompletionResponse::List(list)) => list.items,
+        None => Vec::new(),
+    };
+
+    let mut items: Vec<CompletionItem> = Vec::new();
+    for completion in completions {
+        if !completion.label.starts_with(prefix) || items.iter().any(|item| item.label == completion.label) {
+            continue;
+        }
+        // Functions have their signature without their name as the detail of their label.
+        let signature = completion
+            .label_details
+            .and_then(|details| details.detail)
+            .map(|signature| format!("{}{}", completion.label, signature));
+        items.push(CompletionItem {
+            kind: completion
+                .kind
+                .map(|kind| format!("{:?}", kind).to_lowercase())
+                .unwrap_or_else(|| "text".to_string()),
+            detail: signature.or(completion.detail),
+            label: completion.label,
+            start,
+            end: offset,
+        });
+    }
+    items.sort_by(|a, b| a.label.cmp(&b.label));
+
+    Ok(items)
+}
+
+/// Documentation for the symbol under the cursor.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass]
+pub struct Hover {
+    /// The documentation, as markdown.
+    #[pyo3(get)]
+    pub contents: String,
+    /// Byte offset of the start of the symbol.
+    #[pyo3(get)]
+    pub start: usize,
+    /// Byte offset of the end of the symbol.
+    #[pyo3(get)]
+    pub end: usize,
+}
+
+/// The signature of the function call the cursor is in.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass]
+pub struct SignatureHelp {
+    /// The name of the function, e.g. `extrude`.
+    #[pyo3(get)]
+    pub label: String,
+    /// The names of the parameters of the function, in order.
+    #[pyo3(get)]
+    pub parameters: Vec<String>,
+    /// The index of the parameter the cursor is in.
+    #[pyo3(get)]
+    pub active_parameter: usize,
+}
+
+/// The byte offset of a position kcl-lib's language server gave, where columns are in bytes.
+fn lsp_offset(code: &str, position: lsp_types::Position) -> usize {
+    let line_start = match position.line {
+        0 => 0,
+        line => code
+            .match_indices('\n')
+            .nth(line as usize - 1)
+            .map(|(i, _)| i + 1)
+            .unwrap_or(code.len()),
+    };
+    (line_start + position.character as usize).min(code.len())
+}
+
+/// Get documentation for the standard library function at a zero-based line and column of the kcl code.
+///
+/// The documentation is kcl-lib's, as its language server shows it. Returns `None` if the cursor isn't over a call to
+/// a standard library function or a comment, or if the code doesn't parse.
+#[pyfunction]
+pub(crate) fn hover(py: Python<'_>, code: &str, line: usize, column: usize) -> PyResult<Option<Hover>> {
+    let offset = offset_at(code, line, column)?;
+    let hover = with_server(py, |server| {
+        open(server, code);
+        tokio()
+            .block_on(server.hover(lsp_types::HoverParams {
+                text_document_position_params: lsp_position(code, offset),
+                work_done_progress_params: Default::default(),
+            }))
+            .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))
+    })?;
+    let Some(hover) = hover else {
+        return Ok(None);
+    };
+
+    let contents = match hover.contents {
+        lsp_types::HoverContents::Markup(markup) => markup.value,
+        lsp_types::HoverContents::Scalar(lsp_types::MarkedString::String(text)) => text,
+        lsp_types::HoverContents::Scalar(lsp_types::MarkedString::LanguageString(text)) => text.value,
+        lsp_types::HoverContents::Array(texts) => texts
+            .into_iter()
+            .map(|text| match text {
+                lsp_types::MarkedString::String(text) => text,
+                lsp_types::MarkedString::LanguageString(text) => text.value,
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    };
+    let (start, end) = match hover.range {
+        Some(range) => {
+            let mut start = lsp_offset(code, range.start);
+            // kcl-lib gives the start of a line as the end of the line before it.
+            if code[start..].starts_with('\n') {
+                start += 1;
+            }
+            (start, lsp_offset(code, range.end).max(start))
+        }
+        None => word_at(code, offset),
+    };
+
+    Ok(Some(Hover { contents, start, end }))
+}
+
+/// Ask kcl-lib's language server for the signature of the call at a byte offset into the code.
+fn lsp_signature_help(
+    server: &kcl_lib::KclLspBackend,
+    code: &str,
+    offset: usize,
+) -> PyResult<Option<lsp_types::SignatureHelp>> {
+    open(server, code);
+    tokio()
+        .block_on(server.signature_help(lsp_types::SignatureHelpParams {
+            context: None,
+            text_document_position_params: lsp_position(code, offset),
+            work_done_progress_params: Default::default(),
+        }))
+        .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))
+}
+
+/// The brackets that close the ones left open in the code, skipping any in strings and comments.
+fn closing_brackets(code: &str) -> String {
+    let mut open = Vec::new();
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => open.push(')'),
+            '[' => open.push(']'),
+            '{' => open.push('}'),
+            ')' | ']' | '}' => {
+                open.pop();
+            }
+            '"' | '\'' => {
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => {
+                            chars.next();
+                        }
+                        next if next == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|next| *next == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    open.iter().rev().collect()
+}
+
+/// Get the signature of the standard library function call at a zero-based line and column of the kcl code.
+///
+/// The signature is kcl-lib's, as its language server gives it. kcl-lib only knows which parameter the cursor is in
+/// when it is in an argument of a call in code that parses, so if that doesn't find a signature, it is asked again
+/// with just the code before the cursor, an argument where the cursor is and the brackets left open closed. This
+/// gives help while the rest of the call is still being typed. Returns `None` if the cursor isn't in the arguments of
+/// a call to a standard library function.
+#[pyfunction]
+pub(crate) fn signature_help(
+    py: Python<'_>,
+    code: &str,
+    line: usize,
+    column: usize,
+) -> PyResult<Option<SignatureHelp>> {
+    let offset = offset_at(code, line, column)?;
+    let help = with_server(py, |server| match lsp_signature_help(server, code, offset)? {
+        Some(help) => Ok(Some(help)),
+        None => {
+            let before = format!("{}0{}", &code[..offset], closing_brackets(&code[..offset]));
+            lsp_signature_help(server, &before, offset)
+        }
+    })?;
+    let Some(help) = help else {
+        return Ok(None);
+    };
+    let Some(signature) = help.signatures.into_iter().next() else {
+        return Ok(None);
+    };
+
+    // Offsets into the label are in UTF-16 code units.
+    let label: Vec<u16> = signature.label.encode_utf16().collect();
+    let parameters = signature
+        .parameters
+        .into_iter()
+        .flatten()
+        .map(|parameter| match parameter.label {
+            lsp_types::ParameterLabel::Simple(name) => name,
+            lsp_types::ParameterLabel::LabelOffsets([start, end]) => {
+                String::from_utf16_lossy(label.get(start as usize..end as usize).unwrap_or_default())
+            }
+        })
+        .collect();
+
+    Ok(Some(SignatureHelp {
+        label: signature.label,
+        parameters,
+        active_parameter: help.active_parameter.or(signature.active_parameter).unwrap_or_default() as usize,
+    }))
+}
+
+/// The names of the semantic token types and modifiers kcl-lib's language server uses, in the order it numbers them.
+fn semantic_token_legend(server: &kcl_lib::KclLspBackend) -> PyResult<lsp_types::SemanticTokensLegend> {
+    let initialized = tokio()
+        .block_on(server.initialize(Default::default()))
+        .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+    match initialized.capabilities.semantic_tokens_provider {
+        Some(lsp_types::SemanticTokensServerCapabilities::SemanticTokensOptions(options)) => Ok(options.legend),
+        Some(lsp_types::SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(options)) => {
+            Ok(options.semantic_tokens_options.legend)
+        }
+        None => Err(pyo3::exceptions::PyException::new_err(
+            "kcl-lib's language server doesn't give semantic tokens",
+        )),
+    }
+}
+
+/// kcl-lib's semantic tokens for the code, as `(start, length, token_type, modifiers)` entries, or `None` if kcl-lib
+/// couldn't tokenize it.
+#[allow(clippy::type_complexity)]
+fn lsp_semantic_tokens(
+    server: &kcl_lib::KclLspBackend,
+    code: &str,
+) -> PyResult<Option<Vec<(usize, usize, String, Vec<String>)>>> {
+    open(server, code);
+    // The server forgets the tokens of code it couldn't tokenize.
+    if !server.semantic_tokens_map.contains_key(document().as_str()) {
+        return Ok(None);
+    }
+    let legend = semantic_token_legend(server)?;
+    let result = tokio()
+        .block_on(server.semantic_tokens_full(lsp_types::SemanticTokensParams {
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            text_document: lsp_types::TextDocumentIdentifier { uri: document() },
+        }))
+        .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+    let tokens = match result {
+        Some(lsp_types::SemanticTokensResult::Tokens(tokens)) => tokens.data,
+        Some(lsp_types::SemanticTokensResult::Partial(tokens)) => tokens.data,
+        None => Vec::new(),
+    };
+
+    // Each token's position is relative to the one before it.
+    let (mut line, mut column) = (0, 0);
+    let mut entries = Vec::new();
+    for token in tokens {
+        line += token.delta_line;
+        column = if token.delta_line > 0 {
+            token.delta_start
+        } else {
+            column + token.delta_start
+        };
+        let token_type = legend
+            .token_types
+            .get(token.token_type as usize)
+            .map(|token_type| token_type.as_str().to_string())
+            .unwrap_or_default();
+        let modifiers = legend
+            .token_modifiers
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| token.token_modifiers_bitset & (1 << bit) != 0)
+            .map(|(_, modifier)| modifier.as_str().to_string())
+            .collect();
+        entries.push((
+            lsp_offset(code, lsp_types::Position::new(line, column)),
+            token.length as usize,
+            token_type,
+            modifiers,
+        ));
+    }
+
+    Ok(Some(entries))
+}
+
+/// Get kcl-lib's semantic tokens for highlighting the kcl code, as `(start, length, token_type, modifiers)` entries.
+///
+/// Offsets and lengths are in bytes. Token types and modifiers are the names kcl-lib's language server uses, e.g.
+/// `function`, `variable`, `number` or `keyword`, and `declaration` or `defaultLibrary`. Code with syntax errors still
+/// gets a token type for everything kcl-lib can tokenize, but no modifiers, and code it can't tokenize gets no tokens.
+#[pyfunction]
+#[allow(clippy::type_complexity)]
+pub(crate) fn semantic_tokens(py: Python<'_>, code: &str) -> PyResult<Vec<(usize, usize, String, Vec<String>)>> {
+    Ok(with_server(py, |server| lsp_semantic_tokens(server, code))?.unwrap_or_default())
+}
+
+/// A token in kcl code.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass]
+pub struct Token {
+    /// The kind of token. Tokens kcl-lib's language server highlights have its semantic token type, e.g. `keyword`,
+    /// `variable`, `function`, `number`, `string`, `operator` or `comment`. The others are `whitespace`, `brace`,
+    /// `comma`, `colon`, `period`, `doublePeriod`, `hash`, `dollar` or `at`, or `unknown` for text kcl-lib can't
+    /// tokenize.
+    #[pyo3(get)]
+    pub kind: String,
+    /// The source text of the token.
+    #[pyo3(get)]
+    pub value: String,
+    /// Byte offset of the start of the token.
+    #[pyo3(get)]
+    pub start: usize,
+    /// Byte offset of the end of the token.
+    #[pyo3(get)]
+    pub end: usize,
+    /// Whether kcl-lib couldn't tokenize this text.
+    #[pyo3(get)]
+    pub is_error: bool,
+}
+
+impl Token {
+    fn new(code: &str, kind: &str, start: usize, end: usize) -> Self {
+        Token {
+            kind: kind.to_string(),
+            value: code[start..end].to_string(),
+            start,
+            end,
+            is_error: kind == "unknown",
+        }
+    }
+}
+
+/// The tokens kcl-lib's language server doesn't highlight, which are all that can be between those it does, for
+/// the code from `start` to `end`.
+fn punctuation_tokens(code: &str, start: usize, end: usize, tokens: &mut Vec<Token>) {
+    let text = &code[start..end];
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let mut len = c.len_utf8();
+        let kind = match c {
+            ' ' | '\t' | '\r' | '\n' => {
+                while let Some((_, next)) = chars.next_if(|(_, next)| matches!(next, ' ' | '\t' | '\r' | '\n')) {
+                    len += next.len_utf8();
+                }
+                "whitespace"
+            }
+            '{' | '(' | '[' | '}' | ')' | ']' => "brace",
+            ',' => "comma",
+            ':' => "colon",
+            '.' if chars.next_if(|(_, next)| *next == '.').is_some() => {
+                len += 1;
+                "doublePeriod"
+            }
+            '.' => "period",
+            '#' => "hash",
+            '$' => "dollar",
+            '@' => "at",
+            _ => "unknown",
+        };
+        tokens.push(Token::new(code, kind, start + i, start + i + len));
+    }
+}
+
+/// Where kcl-lib stopped tokenizing the code it was last given, from the error its language server reported.
+fn lex_error_offset(server: &kcl_lib::KclLspBackend, code: &str) -> usize {
+    server
+        .diagnostics_map
+        .get(document().as_str())
+        .and_then(|diagnostics| diagnostics.first().map(|diagnostic| diagnostic.range.start))
+        .map(|position| lsp_offset(code, position))
+        .unwrap_or(code.len())
+}
+
+/// Split the kcl code into tokens, built on the semantic tokens of kcl-lib's language server.
+///
+/// The code doesn't have to parse. Text kcl-lib can't tokenize, like a string that is never closed, is returned as an
+/// `unknown` token with `is_error` set, along with everything after it. Concatenating the values of the tokens always
+/// gives back the code.
+#[pyfunction]
+pub(crate) fn tokenize(py: Python<'_>, code: &str) -> PyResult<Vec<Token>> {
+    let (highlighted, end) = with_server(py, |server| {
+        // Tokenize the code before where kcl-lib stopped, until it gets through all of what it is given.
+        let mut end = code.len();
+        loop {
+            if let Some(highlighted) = lsp_semantic_tokens(server, &code[..end])? {
+                return Ok((highlighted, end));
+            }
+            if end == 0 {
+                return Ok((Vec::new(), 0));
+            }
+            let stop = lex_error_offset(server, &code[..end]).min(end - 1);
+            end = (0..=stop).rev().find(|i| code.is_char_boundary(*i)).unwrap_or(0);
+        }
+    })?;
+
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    for (start, length, token_type, _) in highlighted {
+        let token_end = start + length;
+        if start < offset || token_end > end || !code.is_char_boundary(token_end) {
+            continue;
+        }
+        punctuation_tokens(code, offset, start, &mut tokens);
+        tokens.push(Token::new(code, &token_type, start, token_end));
+        offset = token_end;
+    }
+    punctuation_tokens(code, offset, end, &mut tokens);
+    if end < code.len() {
+        tokens.push(Token::new(code, "unknown", end, code.len()));
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,106 @@
+//! Errors from the engine, saying which command it rejected and why.
+
+use kcl_lib::{KclError, SourceRange};
+use kittycad_modeling_cmds::websocket::OkWebSocketResponseData;
+use pyo3::{prelude::PyAnyMethods, types::PyAny, Bound, PyErr, PyResult, Python};
+
+pyo3::create_exception!(
+    kcl,
+    KclEngineError,
+    pyo3::exceptions::PyException,
+    "The engine rejected a modeling command. `command_id` is the id of the command, `error_code` and `message` are \
+     what the engine said, and `start` and `end` are the byte offsets of the kcl code that sent it, each None when not \
+     known."
+);
+
+pyo3::create_exception!(
+    kcl,
+    UnexpectedResponseError,
+    KclEngineError,
+    "The engine answered a command with the wrong kind of response. `expected` and `actual` name the two."
+);
+
+/// The first error code and message the engine gave, from an error as kcl-lib words it, e.g.
+/// `Modeling command failed: [ApiError { error_code: BadRequest, message: "..." }]`.
+fn api_error(message: &str) -> Option<(String, String)> {
+    let (_, rest) = message.split_once("error_code: ")?;
+    let (code, rest) = rest.split_once(',')?;
+    let (_, rest) = rest.split_once("message: \"")?;
+
+    // The message is debug formatted, so it ends at the first quote that isn't escaped.
+    let mut text = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => text.push('\n'),
+                't' => text.push('\t'),
+                c => text.push(c),
+            },
+            '"' => return Some((code.trim().to_string(), text)),
+            c => text.push(c),
+        }
+    }
+
+    None
+}
+
+/// Set attributes on the exception an error raises.
+fn with_attributes(err: PyErr, set: impl FnOnce(&Bound<'_, PyAny>) -> PyResult<()>) -> PyErr {
+    Python::with_gil(|py| match set(err.value_bound(py).as_any()) {
+        Ok(()) => err,
+        Err(set_err) => set_err,
+    })
+}
+
+/// Convert an error the engine gave into a `KclEngineError`, with the id of the command if it's known.
+pub(crate) fn engine_error(err: &KclError, command_id: Option<uuid::Uuid>) -> PyErr {
+    let (error_code, message) = match api_error(err.message()) {
+        Some((code, message)) => (Some(code), message),
+        None => (None, err.message().to_string()),
+    };
+    // Commands sent by this module rather than by the program have no kcl code to point at.
+    let range = err
+        .source_ranges()
+        .first()
+        .copied()
+        .filter(|range| *range != SourceRange::default());
+
+    with_attributes(KclEngineError::new_err(err.to_string()), |value| {
+        value.setattr("command_id", command_id.map(|id| id.to_string()))?;
+        value.setattr("error_code", error_code)?;
+        value.setattr("message", message)?;
+        value.setattr("start", range.map(|range| range.start()))?;
+        value.setattr("end", range.map(|range| range.end()))
+    })
+}
+
+/// The name of the kind of response the engine gave, e.g. `TakeSnapshot` or `Export`.
+fn response_name(resp: &OkWebSocketResponseData) -> String {
+    let debug = match resp {
+        OkWebSocketResponseData::Modeling { modeling_response } => format!("{:?}", modeling_response),
+        resp => format!("{:?}", resp),
+    };
+
+    debug
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The error for a response from the engine that isn't the `expected` kind for the command sent.
+pub(crate) fn unexpected_response(expected: &str, resp: &OkWebSocketResponseData) -> PyErr {
+    let actual = response_name(resp);
+    let message = format!("Expected a {} response from the engine, got {}", expected, actual);
+
+    with_attributes(UnexpectedResponseError::new_err(message.clone()), |value| {
+        value.setattr("command_id", None::<String>)?;
+        value.setattr("error_code", None::<String>)?;
+        value.setattr("message", message)?;
+        value.setattr("start", None::<usize>)?;
+        value.setattr("end", None::<usize>)?;
+        value.setattr("expected", expected)?;
+        value.setattr("actual", actual)
+    })
+}
@@ -0,0 +1,510 @@
+//! Checking the structure of exported files, so a broken export is caught here rather than by whatever loads it.
+
+use pyo3::{pyfunction, Bound, PyResult};
+use serde_json::Value;
+
+use crate::{mesh, ExportFile};
+
+pyo3::create_exception!(
+    kcl,
+    InvalidExportError,
+    pyo3::exceptions::PyException,
+    "Raised when an export asked to be validated has structural problems. The second argument is the list of \
+     problems, as `validate_export` returns them, prefixed with the name of the file."
+);
+
+/// The GLB chunk types, as their little-endian `u32`s.
+const GLB_JSON: u32 = 0x4E4F_534A;
+const GLB_BIN: u32 = 0x004E_4942;
+
+/// The first bytes of a binary FBX.
+const FBX_BINARY_MAGIC: &[u8] = b"Kaydara FBX Binary  \0";
+
+/// A little-endian `u32` at `offset`, if the contents are long enough to have one.
+fn read_u32(contents: &[u8], offset: usize) -> Option<u32> {
+    let bytes = contents.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The size in bytes of one component of an accessor, by its `componentType`.
+fn component_size(component_type: u64) -> Option<usize> {
+    match component_type {
+        5120 | 5121 => Some(1),
+        5122 | 5123 => Some(2),
+        5125 | 5126 => Some(4),
+        _ => None,
+    }
+}
+
+/// The number of components in one element of an accessor, by its `type`.
+fn component_count(kind: &str) -> Option<usize> {
+    match kind {
+        "SCALAR" => Some(1),
+        "VEC2" => Some(2),
+        "VEC3" => Some(3),
+        "VEC4" | "MAT2" => Some(4),
+        "MAT3" => Some(9),
+        "MAT4" => Some(16),
+        _ => None,
+    }
+}
+
+/// The length of the data in a base64 `data:` URI, without decoding it.
+fn data_uri_length(uri: &str) -> Option<usize> {
+    let (_, data) = uri.strip_prefix("data:")?.split_once(";base64,")?;
+    let padding = data.bytes().rev().take_while(|byte| *byte == b'=').count();
+    Some((data.len() * 3 / 4).saturating_sub(padding))
+}
+
+/// Check that everything a glTF document refers to exists and fits in the buffers it is in.
+///
+/// `buffer_length` gives the actual length of a buffer from its index and definition, or None if the data isn't at
+/// hand to measure, e.g. a `.bin` file that wasn't given.
+fn check_gltf(document: &Value, buffer_length: impl Fn(usize, &Value) -> Option<usize>, problems: &mut Vec<String>) {
+    let array = |key: &str| document[key].as_array().map(Vec::as_slice).unwrap_or_default();
+    let (buffers, views, accessors) = (array("buffers"), array("bufferViews"), array("accessors"));
+
+    match document["asset"]["version"].as_str() {
+        None => problems.push("the glTF has no asset version".to_string()),
+        Some(version) if !version.starts_with("2.") => {
+            problems.push(format!("the glTF is version {}, not 2.0", version))
+        }
+        Some(_) => {}
+    }
+
+    for (index, buffer) in buffers.iter().enumerate() {
+        let Some(declared) = buffer["byteLength"].as_u64() else {
+            problems.push(format!("buffer {} has no byteLength", index));
+            continue;
+        };
+        if let Some(actual) = buffer_length(index, buffer).filter(|actual| (*actual as u64) < declared) {
+            problems.push(format!(
+                "buffer {} says it is {} bytes, but only {} bytes of it are there",
+                index, declared, actual
+            ));
+        }
+    }
+
+    for (index, view) in views.iter().enumerate() {
+        let Some(buffer) = view["buffer"].as_u64().and_then(|buffer| buffers.get(buffer as usize)) else {
+            problems.push(format!("buffer view {} refers to a buffer that doesn't exist", index));
+            continue;
+        };
+        let end = view["byteOffset"].as_u64().unwrap_or_default() + view["byteLength"].as_u64().unwrap_or_default();
+        let length = buffer["byteLength"].as_u64().unwrap_or_default();
+        if end > length {
+            problems.push(format!(
+                "buffer view {} ends at byte {}, past the end of its {} byte buffer",
+                index, end, length
+            ));
+        }
+    }
+
+    for (index, accessor) in accessors.iter().enumerate() {
+        let count = accessor["count"].as_u64().unwrap_or_default() as usize;
+        if count == 0 {
+            problems.push(format!("accessor {} has no elements", index));
+        }
+        let (Some(size), Some(components)) = (
+            accessor["componentType"].as_u64().and_then(component_size),
+            accessor["type"].as_str().and_then(component_count),
+        ) else {
+            problems.push(format!("accessor {} has an unknown componentType or type", index));
+            continue;
+        };
+        let Some(view_index) = accessor["bufferView"].as_u64() else {
+            // Without a buffer view the accessor is all zeros, which takes no space.
+            continue;
+        };
+        let Some(view) = views.get(view_index as usize) else {
+            problems.push(format!("accessor {} refers to a buffer view that doesn't exist", index));
+            continue;
+        };
+        let element = size * components;
+        let stride = view["byteStride"].as_u64().map_or(element, |stride| stride as usize);
+        let needed =
+            accessor["byteOffset"].as_u64().unwrap_or_default() as usize + stride * count.saturating_sub(1) + element;
+        let available = view["byteLength"].as_u64().unwrap_or_default() as usize;
+        if needed > available {
+            problems.push(format!(
+                "accessor {} needs {} bytes of buffer view {}, which only has {}",
+                index, needed, view_index, available
+            ));
+        }
+    }
+
+    let accessor_count = |index: &Value| {
+        index
+            .as_u64()
+            .and_then(|index| accessors.get(index as usize))
+            .map(|accessor| accessor["count"].as_u64().unwrap_or_default())
+    };
+    for (mesh_index, mesh) in array("meshes").iter().enumerate() {
+        for (primitive_index, primitive) in mesh["primitives"].as_array().into_iter().flatten().enumerate() {
+            let name = format!("mesh {} primitive {}", mesh_index, primitive_index);
+            let attributes = primitive["attributes"].as_object().into_iter().flatten();
+            for (attribute, accessor) in attributes {
+                if accessor_count(accessor).is_none() {
+                    problems.push(format!(
+                        "{} refers to a {} accessor that doesn't exist",
+                        name, attribute
+                    ));
+                }
+            }
+            let Some(positions) = accessor_count(&primitive["attributes"]["POSITION"]) else {
+                problems.push(format!("{} has no positions", name));
+                continue;
+            };
+            let corners = match primitive.get("indices") {
+                Some(indices) => match accessor_count(indices) {
+                    Some(count) => count,
+                    None => {
+                        problems.push(format!("{} refers to an indices accessor that doesn't exist", name));
+                        continue;
+                    }
+                },
+                None => positions,
+            };
+            // Triangles are the default mode.
+            if primitive["mode"].as_u64().unwrap_or(4) == 4 && corners % 3 != 0 {
+                problems.push(format!(
+                    "{} has {} corners, which isn't a whole number of triangles",
+                    name, corners
+                ));
+            }
+        }
+    }
+
+    let meshes = array("meshes").len();
+    let nodes = array("nodes");
+    for (index, node) in nodes.iter().enumerate() {
+        if node["mesh"].as_u64().is_some_and(|mesh| mesh as usize >= meshes) {
+            problems.push(format!("node {} refers to a mesh that doesn't exist", index));
+        }
+        let children = node["children"].as_array().into_iter().flatten();
+        if children
+            .filter_map(Value::as_u64)
+            .any(|child| child as usize >= nodes.len())
+        {
+            problems.push(format!("node {} has a child that doesn't exist", index));
+        }
+    }
+    for (index, scene) in array("scenes").iter().enumerate() {
+        let scene_nodes = scene["nodes"].as_array().into_iter().flatten();
+        if scene_nodes
+            .filter_map(Value::as_u64)
+            .any(|node| node as usize >= nodes.len())
+        {
+            problems.push(format!("scene {} has a node that doesn't exist", index));
+        }
+    }
+}
+
+/// The actual length of a glTF buffer that isn't in a GLB, from a data URI or another file of the same export.
+fn external_buffer_length(buffer: &Value, siblings: &[ExportFile]) -> Option<usize> {
+    let uri = buffer["uri"].as_str()?;
+    data_uri_length(uri).or_else(|| {
+        siblings
+            .iter()
+            .find(|sibling| sibling.name == uri)
+            .map(|sibling| sibling.contents.len())
+    })
+}
+
+/// Check a GLB's header and chunks, and the glTF document in it.
+fn check_glb(contents: &[u8], problems: &mut Vec<String>) {
+    if contents.len() < 12 {
+        problems.push(format!(
+            "the file is {} bytes, too short for a GLB header",
+            contents.len()
+        ));
+        return;
+    }
+    if &contents[0..4] != b"glTF" {
+        problems.push("the file doesn't start with the GLB magic `glTF`".to_string());
+        return;
+    }
+    let version = read_u32(contents, 4).unwrap_or_default();
+    if version != 2 {
+        problems.push(format!("the GLB is version {}, not 2", version));
+    }
+    let length = read_u32(contents, 8).unwrap_or_default() as usize;
+    if length != contents.len() {
+        problems.push(format!(
+            "the GLB header says the file is {} bytes, but it is {}",
+            length,
+            contents.len()
+        ));
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset < contents.len().min(length) {
+        let (Some(chunk_length), Some(chunk_type)) = (read_u32(contents, offset), read_u32(contents, offset + 4))
+        else {
+            problems.push(format!("the chunk header at byte {} is truncated", offset));
+            break;
+        };
+        let (chunk_length, start) = (chunk_length as usize, offset + 8);
+        let name = match chunk_type {
+            GLB_JSON => "JSON",
+            GLB_BIN => "BIN",
+            _ => "unknown",
+        };
+        if chunk_length % 4 != 0 {
+            problems.push(format!("the {} chunk is {} bytes, not padded to 4", name, chunk_length));
+        }
+        if start + chunk_length > contents.len() {
+            problems.push(format!(
+                "the {} chunk says it is {} bytes, but only {} are left",
+                name,
+                chunk_length,
+                contents.len() - start
+            ));
+        }
+        chunks.push((
+            chunk_type,
+            &contents[start.min(contents.len())..(start + chunk_length).min(contents.len())],
+        ));
+        offset = start + chunk_length;
+    }
+
+    let Some((GLB_JSON, json)) = chunks.first().copied() else {
+        problems.push("the first chunk of the GLB isn't JSON".to_string());
+        return;
+    };
+    let bin = chunks
+        .get(1)
+        .filter(|(chunk_type, _)| *chunk_type == GLB_BIN)
+        .map(|(_, bin)| bin.len());
+    let document: Value = match serde_json::from_slice(json) {
+        Ok(document) => document,
+        Err(err) => {
+            problems.push(format!("the JSON chunk isn't valid JSON: {}", err));
+            return;
+        }
+    };
+    if bin.is_none() && document["buffers"][0].is_object() && document["buffers"][0]["uri"].is_null() {
+        problems.push("buffer 0 is the GLB's BIN chunk, but there isn't one".to_string());
+    }
+
+    check_gltf(
+        &document,
+        |index, buffer| match index {
+            0 if buffer["uri"].is_null() => bin,
+            _ => external_buffer_length(buffer, &[]),
+        },
+        problems,
+    );
+}
+
+/// Check that an STL, binary or ASCII, has as many triangles as it says it does.
+fn check_stl(contents: &[u8], problems: &mut Vec<String>) {
+    // A binary STL's 80 byte header can happen to start with `solid` too, so it only counts as ASCII if it has the
+    // keywords of one.
+    let text = std::str::from_utf8(contents)
+        .ok()
+        .filter(|text| text.trim_start().starts_with("solid") && (text.contains("facet") || text.contains("endsolid")));
+    let Some(text) = text else {
+        let Some(count) = read_u32(contents, 80) else {
+            problems.push(format!(
+                "the file is {} bytes, too short for a binary STL header",
+                contents.len()
+            ));
+            return;
+        };
+        let expected = 84 + 50 * count as usize;
+        if expected != contents.len() {
+            problems.push(format!(
+                "the header says there are {} triangles, which take {} bytes, but the file is {} bytes",
+                count,
+                expected,
+                contents.len()
+            ));
+        }
+        if count == 0 {
+            problems.push("the STL has no triangles".to_string());
+        }
+        return;
+    };
+
+    let (mut facets, mut ends, mut vertices, mut bad_vertices) = (0, 0, 0, 0);
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("facet") => facets += 1,
+            Some("endfacet") => ends += 1,
+            Some("vertex") => {
+                vertices += 1;
+                let numbers: Vec<Option<f64>> = words.map(|word| word.parse().ok()).collect();
+                if numbers.len() != 3 || numbers.iter().any(|number| !number.is_some_and(f64::is_finite)) {
+                    bad_vertices += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if !text
+        .trim_end()
+        .lines()
+        .last()
+        .unwrap_or_default()
+        .trim_start()
+        .starts_with("endsolid")
+    {
+        problems.push("the ASCII STL doesn't end with `endsolid`, so it may be truncated".to_string());
+    }
+    if facets != ends {
+        problems.push(format!("{} facets are started but {} are ended", facets, ends));
+    }
+    if vertices != 3 * facets {
+        problems.push(format!(
+            "there are {} vertices for {} facets, rather than 3 each",
+            vertices, facets
+        ));
+    }
+    if bad_vertices > 0 {
+        problems.push(format!("{} vertices don't have 3 finite coordinates", bad_vertices));
+    }
+    if facets == 0 {
+        problems.push("the STL has no triangles".to_string());
+    }
+}
+
+/// Check that a STEP file has the header and sections every STEP file has, and its end.
+fn check_step(contents: &[u8], problems: &mut Vec<String>) {
+    let Ok(text) = std::str::from_utf8(contents) else {
+        problems.push("the STEP file isn't text".to_string());
+        return;
+    };
+    if !text.trim_start().starts_with("ISO-10303-21;") {
+        problems.push("the file doesn't start with `ISO-10303-21;`".to_string());
+    }
+    for section in ["HEADER;", "FILE_SCHEMA", "DATA;"] {
+        if !text.contains(section) {
+            problems.push(format!("the file has no `{}`", section));
+        }
+    }
+    if !text.trim_end().ends_with("END-ISO-10303-21;") {
+        problems.push("the file doesn't end with `END-ISO-10303-21;`, so it may be truncated".to_string());
+    }
+}
+
+/// Check that an OBJ's faces only use vertices it has.
+fn check_obj(contents: &[u8], problems: &mut Vec<String>) {
+    let Ok(text) = std::str::from_utf8(contents) else {
+        problems.push("the OBJ file isn't text".to_string());
+        return;
+    };
+    let (mut vertices, mut bad_faces) = (0i64, 0);
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => vertices += 1,
+            Some("f") => {
+                // Each corner is `v`, `v/vt`, `v/vt/vn` or `v//vn`, and negative indices count back from the end.
+                let in_range = |corner: &str| {
+                    let index: Option<i64> = corner.split('/').next().and_then(|index| index.parse().ok());
+                    index.is_some_and(|index| (1..=vertices).contains(&index) || (-vertices..0).contains(&index))
+                };
+                if !words.all(in_range) {
+                    bad_faces += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if bad_faces > 0 {
+        problems.push(format!("{} faces use vertices that don't exist", bad_faces));
+    }
+}
+
+/// Check a PLY's header, and that an ASCII PLY has as many elements as its header says.
+fn check_ply(contents: &[u8], problems: &mut Vec<String>) {
+    if !contents.starts_with(b"ply") {
+        problems.push("the file doesn't start with `ply`".to_string());
+        return;
+    }
+    let header_end = contents
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header");
+    if header_end.is_none() {
+        problems.push("the PLY header has no `end_header`".to_string());
+        return;
+    }
+    if std::str::from_utf8(contents).is_ok_and(|text| text.contains("format ascii")) {
+        if let Err(err) = mesh::ply_counts(contents) {
+            problems.push(err);
+        }
+    }
+}
+
+/// Check that an FBX starts like a binary or ASCII FBX does.
+fn check_fbx(contents: &[u8], problems: &mut Vec<String>) {
+    if !contents.starts_with(FBX_BINARY_MAGIC) && !contents.starts_with(b"; FBX") {
+        problems.push("the file doesn't start like a binary or ASCII FBX".to_string());
+    }
+}
+
+/// The structural problems with an exported file, by its format, checking a glTF's buffers against the other files
+/// of the same export.
+pub(crate) fn problems(file: &ExportFile, siblings: &[ExportFile]) -> Vec<String> {
+    let extension = std::path::Path::new(&file.name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    let mut problems = Vec::new();
+    if file.contents.is_empty() {
+        problems.push("the file is empty".to_string());
+        return problems;
+    }
+    match file.format.as_deref().or(extension.as_deref()) {
+        Some("glb") => check_glb(&file.contents, &mut problems),
+        Some("gltf") => match serde_json::from_slice::<Value>(&file.contents) {
+            Ok(document) => check_gltf(
+                &document,
+                |_, buffer| external_buffer_length(buffer, siblings),
+                &mut problems,
+            ),
+            Err(err) => problems.push(format!("the glTF isn't valid JSON: {}", err)),
+        },
+        Some("stl") => check_stl(&file.contents, &mut problems),
+        Some("step" | "stp") => check_step(&file.contents, &mut problems),
+        Some("obj") => check_obj(&file.contents, &mut problems),
+        Some("ply") => check_ply(&file.contents, &mut problems),
+        Some("fbx") => check_fbx(&file.contents, &mut problems),
+        _ => {}
+    }
+
+    problems
+}
+
+/// Raise `InvalidExportError` if any of the files of an export has problems.
+pub(crate) fn check(files: &[ExportFile]) -> PyResult<()> {
+    let problems: Vec<String> = files
+        .iter()
+        .flat_map(|file| {
+            problems(file, files)
+                .into_iter()
+                .map(move |problem| format!("{}: {}", file.name, problem))
+        })
+        .collect();
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!("The export has {} problem(s):\n{}", problems.len(), problems.join("\n"));
+    Err(InvalidExportError::new_err((message, problems)))
+}
+
+/// Check the structure of an exported file for its format, returning the problems found, or nothing if it's sound.
+///
+/// GLB files have their header and chunks checked, and GLB and glTF files that every buffer view, accessor and mesh
+/// fits in the data it refers to. STL files must have as many triangles as they say, STEP files the header and
+/// sections every STEP file has, OBJ faces only use vertices that exist, and PLY and FBX files must start like one.
+/// The buffers of a glTF in other files can't be measured, so only their definitions are checked. Formats with
+/// nothing to check, like the materials of an OBJ, have no problems.
+#[pyfunction]
+pub(crate) fn validate_export(file: &Bound<'_, ExportFile>) -> Vec<String> {
+    problems(file.get(), &[])
+}
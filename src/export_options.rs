@@ -0,0 +1,197 @@
+//! Options for exports that only some file formats have.
+
+use kittycad_modeling_cmds::format::{fbx, gltf, ply, stl, Selection};
+use pyo3::{pyclass, pymethods, FromPyObject, PyResult};
+use serde::{Deserialize, Serialize};
+
+use crate::{ExportSelection, FileExportFormat};
+
+/// Check the name of a storage or presentation, e.g. `ascii`, and return it as the engine writes it.
+fn parse<T: std::str::FromStr + std::fmt::Display>(what: &str, name: &str, valid: &str) -> PyResult<String> {
+    let value: T = name.trim().to_lowercase().parse().map_err(|_| {
+        pyo3::exceptions::PyValueError::new_err(format!("Unknown {} {:?}, expected one of: {}", what, name, valid))
+    })?;
+    Ok(value.to_string())
+}
+
+/// Options for FBX exports.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq)]
+pub struct FbxOptions {
+    /// How the file is encoded: `binary` or `ascii`.
+    #[pyo3(get)]
+    pub storage: String,
+}
+
+#[pymethods]
+impl FbxOptions {
+    #[new]
+    #[pyo3(signature = (storage = "binary".to_string()))]
+    fn new(storage: String) -> PyResult<Self> {
+        let storage = parse::<fbx::export::Storage>("FBX storage", &storage, "binary, ascii")?;
+        Ok(FbxOptions { storage })
+    }
+}
+
+/// Options for STL exports.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq)]
+pub struct StlOptions {
+    /// How the file is encoded: `ascii` or `binary`.
+    #[pyo3(get)]
+    pub storage: String,
+    /// Which part of the scene to export, or the default scene if not set.
+    #[pyo3(get)]
+    pub selection: Option<ExportSelection>,
+}
+
+#[pymethods]
+impl StlOptions {
+    #[new]
+    #[pyo3(signature = (storage = "ascii".to_string(), selection = None))]
+    fn new(storage: String, selection: Option<ExportSelection>) -> PyResult<Self> {
+        let storage = parse::<stl::export::Storage>("STL storage", &storage, "ascii, binary")?;
+        Ok(StlOptions { storage, selection })
+    }
+}
+
+/// Options for PLY exports.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq)]
+pub struct PlyOptions {
+    /// How the file is encoded: `ascii`, `binary_little_endian` or `binary_big_endian`.
+    #[pyo3(get)]
+    pub storage: String,
+    /// Which part of the scene to export, or the default scene if not set.
+    #[pyo3(get)]
+    pub selection: Option<ExportSelection>,
+}
+
+#[pymethods]
+impl PlyOptions {
+    #[new]
+    #[pyo3(signature = (storage = "ascii".to_string(), selection = None))]
+    fn new(storage: String, selection: Option<ExportSelection>) -> PyResult<Self> {
+        let storage = parse::<ply::export::Storage>(
+            "PLY storage",
+            &storage,
+            "ascii, binary_little_endian, binary_big_endian",
+        )?;
+        Ok(PlyOptions { storage, selection })
+    }
+}
+
+/// Options for glTF and GLB exports.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq)]
+pub struct GltfOptions {
+    /// How the file is stored: `binary` for GLB, or `embedded` or `standard` for glTF. Defaults to what the export
+    /// format says.
+    #[pyo3(get)]
+    pub storage: Option<String>,
+    /// How the JSON is laid out: `compact` or `pretty`. Defaults to compact for GLB and pretty for glTF.
+    #[pyo3(get)]
+    pub presentation: Option<String>,
+}
+
+#[pymethods]
+impl GltfOptions {
+    #[new]
+    #[pyo3(signature = (storage = None, presentation = None))]
+    fn new(storage: Option<String>, presentation: Option<String>) -> PyResult<Self> {
+        let storage = storage
+            .map(|storage| parse::<gltf::export::Storage>("glTF storage", &storage, "binary, embedded, standard"))
+            .transpose()?;
+        let presentation = presentation
+            .map(|presentation| {
+                parse::<gltf::export::Presentation>("glTF presentation", &presentation, "compact, pretty")
+            })
+            .transpose()?;
+        Ok(GltfOptions { storage, presentation })
+    }
+}
+
+/// The options for any one format, as passed to the export functions.
+#[derive(FromPyObject)]
+pub(crate) enum ExportOptions {
+    Fbx(FbxOptions),
+    Stl(StlOptions),
+    Ply(PlyOptions),
+    Gltf(GltfOptions),
+}
+
+/// Everything about an export besides its format that the engine is told.
+///
+/// The defaults are what each format is exported with when no options are given.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OutputOptions {
+    pub fbx_storage: Option<fbx::export::Storage>,
+    pub stl_storage: Option<stl::export::Storage>,
+    pub ply_storage: Option<ply::export::Storage>,
+    pub gltf_storage: Option<gltf::export::Storage>,
+    pub gltf_presentation: Option<gltf::export::Presentation>,
+    pub selection: Selection,
+}
+
+impl OutputOptions {
+    /// Check the options and selection suit the format and the objects being exported, before anything is sent to the
+    /// engine.
+    pub(crate) fn new(
+        export_format: &FileExportFormat,
+        format_options: Option<ExportOptions>,
+        selection: Option<ExportSelection>,
+        entity_ids: &[uuid::Uuid],
+    ) -> PyResult<Self> {
+        let mismatch = |options: &str| {
+            Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "{} can't be used with {:?} exports",
+                options, export_format
+            )))
+        };
+        let mut output = OutputOptions::default();
+        let mut options_selection = None;
+        match (format_options, export_format) {
+            (None, _) => {}
+            (Some(ExportOptions::Fbx(options)), FileExportFormat::Fbx) => {
+                output.fbx_storage = options.storage.parse().ok();
+            }
+            (Some(ExportOptions::Stl(options)), FileExportFormat::Stl) => {
+                output.stl_storage = options.storage.parse().ok();
+                options_selection = options.selection;
+            }
+            (Some(ExportOptions::Ply(options)), FileExportFormat::Ply) => {
+                output.ply_storage = options.storage.parse().ok();
+                options_selection = options.selection;
+            }
+            (Some(ExportOptions::Gltf(options)), FileExportFormat::Glb | FileExportFormat::Gltf) => {
+                let storage: Option<gltf::export::Storage> = options.storage.and_then(|storage| storage.parse().ok());
+                let binary = *export_format == FileExportFormat::Glb;
+                if storage.is_some_and(|storage| (storage == gltf::export::Storage::Binary) != binary) {
+                    return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                        "{:?} exports can't have {} storage, binary storage is Glb and the others are Gltf",
+                        export_format,
+                        storage.map(|storage| storage.to_string()).unwrap_or_default()
+                    )));
+                }
+                output.gltf_storage = storage;
+                output.gltf_presentation = options.presentation.and_then(|presentation| presentation.parse().ok());
+            }
+            (Some(ExportOptions::Fbx(_)), _) => return mismatch("FbxOptions"),
+            (Some(ExportOptions::Stl(_)), _) => return mismatch("StlOptions"),
+            (Some(ExportOptions::Ply(_)), _) => return mismatch("PlyOptions"),
+            (Some(ExportOptions::Gltf(_)), _) => return mismatch("GltfOptions"),
+        }
+
+        let selection = match (selection, options_selection) {
+            (Some(_), Some(_)) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "Give the selection either as an argument or in the format options, not both",
+                ))
+            }
+            (selection, options_selection) => selection.or(options_selection),
+        };
+        output.selection = ExportSelection::validate(selection.as_ref(), export_format, entity_ids)?;
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,277 @@
+//! Linting and checking the formatting of code, of one program or many files at once, reporting every problem rather
+//! than stopping at the first.
+
+use std::collections::BTreeMap;
+
+use pyo3::{pyclass, pyfunction, pymethods, types::PyAny, Bound, PyResult};
+
+use crate::{
+    diff, lint_program,
+    lints::{LintFinding, LintSeverity, Severity},
+    recast, Diagnostic, FormatOptions,
+};
+
+/// What `check` found in some code.
+#[derive(Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct CheckReport {
+    /// The errors that stopped the code parsing. When there are any, the code isn't formatted or linted.
+    #[pyo3(get)]
+    pub parse_errors: Vec<Diagnostic>,
+    /// The unified diff from the code to the formatted code, or empty if the code is already formatted.
+    #[pyo3(get)]
+    pub format_diff: String,
+    /// The lint findings.
+    #[pyo3(get)]
+    pub lint_findings: Vec<LintFinding>,
+}
+
+#[pymethods]
+impl CheckReport {
+    /// Whether the code parses, is formatted and has no lint findings.
+    #[getter]
+    fn ok(&self) -> bool {
+        self.parse_errors.is_empty() && self.format_diff.is_empty() && self.lint_findings.is_empty()
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// What was found in one file by `lint_paths` or `format_check_paths`.
+#[derive(Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct FileReport {
+    /// The path, as it was given.
+    #[pyo3(get)]
+    pub path: String,
+    /// The lint findings, from `lint_paths`. Always empty from `format_check_paths`.
+    #[pyo3(get)]
+    pub findings: Vec<LintFinding>,
+    /// Whether the file is already formatted, from `format_check_paths`. None from `lint_paths`, or if the file
+    /// couldn't be read or parsed.
+    #[pyo3(get)]
+    pub formatted: Option<bool>,
+    /// Why the file couldn't be checked: it doesn't exist, isn't UTF-8, or doesn't parse.
+    #[pyo3(get)]
+    pub error: Option<Diagnostic>,
+}
+
+#[pymethods]
+impl FileReport {
+    /// Whether the file was checked and nothing was found.
+    #[getter]
+    fn ok(&self) -> bool {
+        self.error.is_none() && self.findings.is_empty() && self.formatted != Some(false)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// What was found in each of many files, in the order the paths were given.
+#[derive(Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct PathsReport {
+    /// The report for each file.
+    #[pyo3(get)]
+    pub files: Vec<FileReport>,
+}
+
+#[pymethods]
+impl PathsReport {
+    /// Whether every file was checked and nothing was found in any of them.
+    #[getter]
+    fn ok(&self) -> bool {
+        self.files.iter().all(FileReport::ok)
+    }
+
+    /// The reports of the files that weren't ok.
+    fn failed(&self) -> Vec<FileReport> {
+        self.files.iter().filter(|file| !file.ok()).cloned().collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.files.len()
+    }
+
+    fn __getitem__(&self, path: &str) -> PyResult<FileReport> {
+        self.files
+            .iter()
+            .find(|file| file.path == path)
+            .cloned()
+            .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err(path.to_string()))
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// What `format_dir` did: the files it changed, and why it couldn't format the others.
+#[derive(Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct FormatDirResult {
+    /// The paths of the files that were formatted, or with `check` that would be.
+    #[pyo3(get)]
+    pub changed: Vec<String>,
+    /// Why each file or directory that couldn't be formatted wasn't, by its path: it couldn't be read or written,
+    /// isn't UTF-8, or doesn't parse.
+    #[pyo3(get)]
+    pub errors: BTreeMap<String, Diagnostic>,
+}
+
+#[pymethods]
+impl FormatDirResult {
+    /// Whether every file was formatted, or with `check` is already formatted.
+    #[getter]
+    fn ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// An error about a whole file rather than a place in it.
+pub(crate) fn file_error(message: String) -> Diagnostic {
+    Diagnostic {
+        severity: "error".to_string(),
+        message,
+        start: 0,
+        end: 0,
+    }
+}
+
+/// Read a file as kcl code.
+pub(crate) fn read(path: &str) -> Result<String, Diagnostic> {
+    let contents = std::fs::read(path).map_err(|err| file_error(format!("Could not read {}: {}", path, err)))?;
+    String::from_utf8(contents).map_err(|_| file_error(format!("{} is not valid UTF-8", path)))
+}
+
+/// What checking one file found.
+enum Checked {
+    Findings(Vec<LintFinding>),
+    Formatted(bool),
+    Failed(Diagnostic),
+}
+
+/// Check each file, with anything that stops a file being checked reported as its error.
+fn check_paths(paths: Vec<String>, check: impl Fn(String) -> PyResult<Checked>) -> PyResult<PathsReport> {
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let checked = match read(&path) {
+                Ok(code) => check(code)?,
+                Err(err) => Checked::Failed(err),
+            };
+            let (findings, formatted, error) = match checked {
+                Checked::Findings(findings) => (findings, None, None),
+                Checked::Formatted(formatted) => (Vec::new(), Some(formatted), None),
+                Checked::Failed(err) => (Vec::new(), None, Some(err)),
+            };
+            Ok(FileReport {
+                path,
+                findings,
+                formatted,
+                error,
+            })
+        })
+        .collect::<PyResult<_>>()?;
+
+    Ok(PathsReport { files })
+}
+
+/// Lint every file, reporting the findings of each, or why it couldn't be linted, without raising for any one file.
+///
+/// With `min_severity`, only findings at least that severe are reported, as with `lint`.
+#[pyfunction]
+#[pyo3(signature = (paths, min_severity = None))]
+pub(crate) fn lint_paths(paths: Vec<String>, min_severity: Option<Severity>) -> PyResult<PathsReport> {
+    let min_severity = min_severity.map(LintSeverity::try_from).transpose()?;
+    check_paths(paths, |code| match kcl_lib::Program::parse_no_errs(&code) {
+        Ok(program) => Ok(Checked::Findings(lint_program(&program, min_severity)?)),
+        Err(err) => Ok(Checked::Failed(Diagnostic::from(err))),
+    })
+}
+
+/// Check whether every file is formatted, reporting each one, or why it couldn't be checked, without raising for any
+/// one file. Nothing is written.
+#[pyfunction]
+#[pyo3(signature = (paths, options = None))]
+pub(crate) fn format_check_paths(paths: Vec<String>, options: Option<FormatOptions>) -> PyResult<PathsReport> {
+    let options: kcl_lib::FormatOptions = options.unwrap_or_default().into();
+    check_paths(paths, |code| match recast(&code, &options) {
+        Ok(recasted) => Ok(Checked::Formatted(recasted == code)),
+        Err(err) => Ok(Checked::Failed(Diagnostic::from(err))),
+    })
+}
+
+/// Check that the code parses, is formatted and is lint clean, reporting everything found rather than raising.
+///
+/// Only the lint rules with the given codes (e.g. `["Z0001"]`) are used, or every rule if none are given. The format
+/// diff ignores line endings, so code with `\r\n` line endings that is otherwise formatted has an empty diff.
+#[pyfunction]
+#[pyo3(signature = (code, rules = None, format_options = None))]
+pub(crate) fn check(
+    code: String,
+    rules: Option<Vec<String>>,
+    format_options: Option<FormatOptions>,
+) -> PyResult<CheckReport> {
+    let mut report = CheckReport {
+        parse_errors: Vec::new(),
+        format_diff: String::new(),
+        lint_findings: Vec::new(),
+    };
+    let program = match kcl_lib::Program::parse(&code) {
+        Ok((program, errors)) => {
+            report.parse_errors = errors
+                .into_iter()
+                .filter(|err| err.severity.is_err())
+                .map(Diagnostic::from)
+                .collect();
+            program
+        }
+        Err(err) => {
+            report.parse_errors.push(Diagnostic::from(err));
+            None
+        }
+    };
+    let Some(program) = program.filter(|_| report.parse_errors.is_empty()) else {
+        return Ok(report);
+    };
+
+    // The formatter always writes `\n`, so the code is compared with its own line endings made the same.
+    let options: kcl_lib::FormatOptions = format_options.unwrap_or_default().into();
+    let code = code.replace("\r\n", "\n");
+    report.format_diff = diff::unified_diff(&code, &program.ast.recast(&options, 0), "original", "formatted");
+    report.lint_findings = lint_program(&program, None)?
+        .into_iter()
+        .filter(|finding| {
+            rules
+                .as_ref()
+                .is_none_or(|rules| rules.iter().any(|rule| rule == finding.discovered.finding.code))
+        })
+        .collect();
+
+    Ok(report)
+}
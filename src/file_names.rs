@@ -0,0 +1,125 @@
+//! Naming exported files, rather than keeping the names the engine chose.
+
+use std::collections::HashMap;
+
+use pyo3::PyResult;
+
+use crate::ExportFile;
+
+/// Check a name given for exported files is a plain file name, that can't put them anywhere but where they're saved.
+pub(crate) fn check_base_name(base_name: &str) -> PyResult<()> {
+    let invalid = |reason: &str| {
+        Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Invalid base_name {:?}: {}",
+            base_name, reason
+        )))
+    };
+    if base_name.trim().is_empty() {
+        return invalid("it is empty");
+    }
+    if base_name.contains(['/', '\\']) {
+        return invalid("it must not contain path separators");
+    }
+    if base_name == "." || base_name == ".." || base_name.contains('\0') {
+        return invalid("it must be a file name");
+    }
+
+    Ok(())
+}
+
+/// The extension of a file name, e.g. `bin` for `output.bin`, or empty if it has none.
+fn extension(name: &str) -> &str {
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+}
+
+/// Rename exported files to `{base_name}.{ext}`, keeping each file's extension.
+///
+/// If two files have the same extension, the engine's name for each is kept after the base name, e.g.
+/// `part_output.bin`. Files that refer to each other by name, the buffers and images of a glTF and the materials of
+/// an OBJ, are changed to use the new names.
+pub(crate) fn rename(files: Vec<ExportFile>, base_name: &str) -> PyResult<Vec<ExportFile>> {
+    check_base_name(base_name)?;
+
+    let mut counts = HashMap::new();
+    for file in &files {
+        *counts.entry(extension(&file.name)).or_insert(0) += 1;
+    }
+    let renamed: HashMap<String, String> = files
+        .iter()
+        .map(|file| {
+            let extension = extension(&file.name);
+            let name = match (counts[extension], extension) {
+                (1, "") => base_name.to_string(),
+                (1, extension) => format!("{}.{}", base_name, extension),
+                _ => format!("{}_{}", base_name, file.name),
+            };
+            (file.name.clone(), name)
+        })
+        .collect();
+
+    files
+        .into_iter()
+        .map(|file| {
+            let contents = match extension(&file.name) {
+                "gltf" => gltf_references(file.contents, &renamed)?,
+                "obj" => obj_references(file.contents, &renamed),
+                _ => file.contents,
+            };
+            Ok(ExportFile {
+                contents,
+                name: renamed[&file.name].clone(),
+                ..file
+            })
+        })
+        .collect()
+}
+
+/// Point the buffer and image URIs of a glTF at the renamed files.
+///
+/// Only the names change, so the rest of the file is left as the engine wrote it.
+fn gltf_references(contents: Vec<u8>, renamed: &HashMap<String, String>) -> PyResult<Vec<u8>> {
+    let gltf: serde_json::Value = serde_json::from_slice(&contents).map_err(|err| {
+        pyo3::exceptions::PyValueError::new_err(format!("The exported glTF could not be read: {}", err))
+    })?;
+    let uris: Vec<&str> = ["buffers", "images"]
+        .iter()
+        .filter_map(|key| gltf.get(key).and_then(|items| items.as_array()))
+        .flatten()
+        .filter_map(|item| item.get("uri").and_then(|uri| uri.as_str()))
+        .filter(|uri| renamed.contains_key(*uri))
+        .collect();
+    if uris.is_empty() {
+        return Ok(contents);
+    }
+
+    let mut text = String::from_utf8_lossy(&contents).into_owned();
+    for uri in uris {
+        // The names are JSON strings, so they are replaced as they are quoted in the file.
+        let quoted = |name: &str| serde_json::Value::from(name).to_string();
+        text = text.replace(&quoted(uri), &quoted(&renamed[uri]));
+    }
+
+    Ok(text.into_bytes())
+}
+
+/// Point the `mtllib` lines of an OBJ at the renamed material files.
+fn obj_references(contents: Vec<u8>, renamed: &HashMap<String, String>) -> Vec<u8> {
+    let text = String::from_utf8_lossy(&contents);
+    if !text.lines().any(|line| line.starts_with("mtllib ")) {
+        return contents;
+    }
+
+    text.split_inclusive('\n')
+        .map(|line| match line.strip_prefix("mtllib ") {
+            Some(name) => match renamed.get(name.trim_end()) {
+                Some(new_name) => format!("mtllib {}{}", new_name, &name[name.trim_end().len()..]),
+                None => line.to_string(),
+            },
+            None => line.to_string(),
+        })
+        .collect::<String>()
+        .into_bytes()
+}
@@ -0,0 +1,371 @@
+//! Checking that the solids a program made are sound, e.g. closed, before they are exported to be printed.
+
+use std::collections::BTreeMap;
+
+use kcl_lib::ExecutorContext;
+use kittycad_modeling_cmds::{ok_response::OkModelingCmdResponse, ModelingCmd};
+use pyo3::{pyclass, pyfunction, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    artifact_graph, connection, defaults, find_solids, pickle, program::Code, run_code, scene, spawn, tags, trace,
+    with_reconnect, RunOptions, UnitLength, Units,
+};
+
+/// The smallest area or volume, in the units the program was executed in, that isn't taken to be none at all.
+const TOLERANCE: f64 = 1e-9;
+
+/// The result of one check of a solid.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct GeometryCheck {
+    /// What was checked: `watertight`, `degenerate_faces`, `volume` or `self_intersection`.
+    #[pyo3(get)]
+    pub name: String,
+    /// Whether the check `passed`, `failed` or was `skipped`.
+    #[pyo3(get)]
+    pub status: String,
+    /// Why the check was skipped, e.g. because the engine can't do it.
+    #[pyo3(get)]
+    pub reason: Option<String>,
+    /// What was found wrong, one problem per item, or anything the check couldn't look at.
+    #[pyo3(get)]
+    pub details: Vec<String>,
+}
+
+impl GeometryCheck {
+    /// A check that passed, or failed if there are problems.
+    fn checked(name: &str, problems: Vec<String>, notes: Vec<String>) -> Self {
+        let status = if problems.is_empty() { "passed" } else { "failed" };
+        GeometryCheck {
+            name: name.to_string(),
+            status: status.to_string(),
+            reason: None,
+            details: problems.into_iter().chain(notes).collect(),
+        }
+    }
+
+    /// A check that couldn't be done.
+    fn skipped(name: &str, reason: &str) -> Self {
+        GeometryCheck {
+            name: name.to_string(),
+            status: "skipped".to_string(),
+            reason: Some(reason.to_string()),
+            details: Vec::new(),
+        }
+    }
+}
+
+#[pymethods]
+impl GeometryCheck {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// The checks of one solid.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct SolidReport {
+    /// The name of the variable the solid is bound to, with its index if the variable holds more than one solid.
+    #[pyo3(get)]
+    pub name: String,
+    /// The id of the solid in the engine.
+    #[pyo3(get)]
+    pub id: String,
+    /// The source range of the code that made the solid, as a `(start, end)` pair of byte offsets, if known.
+    #[pyo3(get)]
+    pub source_range: Option<(usize, usize)>,
+    /// Each check of the solid, in the order they were done.
+    #[pyo3(get)]
+    pub checks: Vec<GeometryCheck>,
+}
+
+#[pymethods]
+impl SolidReport {
+    /// Whether none of the checks failed. Skipped checks don't count against the solid.
+    #[getter]
+    fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.status != "failed")
+    }
+
+    /// The check with the given name, if it was done.
+    fn check(&self, name: &str) -> Option<GeometryCheck> {
+        self.checks.iter().find(|check| check.name == name).cloned()
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// The checks of every solid a program made.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct GeometryReport {
+    /// The checks of each solid bound to a top level variable.
+    #[pyo3(get)]
+    pub solids: Vec<SolidReport>,
+}
+
+#[pymethods]
+impl GeometryReport {
+    /// Whether no check of any solid failed.
+    #[getter]
+    fn passed(&self) -> bool {
+        self.solids.iter().all(SolidReport::passed)
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// The unit of volume for a unit of length.
+fn volume_unit(units: UnitLength) -> kittycad_modeling_cmds::units::UnitVolume {
+    use kittycad_modeling_cmds::units::UnitVolume;
+
+    match units {
+        // There is no cubic millimeter, so the volume is only compared with zero.
+        UnitLength::Mm | UnitLength::Cm => UnitVolume::CubicCentimeters,
+        UnitLength::M => UnitVolume::CubicMeters,
+        UnitLength::In => UnitVolume::CubicInches,
+        UnitLength::Ft => UnitVolume::CubicFeet,
+        UnitLength::Yd => UnitVolume::CubicYards,
+    }
+}
+
+/// What the execution recorded about a solid: its faces and edges, by id.
+struct Parts<'a> {
+    faces: Vec<&'a str>,
+    edges: Vec<&'a str>,
+    source_range: Option<(usize, usize)>,
+}
+
+/// Find the faces and edges of a solid in the artifact graph. A solid has the id of the sketch it was made from, and
+/// its edges are the sketch's segments.
+fn parts<'a>(graph: &'a Value, id: &str) -> Option<Parts<'a>> {
+    let nodes: Vec<&Value> = graph["nodes"].as_array()?.iter().collect();
+    let sweep = nodes.iter().find(|node| node["id"] == id && node["type"] == "sweep")?;
+    let of_type = |types: &[&str], field: &str| -> Vec<&'a str> {
+        nodes
+            .iter()
+            .filter(|node| types.iter().any(|kind| node["type"] == *kind) && node[field] == id)
+            .filter_map(|node| node["id"].as_str())
+            .collect()
+    };
+
+    Some(Parts {
+        faces: of_type(&["wall", "cap"], "sweepId"),
+        edges: of_type(&["segment"], "pathId"),
+        source_range: scene::source_range(sweep),
+    })
+}
+
+/// What to call a face or edge in a problem: its tag, or else its id.
+fn describe(id: &str, names: &BTreeMap<String, String>) -> String {
+    match names.get(id) {
+        Some(name) => format!("{} ({})", name, id),
+        None => id.to_string(),
+    }
+}
+
+/// Parse an id recorded by the execution.
+fn uuid(id: &str) -> PyResult<uuid::Uuid> {
+    uuid::Uuid::parse_str(id).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))
+}
+
+/// Check that every edge of a solid is between exactly two faces, so the solid is closed and manifold.
+async fn check_watertight(
+    ctx: &ExecutorContext,
+    solid: uuid::Uuid,
+    parts: Option<&Parts<'_>>,
+    names: &BTreeMap<String, String>,
+) -> PyResult<GeometryCheck> {
+    const NAME: &str = "watertight";
+    let Some(parts) = parts.filter(|parts| !parts.edges.is_empty()) else {
+        return Ok(GeometryCheck::skipped(
+            NAME,
+            "the edges of the solid weren't recorded, e.g. as it is a copy made by a pattern",
+        ));
+    };
+
+    let (mut problems, mut unanswered) = (Vec::new(), Vec::new());
+    for edge in &parts.edges {
+        let cmd = ModelingCmd::Solid3dGetAllEdgeFaces(kittycad_modeling_cmds::Solid3dGetAllEdgeFaces {
+            object_id: solid,
+            edge_id: uuid(edge)?,
+        });
+        match tags::query(ctx, cmd).await {
+            Some(OkModelingCmdResponse::Solid3dGetAllEdgeFaces(data)) if data.faces.len() != 2 => problems.push(
+                format!("edge {} is on {} faces, not 2", describe(edge, names), data.faces.len()),
+            ),
+            Some(OkModelingCmdResponse::Solid3dGetAllEdgeFaces(_)) => {}
+            // An edge replaced by a fillet or chamfer is no longer on the solid.
+            _ => unanswered.push(*edge),
+        }
+    }
+    if unanswered.len() == parts.edges.len() {
+        return Ok(GeometryCheck::skipped(
+            NAME,
+            "the engine couldn't say which faces are on the solid's edges",
+        ));
+    }
+    let notes = unanswered
+        .iter()
+        .map(|edge| format!("edge {} couldn't be checked", describe(edge, names)))
+        .collect();
+
+    Ok(GeometryCheck::checked(NAME, problems, notes))
+}
+
+/// Check that no face of a solid has collapsed to nothing.
+async fn check_degenerate_faces(
+    ctx: &ExecutorContext,
+    parts: Option<&Parts<'_>>,
+    names: &BTreeMap<String, String>,
+    units: UnitLength,
+) -> PyResult<GeometryCheck> {
+    const NAME: &str = "degenerate_faces";
+    let Some(parts) = parts.filter(|parts| !parts.faces.is_empty()) else {
+        return Ok(GeometryCheck::skipped(
+            NAME,
+            "the faces of the solid weren't recorded, e.g. as it is a copy made by a pattern",
+        ));
+    };
+
+    let (mut problems, mut unanswered) = (Vec::new(), Vec::new());
+    for face in &parts.faces {
+        let cmd = ModelingCmd::SurfaceArea(kittycad_modeling_cmds::SurfaceArea {
+            entity_ids: vec![uuid(face)?],
+            output_unit: tags::area_unit(units),
+        });
+        match tags::query(ctx, cmd).await {
+            Some(OkModelingCmdResponse::SurfaceArea(data)) if data.surface_area <= TOLERANCE => {
+                problems.push(format!("face {} has no area", describe(face, names)))
+            }
+            Some(OkModelingCmdResponse::SurfaceArea(_)) => {}
+            _ => unanswered.push(*face),
+        }
+    }
+    if unanswered.len() == parts.faces.len() {
+        return Ok(GeometryCheck::skipped(
+            NAME,
+            "the engine couldn't measure the area of the solid's faces",
+        ));
+    }
+    let notes = unanswered
+        .iter()
+        .map(|face| format!("face {} couldn't be checked", describe(face, names)))
+        .collect();
+
+    Ok(GeometryCheck::checked(NAME, problems, notes))
+}
+
+/// Check that a solid encloses some volume, which a solid turned inside out or flattened doesn't.
+async fn check_volume(ctx: &ExecutorContext, solid: uuid::Uuid, units: UnitLength) -> GeometryCheck {
+    const NAME: &str = "volume";
+    let cmd = ModelingCmd::Volume(kittycad_modeling_cmds::Volume {
+        entity_ids: vec![solid],
+        output_unit: volume_unit(units),
+    });
+    match tags::query(ctx, cmd).await {
+        Some(OkModelingCmdResponse::Volume(data)) if data.volume <= TOLERANCE => GeometryCheck::checked(
+            NAME,
+            vec![format!("the volume is {} {}", data.volume, data.output_unit)],
+            Vec::new(),
+        ),
+        Some(OkModelingCmdResponse::Volume(_)) => GeometryCheck::checked(NAME, Vec::new(), Vec::new()),
+        _ => GeometryCheck::skipped(NAME, "the engine couldn't measure the volume of the solid"),
+    }
+}
+
+/// Execute the kcl code and check each solid it made is sound enough to export, e.g. to be printed.
+///
+/// Each solid bound to a top level variable is checked for being `watertight` (every edge is between exactly two
+/// faces), for `degenerate_faces` (faces with no area) and for having a positive `volume`. A check the engine can't
+/// do is `skipped` with the reason, rather than failing the whole call: the engine has no `self_intersection` check
+/// yet, so that one is always skipped. A solid or report `passed` if none of its checks failed.
+#[pyfunction]
+#[pyo3(signature = (code, units = None))]
+pub(crate) async fn execute_and_check_geometry(code: Code, units: Option<Units>) -> PyResult<GeometryReport> {
+    connection::require_token(None)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options) = (&code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            let graph = artifact_graph(&executed.exec_state)?;
+            let mut names = BTreeMap::new();
+            for value in trace::root_bindings(&executed.exec_state)?.values() {
+                tags::names_by_id(value, &mut names);
+            }
+
+            let solids = find_solids(&executed.exec_state)?;
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for (name, _) in &solids {
+                *counts.entry(name).or_default() += 1;
+            }
+            let mut indexes: BTreeMap<&str, usize> = BTreeMap::new();
+            let mut reports = Vec::new();
+            for (name, id) in &solids {
+                let index = indexes.entry(name).or_default();
+                let name = if counts[name.as_str()] > 1 {
+                    format!("{}[{}]", name, index)
+                } else {
+                    name.clone()
+                };
+                *index += 1;
+
+                let parts = parts(&graph, &id.to_string());
+                let checks = vec![
+                    check_watertight(&executed.ctx, *id, parts.as_ref(), &names).await?,
+                    check_degenerate_faces(&executed.ctx, parts.as_ref(), &names, executed.units).await?,
+                    check_volume(&executed.ctx, *id, executed.units).await,
+                    GeometryCheck::skipped(
+                        "self_intersection",
+                        "the engine has no check for a solid intersecting itself",
+                    ),
+                ];
+                reports.push(SolidReport {
+                    name,
+                    id: id.to_string(),
+                    source_range: parts.and_then(|parts| parts.source_range),
+                    checks,
+                });
+            }
+
+            Ok(GeometryReport { solids: reports })
+        })
+        .await
+    })
+    .await
+}
@@ -1,23 +1,428 @@
-use anyhow::Result;
+mod ast;
+mod batch;
+mod connection;
+mod default_planes;
+mod defaults;
+mod diff;
+mod editor;
+mod engine_error;
+mod export_checks;
+mod export_options;
+mod file_checks;
+mod file_names;
+mod geometry_checks;
+mod lints;
+mod logging;
+mod mesh;
+mod pickle;
+mod program;
+mod progress;
+mod project;
+mod rescale;
+mod sarif;
+mod scene;
+mod session;
+mod sketch;
+mod snapshot;
+mod stats;
+mod stdlib;
+mod storyboard;
+mod stubs;
+mod tags;
+mod trace;
+mod variables;
+mod version;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::c_int,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::{pin, Pin},
+    task::{Context, Poll},
+};
+
 use kcl_lib::{
-    lint::{checks, Discovered},
-    ExecutorContext, UnitLength,
+    lint::{checks, Discovered, Finding},
+    ExecutorContext,
 };
 use pyo3::{
-    prelude::PyModuleMethods, pyclass, pyfunction, pymethods, pymodule, types::PyModule, wrap_pyfunction, Bound, PyErr,
-    PyResult,
+    prelude::{PyAnyMethods, PyModuleMethods},
+    pyclass, pyfunction, pymethods, pymodule,
+    types::{PyAny, PyBytes, PyMemoryView, PyModule, PyType},
+    wrap_pyfunction, Bound, FromPyObject, PyErr, PyObject, PyResult, Python,
 };
 use serde::{Deserialize, Serialize};
 
+use crate::program::Code;
+
+/// The runtime every call runs on, whichever Python thread or event loop it came from.
 fn tokio() -> &'static tokio::runtime::Runtime {
     use std::sync::OnceLock;
     static RT: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
-    RT.get_or_init(|| tokio::runtime::Runtime::new().unwrap())
+    RT.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("kcl-runtime")
+            .build()
+            .expect("Failed to start the tokio runtime")
+    })
+}
+
+/// Convert a task on our runtime that didn't finish into a python exception, so that a panic inside it is raised
+/// rather than taking down the interpreter.
+fn join_error(err: tokio::task::JoinError) -> PyErr {
+    if !err.is_panic() {
+        return pyo3::exceptions::PyException::new_err(format!("The task running the call was stopped: {}", err));
+    }
+    let panic = err.into_panic();
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "no message".to_string());
+
+    pyo3::exceptions::PyRuntimeError::new_err(format!("kcl panicked: {}", message))
+}
+
+/// A task on our runtime that is aborted if it is dropped before it finishes.
+///
+/// When the Python coroutine waiting for a call is cancelled, e.g. by Ctrl-C under `asyncio.run` or by
+/// `asyncio.wait_for` timing out, the task stops at its next await and its engine connection is closed, rather than
+/// running on in the background until the engine finishes.
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, tokio::task::JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        pin!(&mut self.0).poll(cx)
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run a future on our tokio runtime, without holding the GIL while we wait for it. The future is dropped if the
+/// call is cancelled.
+async fn spawn<F, T>(future: F) -> PyResult<T>
+where
+    F: Future<Output = PyResult<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let future = DISCONNECTED.scope(std::cell::Cell::new(false), future);
+    AbortOnDrop(tokio().spawn(future)).await.map_err(join_error)?
+}
+
+pyo3::create_exception!(
+    kcl,
+    EngineConnectionError,
+    pyo3::exceptions::PyException,
+    "The connection to the engine could not be established, or was lost part way through."
+);
+
+pyo3::create_exception!(
+    kcl,
+    HostNotFoundError,
+    EngineConnectionError,
+    "The address of the API could not be resolved."
+);
+
+pyo3::create_exception!(
+    kcl,
+    KclAuthError,
+    pyo3::exceptions::PyException,
+    "The API token is missing, or was rejected. `status` is the HTTP status it was rejected with, or None if there \
+     was no token, and `hint` says how to give one."
+);
+
+tokio::task_local! {
+    /// Whether the connection to the engine dropped during the current call. It is set where that is found out, so the
+    /// errors that follow can be told apart from ones about the code or the commands without looking at their messages,
+    /// or taking the GIL to look at the exceptions they became.
+    static DISCONNECTED: std::cell::Cell<bool>;
+}
+
+/// Record that the connection to the engine dropped during the current call.
+pub(crate) fn mark_disconnected() {
+    let _ = DISCONNECTED.try_with(|disconnected| disconnected.set(true));
+}
+
+/// Whether the connection to the engine has dropped during the current call.
+fn is_disconnected() -> bool {
+    DISCONNECTED.try_with(std::cell::Cell::get).unwrap_or(false)
+}
+
+/// Run a future with its own record of whether the connection to the engine dropped, returning whether it did along
+/// with the future's output.
+async fn watch_disconnects<F: Future>(future: F) -> (F::Output, bool) {
+    DISCONNECTED
+        .scope(std::cell::Cell::new(false), async {
+            let output = future.await;
+            (output, is_disconnected())
+        })
+        .await
+}
+
+/// Convert an error from kcl-lib into a python exception, so that dropped connections and commands the engine
+/// rejected can be told apart.
+fn kcl_error(err: kcl_lib::KclError) -> PyErr {
+    command_error(err, None)
+}
+
+/// Convert an error from kcl-lib into a python exception, with the id of the command that failed if it's known.
+fn command_error(err: kcl_lib::KclError, command_id: Option<uuid::Uuid>) -> PyErr {
+    if is_disconnected() {
+        EngineConnectionError::new_err(err.to_string())
+    } else if matches!(err, kcl_lib::KclError::Engine(_)) {
+        engine_error::engine_error(&err, command_id)
+    } else {
+        PyErr::from(err)
+    }
+}
+
+/// How to retry when the connection to the engine fails.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq)]
+pub struct RetrySettings {
+    /// How many times to try connecting to the engine before giving up. 1 means don't retry.
+    #[pyo3(get, set)]
+    pub connect_attempts: u32,
+    /// Seconds to wait before the first retry, doubled after each failed attempt.
+    #[pyo3(get, set)]
+    pub initial_delay: f64,
+    /// The most seconds to wait between attempts.
+    #[pyo3(get, set)]
+    pub max_delay: f64,
+    /// Whether to reconnect and run everything again, once, when the connection drops part way through.
+    #[pyo3(get, set)]
+    pub rerun_on_disconnect: bool,
+}
+
+static RETRY_SETTINGS: std::sync::RwLock<RetrySettings> = std::sync::RwLock::new(RetrySettings::DEFAULT);
+
+impl RetrySettings {
+    const DEFAULT: RetrySettings = RetrySettings {
+        connect_attempts: 3,
+        initial_delay: 0.5,
+        max_delay: 8.0,
+        rerun_on_disconnect: true,
+    };
+
+    /// The retry settings currently in use.
+    fn current() -> RetrySettings {
+        RETRY_SETTINGS.read().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings::DEFAULT
+    }
+}
+
+#[pymethods]
+impl RetrySettings {
+    #[new]
+    #[pyo3(signature = (connect_attempts = None, initial_delay = None, max_delay = None, rerun_on_disconnect = None))]
+    fn new(
+        connect_attempts: Option<u32>,
+        initial_delay: Option<f64>,
+        max_delay: Option<f64>,
+        rerun_on_disconnect: Option<bool>,
+    ) -> PyResult<Self> {
+        let default = RetrySettings::default();
+        let settings = RetrySettings {
+            connect_attempts: connect_attempts.unwrap_or(default.connect_attempts),
+            initial_delay: initial_delay.unwrap_or(default.initial_delay),
+            max_delay: max_delay.unwrap_or(default.max_delay),
+            rerun_on_disconnect: rerun_on_disconnect.unwrap_or(default.rerun_on_disconnect),
+        };
+        if settings.connect_attempts == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "connect_attempts must be at least 1",
+            ));
+        }
+        if !(settings.initial_delay >= 0.0 && settings.max_delay >= 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err("Delays must not be negative"));
+        }
+
+        Ok(settings)
+    }
+
+    /// Settings that never retry.
+    #[staticmethod]
+    fn disabled() -> Self {
+        RetrySettings {
+            connect_attempts: 1,
+            rerun_on_disconnect: false,
+            ..Default::default()
+        }
+    }
+}
+
+/// Run an engine session, running the whole thing again from the start if the connection to the engine drops part
+/// way through, within the default timeout if there is one.
+async fn with_reconnect<F, Fut, T>(session: F) -> PyResult<T>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = PyResult<T>>,
+{
+    let run = async {
+        match watch_disconnects(session()).await {
+            (Err(_), true) if RetrySettings::current().rerun_on_disconnect => session().await,
+            (result, _) => result,
+        }
+    };
+    let Some(timeout_seconds) = defaults::Defaults::current().timeout_seconds else {
+        return run.await;
+    };
+    tokio::time::timeout(std::time::Duration::from_secs_f64(timeout_seconds), run)
+        .await
+        .map_err(|_| {
+            pyo3::exceptions::PyTimeoutError::new_err(format!(
+                "The execution did not finish within {} seconds",
+                timeout_seconds
+            ))
+        })?
+}
+
+/// Set how to retry when the connection to the engine fails, for every call from now on.
+#[pyfunction]
+fn set_retry_settings(settings: RetrySettings) {
+    *RETRY_SETTINGS.write().unwrap_or_else(|err| err.into_inner()) = settings;
+}
+
+/// Get how failed connections to the engine are currently retried.
+#[pyfunction]
+fn get_retry_settings() -> RetrySettings {
+    RetrySettings::current()
+}
+
+pyo3::create_exception!(
+    kcl,
+    KclWarningError,
+    pyo3::exceptions::PyException,
+    "Raised when warnings are treated as errors and the kcl code produced warnings. The second argument is the list of \
+     diagnostics."
+);
+
+pyo3::create_exception!(
+    kcl,
+    KclEmptySceneError,
+    pyo3::exceptions::PyException,
+    "Raised when the kcl code created no solids or sketches, so there is nothing to snapshot or export. `object_count` \
+     is how many objects it created, and `start` and `end` are the byte offsets of its last statement, None if it has \
+     none."
+);
+
+/// The error for a program that created nothing to snapshot or export, pointing at its last statement.
+fn empty_scene_error(program: &kcl_lib::Program, object_count: usize) -> PyErr {
+    let last = serde_json::to_value(&program.ast)
+        .ok()
+        .and_then(|ast| ast.get("body")?.as_array()?.last().cloned());
+    let offset = |key: &str| {
+        last.as_ref()
+            .and_then(|statement| statement.get(key)?.as_u64())
+            .map(|offset| offset as usize)
+    };
+    let err = KclEmptySceneError::new_err(format!(
+        "The kcl code created {} objects, so there is nothing to snapshot or export. Pass allow_empty=True to do it \
+         anyway.",
+        object_count
+    ));
+
+    Python::with_gil(|py| {
+        let value = err.value_bound(py);
+        match value
+            .setattr("object_count", object_count)
+            .and_then(|()| value.setattr("start", offset("start")))
+            .and_then(|()| value.setattr("end", offset("end")))
+        {
+            Ok(()) => err,
+            Err(set_err) => set_err,
+        }
+    })
+}
+
+/// A problem found while parsing or executing kcl code.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct Diagnostic {
+    /// How severe the problem is, e.g. `warning`.
+    #[pyo3(get)]
+    pub severity: String,
+    /// A description of the problem.
+    #[pyo3(get)]
+    pub message: String,
+    /// Byte offset of the start of the code the problem is in.
+    #[pyo3(get)]
+    pub start: usize,
+    /// Byte offset of the end of the code the problem is in.
+    #[pyo3(get)]
+    pub end: usize,
+}
+
+impl From<kcl_lib::CompilationError> for Diagnostic {
+    fn from(err: kcl_lib::CompilationError) -> Self {
+        Diagnostic {
+            severity: if err.severity.is_err() { "error" } else { "warning" }.to_string(),
+            message: err.message,
+            start: err.source_range.start(),
+            end: err.source_range.end(),
+        }
+    }
+}
+
+impl From<kcl_lib::KclError> for Diagnostic {
+    fn from(err: kcl_lib::KclError) -> Self {
+        let range = err.source_ranges().first().copied().unwrap_or_default();
+        Diagnostic {
+            severity: "error".to_string(),
+            message: err.message().to_string(),
+            start: range.start(),
+            end: range.end(),
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}..{}]: {}", self.severity, self.start, self.end, self.message)
+    }
+}
+
+#[pymethods]
+impl Diagnostic {
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+
+    /// The lines of the code the problem is in, with `context_lines` lines either side.
+    #[pyo3(signature = (code, context_lines = 1))]
+    fn snippet(&self, code: &str, context_lines: usize) -> PyResult<editor::Snippet> {
+        editor::snippet_at(code, self.start, self.end, context_lines)
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 /// The variety of image formats snapshots may be exported to.
 #[derive(Serialize, Deserialize, PartialEq, Hash, Debug, Clone, Copy)]
-#[pyclass(eq, eq_int)]
+#[pyclass(eq, eq_int, hash, frozen, module = "kcl")]
 #[serde(rename_all = "lowercase")]
 pub enum ImageFormat {
     /// .png format
@@ -35,14 +440,248 @@ impl From<ImageFormat> for kittycad_modeling_cmds::ImageFormat {
     }
 }
 
+impl std::fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => write!(f, "{}", name),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+#[pymethods]
+impl ImageFormat {
+    /// Parse an image format from its name, e.g. `"jpeg"`.
+    #[staticmethod]
+    #[pyo3(name = "from_str")]
+    fn parse(name: &str) -> PyResult<Self> {
+        serde_json::from_value(serde_json::Value::String(name.trim().to_lowercase())).map_err(|_| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown image format {:?}, expected one of: png, jpeg",
+                name
+            ))
+        })
+    }
+
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject, String))> {
+        pickle::reduce_variant::<Self>(py, format!("{:?}", self))
+    }
+
+    fn __copy__(&self) -> Self {
+        *self
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        *self
+    }
+}
+
+/// How solids are drawn in snapshots.
+#[derive(Serialize, Deserialize, PartialEq, Hash, Debug, Clone, Copy)]
+#[pyclass(eq, eq_int, hash, frozen, module = "kcl")]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    /// Shaded faces without edge lines.
+    Shaded,
+    /// Shaded faces with their edges drawn as lines.
+    ShadedWithEdges,
+    /// Only the edges, without faces. The engine doesn't support this yet.
+    Wireframe,
+}
+
+#[pymethods]
+impl RenderMode {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject, String))> {
+        pickle::reduce_variant::<Self>(py, format!("{:?}", self))
+    }
+
+    fn __copy__(&self) -> Self {
+        *self
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        *self
+    }
+}
+
+impl RenderMode {
+    /// Whether the engine should draw edge lines, or an error if the engine can't draw this mode.
+    fn edge_lines_visible(self) -> PyResult<bool> {
+        match self {
+            RenderMode::Shaded => Ok(false),
+            RenderMode::ShadedWithEdges => Ok(true),
+            RenderMode::Wireframe => Err(pyo3::exceptions::PyNotImplementedError::new_err(
+                "The Wireframe render mode is not supported by the engine, use Shaded or ShadedWithEdges",
+            )),
+        }
+    }
+}
+
+/// A unit of length.
+#[derive(Serialize, Deserialize, PartialEq, Hash, Debug, Clone, Copy)]
+#[pyclass(eq, eq_int, hash, frozen, module = "kcl")]
+#[serde(rename_all = "lowercase")]
+pub enum UnitLength {
+    /// Millimeters.
+    Mm,
+    /// Centimeters.
+    Cm,
+    /// Meters.
+    M,
+    /// Inches.
+    In,
+    /// Feet.
+    Ft,
+    /// Yards.
+    Yd,
+}
+
+impl UnitLength {
+    const ALL: [UnitLength; 6] = [
+        UnitLength::Mm,
+        UnitLength::Cm,
+        UnitLength::M,
+        UnitLength::In,
+        UnitLength::Ft,
+        UnitLength::Yd,
+    ];
+
+    /// The abbreviation kcl uses for this unit.
+    fn abbreviation(&self) -> &'static str {
+        match self {
+            UnitLength::Mm => "mm",
+            UnitLength::Cm => "cm",
+            UnitLength::M => "m",
+            UnitLength::In => "in",
+            UnitLength::Ft => "ft",
+            UnitLength::Yd => "yd",
+        }
+    }
+
+    /// How many millimeters there are in one of this unit.
+    fn in_mm(&self) -> f64 {
+        match self {
+            UnitLength::Mm => 1.0,
+            UnitLength::Cm => 10.0,
+            UnitLength::M => 1000.0,
+            UnitLength::In => 25.4,
+            UnitLength::Ft => 304.8,
+            UnitLength::Yd => 914.4,
+        }
+    }
+}
+
+impl std::fmt::Display for UnitLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.abbreviation())
+    }
+}
+
+impl std::str::FromStr for UnitLength {
+    type Err = PyErr;
+
+    fn from_str(s: &str) -> PyResult<Self> {
+        let name = s.trim().to_lowercase();
+        if name == "inch" {
+            return Ok(UnitLength::In);
+        }
+        UnitLength::ALL
+            .into_iter()
+            .find(|unit| unit.abbreviation() == name)
+            .ok_or_else(|| {
+                let valid = UnitLength::ALL.map(|unit| unit.abbreviation()).join(", ");
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "Unknown length unit {:?}, expected one of: {}",
+                    s, valid
+                ))
+            })
+    }
+}
+
+impl From<UnitLength> for kcl_lib::UnitLength {
+    fn from(unit: UnitLength) -> Self {
+        match unit {
+            UnitLength::Mm => kcl_lib::UnitLength::Mm,
+            UnitLength::Cm => kcl_lib::UnitLength::Cm,
+            UnitLength::M => kcl_lib::UnitLength::M,
+            UnitLength::In => kcl_lib::UnitLength::In,
+            UnitLength::Ft => kcl_lib::UnitLength::Ft,
+            UnitLength::Yd => kcl_lib::UnitLength::Yd,
+        }
+    }
+}
+
+impl From<UnitLength> for kittycad_modeling_cmds::units::UnitLength {
+    fn from(unit: UnitLength) -> Self {
+        kcl_lib::UnitLength::from(unit).into()
+    }
+}
+
+#[pymethods]
+impl UnitLength {
+    /// Get a unit from its abbreviation, e.g. `"mm"` or `"in"`, ignoring case.
+    #[staticmethod]
+    #[pyo3(name = "from_str")]
+    fn parse(s: &str) -> PyResult<Self> {
+        s.parse()
+    }
+
+    fn __str__(&self) -> &'static str {
+        self.abbreviation()
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject, String))> {
+        pickle::reduce_variant::<Self>(py, format!("{:?}", self))
+    }
+
+    fn __copy__(&self) -> Self {
+        *self
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        *self
+    }
+}
+
+/// A length unit given from python, either as a `UnitLength` or its abbreviation, e.g. `"mm"`.
+#[derive(FromPyObject, Debug, Clone)]
+pub enum Units {
+    Unit(UnitLength),
+    Name(String),
+}
+
+impl TryFrom<Units> for UnitLength {
+    type Error = PyErr;
+
+    fn try_from(units: Units) -> PyResult<Self> {
+        match units {
+            Units::Unit(unit) => Ok(unit),
+            Units::Name(name) => name.parse(),
+        }
+    }
+}
+
 /// A file that was exported from the engine.
+///
+/// The file supports the buffer protocol, so `memoryview(file)`, like `file.contents`, reads the contents in place
+/// rather than copying them. It's frozen so the contents can't change while they're being read.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-#[pyclass]
+#[pyclass(frozen, module = "kcl")]
 pub struct ExportFile {
     /// Binary contents of the file.
     pub contents: Vec<u8>,
     /// Name of the file.
     pub name: String,
+    /// The units lengths in the file are in, if it was exported in known units.
+    #[serde(default)]
+    pub units: Option<UnitLength>,
+    /// The format the file was exported as, e.g. `stl` or `svg`.
+    #[serde(default)]
+    pub format: Option<String>,
 }
 
 impl From<kittycad_modeling_cmds::shared::ExportFile> for ExportFile {
@@ -50,6 +689,8 @@ impl From<kittycad_modeling_cmds::shared::ExportFile> for ExportFile {
         ExportFile {
             contents: file.contents.0,
             name: file.name,
+            units: None,
+            format: None,
         }
     }
 }
@@ -59,26 +700,109 @@ impl From<kittycad_modeling_cmds::websocket::RawFile> for ExportFile {
         ExportFile {
             contents: file.contents,
             name: file.name,
+            units: None,
+            format: None,
         }
     }
 }
 
 #[pymethods]
 impl ExportFile {
+    #[new]
+    #[pyo3(signature = (contents, name, units = None, format = None))]
+    fn new(contents: Vec<u8>, name: String, units: Option<Units>, format: Option<String>) -> PyResult<Self> {
+        Ok(ExportFile {
+            contents,
+            name,
+            units: units.map(UnitLength::try_from).transpose()?,
+            format,
+        })
+    }
+
+    /// The contents of the file, as a read-only `memoryview` of the file itself, so even a very large file isn't
+    /// copied. `bytes(file.contents)` makes a copy.
     #[getter]
-    fn contents(&self) -> Vec<u8> {
-        self.contents.clone()
+    fn contents<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyMemoryView>> {
+        PyMemoryView::from_bound(slf.as_any())
+    }
+
+    /// Write the file to `path`, straight from its contents without copying them first.
+    fn save(&self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+        py.allow_threads(|| std::fs::write(path, &self.contents))?;
+        Ok(())
+    }
+
+    /// Share the contents as a read-only buffer. They live as long as the file, which the buffer keeps alive.
+    unsafe fn __getbuffer__(slf: Bound<'_, Self>, view: *mut pyo3::ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        let contents = &slf.get().contents;
+        let filled = pyo3::ffi::PyBuffer_FillInfo(
+            view,
+            slf.as_ptr(),
+            contents.as_ptr() as *mut std::ffi::c_void,
+            contents.len() as pyo3::ffi::Py_ssize_t,
+            1,
+            flags,
+        );
+        if filled == -1 {
+            return Err(PyErr::fetch(slf.py()));
+        }
+
+        Ok(())
     }
 
     #[getter]
     fn name(&self) -> String {
         self.name.clone()
     }
+
+    #[getter]
+    fn units(&self) -> Option<UnitLength> {
+        self.units
+    }
+
+    #[getter]
+    fn format(&self) -> Option<String> {
+        self.format.clone()
+    }
+
+    /// A copy of the file with its lengths converted from its units to the given ones.
+    ///
+    /// Only ASCII STL and OBJ files can be converted so far; other formats raise `NotImplementedError`.
+    fn scale_to(&self, units: Units) -> PyResult<Self> {
+        rescale::scale_to(self, units.try_into()?)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> (
+        Bound<'py, PyType>,
+        (Bound<'py, PyBytes>, String, Option<UnitLength>, Option<String>),
+    ) {
+        let file = slf.get();
+        (
+            slf.get_type(),
+            (
+                PyBytes::new_bound(slf.py(), &file.contents),
+                file.name.clone(),
+                file.units,
+                file.format.clone(),
+            ),
+        )
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 /// The valid types of output file formats.
 #[derive(Serialize, Deserialize, PartialEq, Hash, Debug, Clone)]
-#[pyclass(eq, eq_int)]
+#[pyclass(eq, eq_int, hash, frozen, module = "kcl")]
 #[serde(rename_all = "lowercase")]
 pub enum FileExportFormat {
     /// Autodesk Filmbox (FBX) format. <https://en.wikipedia.org/wiki/FBX>
@@ -111,46 +835,608 @@ pub enum FileExportFormat {
     Stl,
 }
 
-fn get_output_format(
-    format: &FileExportFormat,
-    src_unit: kittycad_modeling_cmds::units::UnitLength,
-) -> kittycad_modeling_cmds::format::OutputFormat {
-    // Zoo co-ordinate system.
-    //
-    // * Forward: -Y
-    // * Up: +Z
-    // * Handedness: Right
-    let coords = kittycad_modeling_cmds::coord::System {
-        forward: kittycad_modeling_cmds::coord::AxisDirectionPair {
-            axis: kittycad_modeling_cmds::coord::Axis::Y,
-            direction: kittycad_modeling_cmds::coord::Direction::Negative,
-        },
-        up: kittycad_modeling_cmds::coord::AxisDirectionPair {
-            axis: kittycad_modeling_cmds::coord::Axis::Z,
-            direction: kittycad_modeling_cmds::coord::Direction::Positive,
-        },
-    };
-
-    match format {
-        FileExportFormat::Fbx => {
-            kittycad_modeling_cmds::format::OutputFormat::Fbx(kittycad_modeling_cmds::format::fbx::export::Options {
-                storage: kittycad_modeling_cmds::format::fbx::export::Storage::Binary,
-                created: None,
-            })
+impl FileExportFormat {
+    /// The units lengths in an export in this format are in, when the scene is in `units`.
+    ///
+    /// glTF is always in meters, and FBX and STEP files say in themselves which units they use.
+    fn file_units(&self, units: UnitLength) -> Option<UnitLength> {
+        match self {
+            FileExportFormat::Obj | FileExportFormat::Ply | FileExportFormat::Stl => Some(units),
+            FileExportFormat::Glb | FileExportFormat::Gltf => Some(UnitLength::M),
+            FileExportFormat::Fbx | FileExportFormat::Step => None,
         }
-        FileExportFormat::Glb => {
-            kittycad_modeling_cmds::format::OutputFormat::Gltf(kittycad_modeling_cmds::format::gltf::export::Options {
-                storage: kittycad_modeling_cmds::format::gltf::export::Storage::Binary,
-                presentation: kittycad_modeling_cmds::format::gltf::export::Presentation::Compact,
-            })
+    }
+}
+
+impl std::fmt::Display for FileExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => write!(f, "{}", name),
+            _ => write!(f, "{:?}", self),
         }
-        FileExportFormat::Gltf => {
-            kittycad_modeling_cmds::format::OutputFormat::Gltf(kittycad_modeling_cmds::format::gltf::export::Options {
-                storage: kittycad_modeling_cmds::format::gltf::export::Storage::Embedded,
-                presentation: kittycad_modeling_cmds::format::gltf::export::Presentation::Pretty,
-            })
+    }
+}
+
+#[pymethods]
+impl FileExportFormat {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject, String))> {
+        pickle::reduce_variant::<Self>(py, format!("{:?}", self))
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// What an `ExportSelection` selects.
+#[derive(Serialize, Deserialize, PartialEq, Hash, Debug, Clone, Copy)]
+#[pyclass(eq, eq_int, hash, frozen, module = "kcl")]
+#[serde(rename_all = "snake_case")]
+pub enum ExportSelectionKind {
+    /// The default scene.
+    DefaultScene,
+    /// The scene at an index.
+    SceneByIndex,
+    /// The first scene with a name.
+    SceneByName,
+    /// The mesh at an index.
+    MeshByIndex,
+    /// The first mesh with a name.
+    MeshByName,
+    /// Only the objects being exported.
+    Objects,
+}
+
+impl std::fmt::Display for ExportSelectionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => write!(f, "{}", name),
+            _ => write!(f, "{:?}", self),
         }
-        FileExportFormat::Obj => {
+    }
+}
+
+#[pymethods]
+impl ExportSelectionKind {
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject, String))> {
+        pickle::reduce_variant::<Self>(py, format!("{:?}", self))
+    }
+
+    fn __copy__(&self) -> Self {
+        *self
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        *self
+    }
+}
+
+/// Which part of the scene an STL or PLY export holds.
+///
+/// Other formats always hold the default scene, or the objects being exported.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq, module = "kcl")]
+pub struct ExportSelection {
+    /// What is selected.
+    #[pyo3(get)]
+    pub kind: ExportSelectionKind,
+    /// The index of the scene or mesh, for the `_by_index` kinds.
+    #[pyo3(get)]
+    pub index: Option<usize>,
+    /// The name of the scene or mesh, for the `_by_name` kinds.
+    #[pyo3(get)]
+    pub name: Option<String>,
+}
+
+impl ExportSelection {
+    fn new(kind: ExportSelectionKind, index: Option<usize>, name: Option<String>) -> Self {
+        ExportSelection { kind, index, name }
+    }
+
+    /// Convert the selection for the engine, checking it has the index or name its kind needs and nothing else.
+    fn to_engine(&self) -> PyResult<kittycad_modeling_cmds::format::Selection> {
+        use kittycad_modeling_cmds::format::Selection;
+
+        Ok(match (self.kind, self.index, self.name.clone()) {
+            (ExportSelectionKind::DefaultScene | ExportSelectionKind::Objects, None, None) => Selection::DefaultScene,
+            (ExportSelectionKind::SceneByIndex, Some(index), None) => Selection::SceneByIndex { index },
+            (ExportSelectionKind::SceneByName, None, Some(name)) => Selection::SceneByName { name },
+            (ExportSelectionKind::MeshByIndex, Some(index), None) => Selection::MeshByIndex { index },
+            (ExportSelectionKind::MeshByName, None, Some(name)) => Selection::MeshByName { name },
+            (kind, index, name) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Invalid {} selection with index {:?} and name {:?}, make it with ExportSelection.{}()",
+                    kind, index, name, kind
+                )))
+            }
+        })
+    }
+
+    /// Check the selection makes sense for the format and the objects being exported, and convert it for the engine.
+    fn validate(
+        selection: Option<&ExportSelection>,
+        export_format: &FileExportFormat,
+        entity_ids: &[uuid::Uuid],
+    ) -> PyResult<kittycad_modeling_cmds::format::Selection> {
+        use kittycad_modeling_cmds::format::Selection;
+
+        let Some(selection) = selection else {
+            return Ok(Selection::DefaultScene);
+        };
+        let converted = selection.to_engine()?;
+        if !matches!(export_format, FileExportFormat::Stl | FileExportFormat::Ply) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Only Stl and Ply exports take a selection, not {:?}",
+                export_format
+            )));
+        }
+        let by_objects = selection.kind == ExportSelectionKind::Objects;
+        if by_objects && entity_ids.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "ExportSelection.objects() needs objects to export, but none were given",
+            ));
+        }
+        if !by_objects && selection.kind != ExportSelectionKind::DefaultScene && !entity_ids.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "A {} selection can't be combined with objects to export, use ExportSelection.objects() instead",
+                selection.kind
+            )));
+        }
+
+        Ok(converted)
+    }
+}
+
+#[pymethods]
+impl ExportSelection {
+    /// Select the default scene. This is what is exported when no selection is given.
+    #[staticmethod]
+    fn default_scene() -> Self {
+        ExportSelection::new(ExportSelectionKind::DefaultScene, None, None)
+    }
+
+    /// Select the scene at an index.
+    #[staticmethod]
+    fn scene_by_index(index: usize) -> Self {
+        ExportSelection::new(ExportSelectionKind::SceneByIndex, Some(index), None)
+    }
+
+    /// Select the first scene with a name.
+    #[staticmethod]
+    fn scene_by_name(name: String) -> Self {
+        ExportSelection::new(ExportSelectionKind::SceneByName, None, Some(name))
+    }
+
+    /// Select the mesh at an index.
+    #[staticmethod]
+    fn mesh_by_index(index: usize) -> Self {
+        ExportSelection::new(ExportSelectionKind::MeshByIndex, Some(index), None)
+    }
+
+    /// Select the first mesh with a name.
+    #[staticmethod]
+    fn mesh_by_name(name: String) -> Self {
+        ExportSelection::new(ExportSelectionKind::MeshByName, None, Some(name))
+    }
+
+    /// Select only the objects being exported, which must be given.
+    #[staticmethod]
+    fn objects() -> Self {
+        ExportSelection::new(ExportSelectionKind::Objects, None, None)
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Options for how kcl code is formatted.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq, module = "kcl")]
+pub struct FormatOptions {
+    /// Size of a tab in spaces.
+    #[pyo3(get, set)]
+    pub tab_size: usize,
+    /// Prefer tabs over spaces.
+    #[pyo3(get, set)]
+    pub use_tabs: bool,
+    /// Insert a final newline at the end of the file.
+    #[pyo3(get, set)]
+    pub insert_final_newline: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        kcl_lib::FormatOptions::default().into()
+    }
+}
+
+impl From<kcl_lib::FormatOptions> for FormatOptions {
+    fn from(options: kcl_lib::FormatOptions) -> Self {
+        FormatOptions {
+            tab_size: options.tab_size,
+            use_tabs: options.use_tabs,
+            insert_final_newline: options.insert_final_newline,
+        }
+    }
+}
+
+impl From<FormatOptions> for kcl_lib::FormatOptions {
+    fn from(options: FormatOptions) -> Self {
+        kcl_lib::FormatOptions {
+            tab_size: options.tab_size,
+            use_tabs: options.use_tabs,
+            insert_final_newline: options.insert_final_newline,
+        }
+    }
+}
+
+#[pymethods]
+impl FormatOptions {
+    #[new]
+    #[pyo3(signature = (tab_size = None, use_tabs = None, insert_final_newline = None))]
+    fn new(tab_size: Option<usize>, use_tabs: Option<bool>, insert_final_newline: Option<bool>) -> Self {
+        let default = FormatOptions::default();
+        FormatOptions {
+            tab_size: tab_size.unwrap_or(default.tab_size),
+            use_tabs: use_tabs.unwrap_or(default.use_tabs),
+            insert_final_newline: insert_final_newline.unwrap_or(default.insert_final_newline),
+        }
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Visual settings for rendering snapshots.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq, module = "kcl")]
+pub struct RenderSettings {
+    /// Highlight edges of 3D objects.
+    #[pyo3(get, set)]
+    pub highlight_edges: bool,
+    /// Enable screen space ambient occlusion.
+    #[pyo3(get, set)]
+    pub enable_ssao: bool,
+    /// Show the grid.
+    #[pyo3(get, set)]
+    pub show_grid: bool,
+    /// How many frames to wait once the camera has settled before a deterministic snapshot, so effects the engine
+    /// builds up over several frames have finished.
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub settle_frames: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        let settings = kcl_lib::ExecutorSettings::default();
+        RenderSettings {
+            highlight_edges: settings.highlight_edges,
+            enable_ssao: settings.enable_ssao,
+            show_grid: settings.show_grid,
+            settle_frames: 0,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// How long to wait before a snapshot: None to not wait, or how many frames to wait once the camera has settled.
+    pub(crate) fn settle(&self, deterministic: bool) -> Option<u32> {
+        deterministic.then_some(self.settle_frames)
+    }
+}
+
+#[pymethods]
+impl RenderSettings {
+    #[new]
+    #[pyo3(signature = (highlight_edges = None, enable_ssao = None, show_grid = None, settle_frames = None))]
+    fn new(
+        highlight_edges: Option<bool>,
+        enable_ssao: Option<bool>,
+        show_grid: Option<bool>,
+        settle_frames: Option<u32>,
+    ) -> Self {
+        let default = RenderSettings::default();
+        RenderSettings {
+            highlight_edges: highlight_edges.unwrap_or(default.highlight_edges),
+            enable_ssao: enable_ssao.unwrap_or(default.enable_ssao),
+            show_grid: show_grid.unwrap_or(default.show_grid),
+            settle_frames: settle_frames.unwrap_or(default.settle_frames),
+        }
+    }
+
+    /// Settings that render the same program to the same bytes every time, for comparing snapshots in tests.
+    ///
+    /// The engine takes no random seed, so this turns off SSAO, whose sampling noise differs from frame to frame, and
+    /// waits a few frames after the camera settles before each snapshot. Snapshots must also be taken with
+    /// `deterministic=True`, which is the default.
+    #[staticmethod]
+    fn deterministic() -> Self {
+        RenderSettings {
+            enable_ssao: false,
+            settle_frames: DETERMINISTIC_SETTLE_FRAMES,
+            ..Default::default()
+        }
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// A color given from python, either as a hex string (`#RRGGBB` or `#RRGGBBAA`) or an RGBA tuple of floats between 0
+/// and 1.
+#[derive(FromPyObject, Debug, Clone)]
+pub enum Color {
+    Hex(String),
+    Rgba((f32, f32, f32, f32)),
+}
+
+impl TryFrom<Color> for kittycad_modeling_cmds::shared::Color {
+    type Error = PyErr;
+
+    fn try_from(color: Color) -> PyResult<Self> {
+        let (r, g, b, a) = match color {
+            Color::Hex(hex) => parse_hex_color(&hex)?,
+            Color::Rgba(rgba) => rgba,
+        };
+        for channel in [r, g, b, a] {
+            if !(0.0..=1.0).contains(&channel) {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Color channels must be between 0 and 1, got {}",
+                    channel
+                )));
+            }
+        }
+
+        Ok(kittycad_modeling_cmds::shared::Color { r, g, b, a })
+    }
+}
+
+/// Parse a `#RRGGBB` or `#RRGGBBAA` hex string into RGBA floats between 0 and 1.
+fn parse_hex_color(hex: &str) -> PyResult<(f32, f32, f32, f32)> {
+    let invalid = || pyo3::exceptions::PyValueError::new_err(format!("Invalid hex color: {:?}", hex));
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+        return Err(invalid());
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&digits[i..i + 2], 16)
+            .map(|value| value as f32 / 255.0)
+            .map_err(|_| invalid())
+    };
+    let alpha = if digits.len() == 8 { channel(6)? } else { 1.0 };
+
+    Ok((channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+/// The appearance to give objects in a snapshot.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct Appearance {
+    color: kittycad_modeling_cmds::shared::Color,
+    /// Metalness of the material, between 0 and 1.
+    #[pyo3(get)]
+    pub metalness: f32,
+    /// Roughness of the material, between 0 and 1.
+    #[pyo3(get)]
+    pub roughness: f32,
+}
+
+#[pymethods]
+impl Appearance {
+    #[new]
+    #[pyo3(signature = (color, metalness = 0.0, roughness = 0.0))]
+    fn new(color: String, metalness: f32, roughness: f32) -> PyResult<Self> {
+        for (name, value) in [("metalness", metalness), ("roughness", roughness)] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "{} must be between 0 and 1, got {}",
+                    name, value
+                )));
+            }
+        }
+
+        Ok(Appearance {
+            color: Color::Hex(color).try_into()?,
+            metalness,
+            roughness,
+        })
+    }
+
+    /// The color, as a `#RRGGBBAA` hex string.
+    #[getter]
+    fn color(&self) -> String {
+        let channel = |value: f32| (value * 255.0).round() as u8;
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            channel(self.color.r),
+            channel(self.color.g),
+            channel(self.color.b),
+            channel(self.color.a)
+        )
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Where to put the camera for a snapshot, in the units of the model.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(eq, module = "kcl")]
+pub struct CameraPose {
+    /// The position of the camera.
+    #[pyo3(get, set)]
+    pub eye: (f64, f64, f64),
+    /// The point the camera looks at.
+    #[pyo3(get, set)]
+    pub center: (f64, f64, f64),
+    /// Which way is up in the image.
+    #[pyo3(get, set)]
+    pub up: (f64, f64, f64),
+    /// The vertical field of view in degrees, or the engine's default if not set.
+    #[pyo3(get, set)]
+    pub fov_degrees: Option<f64>,
+}
+
+#[pymethods]
+impl CameraPose {
+    #[new]
+    #[pyo3(signature = (eye, center, up = (0.0, 0.0, 1.0), fov_degrees = None))]
+    fn new(
+        eye: (f64, f64, f64),
+        center: (f64, f64, f64),
+        up: (f64, f64, f64),
+        fov_degrees: Option<f64>,
+    ) -> PyResult<Self> {
+        if eye == center {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "The camera's eye and center must be different points",
+            ));
+        }
+        if let Some(fov) = fov_degrees.filter(|fov| !(*fov > 0.0 && *fov < 180.0)) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "fov_degrees must be between 0 and 180, got {}",
+                fov
+            )));
+        }
+
+        Ok(CameraPose {
+            eye,
+            center,
+            up,
+            fov_degrees,
+        })
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+impl From<kittycad_modeling_cmds::shared::CameraSettings> for CameraPose {
+    fn from(camera: kittycad_modeling_cmds::shared::CameraSettings) -> Self {
+        let point = |point: kittycad_modeling_cmds::shared::Point3d| (point.x as f64, point.y as f64, point.z as f64);
+        CameraPose {
+            eye: point(camera.pos),
+            center: point(camera.center),
+            up: point(camera.up),
+            fov_degrees: camera.fov_y.map(f64::from),
+        }
+    }
+}
+
+fn point(point: (f64, f64, f64)) -> kittycad_modeling_cmds::shared::Point3d {
+    kittycad_modeling_cmds::shared::Point3d {
+        x: point.0 as f32,
+        y: point.1 as f32,
+        z: point.2 as f32,
+    }
+}
+
+fn get_output_format(
+    format: &FileExportFormat,
+    src_unit: kittycad_modeling_cmds::units::UnitLength,
+    options: export_options::OutputOptions,
+) -> kittycad_modeling_cmds::format::OutputFormat {
+    let selection = options.selection;
+    // Zoo co-ordinate system.
+    //
+    // * Forward: -Y
+    // * Up: +Z
+    // * Handedness: Right
+    let coords = kittycad_modeling_cmds::coord::System {
+        forward: kittycad_modeling_cmds::coord::AxisDirectionPair {
+            axis: kittycad_modeling_cmds::coord::Axis::Y,
+            direction: kittycad_modeling_cmds::coord::Direction::Negative,
+        },
+        up: kittycad_modeling_cmds::coord::AxisDirectionPair {
+            axis: kittycad_modeling_cmds::coord::Axis::Z,
+            direction: kittycad_modeling_cmds::coord::Direction::Positive,
+        },
+    };
+
+    match format {
+        FileExportFormat::Fbx => {
+            kittycad_modeling_cmds::format::OutputFormat::Fbx(kittycad_modeling_cmds::format::fbx::export::Options {
+                storage: options
+                    .fbx_storage
+                    .unwrap_or(kittycad_modeling_cmds::format::fbx::export::Storage::Binary),
+                created: None,
+            })
+        }
+        FileExportFormat::Glb => {
+            kittycad_modeling_cmds::format::OutputFormat::Gltf(kittycad_modeling_cmds::format::gltf::export::Options {
+                storage: options
+                    .gltf_storage
+                    .unwrap_or(kittycad_modeling_cmds::format::gltf::export::Storage::Binary),
+                presentation: options
+                    .gltf_presentation
+                    .unwrap_or(kittycad_modeling_cmds::format::gltf::export::Presentation::Compact),
+            })
+        }
+        FileExportFormat::Gltf => {
+            kittycad_modeling_cmds::format::OutputFormat::Gltf(kittycad_modeling_cmds::format::gltf::export::Options {
+                storage: options
+                    .gltf_storage
+                    .unwrap_or(kittycad_modeling_cmds::format::gltf::export::Storage::Embedded),
+                presentation: options
+                    .gltf_presentation
+                    .unwrap_or(kittycad_modeling_cmds::format::gltf::export::Presentation::Pretty),
+            })
+        }
+        FileExportFormat::Obj => {
             kittycad_modeling_cmds::format::OutputFormat::Obj(kittycad_modeling_cmds::format::obj::export::Options {
                 coords,
                 units: src_unit,
@@ -158,9 +1444,11 @@ fn get_output_format(
         }
         FileExportFormat::Ply => {
             kittycad_modeling_cmds::format::OutputFormat::Ply(kittycad_modeling_cmds::format::ply::export::Options {
-                storage: kittycad_modeling_cmds::format::ply::export::Storage::Ascii,
+                storage: options
+                    .ply_storage
+                    .unwrap_or(kittycad_modeling_cmds::format::ply::export::Storage::Ascii),
                 coords,
-                selection: kittycad_modeling_cmds::format::Selection::DefaultScene,
+                selection,
                 units: src_unit,
             })
         }
@@ -170,153 +1458,2070 @@ fn get_output_format(
                 created: None,
             })
         }
-        FileExportFormat::Stl => {
-            kittycad_modeling_cmds::format::OutputFormat::Stl(kittycad_modeling_cmds::format::stl::export::Options {
-                storage: kittycad_modeling_cmds::format::stl::export::Storage::Ascii,
-                coords,
-                units: src_unit,
-                selection: kittycad_modeling_cmds::format::Selection::DefaultScene,
-            })
+        FileExportFormat::Stl => {
+            kittycad_modeling_cmds::format::OutputFormat::Stl(kittycad_modeling_cmds::format::stl::export::Options {
+                storage: options
+                    .stl_storage
+                    .unwrap_or(kittycad_modeling_cmds::format::stl::export::Storage::Ascii),
+                coords,
+                units: src_unit,
+                selection,
+            })
+        }
+    }
+}
+
+/// Connect to the engine, with the token and address from the environment.
+async fn new_context(
+    units: UnitLength,
+    render_settings: &RenderSettings,
+    project_directory: Option<PathBuf>,
+) -> PyResult<ExecutorContext> {
+    new_context_with(None, units, render_settings, project_directory).await
+}
+
+/// Connect to the engine with the given config, or with what the environment says if there isn't one.
+///
+/// If the config has a token provider it is asked for a fresh token for each attempt, and the connection is tried
+/// once more if the token it gave was rejected.
+async fn new_context_with(
+    config: Option<&connection::ClientConfig>,
+    units: UnitLength,
+    render_settings: &RenderSettings,
+    project_directory: Option<PathBuf>,
+) -> PyResult<ExecutorContext> {
+    let config = defaults::client_config(config);
+    let config = config.as_ref();
+    connection::require_token(config)?;
+    let settings = kcl_lib::ExecutorSettings {
+        units: units.into(),
+        highlight_edges: render_settings.highlight_edges,
+        enable_ssao: render_settings.enable_ssao,
+        show_grid: render_settings.show_grid,
+        project_directory,
+        ..Default::default()
+    };
+
+    let retry = RetrySettings::current();
+    let mut delay = std::time::Duration::from_secs_f64(retry.initial_delay);
+    let mut attempt = 1;
+    let mut refreshed = false;
+    loop {
+        let result = match config {
+            Some(config) => ExecutorContext::new(&config.client()?, settings.clone()).await,
+            None => ExecutorContext::new_with_client(settings.clone(), None, None).await,
+        };
+        let err = match result {
+            Ok(mut ctx) => {
+                // Installed on every connection, so that it dropping is noticed whatever the context is used for.
+                stats::CountingEngine::install(&mut ctx);
+                return Ok(ctx);
+            }
+            Err(err) => err,
+        };
+        if let Some(status) = connection::engine_auth_status(&err) {
+            // The token may have expired since the provider gave it, so it gets one chance to give a new one.
+            if !refreshed && config.is_some_and(|config| config.token_provider.is_some()) {
+                refreshed = true;
+                continue;
+            }
+            return Err(connection::auth_error(
+                &format!("The engine rejected the token: {}.", err),
+                Some(status),
+            ));
+        }
+        if attempt >= retry.connect_attempts {
+            mark_disconnected();
+            return Err(EngineConnectionError::new_err(format!(
+                "Failed to connect to the engine after {} attempt(s): {}",
+                attempt, err
+            )));
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(std::time::Duration::from_secs_f64(retry.max_delay));
+        attempt += 1;
+    }
+}
+
+/// A kcl program that has been executed, along with the context and state it was executed in.
+struct Executed {
+    ctx: ExecutorContext,
+    program: kcl_lib::Program,
+    exec_state: kcl_lib::ExecState,
+    /// The units the program was executed in.
+    units: UnitLength,
+    /// Non-fatal diagnostics from parsing the program. These are the only warnings there are: kcl-lib doesn't report
+    /// any while executing.
+    warnings: Vec<Diagnostic>,
+    /// How long parsing, connecting and executing took. The command count is filled in by `stats`.
+    stats: stats::RunStats,
+    /// How many commands have been sent over `ctx` since the program was executed, counted by its engine.
+    commands_sent: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl Executed {
+    /// The stats for everything done since the program was parsed, including any snapshots and exports.
+    fn stats(&self) -> stats::RunStats {
+        stats::RunStats {
+            command_count: self.commands_sent.load(std::sync::atomic::Ordering::Relaxed),
+            ..self.stats.clone()
+        }
+    }
+}
+
+/// If the syntax error at `offset` is a settings annotation, e.g. `@settings(defaultLengthUnit = in)`, an error that
+/// says so, and which units the code sets.
+///
+/// kcl-lib 0.2.29 can't parse settings annotations, so code that sets its own units this way can't be run in them.
+/// Saying which units it wanted means they can be passed in instead, rather than the annotation being taken out and
+/// the code run in millimeters.
+fn settings_annotation_error(code: &str, offset: usize) -> Option<Diagnostic> {
+    let annotation = code.get(offset..)?.strip_prefix("@settings")?;
+    let arguments = annotation.trim_start().strip_prefix('(')?;
+    let close = arguments.find(')')?;
+    let end = code.len() - arguments.len() + close + 1;
+    let units = arguments[..close].split(',').find_map(|argument| {
+        let (key, value) = argument.split_once('=')?;
+        (key.trim() == "defaultLengthUnit").then(|| value.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+    });
+    let message = match units {
+        Some(units) => format!(
+            "The code sets its units to {} with a settings annotation, which kcl-lib {} can't read. Remove the \
+             annotation and pass the units in instead, e.g. units=\"{}\"",
+            units,
+            version::KCL_LIB_VERSION,
+            units
+        ),
+        None => format!(
+            "kcl-lib {} can't read settings annotations, remove the annotation",
+            version::KCL_LIB_VERSION
+        ),
+    };
+
+    Some(Diagnostic {
+        severity: "error".to_string(),
+        message,
+        start: offset,
+        end,
+    })
+}
+
+/// Parse the kcl code, returning any non-fatal diagnostics along with the program.
+fn parse_code(code: &str) -> PyResult<(kcl_lib::Program, Vec<Diagnostic>)> {
+    let annotation_error = |offset: usize| {
+        settings_annotation_error(code, offset).map(|err| pyo3::exceptions::PyValueError::new_err(err.message))
+    };
+    let (program, errors) = kcl_lib::Program::parse(code).map_err(|err| {
+        let offset = err
+            .source_ranges()
+            .first()
+            .map(|range| range.start())
+            .unwrap_or_default();
+        annotation_error(offset).unwrap_or_else(|| PyErr::from(err))
+    })?;
+    if let Some(err) = errors.iter().find(|err| err.severity.is_err()) {
+        if let Some(err) = annotation_error(err.source_range.start()) {
+            return Err(err);
+        }
+        return Err(PyErr::from(kcl_lib::KclError::Syntax(err.clone().into())));
+    }
+    let Some(program) = program else {
+        return Err(pyo3::exceptions::PyException::new_err("Failed to parse the kcl code"));
+    };
+
+    Ok((program, errors.into_iter().map(Diagnostic::from).collect()))
+}
+
+/// Everything about how to run kcl code, other than the code itself.
+#[derive(Default, Debug, Clone)]
+struct RunOptions {
+    /// The units to use, or millimeters if none are given.
+    units: Option<UnitLength>,
+    render_settings: RenderSettings,
+    /// Raise a `KclWarningError` once execution finishes if there were any non-fatal diagnostics.
+    warnings_as_errors: bool,
+    /// The directory imports are resolved relative to.
+    project_directory: Option<PathBuf>,
+    /// Where to report progress to.
+    progress: progress::Progress,
+    /// Raise a `KclEmptySceneError` once execution finishes if the program created no solids or sketches, for when
+    /// something is going to be snapshotted or exported.
+    require_objects: bool,
+}
+
+/// Raise if the code was given in-memory module files or binary attachments to import.
+///
+/// kcl-lib only resolves imports by reading from disk, through a file manager that can't be swapped for one that
+/// reads from memory, so there is no way to hand it files that aren't on disk. Rather than write them somewhere
+/// behind the caller's back, ask them to do it.
+fn reject_module_files(
+    files: Option<HashMap<String, String>>,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+) -> PyResult<()> {
+    let mut paths: Vec<String> = files
+        .unwrap_or_default()
+        .into_keys()
+        .chain(attachments.unwrap_or_default().into_keys())
+        .collect();
+    if paths.is_empty() {
+        return Ok(());
+    }
+    paths.sort();
+
+    Err(pyo3::exceptions::PyNotImplementedError::new_err(format!(
+        "kcl-lib {} can only import files from disk, so the in-memory files {:?} can't be imported. Write them to a \
+         project directory and use execute_project instead",
+        version::KCL_LIB_VERSION,
+        paths
+    )))
+}
+
+/// Parse and execute the kcl code.
+async fn run_code(code: &Code, options: &RunOptions) -> PyResult<Executed> {
+    run_code_in(None, code, options).await
+}
+
+/// Parse and execute the kcl code, on an existing connection to the engine if one is given or a new one otherwise.
+async fn run_code_in(ctx: Option<&ExecutorContext>, code: &Code, options: &RunOptions) -> PyResult<Executed> {
+    options.progress.report(progress::ProgressPhase::Parsing, None)?;
+    let mut stats = stats::RunStats::default();
+    let start = std::time::Instant::now();
+    let (program, warnings) = code.parse()?;
+    let units = options.units.unwrap_or(UnitLength::Mm);
+    stats.parse_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let project_directory = options.project_directory.clone();
+    let mut ctx = match ctx {
+        Some(ctx) => {
+            let mut ctx = ctx.clone();
+            ctx.settings.units = units.into();
+            ctx.settings.project_directory = project_directory;
+            ctx
+        }
+        None => {
+            options.progress.report(progress::ProgressPhase::Connecting, None)?;
+            let start = std::time::Instant::now();
+            let ctx = new_context(units, &options.render_settings, project_directory).await?;
+            stats.connect_ms = start.elapsed().as_secs_f64() * 1000.0;
+            ctx
+        }
+    };
+    let commands_sent = stats::CountingEngine::install(&mut ctx);
+    // Execute the program.
+    let statements = format!("{} top level statements", program.ast.body.len());
+    options
+        .progress
+        .report(progress::ProgressPhase::Executing, Some(statements))?;
+    let mut exec_state = kcl_lib::ExecState::default();
+    let start = std::time::Instant::now();
+    ctx.run(program.clone().into(), &mut exec_state)
+        .await
+        .map_err(kcl_error)?;
+    stats.execution_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    if options.require_objects {
+        let object_count = find_objects(&exec_state)?
+            .iter()
+            .filter(|object| object.kind != "plane")
+            .count();
+        if object_count == 0 {
+            return Err(empty_scene_error(&program, object_count));
+        }
+    }
+
+    if options.warnings_as_errors && !warnings.is_empty() {
+        let message = warnings
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(KclWarningError::new_err((message, warnings)));
+    }
+
+    Ok(Executed {
+        ctx,
+        program,
+        exec_state,
+        units,
+        warnings,
+        stats,
+        commands_sent,
+    })
+}
+
+/// Something the program created in the scene, which later commands can refer to.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct ObjectHandle {
+    /// The id of the object in the engine.
+    #[pyo3(get)]
+    pub id: String,
+    /// What the object is: `solid`, `sketch` or `plane`.
+    #[pyo3(get)]
+    pub kind: String,
+    /// The name of the top level variable the object is bound to.
+    #[pyo3(get)]
+    pub name: Option<String>,
+    /// Byte offset of the start of the code that created the object.
+    #[pyo3(get)]
+    pub start: usize,
+    /// Byte offset of the end of the code that created the object.
+    #[pyo3(get)]
+    pub end: usize,
+}
+
+#[pymethods]
+impl ObjectHandle {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+impl ObjectHandle {
+    /// A handle for an object, with the source range of the code that first made it, if there is one.
+    fn new(kind: &str, name: &str, id: uuid::Uuid, range: Option<kcl_lib::SourceRange>) -> Self {
+        let range = range.unwrap_or_default();
+        ObjectHandle {
+            id: id.to_string(),
+            kind: kind.to_string(),
+            name: Some(name.to_string()),
+            start: range.start(),
+            end: range.end(),
+        }
+    }
+
+    fn uuid(&self) -> PyResult<uuid::Uuid> {
+        self.id
+            .parse()
+            .map_err(|_| pyo3::exceptions::PyValueError::new_err(format!("Invalid object id {:?}", self.id)))
+    }
+}
+
+/// Find every solid, sketch and plane bound to a variable at the top level of the program.
+fn find_objects(exec_state: &kcl_lib::ExecState) -> PyResult<Vec<ObjectHandle>> {
+    let mut objects: Vec<ObjectHandle> = Vec::new();
+    for (name, value) in trace::root_values(exec_state)? {
+        let found = match value {
+            kcl_lib::exec::KclValue::Solid(solid) => {
+                let range = solid.meta.first().map(|meta| meta.source_range);
+                vec![ObjectHandle::new("solid", &name, solid.id, range)]
+            }
+            kcl_lib::exec::KclValue::Solids { value } => value
+                .iter()
+                .map(|solid| {
+                    let range = solid.meta.first().map(|meta| meta.source_range);
+                    ObjectHandle::new("solid", &name, solid.id, range)
+                })
+                .collect(),
+            kcl_lib::exec::KclValue::Sketch { value } => {
+                let range = value.meta.first().map(|meta| meta.source_range);
+                vec![ObjectHandle::new("sketch", &name, value.id, range)]
+            }
+            kcl_lib::exec::KclValue::Sketches { value } => value
+                .iter()
+                .map(|sketch| {
+                    let range = sketch.meta.first().map(|meta| meta.source_range);
+                    ObjectHandle::new("sketch", &name, sketch.id, range)
+                })
+                .collect(),
+            kcl_lib::exec::KclValue::Plane(plane) => {
+                let range = plane.meta.first().map(|meta| meta.source_range);
+                vec![ObjectHandle::new("plane", &name, plane.id, range)]
+            }
+            _ => Vec::new(),
+        };
+        for object in found {
+            // The same object can be bound to more than one variable.
+            if !objects.iter().any(|other| other.id == object.id) {
+                objects.push(object);
+            }
+        }
+    }
+    objects.sort_by_key(|object| object.start);
+
+    Ok(objects)
+}
+
+/// Find the objects bound to each of the named top level variables.
+///
+/// Raises a `KeyError` for a name that isn't bound to any object, rather than quietly leaving it out.
+fn find_named_objects(exec_state: &kcl_lib::ExecState, names: &[String]) -> PyResult<Vec<ObjectHandle>> {
+    let objects = find_objects(exec_state)?;
+    let mut found = Vec::new();
+    for name in names {
+        let named: Vec<&ObjectHandle> = objects
+            .iter()
+            .filter(|object| object.name.as_deref() == Some(name))
+            .collect();
+        if named.is_empty() {
+            let mut available: Vec<&str> = objects.iter().filter_map(|object| object.name.as_deref()).collect();
+            available.dedup();
+            return Err(pyo3::exceptions::PyKeyError::new_err(format!(
+                "No object is named {:?}, the named objects are: {}",
+                name,
+                available.join(", ")
+            )));
+        }
+        found.extend(named.into_iter().cloned());
+    }
+
+    Ok(found)
+}
+
+/// Find every solid bound to a variable at the top level of the program, along with the name of that variable.
+fn find_solids(exec_state: &kcl_lib::ExecState) -> PyResult<Vec<(String, uuid::Uuid)>> {
+    let mut solids = Vec::new();
+    for (name, value) in trace::root_values(exec_state)? {
+        match value {
+            kcl_lib::exec::KclValue::Solid(solid) => solids.push((name, solid.id)),
+            kcl_lib::exec::KclValue::Solids { value } => {
+                solids.extend(value.iter().map(|solid| (name.clone(), solid.id)));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(solids)
+}
+
+/// Send a modeling command to the engine and wait for its response.
+async fn send_modeling_cmd(
+    ctx: &ExecutorContext,
+    cmd: kittycad_modeling_cmds::ModelingCmd,
+) -> PyResult<kittycad_modeling_cmds::websocket::OkWebSocketResponseData> {
+    let id = uuid::Uuid::new_v4();
+    ctx.engine
+        .send_modeling_cmd(id, kcl_lib::SourceRange::default(), cmd)
+        .await
+        .map_err(|err| command_error(err, Some(id)))
+}
+
+/// Set the background color of the scene.
+async fn set_background_color(ctx: &ExecutorContext, color: kittycad_modeling_cmds::shared::Color) -> PyResult<()> {
+    send_modeling_cmd(
+        ctx,
+        kittycad_modeling_cmds::ModelingCmd::SetBackgroundColor(kittycad_modeling_cmds::SetBackgroundColor { color }),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Show or hide the lines drawn along the edges of solids.
+async fn set_edge_lines_visible(ctx: &ExecutorContext, visible: bool) -> PyResult<()> {
+    send_modeling_cmd(
+        ctx,
+        kittycad_modeling_cmds::ModelingCmd::EdgeLinesVisible(kittycad_modeling_cmds::EdgeLinesVisible {
+            hidden: !visible,
+        }),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Zoom the camera to fit the whole scene.
+async fn zoom_to_fit(ctx: &ExecutorContext) -> PyResult<()> {
+    zoom_to_fit_objects(ctx, Default::default()).await
+}
+
+/// Move the camera so the given objects fill the view, or everything if no objects are given.
+async fn zoom_to_fit_objects(ctx: &ExecutorContext, object_ids: Vec<uuid::Uuid>) -> PyResult<()> {
+    send_modeling_cmd(
+        ctx,
+        kittycad_modeling_cmds::ModelingCmd::ZoomToFit(kittycad_modeling_cmds::ZoomToFit {
+            object_ids: object_ids.into_iter().collect(),
+            padding: 0.1,
+            animated: false,
+        }),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// How many times to read the camera back while waiting for it to stop moving, before snapshotting anyway.
+const SETTLE_ATTEMPTS: usize = 10;
+
+/// How long the engine takes to draw a frame, at the 60 frames per second it streams at by default.
+const FRAME: std::time::Duration = std::time::Duration::from_micros(1_000_000 / 60);
+
+/// How many frames `RenderSettings.deterministic()` waits after the camera settles.
+const DETERMINISTIC_SETTLE_FRAMES: u32 = 4;
+
+/// Wait for the camera to stop moving, so a snapshot can't catch it partway to where it was sent.
+///
+/// The engine answers a camera command before the view has necessarily been drawn from there, so the camera is read
+/// back a frame apart until two reads agree.
+async fn settle(ctx: &ExecutorContext, frames: u32) -> PyResult<()> {
+    let mut previous = CameraPose::from(get_camera(ctx).await?);
+    for _ in 0..SETTLE_ATTEMPTS {
+        tokio::time::sleep(FRAME).await;
+        let camera = CameraPose::from(get_camera(ctx).await?);
+        if camera == previous {
+            break;
+        }
+        previous = camera;
+    }
+    tokio::time::sleep(FRAME * frames).await;
+
+    Ok(())
+}
+
+/// Take a snapshot from wherever the camera currently is, first waiting for it to settle and then `settle` more frames
+/// if `settle` is given.
+async fn take_snapshot(ctx: &ExecutorContext, image_format: ImageFormat, settle: Option<u32>) -> PyResult<Vec<u8>> {
+    if let Some(frames) = settle {
+        self::settle(ctx, frames).await?;
+    }
+    // Send a snapshot request to the engine.
+    let resp = send_modeling_cmd(
+        ctx,
+        kittycad_modeling_cmds::ModelingCmd::TakeSnapshot(kittycad_modeling_cmds::TakeSnapshot {
+            format: image_format.into(),
+        }),
+    )
+    .await?;
+
+    let kittycad_modeling_cmds::websocket::OkWebSocketResponseData::Modeling {
+        modeling_response: kittycad_modeling_cmds::ok_response::OkModelingCmdResponse::TakeSnapshot(data),
+    } = resp
+    else {
+        return Err(engine_error::unexpected_response("TakeSnapshot", &resp));
+    };
+
+    Ok(data.contents.0)
+}
+
+/// Zoom to fit the scene and take a snapshot of it, optionally on a different background color.
+async fn snapshot(
+    ctx: &ExecutorContext,
+    image_format: ImageFormat,
+    background_color: Option<kittycad_modeling_cmds::shared::Color>,
+    settle: Option<u32>,
+) -> PyResult<Vec<u8>> {
+    snapshot_framing(ctx, vec![], image_format, background_color, settle).await
+}
+
+/// Zoom to fit some objects and take a snapshot, with the rest of the scene still shown around them. No objects
+/// fits the whole scene.
+async fn snapshot_framing(
+    ctx: &ExecutorContext,
+    object_ids: Vec<uuid::Uuid>,
+    image_format: ImageFormat,
+    background_color: Option<kittycad_modeling_cmds::shared::Color>,
+    settle: Option<u32>,
+) -> PyResult<Vec<u8>> {
+    if let Some(color) = background_color {
+        set_background_color(ctx, color).await?;
+    }
+    zoom_to_fit_objects(ctx, object_ids).await?;
+    take_snapshot(ctx, image_format, settle).await
+}
+
+/// Put the camera exactly where the pose says.
+async fn set_camera(ctx: &ExecutorContext, camera: &CameraPose) -> PyResult<()> {
+    look_at(ctx, point(camera.eye), point(camera.center), point(camera.up)).await?;
+    if let Some(fov) = camera.fov_degrees {
+        send_modeling_cmd(
+            ctx,
+            kittycad_modeling_cmds::ModelingCmd::DefaultCameraPerspectiveSettings(
+                kittycad_modeling_cmds::DefaultCameraPerspectiveSettings {
+                    vantage: point(camera.eye),
+                    center: point(camera.center),
+                    up: point(camera.up),
+                    fov_y: Some(fov as f32),
+                    z_near: None,
+                    z_far: None,
+                    sequence: None,
+                },
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Snapshot the scene from a specific camera pose, rather than zooming to fit it.
+async fn snapshot_from(
+    ctx: &ExecutorContext,
+    camera: &CameraPose,
+    image_format: ImageFormat,
+    background_color: Option<kittycad_modeling_cmds::shared::Color>,
+    settle: Option<u32>,
+) -> PyResult<Vec<u8>> {
+    if let Some(color) = background_color {
+        set_background_color(ctx, color).await?;
+    }
+    set_camera(ctx, camera).await?;
+    take_snapshot(ctx, image_format, settle).await
+}
+
+/// Snapshot only some of the objects in the scene, hiding the rest while the snapshot is taken.
+///
+/// The camera fits `frame`, or the objects if it is empty, unless a camera pose is given.
+async fn snapshot_objects(
+    executed: &Executed,
+    objects: &[ObjectHandle],
+    frame: Vec<uuid::Uuid>,
+    camera: Option<&CameraPose>,
+    image_format: ImageFormat,
+    background_color: Option<kittycad_modeling_cmds::shared::Color>,
+    settle: Option<u32>,
+) -> PyResult<Vec<u8>> {
+    let ids = objects.iter().map(ObjectHandle::uuid).collect::<PyResult<Vec<_>>>()?;
+    let hidden = find_objects(&executed.exec_state)?
+        .iter()
+        .map(ObjectHandle::uuid)
+        .filter(|id| !matches!(id, Ok(id) if ids.contains(id)))
+        .collect::<PyResult<Vec<_>>>()?;
+    set_visibility(&executed.ctx, &hidden, false).await?;
+
+    let image = async {
+        if let Some(color) = background_color {
+            set_background_color(&executed.ctx, color).await?;
+        }
+        match camera {
+            Some(camera) => set_camera(&executed.ctx, camera).await?,
+            None if frame.is_empty() => zoom_to_fit_objects(&executed.ctx, ids).await?,
+            None => zoom_to_fit_objects(&executed.ctx, frame).await?,
         }
+        take_snapshot(&executed.ctx, image_format, settle).await
+    }
+    .await;
+    set_visibility(&executed.ctx, &hidden, true).await?;
+
+    image
+}
+
+/// Show or hide objects in the scene.
+async fn set_visibility(ctx: &ExecutorContext, object_ids: &[uuid::Uuid], visible: bool) -> PyResult<()> {
+    for object_id in object_ids {
+        send_modeling_cmd(
+            ctx,
+            kittycad_modeling_cmds::ModelingCmd::ObjectVisible(kittycad_modeling_cmds::ObjectVisible {
+                object_id: *object_id,
+                hidden: !visible,
+            }),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Get the current settings of the default camera.
+async fn get_camera(ctx: &ExecutorContext) -> PyResult<kittycad_modeling_cmds::shared::CameraSettings> {
+    let resp = send_modeling_cmd(
+        ctx,
+        kittycad_modeling_cmds::ModelingCmd::DefaultCameraGetSettings(
+            kittycad_modeling_cmds::DefaultCameraGetSettings {},
+        ),
+    )
+    .await?;
+
+    let kittycad_modeling_cmds::websocket::OkWebSocketResponseData::Modeling {
+        modeling_response: kittycad_modeling_cmds::ok_response::OkModelingCmdResponse::DefaultCameraGetSettings(data),
+    } = resp
+    else {
+        return Err(engine_error::unexpected_response("DefaultCameraGetSettings", &resp));
+    };
+
+    Ok(data.settings)
+}
+
+/// Point the default camera at `center` from `vantage`.
+async fn look_at(
+    ctx: &ExecutorContext,
+    vantage: kittycad_modeling_cmds::shared::Point3d,
+    center: kittycad_modeling_cmds::shared::Point3d,
+    up: kittycad_modeling_cmds::shared::Point3d,
+) -> PyResult<()> {
+    send_modeling_cmd(
+        ctx,
+        kittycad_modeling_cmds::ModelingCmd::DefaultCameraLookAt(kittycad_modeling_cmds::DefaultCameraLookAt {
+            vantage,
+            center,
+            up,
+            sequence: None,
+        }),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Orbit the camera around the scene, taking a snapshot at each of `frames` equal steps of a full revolution.
+///
+/// The camera starts from where zoom to fit puts it and keeps that distance from the center of the scene, rotating
+/// around the vertical (+Z) axis. Each snapshot waits for the camera to settle and then `settle_frames` more frames.
+async fn turntable(
+    ctx: &ExecutorContext,
+    image_format: ImageFormat,
+    frames: usize,
+    settle_frames: u32,
+    progress: &progress::Progress,
+) -> PyResult<Vec<Vec<u8>>> {
+    zoom_to_fit(ctx).await?;
+    let camera = get_camera(ctx).await?;
+    let offset = (camera.pos.x - camera.center.x, camera.pos.y - camera.center.y);
+
+    let mut images = Vec::with_capacity(frames);
+    for frame in 0..frames {
+        progress.report_operation(progress::ProgressPhase::Snapshotting, frame, frames)?;
+        let angle = std::f32::consts::TAU * frame as f32 / frames as f32;
+        let (sin, cos) = angle.sin_cos();
+        let vantage = kittycad_modeling_cmds::shared::Point3d {
+            x: camera.center.x + offset.0 * cos - offset.1 * sin,
+            y: camera.center.y + offset.0 * sin + offset.1 * cos,
+            z: camera.pos.z,
+        };
+        look_at(ctx, vantage, camera.center, camera.up).await?;
+        images.push(take_snapshot(ctx, image_format, Some(settle_frames)).await?);
     }
+
+    Ok(images)
+}
+
+/// Export the scene to a specific file format.
+async fn export(
+    ctx: &ExecutorContext,
+    units: UnitLength,
+    export_format: FileExportFormat,
+) -> PyResult<Vec<ExportFile>> {
+    export_entities(ctx, units, export_format, vec![], Default::default()).await
 }
 
-async fn new_context(units: UnitLength) -> Result<ExecutorContext> {
-    let ctx = ExecutorContext::new_with_default_client(units).await?;
-    Ok(ctx)
+/// Export some of the scene to a specific file format, or all of it if no entities are given.
+///
+/// The options should have been checked against the format and entities with `export_options::OutputOptions::new`.
+async fn export_entities(
+    ctx: &ExecutorContext,
+    units: UnitLength,
+    export_format: FileExportFormat,
+    entity_ids: Vec<uuid::Uuid>,
+    output_options: export_options::OutputOptions,
+) -> PyResult<Vec<ExportFile>> {
+    // This will not return until there are files.
+    let resp = send_modeling_cmd(
+        ctx,
+        kittycad_modeling_cmds::ModelingCmd::Export(kittycad_modeling_cmds::Export {
+            entity_ids,
+            format: get_output_format(&export_format, units.into(), output_options),
+        }),
+    )
+    .await?;
+
+    let kittycad_modeling_cmds::websocket::OkWebSocketResponseData::Export { files } = resp else {
+        return Err(engine_error::unexpected_response("Export", &resp));
+    };
+
+    Ok(files
+        .into_iter()
+        .map(|file| ExportFile {
+            units: export_format.file_units(units),
+            format: Some(export_format.to_string()),
+            ..ExportFile::from(file)
+        })
+        .collect())
 }
 
-/// Execute the kcl code.
+/// Execute the kcl code, returning any non-fatal diagnostics.
+///
+/// Only the parser's warnings are returned. kcl-lib doesn't report warnings while executing, so a program that runs
+/// into something questionable at runtime still returns none, and `warnings_as_errors` only applies to parse-time
+/// warnings. Giving in-memory `files` or `attachments` raises `NotImplementedError`, since kcl-lib can only import files from
+/// disk; write them to a project directory and use `execute_project` instead. With `return_stats`, returns a
+/// `(diagnostics, RunStats)` tuple instead.
 #[pyfunction]
-async fn execute(code: String, units: UnitLength) -> PyResult<()> {
-    tokio()
-        .spawn(async move {
-            let program = kcl_lib::Program::parse_no_errs(&code).map_err(PyErr::from)?;
-            let ctx = new_context(units)
-                .await
-                .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
-            // Execute the program.
-            ctx.run(program.into(), &mut Default::default()).await?;
-
-            Ok(())
+#[pyo3(signature = (
+    code,
+    units = None,
+    warnings_as_errors = false,
+    files = None,
+    on_progress = None,
+    attachments = None,
+    return_stats = false,
+))]
+async fn execute(
+    code: Code,
+    units: Option<Units>,
+    warnings_as_errors: bool,
+    files: Option<HashMap<String, String>>,
+    on_progress: Option<PyObject>,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+    return_stats: bool,
+) -> PyResult<stats::WithStats<Vec<Diagnostic>>> {
+    reject_module_files(files, attachments)?;
+    connection::require_token(None)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        warnings_as_errors,
+        progress: progress::Progress::new(on_progress),
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options) = (&code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            let stats = executed.stats();
+
+            Ok(stats::WithStats::new(executed.warnings, stats, return_stats))
         })
         .await
-        .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?
+    })
+    .await
+}
+
+/// Check the kcl code for errors without contacting the engine, returning every problem found or nothing if it's
+/// clean.
+///
+/// Besides syntax errors this finds the errors that only show up when the program runs, like undefined variables or
+/// calling a function with the wrong arguments, by executing it against a mock engine. Problems that depend on the
+/// actual geometry can only be found by `execute`.
+#[pyfunction]
+#[pyo3(signature = (code, units = None))]
+async fn validate(code: String, units: Option<Units>) -> PyResult<Vec<Diagnostic>> {
+    let units = defaults::units(units)?;
+    spawn(async move {
+        let (program, errors) = match kcl_lib::Program::parse(&code) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                let offset = err
+                    .source_ranges()
+                    .first()
+                    .map(|range| range.start())
+                    .unwrap_or_default();
+                return Ok(vec![
+                    settings_annotation_error(&code, offset).unwrap_or_else(|| err.into())
+                ]);
+            }
+        };
+        let mut diagnostics: Vec<Diagnostic> = errors
+            .into_iter()
+            .map(|err| settings_annotation_error(&code, err.source_range.start()).unwrap_or_else(|| err.into()))
+            .collect();
+        let Some(program) = program.filter(|_| !diagnostics.iter().any(|d| d.severity == "error")) else {
+            return Ok(diagnostics);
+        };
+        let mut ctx = ExecutorContext::new_mock().await;
+        ctx.settings.units = units.unwrap_or(UnitLength::Mm).into();
+        let mut exec_state = kcl_lib::ExecState::default();
+        if let Err(err) = ctx.run(program.into(), &mut exec_state).await {
+            diagnostics.push(Diagnostic::from(err));
+        }
+
+        Ok(diagnostics)
+    })
+    .await
+}
+
+/// Find the names of the top level variables whose value comes from a call to `appearance`.
+fn find_explicit_appearances(program: &kcl_lib::Program) -> PyResult<Vec<String>> {
+    fn calls_appearance(node: &serde_json::Value) -> bool {
+        match node {
+            serde_json::Value::Object(object) => {
+                let is_call = object.get("type").and_then(|t| t.as_str()) == Some("CallExpression")
+                    && object
+                        .get("callee")
+                        .and_then(|callee| callee.get("name"))
+                        .and_then(|name| name.as_str())
+                        == Some("appearance");
+                is_call || object.values().any(calls_appearance)
+            }
+            serde_json::Value::Array(array) => array.iter().any(calls_appearance),
+            _ => false,
+        }
+    }
+
+    let ast =
+        serde_json::to_value(&program.ast).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+    let body = ast
+        .get("body")
+        .and_then(|body| body.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let mut names = Vec::new();
+    for item in body.iter().filter(|item| calls_appearance(item)) {
+        // Both the singular and plural forms have been used for declarations.
+        let declarations = item
+            .get("declarations")
+            .and_then(|d| d.as_array().cloned())
+            .or_else(|| item.get("declaration").map(|d| vec![d.clone()]))
+            .unwrap_or_default();
+        names.extend(declarations.iter().filter_map(|declaration| {
+            declaration
+                .get("id")
+                .and_then(|id| id.get("name"))
+                .and_then(|name| name.as_str())
+                .map(String::from)
+        }));
+    }
+
+    Ok(names)
+}
+
+/// Set the appearance of every solid that doesn't already have one set explicitly in the kcl code.
+async fn set_default_appearance(executed: &Executed, appearance: &Appearance) -> PyResult<()> {
+    let explicit = find_explicit_appearances(&executed.program)?;
+    for (name, id) in find_solids(&executed.exec_state)? {
+        if explicit.contains(&name) {
+            continue;
+        }
+        send_modeling_cmd(
+            &executed.ctx,
+            kittycad_modeling_cmds::ModelingCmd::ObjectSetMaterialParamsPbr(
+                kittycad_modeling_cmds::ObjectSetMaterialParamsPbr {
+                    object_id: id,
+                    color: appearance.color,
+                    metalness: appearance.metalness,
+                    roughness: appearance.roughness,
+                    ambient_occlusion: 0.0,
+                },
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Work out the background color for a snapshot, making sure the image format can represent it.
+fn get_background_color(
+    image_format: ImageFormat,
+    background_color: Option<Color>,
+    transparent: bool,
+) -> PyResult<Option<kittycad_modeling_cmds::shared::Color>> {
+    let color = match (background_color, transparent) {
+        (Some(_), true) => {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Only one of background_color and transparent may be given",
+            ))
+        }
+        (Some(color), false) => Some(kittycad_modeling_cmds::shared::Color::try_from(color)?),
+        (None, true) => Some(kittycad_modeling_cmds::shared::Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+            a: 0.0,
+        }),
+        (None, false) => None,
+    };
+
+    if image_format == ImageFormat::Jpeg && color.as_ref().is_some_and(|color| color.a < 1.0) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "JPEG snapshots can't have a transparent background, use ImageFormat.Png instead",
+        ));
+    }
+
+    Ok(color)
 }
 
 /// Execute the kcl code and snapshot it in a specific format.
+///
+/// If `entity_names` is given, only the objects bound to those top level variables are shown, and the camera fits
+/// them alone. `render_mode` overrides whether edges are drawn; a mode the engine can't draw raises
+/// `NotImplementedError` rather than falling back to another. If `camera` is given the snapshot is taken from exactly
+/// there, instead of zooming to fit. With `deterministic`, the camera is given time to settle, and then the render
+/// settings' `settle_frames`, before the snapshot, so the same program gives the same image; use
+/// `RenderSettings.deterministic()` for the same bytes every time. With `return_stats`, returns an `(image, RunStats)`
+/// tuple instead.
+///
+/// `frame_objects` names top level variables for the camera to fit, with everything else still shown, e.g. to fill
+/// the image with a small part next to a large jig. It can't be given along with `camera`.
+///
+/// Raises `KclEmptySceneError` if the code creates no solids or sketches, unless `allow_empty` is set, in which case
+/// the empty scene is snapshotted.
 #[pyfunction]
-async fn execute_and_snapshot(code: String, units: UnitLength, image_format: ImageFormat) -> PyResult<Vec<u8>> {
-    tokio()
-        .spawn(async move {
-            let program = kcl_lib::Program::parse_no_errs(&code).map_err(PyErr::from)?;
-            let ctx = new_context(units)
-                .await
-                .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
-            // Execute the program.
-            ctx.run(program.into(), &mut Default::default()).await?;
-
-            // Zoom to fit.
-            ctx.engine
-                .send_modeling_cmd(
-                    uuid::Uuid::new_v4(),
-                    kcl_lib::SourceRange::default(),
-                    kittycad_modeling_cmds::ModelingCmd::ZoomToFit(kittycad_modeling_cmds::ZoomToFit {
-                        object_ids: Default::default(),
-                        padding: 0.1,
-                        animated: false,
-                    }),
-                )
-                .await?;
+#[pyo3(signature = (
+    code,
+    units = None,
+    image_format = None,
+    render_settings = None,
+    background_color = None,
+    transparent = false,
+    appearance = None,
+    warnings_as_errors = false,
+    files = None,
+    on_progress = None,
+    entity_names = None,
+    render_mode = None,
+    camera = None,
+    deterministic = true,
+    attachments = None,
+    return_stats = false,
+    frame_objects = None,
+    allow_empty = false,
+))]
+#[allow(clippy::too_many_arguments)]
+async fn execute_and_snapshot(
+    code: Code,
+    units: Option<Units>,
+    image_format: Option<ImageFormat>,
+    render_settings: Option<RenderSettings>,
+    background_color: Option<Color>,
+    transparent: bool,
+    appearance: Option<Appearance>,
+    warnings_as_errors: bool,
+    files: Option<HashMap<String, String>>,
+    on_progress: Option<PyObject>,
+    entity_names: Option<Vec<String>>,
+    render_mode: Option<RenderMode>,
+    camera: Option<CameraPose>,
+    deterministic: bool,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+    return_stats: bool,
+    frame_objects: Option<Vec<String>>,
+    allow_empty: bool,
+) -> PyResult<stats::WithStats<Vec<u8>>> {
+    let options = snapshot::SnapshotOptions::new(
+        units,
+        image_format,
+        render_settings,
+        background_color,
+        transparent,
+        appearance,
+        warnings_as_errors,
+        files,
+        on_progress,
+        entity_names,
+        render_mode,
+        camera,
+        deterministic,
+        attachments,
+        frame_objects,
+        allow_empty,
+    )?;
+    let result = run_snapshot(code, options).await?;
 
-            // Send a snapshot request to the engine.
-            let resp = ctx
-                .engine
-                .send_modeling_cmd(
-                    uuid::Uuid::new_v4(),
-                    kcl_lib::SourceRange::default(),
-                    kittycad_modeling_cmds::ModelingCmd::TakeSnapshot(kittycad_modeling_cmds::TakeSnapshot {
-                        format: image_format.into(),
-                    }),
-                )
-                .await?;
+    Ok(stats::WithStats::new(result.data, result.stats, return_stats))
+}
 
-            let kittycad_modeling_cmds::websocket::OkWebSocketResponseData::Modeling {
-                modeling_response: kittycad_modeling_cmds::ok_response::OkModelingCmdResponse::TakeSnapshot(data),
-            } = resp
-            else {
-                return Err(pyo3::exceptions::PyException::new_err(format!(
-                    "Unexpected response from engine: {:?}",
-                    resp
-                )));
+/// Execute the kcl code and snapshot it, like `execute_and_snapshot`, along with the size of the image, where the
+/// camera was and how long execution took.
+///
+/// `options` are the rest of `execute_and_snapshot`'s arguments, or its defaults if not given.
+#[pyfunction]
+#[pyo3(signature = (code, options = None))]
+async fn execute_and_snapshot_ex(
+    code: Code,
+    options: Option<snapshot::SnapshotOptions>,
+) -> PyResult<snapshot::SnapshotResult> {
+    run_snapshot(code, options.unwrap_or_default()).await
+}
+
+/// Execute the kcl code and snapshot it as the options say.
+async fn run_snapshot(code: Code, options: snapshot::SnapshotOptions) -> PyResult<snapshot::SnapshotResult> {
+    let snapshot::SnapshotOptions {
+        units,
+        image_format,
+        render_settings,
+        background_color,
+        transparent,
+        appearance,
+        warnings_as_errors,
+        files,
+        progress,
+        entity_names,
+        render_mode,
+        camera,
+        deterministic,
+        attachments,
+        frame_objects,
+        allow_empty,
+    } = options;
+    reject_module_files(files, attachments)?;
+    connection::require_token(None)?;
+    let image_format = defaults::image_format(image_format)?;
+    let edge_lines_visible = render_mode.map(RenderMode::edge_lines_visible).transpose()?;
+    if entity_names.as_ref().is_some_and(|names| names.is_empty()) {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "entity_names must name at least one object",
+        ));
+    }
+    let frame_objects = frame_objects.unwrap_or_default();
+    if !frame_objects.is_empty() && camera.is_some() {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "Give either a camera or frame_objects, not both",
+        ));
+    }
+    let units = units.or(defaults::units(None)?);
+    let background_color = get_background_color(image_format, background_color, transparent)?;
+    let render_settings = render_settings.unwrap_or_default();
+    let settle = render_settings.settle(deterministic);
+    let options = RunOptions {
+        units,
+        render_settings,
+        warnings_as_errors,
+        progress,
+        require_objects: !allow_empty,
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options, appearance, entity_names, camera, frame_objects) =
+            (&code, &options, &appearance, &entity_names, &camera, &frame_objects);
+        with_reconnect(|| async move {
+            let start = std::time::Instant::now();
+            let executed = run_code(code, options).await?;
+            let execution_ms = start.elapsed().as_secs_f64() * 1000.0;
+            if let Some(appearance) = appearance {
+                set_default_appearance(&executed, appearance).await?;
+            }
+            if let Some(visible) = edge_lines_visible {
+                set_edge_lines_visible(&executed.ctx, visible).await?;
+            }
+            options.progress.report(progress::ProgressPhase::Snapshotting, None)?;
+            let frame = find_named_objects(&executed.exec_state, frame_objects)?
+                .iter()
+                .map(ObjectHandle::uuid)
+                .collect::<PyResult<Vec<_>>>()?;
+            let data = match (entity_names, camera) {
+                (Some(names), camera) => {
+                    let objects = find_named_objects(&executed.exec_state, names)?;
+                    snapshot_objects(
+                        &executed,
+                        &objects,
+                        frame,
+                        camera.as_ref(),
+                        image_format,
+                        background_color,
+                        settle,
+                    )
+                    .await?
+                }
+                (None, Some(camera)) => {
+                    snapshot_from(&executed.ctx, camera, image_format, background_color, settle).await?
+                }
+                (None, None) => snapshot_framing(&executed.ctx, frame, image_format, background_color, settle).await?,
             };
+            let camera = get_camera(&executed.ctx).await?.into();
+            snapshot::SnapshotResult::new(data, image_format, camera, execution_ms, executed.stats())
+        })
+        .await
+    })
+    .await
+}
 
-            Ok(data.contents.0)
+/// Execute the kcl code and snapshot it from `frames` evenly spaced angles in a full turn around it.
+///
+/// The images are returned in order, all from the same engine session.
+#[pyfunction]
+#[pyo3(signature = (
+    code,
+    units = None,
+    image_format = None,
+    frames = 36,
+    render_settings = None,
+    background_color = None,
+    transparent = false,
+    warnings_as_errors = false,
+    files = None,
+    on_progress = None,
+    attachments = None,
+))]
+#[allow(clippy::too_many_arguments)]
+async fn execute_and_snapshot_turntable(
+    code: Code,
+    units: Option<Units>,
+    image_format: Option<ImageFormat>,
+    frames: usize,
+    render_settings: Option<RenderSettings>,
+    background_color: Option<Color>,
+    transparent: bool,
+    warnings_as_errors: bool,
+    files: Option<HashMap<String, String>>,
+    on_progress: Option<PyObject>,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+) -> PyResult<Vec<Vec<u8>>> {
+    reject_module_files(files, attachments)?;
+    connection::require_token(None)?;
+    let image_format = defaults::image_format(image_format)?;
+    let units = defaults::units(units)?;
+    if frames < 2 {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "A turntable needs at least 2 frames, got {}",
+            frames
+        )));
+    }
+    let background_color = get_background_color(image_format, background_color, transparent)?;
+    let options = RunOptions {
+        units,
+        render_settings: render_settings.unwrap_or_default(),
+        warnings_as_errors,
+        progress: progress::Progress::new(on_progress),
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options) = (&code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            if let Some(color) = background_color {
+                set_background_color(&executed.ctx, color).await?;
+            }
+            turntable(
+                &executed.ctx,
+                image_format,
+                frames,
+                options.render_settings.settle_frames,
+                &options.progress,
+            )
+            .await
         })
         .await
-        .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?
+    })
+    .await
 }
 
 /// Execute the kcl code and export it to a specific file format.
+///
+/// STL and PLY exports can be given a `selection` of which scene or mesh to export. `format_options` can be the
+/// `FbxOptions`, `StlOptions`, `PlyOptions` or `GltfOptions` for the format, e.g. for an ASCII FBX; without them
+/// each format is exported as it always has been. With `return_stats`, returns a `(files, RunStats)` tuple instead.
+///
+/// With a `base_name`, the files are named `{base_name}.{ext}` rather than what the engine called them, and files
+/// that refer to each other, like a glTF and its buffers, are changed to match.
+///
+/// Raises `KclEmptySceneError` if the code creates no solids or sketches, unless `allow_empty` is set. With
+/// `validate`, the files are checked like `validate_export` does, raising `InvalidExportError` if any has problems.
 #[pyfunction]
+#[pyo3(signature = (
+    code,
+    units = None,
+    export_format = None,
+    warnings_as_errors = false,
+    files = None,
+    on_progress = None,
+    selection = None,
+    format_options = None,
+    attachments = None,
+    return_stats = false,
+    base_name = None,
+    allow_empty = false,
+    validate = false,
+))]
+#[allow(clippy::too_many_arguments)]
 async fn execute_and_export(
-    code: String,
-    units: UnitLength,
-    export_format: FileExportFormat,
-) -> PyResult<Vec<ExportFile>> {
-    tokio()
-        .spawn(async move {
-            let program = kcl_lib::Program::parse_no_errs(&code).map_err(PyErr::from)?;
-            let ctx = new_context(units)
-                .await
-                .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
-            // Execute the program.
-            ctx.run(program.into(), &mut Default::default()).await?;
-
-            // This will not return until there are files.
-            let resp = ctx
-                .engine
-                .send_modeling_cmd(
-                    uuid::Uuid::new_v4(),
-                    kcl_lib::SourceRange::default(),
-                    kittycad_modeling_cmds::ModelingCmd::Export(kittycad_modeling_cmds::Export {
-                        entity_ids: vec![],
-                        format: get_output_format(&export_format, units.into()),
-                    }),
-                )
-                .await?;
+    code: Code,
+    units: Option<Units>,
+    export_format: Option<FileExportFormat>,
+    warnings_as_errors: bool,
+    files: Option<HashMap<String, String>>,
+    on_progress: Option<PyObject>,
+    selection: Option<ExportSelection>,
+    format_options: Option<export_options::ExportOptions>,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+    return_stats: bool,
+    base_name: Option<String>,
+    allow_empty: bool,
+    validate: bool,
+) -> PyResult<stats::WithStats<Vec<ExportFile>>> {
+    reject_module_files(files, attachments)?;
+    connection::require_token(None)?;
+    let export_format = defaults::export_format(export_format)?;
+    let output_options = export_options::OutputOptions::new(&export_format, format_options, selection, &[])?;
+    if let Some(base_name) = &base_name {
+        file_names::check_base_name(base_name)?;
+    }
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        warnings_as_errors,
+        progress: progress::Progress::new(on_progress),
+        require_objects: !allow_empty,
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options, export_format, output_options, base_name) =
+            (&code, &options, &export_format, &output_options, &base_name);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            options.progress.report(progress::ProgressPhase::Exporting, None)?;
+            let files = export_entities(
+                &executed.ctx,
+                executed.units,
+                export_format.clone(),
+                vec![],
+                output_options.clone(),
+            )
+            .await?;
+            let files = match base_name {
+                Some(base_name) => file_names::rename(files, base_name)?,
+                None => files,
+            };
+            if validate {
+                export_checks::check(&files)?;
+            }
 
-            let kittycad_modeling_cmds::websocket::OkWebSocketResponseData::Export { files } = resp else {
-                return Err(pyo3::exceptions::PyException::new_err(format!(
-                    "Unexpected response from engine: {:?}",
-                    resp
-                )));
+            Ok(stats::WithStats::new(files, executed.stats(), return_stats))
+        })
+        .await
+    })
+    .await
+}
+
+/// Execute the kcl code once, then snapshot it and export it to a specific file format.
+///
+/// The export happens after the snapshot, over the same engine session, so the program only runs once. The camera
+/// has no effect on exported geometry, so zooming to fit for the snapshot does not change the exported files.
+/// Raises `KclEmptySceneError` if the code creates no solids or sketches, unless `allow_empty` is set.
+#[pyfunction]
+#[pyo3(signature = (
+    code,
+    units = None,
+    image_format = None,
+    export_format = None,
+    render_settings = None,
+    warnings_as_errors = false,
+    files = None,
+    on_progress = None,
+    attachments = None,
+    allow_empty = false,
+))]
+#[allow(clippy::too_many_arguments)]
+async fn execute_and_snapshot_and_export(
+    code: Code,
+    units: Option<Units>,
+    image_format: Option<ImageFormat>,
+    export_format: Option<FileExportFormat>,
+    render_settings: Option<RenderSettings>,
+    warnings_as_errors: bool,
+    files: Option<HashMap<String, String>>,
+    on_progress: Option<PyObject>,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+    allow_empty: bool,
+) -> PyResult<(Vec<u8>, Vec<ExportFile>)> {
+    reject_module_files(files, attachments)?;
+    connection::require_token(None)?;
+    let image_format = defaults::image_format(image_format)?;
+    let export_format = defaults::export_format(export_format)?;
+    let units = defaults::units(units)?;
+    let options = RunOptions {
+        units,
+        render_settings: render_settings.unwrap_or_default(),
+        warnings_as_errors,
+        progress: progress::Progress::new(on_progress),
+        require_objects: !allow_empty,
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options, export_format) = (&code, &options, &export_format);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            options.progress.report(progress::ProgressPhase::Snapshotting, None)?;
+            let image = snapshot(&executed.ctx, image_format, None, options.render_settings.settle(true)).await?;
+            options.progress.report(progress::ProgressPhase::Exporting, None)?;
+            let files = export(&executed.ctx, executed.units, export_format.clone()).await?;
+
+            Ok((image, files))
+        })
+        .await
+    })
+    .await
+}
+
+/// Find the sketches and solids in a value from memory, as kcl-lib serializes them, each once by id in the order they are
+/// first seen. A solid holds the sketch it was made from, and a sketch on a face holds the solid the face is on.
+fn collect_geometry<'a>(
+    value: &'a serde_json::Value,
+    sketches: &mut Vec<&'a serde_json::Value>,
+    solids: &mut Vec<&'a serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            let found = match (value["type"].as_str(), value["id"].as_str()) {
+                (Some("Sketch"), Some(id)) if value["paths"].is_array() => Some((&mut *sketches, id)),
+                (Some("Solid"), Some(id)) if value["sketch"].is_object() => Some((&mut *solids, id)),
+                _ => None,
+            };
+            if let Some((found, id)) = found {
+                if !found.iter().any(|other| other["id"] == id) {
+                    found.push(value);
+                }
+            }
+            fields
+                .values()
+                .for_each(|field| collect_geometry(field, sketches, solids));
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|item| collect_geometry(item, sketches, solids)),
+        _ => {}
+    }
+}
+
+/// The artifact for a piece of geometry, with its source range as a `codeRef` if it has one.
+fn artifact(id: &serde_json::Value, kind: &str, range: &serde_json::Value) -> serde_json::Value {
+    let mut artifact = serde_json::json!({ "id": id, "type": kind });
+    if range.is_array() {
+        artifact["codeRef"] = serde_json::json!({ "range": range });
+    }
+    artifact
+}
+
+/// Turn the geometry the program bound at the top level into a list of nodes, one per artifact, and a list of edges,
+/// one for each reference an artifact makes to another (e.g. from a segment to its path).
+///
+/// kcl-lib doesn't record an artifact graph, so the graph is made from the sketches and solids in memory. An extruded
+/// sketch and its solid are the same object in the engine, so they are a single `sweep` artifact.
+fn artifact_graph(exec_state: &kcl_lib::ExecState) -> PyResult<serde_json::Value> {
+    let bindings = serde_json::Value::Object(trace::root_bindings(exec_state)?);
+    let (mut sketches, mut solids) = (Vec::new(), Vec::new());
+    collect_geometry(&bindings, &mut sketches, &mut solids);
+    for solid in &solids {
+        if !sketches.iter().any(|sketch| sketch["id"] == solid["sketch"]["id"]) {
+            sketches.push(&solid["sketch"]);
+        }
+    }
+
+    let mut artifacts: Vec<serde_json::Value> = Vec::new();
+    for (index, sketch) in sketches.iter().enumerate() {
+        // A pattern copies a sketch with a new id but the same segments, and the copies aren't recorded as artifacts.
+        let first_segment = &sketch["paths"][0]["__geoMeta"]["id"];
+        if sketches[..index]
+            .iter()
+            .any(|other| first_segment.is_string() && other["paths"][0]["__geoMeta"]["id"] == *first_segment)
+        {
+            continue;
+        }
+        let on = &sketch["on"];
+        // A sketch on a face refers to the face, which is an artifact of the solid it is on.
+        if on["type"] == "plane" && !artifacts.iter().any(|artifact| artifact["id"] == on["id"]) {
+            artifacts.push(artifact(&on["id"], "plane", &on["__meta"][0]["sourceRange"]));
+        }
+        let solid = solids.iter().find(|solid| solid["id"] == sketch["id"]);
+        if solid.is_none() {
+            let mut path = artifact(&sketch["id"], "path", &sketch["__meta"][0]["sourceRange"]);
+            path["planeId"] = on["id"].clone();
+            artifacts.push(path);
+        }
+        for segment in sketch["paths"].as_array().into_iter().flatten() {
+            let meta = &segment["__geoMeta"];
+            let mut segment = artifact(&meta["id"], "segment", &meta["sourceRange"]);
+            segment["pathId"] = sketch["id"].clone();
+            artifacts.push(segment);
+        }
+        let Some(solid) = solid else {
+            continue;
+        };
+
+        let mut sweep = artifact(&solid["id"], "sweep", &solid["__meta"][0]["sourceRange"]);
+        sweep["planeId"] = on["id"].clone();
+        artifacts.push(sweep);
+        for surface in solid["value"].as_array().into_iter().flatten() {
+            // Fillets and chamfers make faces too, but they are recorded as edge cuts.
+            if surface["type"] == "extrudePlane" || surface["type"] == "extrudeArc" {
+                let mut wall = artifact(&surface["faceId"], "wall", &surface["sourceRange"]);
+                wall["segId"] = surface["id"].clone();
+                wall["sweepId"] = solid["id"].clone();
+                artifacts.push(wall);
+            }
+        }
+        for (side, field) in [("start", "startCapId"), ("end", "endCapId")] {
+            if solid[field].is_string() {
+                let mut cap = artifact(&solid[field], "cap", &serde_json::Value::Null);
+                cap["subType"] = side.into();
+                cap["sweepId"] = solid["id"].clone();
+                artifacts.push(cap);
+            }
+        }
+        for edge_cut in solid["edgeCuts"].as_array().into_iter().flatten() {
+            let mut cut = artifact(&edge_cut["id"], "edgeCut", &serde_json::Value::Null);
+            cut["subType"] = edge_cut["type"].clone();
+            cut["consumedEdgeId"] = edge_cut["edgeId"].clone();
+            cut["sweepId"] = solid["id"].clone();
+            artifacts.push(cut);
+        }
+    }
+
+    let ids: std::collections::HashSet<&str> = artifacts
+        .iter()
+        .filter_map(|artifact| artifact.get("id").and_then(|id| id.as_str()))
+        .collect();
+    let mut edges = Vec::new();
+    for artifact in &artifacts {
+        let (Some(from), Some(fields)) = (artifact.get("id").and_then(|id| id.as_str()), artifact.as_object()) else {
+            continue;
+        };
+        for (field, value) in fields {
+            let (kind, targets) = if let Some(kind) = field.strip_suffix("Ids") {
+                let targets = value.as_array().map(|ids| ids.iter().collect()).unwrap_or_default();
+                (kind, targets)
+            } else if let Some(kind) = field.strip_suffix("Id") {
+                (kind, vec![value])
+            } else {
+                continue;
             };
+            for to in targets.into_iter().filter_map(|id| id.as_str()) {
+                if ids.contains(to) {
+                    edges.push(serde_json::json!({ "from": from, "to": to, "kind": kind }));
+                }
+            }
+        }
+    }
+
+    Ok(serde_json::json!({ "nodes": artifacts, "edges": edges }))
+}
 
-            Ok(files.into_iter().map(ExportFile::from).collect())
+/// Execute the kcl code and return a best-effort graph of the artifacts (sketches, segments, extrusions, faces, ...)
+/// it created.
+///
+/// kcl-lib 0.2.29 doesn't record an artifact graph, so this one is pieced together from the sketches and solids left
+/// in the program's memory once it has run. It is not the graph the engine or the modeling app would give: geometry
+/// only used inside a function or never bound to a variable is missing, an extruded sketch and its solid are one
+/// `sweep` node, and the node types and edge kinds may change once kcl-lib records the real graph. Use it for
+/// inspection and debugging, not for anything that needs every artifact.
+///
+/// Each node has an `id`, a `type` and, where there is one, a `codeRef` with the source range of the code that
+/// created it. Each edge has `from` and `to` ids and the `kind` of relationship.
+#[pyfunction]
+#[pyo3(signature = (code, units = None, files = None, on_progress = None, attachments = None))]
+async fn execute_and_get_artifact_graph(
+    code: Code,
+    units: Option<Units>,
+    files: Option<HashMap<String, String>>,
+    on_progress: Option<PyObject>,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+) -> PyResult<PyObject> {
+    reject_module_files(files, attachments)?;
+    connection::require_token(None)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        progress: progress::Progress::new(on_progress),
+        ..Default::default()
+    };
+    let graph = spawn(async move {
+        let (code, options) = (&code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            artifact_graph(&executed.exec_state)
         })
         .await
-        .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?
+    })
+    .await?;
+
+    Python::with_gil(|py| to_py_object(py, &graph))
+}
+
+/// How long one part of executing kcl code took.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct ReportEntry {
+    /// What was timed, e.g. `parse`, `connect` or `export`.
+    #[pyo3(get)]
+    pub label: String,
+    /// Wall-clock duration in milliseconds.
+    #[pyo3(get)]
+    pub duration_ms: f64,
+    /// The source range of the code this entry is for, as a `(start, end)` pair of byte offsets, if there is one.
+    #[pyo3(get)]
+    pub source_range: Option<(usize, usize)>,
+}
+
+#[pymethods]
+impl ReportEntry {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Timings for each part of executing kcl code, along with whatever it produced.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct ExecutionReport {
+    /// Each timed part, in the order they happened.
+    #[pyo3(get)]
+    pub entries: Vec<ReportEntry>,
+    /// Wall-clock duration of the whole call in milliseconds.
+    #[pyo3(get)]
+    pub total_ms: f64,
+    /// The snapshot, if one was requested.
+    #[pyo3(get)]
+    pub image: Option<Vec<u8>>,
+    /// The exported files, if an export was requested.
+    #[pyo3(get)]
+    pub files: Vec<ExportFile>,
+}
+
+#[pymethods]
+impl ExecutionReport {
+    /// The report as a dict, for logging. The image and files are left out.
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_py_object(
+            py,
+            &serde_json::json!({
+                "entries": self.entries,
+                "total_ms": self.total_ms,
+            }),
+        )
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Records how long each step of executing kcl code takes.
+struct Profiler {
+    start: std::time::Instant,
+    entries: Vec<ReportEntry>,
+}
+
+impl Profiler {
+    fn new() -> Self {
+        Profiler {
+            start: std::time::Instant::now(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Await a step, recording how long it took.
+    async fn time<T>(
+        &mut self,
+        label: &str,
+        source_range: Option<(usize, usize)>,
+        step: impl Future<Output = PyResult<T>>,
+    ) -> PyResult<T> {
+        let start = std::time::Instant::now();
+        let result = step.await?;
+        self.entries.push(ReportEntry {
+            label: label.to_string(),
+            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+            source_range,
+        });
+
+        Ok(result)
+    }
+
+    fn finish(self, image: Option<Vec<u8>>, files: Vec<ExportFile>) -> ExecutionReport {
+        ExecutionReport {
+            entries: self.entries,
+            total_ms: self.start.elapsed().as_secs_f64() * 1000.0,
+            image,
+            files,
+        }
+    }
+}
+
+/// Execute the kcl code, optionally snapshot and export it, and report how long each step took.
+///
+/// Parsing, connecting to the engine, executing the program and each command sent to the engine afterwards are all
+/// timed separately. The other execute functions don't record any timings.
+#[pyfunction]
+#[pyo3(signature = (code, units = None, image_format = None, export_format = None))]
+async fn execute_with_report(
+    code: Code,
+    units: Option<Units>,
+    image_format: Option<ImageFormat>,
+    export_format: Option<FileExportFormat>,
+) -> PyResult<ExecutionReport> {
+    connection::require_token(None)?;
+    let units = defaults::units(units)?;
+    spawn(async move {
+        let mut profiler = Profiler::new();
+        let whole_program = Some((0, code.source().len()));
+        let (program, _) = profiler.time("parse", whole_program, async { code.parse() }).await?;
+        let units = units.unwrap_or(UnitLength::Mm);
+        let ctx = profiler
+            .time("connect", None, new_context(units, &Default::default(), None))
+            .await?;
+        let mut exec_state = kcl_lib::ExecState::default();
+        profiler
+            .time("execute", whole_program, async {
+                ctx.run(program.into(), &mut exec_state).await.map_err(kcl_error)
+            })
+            .await?;
+
+        let mut image = None;
+        if let Some(image_format) = image_format {
+            profiler.time("zoom_to_fit", None, zoom_to_fit(&ctx)).await?;
+            image = Some(
+                profiler
+                    .time("snapshot", None, take_snapshot(&ctx, image_format, Some(0)))
+                    .await?,
+            );
+        }
+        let mut files = Vec::new();
+        if let Some(export_format) = export_format {
+            files = profiler
+                .time("export", None, export(&ctx, units, export_format))
+                .await?;
+        }
+
+        Ok(profiler.finish(image, files))
+    })
+    .await
+}
+
+fn recast(code: &str, options: &kcl_lib::FormatOptions) -> Result<String, kcl_lib::KclError> {
+    let program = kcl_lib::Program::parse_no_errs(code)?;
+    Ok(program.ast.recast(options, 0))
+}
+
+/// Find all the kcl files under a directory, recursively, in a stable order.
+///
+/// Only not being able to list `dir` itself fails; a directory under it that can't be listed is put in `errors`.
+fn find_kcl_files(
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+    errors: &mut BTreeMap<String, Diagnostic>,
+) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if let Err(err) = find_kcl_files(&path, files, errors) {
+                let message = format!("Could not read {}: {}", path.display(), err);
+                errors.insert(path.display().to_string(), file_checks::file_error(message));
+            }
+        } else if path.extension().is_some_and(|ext| ext == "kcl") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
 }
 
 /// Format the kcl code.
 #[pyfunction]
-fn format(code: String) -> PyResult<String> {
-    let program = kcl_lib::Program::parse_no_errs(&code).map_err(PyErr::from)?;
-    let recasted = program.recast();
+#[pyo3(signature = (code, options = None))]
+fn format(code: Code, options: Option<FormatOptions>) -> PyResult<String> {
+    let options: kcl_lib::FormatOptions = options.unwrap_or_default().into();
+    let recasted = match code {
+        Code::Text(code) => recast(&code, &options).map_err(PyErr::from)?,
+        Code::Program(_) => code.parse_no_errs()?.ast.recast(&options, 0),
+    };
 
     Ok(recasted)
 }
 
+/// Format all the kcl files in a directory, recursively.
+///
+/// Files are only written when their formatted contents differ. Returns a `FormatDirResult` with the paths of the files
+/// that changed, or with `check` set, the files that would change without writing anything, and the error for each
+/// file that couldn't be read, parsed or written. A file that fails doesn't stop the others being formatted.
+#[pyfunction]
+#[pyo3(signature = (path, check = false, options = None))]
+fn format_dir(path: PathBuf, check: bool, options: Option<FormatOptions>) -> PyResult<file_checks::FormatDirResult> {
+    let options: kcl_lib::FormatOptions = options.unwrap_or_default().into();
+    let mut files = Vec::new();
+    let mut errors = BTreeMap::new();
+    find_kcl_files(&path, &mut files, &mut errors)?;
+
+    let mut changed = Vec::new();
+    for file in files {
+        let name = file.display().to_string();
+        let code = match file_checks::read(&name) {
+            Ok(code) => code,
+            Err(err) => {
+                errors.insert(name, err);
+                continue;
+            }
+        };
+        let recasted = match recast(&code, &options) {
+            Ok(recasted) => recasted,
+            Err(err) => {
+                errors.insert(name, Diagnostic::from(err));
+                continue;
+            }
+        };
+        if recasted == code {
+            continue;
+        }
+        if !check {
+            if let Err(err) = std::fs::write(&file, recasted) {
+                let message = format!("Could not write {}: {}", name, err);
+                errors.insert(name, file_checks::file_error(message));
+                continue;
+            }
+        }
+        changed.push(name);
+    }
+
+    Ok(file_checks::FormatDirResult { changed, errors })
+}
+
 /// Lint the kcl code.
+///
+/// With `min_severity`, only findings at least that severe are returned, e.g. `"warning"` leaves out `info` ones.
 #[pyfunction]
-fn lint(code: String) -> PyResult<Vec<Discovered>> {
-    let program = kcl_lib::Program::parse_no_errs(&code).map_err(PyErr::from)?;
-    let lints = program
+#[pyo3(signature = (code, min_severity = None))]
+fn lint(code: Code, min_severity: Option<lints::Severity>) -> PyResult<Vec<lints::LintFinding>> {
+    let min_severity = min_severity.map(lints::LintSeverity::try_from).transpose()?;
+    lint_program(&code.parse_no_errs()?, min_severity)
+}
+
+/// Lint a parsed program, keeping only the findings at least as severe as `min_severity` if there is one.
+fn lint_program(
+    program: &kcl_lib::Program,
+    min_severity: Option<lints::LintSeverity>,
+) -> PyResult<Vec<lints::LintFinding>> {
+    let discovered = program
         .lint(checks::lint_variables)
         .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
 
-    Ok(lints)
+    Ok(discovered
+        .into_iter()
+        .map(lints::LintFinding::from)
+        .filter(|finding| min_severity.is_none_or(|min_severity| finding.severity >= min_severity))
+        .collect())
+}
+
+/// A fix applied by `lint_and_fix`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct AppliedFix {
+    /// The code of the lint rule the fix is for, e.g. `Z0001`.
+    #[pyo3(get)]
+    pub rule: String,
+    /// Byte offset of the start of the replaced text, in the original code.
+    #[pyo3(get)]
+    pub start: usize,
+    /// Byte offset of the end of the replaced text, in the original code.
+    #[pyo3(get)]
+    pub end: usize,
+    /// The text it was replaced with.
+    #[pyo3(get)]
+    pub replacement: String,
+}
+
+#[pymethods]
+impl AppliedFix {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Convert a name to camel case, e.g. `box_width` to `boxWidth`.
+fn to_camel_case(name: &str) -> String {
+    let mut camel = String::new();
+    for part in name.split('_').filter(|part| !part.is_empty()) {
+        let part = if part.chars().all(|c| !c.is_lowercase()) {
+            part.to_lowercase()
+        } else {
+            part.to_string()
+        };
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            if camel.is_empty() {
+                camel.extend(first.to_lowercase());
+            } else {
+                camel.extend(first.to_uppercase());
+            }
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+/// An identifier in a program, with the start of the declaration it refers to, if it refers to one in the program.
+struct Reference {
+    name: String,
+    start: usize,
+    end: usize,
+    declaration: Option<usize>,
+}
+
+/// The names a node declares for the scope it starts, with where each is declared: the variables and functions
+/// declared in a block, or the parameters of a function. Nothing for other nodes.
+fn declarations(node: &serde_json::Value) -> Option<Vec<(&str, usize)>> {
+    fn identifier(node: &serde_json::Value) -> Option<(&str, usize)> {
+        Some((node["name"].as_str()?, node["start"].as_u64()? as usize))
+    }
+    if node["type"] == "FunctionExpression" {
+        let params = node["params"].as_array()?;
+        return Some(
+            params
+                .iter()
+                .filter_map(|param| identifier(&param["identifier"]))
+                .collect(),
+        );
+    }
+    let body = node["body"].as_array()?;
+    Some(
+        body.iter()
+            .filter(|statement| statement["type"] == "VariableDeclaration")
+            .filter_map(|statement| identifier(&statement["declaration"]["id"]))
+            .collect(),
+    )
+}
+
+/// Find the identifiers in a program's AST, as kcl-lib serializes it, and resolve each to the declaration it refers
+/// to in the innermost scope that declares its name, so a parameter shadowing a variable isn't mixed up with it.
+/// Object keys and the properties in `a.b` are left out, as they aren't variables.
+fn references<'a>(node: &'a serde_json::Value, scopes: &mut Vec<Vec<(&'a str, usize)>>, found: &mut Vec<Reference>) {
+    match node {
+        serde_json::Value::Object(fields) => {
+            if node["type"] == "Identifier" {
+                let (Some(name), Some(start), Some(end)) =
+                    (node["name"].as_str(), node["start"].as_u64(), node["end"].as_u64())
+                else {
+                    return;
+                };
+                let declaration = scopes
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.iter().find(|(declared, _)| *declared == name))
+                    .map(|(_, start)| *start);
+                found.push(Reference {
+                    name: name.to_string(),
+                    start: start as usize,
+                    end: end as usize,
+                    declaration,
+                });
+                return;
+            }
+            let scope = declarations(node);
+            let has_scope = scope.is_some();
+            scopes.extend(scope);
+            for (key, value) in fields {
+                let is_key = node["type"] == "ObjectProperty" && key == "key";
+                let is_property = node["type"] == "MemberExpression" && key == "property" && node["computed"] != true;
+                if !is_key && !is_property {
+                    references(value, scopes, found);
+                }
+            }
+            if has_scope {
+                scopes.pop();
+            }
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|item| references(item, scopes, found)),
+        _ => {}
+    }
+}
+
+/// The edits that fix a lint finding, or nothing if there is no fix that is safe to apply.
+fn lint_fixes(code: &str, references: &[Reference], discovered: &Discovered) -> Vec<AppliedFix> {
+    // Only the camel case rule has a mechanical fix: rename the variable everywhere it is used.
+    if discovered.finding.code != checks::Z0001.code {
+        return Vec::new();
+    }
+    let (start, end) = (discovered.pos.start(), discovered.pos.end());
+    let Some(name) = code.get(start..end) else {
+        return Vec::new();
+    };
+    let replacement = to_camel_case(name);
+    if replacement.is_empty() || references.iter().any(|other| other.name == replacement) {
+        // Renaming would clash with an existing name.
+        return Vec::new();
+    }
+
+    // Only what refers to the flagged declaration is renamed, not other variables in other scopes with its name.
+    references
+        .iter()
+        .filter(|other| other.name == name && other.declaration == Some(start))
+        .map(|other| AppliedFix {
+            rule: discovered.finding.code.to_string(),
+            start: other.start,
+            end: other.end,
+            replacement: replacement.clone(),
+        })
+        .collect()
+}
+
+/// Lint the kcl code and apply the safe fixes for what was found.
+///
+/// Only the rules with the given codes (e.g. `["Z0001"]`) are used, or every rule if none are given. Returns the
+/// fixed code, the findings that remain in it, and the fixes that were applied, in order of where they were in the
+/// original code.
+#[pyfunction]
+#[pyo3(signature = (code, rules = None))]
+fn lint_and_fix(
+    code: String,
+    rules: Option<Vec<String>>,
+) -> PyResult<(String, Vec<lints::LintFinding>, Vec<AppliedFix>)> {
+    let selected = |finding: &lints::LintFinding| match &rules {
+        Some(rules) => rules.iter().any(|rule| rule == finding.discovered.finding.code),
+        None => true,
+    };
+    let program = kcl_lib::Program::parse_no_errs(&code).map_err(PyErr::from)?;
+    let ast =
+        serde_json::to_value(&program.ast).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+    let mut found = Vec::new();
+    references(&ast, &mut Vec::new(), &mut found);
+    let mut fixes: Vec<AppliedFix> = Vec::new();
+    for finding in lint_program(&program, None)?.iter().filter(|finding| selected(finding)) {
+        for fix in lint_fixes(&code, &found, &finding.discovered) {
+            // Skip anything overlapping a fix we already have, so the result doesn't depend on the order.
+            if !fixes.iter().any(|other| fix.start < other.end && other.start < fix.end) {
+                fixes.push(fix);
+            }
+        }
+    }
+    fixes.sort_by_key(|fix| fix.start);
+
+    // Apply the fixes last to first so the offsets of the ones before stay valid.
+    let mut fixed = code.clone();
+    for fix in fixes.iter().rev() {
+        fixed.replace_range(fix.start..fix.end, &fix.replacement);
+    }
+    let remaining = lint_program(&kcl_lib::Program::parse_no_errs(&fixed).map_err(PyErr::from)?, None)?
+        .into_iter()
+        .filter(|finding| selected(finding))
+        .collect();
+
+    Ok((fixed, remaining, fixes))
+}
+
+/// Convert anything serializable into the equivalent python object (dicts, lists, strings, numbers, ...).
+fn to_py_object<T: Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+    let object = py.import_bound("json")?.call_method1("loads", (json,))?;
+    Ok(object.unbind())
+}
+
+/// Parse the kcl code and return its AST as python dicts and lists.
+#[pyfunction]
+fn parse(py: Python<'_>, code: String) -> PyResult<PyObject> {
+    let program = kcl_lib::Program::parse_no_errs(&code).map_err(PyErr::from)?;
+    to_py_object(py, &program.ast)
+}
+
+/// Parse the kcl code and return its AST as a JSON string.
+#[pyfunction]
+fn parse_to_json(code: String) -> PyResult<String> {
+    let program = kcl_lib::Program::parse_no_errs(&code).map_err(PyErr::from)?;
+    serde_json::to_string(&program.ast).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))
+}
+
+/// Compute a digest of the kcl program, hex encoded.
+///
+/// The digest is computed over the AST rather than the source text, so formatting changes do not affect it.
+#[pyfunction]
+fn program_digest(code: Code) -> PyResult<String> {
+    let mut program = code.parse_no_errs()?;
+    let digest = program.ast.compute_digest();
+
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
 }
 
 /// The kcl python module.
@@ -324,16 +3529,149 @@ fn lint(code: String) -> PyResult<Vec<Discovered>> {
 fn kcl(m: &Bound<'_, PyModule>) -> PyResult<()> {
     // Add our types to the module.
     m.add_class::<ImageFormat>()?;
+    m.add_class::<RenderMode>()?;
     m.add_class::<ExportFile>()?;
     m.add_class::<FileExportFormat>()?;
+    m.add_class::<ExportSelectionKind>()?;
+    m.add_class::<ExportSelection>()?;
+    m.add_class::<export_options::FbxOptions>()?;
+    m.add_class::<export_options::StlOptions>()?;
+    m.add_class::<export_options::PlyOptions>()?;
+    m.add_class::<export_options::GltfOptions>()?;
+    m.add_class::<FormatOptions>()?;
+    m.add_class::<RenderSettings>()?;
+    m.add_class::<Appearance>()?;
+    m.add_class::<CameraPose>()?;
+    m.add_class::<RetrySettings>()?;
+    m.add_class::<defaults::Defaults>()?;
+    m.add_class::<Diagnostic>()?;
     m.add_class::<UnitLength>()?;
     m.add_class::<Discovered>()?;
+    m.add_class::<Finding>()?;
+    m.add_class::<lints::LintFinding>()?;
+    m.add_class::<lints::LintSeverity>()?;
+    m.add_class::<AppliedFix>()?;
+    m.add_class::<ReportEntry>()?;
+    m.add_class::<ExecutionReport>()?;
+    m.add_class::<session::Session>()?;
+    m.add_class::<session::SessionOutcome>()?;
+    m.add_class::<trace::TraceEntry>()?;
+    m.add_class::<file_checks::FileReport>()?;
+    m.add_class::<file_checks::PathsReport>()?;
+    m.add_class::<file_checks::CheckReport>()?;
+    m.add_class::<file_checks::FormatDirResult>()?;
+    m.add_class::<program::Program>()?;
+    m.add_class::<variables::Variable>()?;
+    m.add_class::<variables::Variables>()?;
+    m.add_class::<tags::Tag>()?;
+    m.add_class::<scene::SceneObject>()?;
+    m.add_class::<scene::Scene>()?;
+    m.add_class::<ObjectHandle>()?;
+    m.add_class::<stdlib::StdlibFunction>()?;
+    m.add_class::<stdlib::StdlibParameter>()?;
+    m.add_class::<editor::CompletionItem>()?;
+    m.add_class::<editor::Hover>()?;
+    m.add_class::<editor::SignatureHelp>()?;
+    m.add_class::<editor::Snippet>()?;
+    m.add_class::<editor::Token>()?;
+    m.add_class::<batch::BatchError>()?;
+    m.add_class::<batch::BatchIterator>()?;
+    m.add_class::<connection::ClientConfig>()?;
+    m.add_class::<connection::ConnectionStatus>()?;
+    m.add_class::<progress::ProgressPhase>()?;
+    m.add_class::<progress::ProgressEvent>()?;
+    m.add_class::<snapshot::SnapshotResult>()?;
+    m.add_class::<snapshot::SnapshotOptions>()?;
+    m.add_class::<sketch::SketchExportFormat>()?;
+    m.add_class::<mesh::MeshStats>()?;
+    m.add_class::<geometry_checks::GeometryCheck>()?;
+    m.add_class::<geometry_checks::SolidReport>()?;
+    m.add_class::<geometry_checks::GeometryReport>()?;
+    m.add_class::<stats::RunStats>()?;
+
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+
+    // Add our exceptions to the module.
+    m.add(
+        "EngineConnectionError",
+        m.py().get_type_bound::<EngineConnectionError>(),
+    )?;
+    m.add("HostNotFoundError", m.py().get_type_bound::<HostNotFoundError>())?;
+    m.add("KclAuthError", m.py().get_type_bound::<KclAuthError>())?;
+    m.add(
+        "KclEngineError",
+        m.py().get_type_bound::<engine_error::KclEngineError>(),
+    )?;
+    m.add(
+        "UnexpectedResponseError",
+        m.py().get_type_bound::<engine_error::UnexpectedResponseError>(),
+    )?;
+    m.add("KclWarningError", m.py().get_type_bound::<KclWarningError>())?;
+    m.add("KclEmptySceneError", m.py().get_type_bound::<KclEmptySceneError>())?;
+    m.add("InvalidAstError", m.py().get_type_bound::<ast::InvalidAstError>())?;
+    m.add("KclLintError", m.py().get_type_bound::<lints::KclLintError>())?;
+    m.add(
+        "InvalidExportError",
+        m.py().get_type_bound::<export_checks::InvalidExportError>(),
+    )?;
 
     // Add our functions to the module.
     m.add_function(wrap_pyfunction!(execute, m)?)?;
+    m.add_function(wrap_pyfunction!(variables::execute_and_get_variables, m)?)?;
+    m.add_function(wrap_pyfunction!(tags::execute_and_get_tags, m)?)?;
+    m.add_function(wrap_pyfunction!(validate, m)?)?;
     m.add_function(wrap_pyfunction!(execute_and_snapshot, m)?)?;
+    m.add_function(wrap_pyfunction!(execute_and_snapshot_ex, m)?)?;
     m.add_function(wrap_pyfunction!(execute_and_export, m)?)?;
+    m.add_function(wrap_pyfunction!(sketch::execute_and_export_sketch, m)?)?;
+    m.add_function(wrap_pyfunction!(mesh::execute_and_get_mesh_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(export_checks::validate_export, m)?)?;
+    m.add_function(wrap_pyfunction!(geometry_checks::execute_and_check_geometry, m)?)?;
+    m.add_function(wrap_pyfunction!(execute_and_snapshot_and_export, m)?)?;
+    m.add_function(wrap_pyfunction!(execute_and_snapshot_turntable, m)?)?;
+    m.add_function(wrap_pyfunction!(storyboard::execute_and_snapshot_steps, m)?)?;
+    m.add_function(wrap_pyfunction!(execute_and_get_artifact_graph, m)?)?;
+    m.add_function(wrap_pyfunction!(scene::execute_and_get_scene, m)?)?;
+    m.add_function(wrap_pyfunction!(execute_with_report, m)?)?;
+    m.add_function(wrap_pyfunction!(batch::execute_and_snapshot_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(batch::execute_and_snapshot_iter, m)?)?;
+    m.add_function(wrap_pyfunction!(project::execute_project, m)?)?;
+    m.add_function(wrap_pyfunction!(project::execute_project_and_snapshot, m)?)?;
+    m.add_function(wrap_pyfunction!(project::execute_project_and_export, m)?)?;
     m.add_function(wrap_pyfunction!(format, m)?)?;
+    m.add_function(wrap_pyfunction!(format_dir, m)?)?;
+    m.add_function(wrap_pyfunction!(file_checks::format_check_paths, m)?)?;
+    m.add_function(wrap_pyfunction!(file_checks::lint_paths, m)?)?;
+    m.add_function(wrap_pyfunction!(file_checks::check, m)?)?;
+    m.add_function(wrap_pyfunction!(sarif::lint_sarif, m)?)?;
     m.add_function(wrap_pyfunction!(lint, m)?)?;
+    m.add_function(wrap_pyfunction!(lint_and_fix, m)?)?;
+    m.add_function(wrap_pyfunction!(lints::lint_strict, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_to_json, m)?)?;
+    m.add_function(wrap_pyfunction!(ast::recast_ast, m)?)?;
+    m.add_function(wrap_pyfunction!(program_digest, m)?)?;
+    m.add_function(wrap_pyfunction!(set_retry_settings, m)?)?;
+    m.add_function(wrap_pyfunction!(get_retry_settings, m)?)?;
+    m.add_function(wrap_pyfunction!(defaults::set_defaults, m)?)?;
+    m.add_function(wrap_pyfunction!(defaults::get_defaults, m)?)?;
+    m.add_function(wrap_pyfunction!(defaults::reset_defaults, m)?)?;
+    m.add_function(wrap_pyfunction!(logging::enable_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(logging::disable_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(version::version_info, m)?)?;
+    m.add_function(wrap_pyfunction!(version::engine_version, m)?)?;
+    m.add_function(wrap_pyfunction!(connection::check_connection, m)?)?;
+    m.add_function(wrap_pyfunction!(stdlib::stdlib_docs, m)?)?;
+    m.add_function(wrap_pyfunction!(stdlib::stdlib_function, m)?)?;
+    m.add_function(wrap_pyfunction!(editor::completions, m)?)?;
+    m.add_function(wrap_pyfunction!(editor::hover, m)?)?;
+    m.add_function(wrap_pyfunction!(editor::signature_help, m)?)?;
+    m.add_function(wrap_pyfunction!(editor::semantic_tokens, m)?)?;
+    m.add_function(wrap_pyfunction!(editor::tokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(editor::snippet, m)?)?;
+
+    // Helpers for pickling and for `make stubs`, which are set rather than added so they stay out of `__all__`.
+    m.setattr("_unpickle", wrap_pyfunction!(pickle::unpickle, m)?)?;
+    m.setattr("generate_stubs", wrap_pyfunction!(stubs::generate_stubs, m)?)?;
     Ok(())
 }
@@ -0,0 +1,186 @@
+//! How severe lint findings are, so some can fail a build while others are only reported.
+
+use kcl_lib::lint::{Discovered, Finding};
+use pyo3::{pyclass, pyfunction, pymethods, types::PyAny, Bound, FromPyObject, PyErr, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::{pickle, program::Code};
+
+pyo3::create_exception!(
+    kcl,
+    KclLintError,
+    pyo3::exceptions::PyException,
+    "Raised by `lint_strict` when the kcl code has findings at or above the threshold. The second argument is the \
+     list of findings, as `lint` returns them."
+);
+
+/// How severe a lint finding is. Severities compare in order, so `Info < Warning < Error`.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+#[pyclass(eq, eq_int, ord, hash, frozen, module = "kcl")]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    /// A suggestion, e.g. a simpler way to write something.
+    Info,
+    /// Something that works but goes against convention.
+    Warning,
+    /// Something that is almost certainly a mistake.
+    Error,
+}
+
+impl LintSeverity {
+    /// The severity of the findings of a lint rule, by its code. Rules this doesn't know yet are warnings.
+    pub(crate) fn of_rule(code: &str) -> Self {
+        match code {
+            // Identifiers must be lowerCamelCase.
+            "Z0001" => LintSeverity::Warning,
+            // Standard library functions called with arguments that are better written another way.
+            "Z0002" => LintSeverity::Warning,
+            // Sketches on planes that should be offset planes.
+            "Z0003" => LintSeverity::Info,
+            _ => LintSeverity::Warning,
+        }
+    }
+}
+
+impl std::fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => write!(f, "{}", name),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+#[pymethods]
+impl LintSeverity {
+    /// Parse a severity from its name, e.g. `"warning"`.
+    #[staticmethod]
+    #[pyo3(name = "from_str")]
+    fn parse(name: &str) -> PyResult<Self> {
+        serde_json::from_value(serde_json::Value::String(name.trim().to_lowercase())).map_err(|_| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Unknown lint severity {:?}, expected one of: info, warning, error",
+                name
+            ))
+        })
+    }
+
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject, String))> {
+        pickle::reduce_variant::<Self>(py, format!("{:?}", self))
+    }
+
+    fn __copy__(&self) -> Self {
+        *self
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        *self
+    }
+}
+
+/// A lint severity given from python, either as a `LintSeverity` or its name, e.g. `"warning"`.
+#[derive(FromPyObject, Debug, Clone)]
+pub enum Severity {
+    Level(LintSeverity),
+    Name(String),
+}
+
+impl TryFrom<Severity> for LintSeverity {
+    type Error = PyErr;
+
+    fn try_from(severity: Severity) -> PyResult<Self> {
+        match severity {
+            Severity::Level(level) => Ok(level),
+            Severity::Name(name) => LintSeverity::parse(&name),
+        }
+    }
+}
+
+/// Something a lint rule found in kcl code, and how severe it is.
+#[derive(Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct LintFinding {
+    pub(crate) discovered: Discovered,
+    /// How severe the finding is, from its rule.
+    #[pyo3(get)]
+    pub severity: LintSeverity,
+}
+
+impl From<Discovered> for LintFinding {
+    fn from(discovered: Discovered) -> Self {
+        LintFinding {
+            severity: LintSeverity::of_rule(discovered.finding.code),
+            discovered,
+        }
+    }
+}
+
+#[pymethods]
+impl LintFinding {
+    /// The rule that found it, with its code and title.
+    #[getter]
+    fn finding(&self) -> Finding {
+        self.discovered.finding.clone()
+    }
+
+    /// What was found, e.g. the name that isn't lowerCamelCase.
+    #[getter]
+    fn description(&self) -> String {
+        self.discovered.description.clone()
+    }
+
+    /// Byte offsets of the start and end of the code it was found in.
+    #[getter]
+    fn pos(&self) -> (usize, usize) {
+        (self.discovered.pos.start(), self.discovered.pos.end())
+    }
+
+    /// Whether it was overridden in the code, so it can be ignored.
+    #[getter]
+    fn overridden(&self) -> bool {
+        self.discovered.overridden
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Lint the kcl code, raising `KclLintError` with every finding if any of them is at least as severe as `fail_on`.
+///
+/// Otherwise returns the findings, e.g. for CI to report warnings but only fail on errors with `fail_on="error"`.
+#[pyfunction]
+#[pyo3(signature = (code, fail_on = Severity::Name("warning".to_string())))]
+pub(crate) fn lint_strict(code: Code, fail_on: Severity) -> PyResult<Vec<LintFinding>> {
+    let fail_on = LintSeverity::try_from(fail_on)?;
+    let findings = crate::lint(code, None)?;
+    let failed: Vec<String> = findings
+        .iter()
+        .filter(|finding| finding.severity >= fail_on)
+        .map(|finding| {
+            format!(
+                "{}: {}",
+                finding.discovered.finding.code, finding.discovered.description
+            )
+        })
+        .collect();
+    if !failed.is_empty() {
+        let message = format!(
+            "The kcl code has {} lint finding(s) at or above {}:\n{}",
+            failed.len(),
+            fail_on,
+            failed.join("\n")
+        );
+        return Err(KclLintError::new_err((message, findings)));
+    }
+
+    Ok(findings)
+}
@@ -0,0 +1,156 @@
+//! Forwarding the logs of the executor and engine connection to Python's `logging` module.
+
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    mpsc, Mutex, OnceLock,
+};
+
+use pyo3::{prelude::PyAnyMethods, pyfunction, PyResult, Python};
+use tracing::{field::Field, subscriber::Interest, Event, Level, Metadata, Subscriber};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    Layer,
+};
+
+/// The name of the Python logger records are sent to.
+const LOGGER_NAME: &str = "kcl";
+
+/// The most verbose level being forwarded, as returned by `verbosity`, or 0 when logging is disabled.
+static LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/// Whether our subscriber was installed, or why it couldn't be.
+static INSTALLED: OnceLock<Result<(), String>> = OnceLock::new();
+
+fn verbosity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 1,
+        Level::WARN => 2,
+        Level::INFO => 3,
+        Level::DEBUG => 4,
+        Level::TRACE => 5,
+    }
+}
+
+/// The Python `logging` level for a tracing level. Python has no trace level, so trace is just below debug.
+fn python_level(level: &Level) -> u32 {
+    match *level {
+        Level::ERROR => 40,
+        Level::WARN => 30,
+        Level::INFO => 20,
+        Level::DEBUG => 10,
+        Level::TRACE => 5,
+    }
+}
+
+/// A log record waiting to be handed to Python.
+struct Record {
+    level: u32,
+    message: String,
+}
+
+/// Collects the message and fields of an event into a single line.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl tracing::field::Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// Formats events on the thread that emitted them, without the GIL, and sends them to a thread that logs them.
+struct PythonLayer {
+    records: Mutex<mpsc::Sender<Record>>,
+}
+
+impl<S: Subscriber> Layer<S> for PythonLayer {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The level can change at any time, so check every event.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        verbosity(metadata.level()) <= LEVEL.load(Ordering::Relaxed)
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let record = Record {
+            level: python_level(event.metadata().level()),
+            message: format!("{}: {}{}", event.metadata().target(), visitor.message, visitor.fields),
+        };
+        if let Ok(records) = self.records.lock() {
+            // The forwarding thread only stops with the process, so there is nothing useful to do if this fails.
+            let _ = records.send(record);
+        }
+    }
+}
+
+/// Install our subscriber and start the thread that hands records to Python.
+fn install() -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel::<Record>();
+    let subscriber = tracing_subscriber::registry().with(PythonLayer {
+        records: Mutex::new(sender),
+    });
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|err| format!("Another tracing subscriber is already installed: {}", err))?;
+
+    std::thread::Builder::new()
+        .name("kcl-logging".to_string())
+        .spawn(move || {
+            for record in receiver {
+                Python::with_gil(|py| {
+                    let logger = py.import_bound("logging")?.call_method1("getLogger", (LOGGER_NAME,))?;
+                    logger.call_method1("log", (record.level, record.message))?;
+                    PyResult::Ok(())
+                })
+                .ok();
+            }
+        })
+        .map_err(|err| format!("Failed to start the logging thread: {}", err))?;
+
+    Ok(())
+}
+
+/// Forward the logs of the executor and engine connection to the `kcl` logger of Python's `logging` module.
+///
+/// `level` is the most verbose level to forward: `trace`, `debug`, `info`, `warning` or `error`. Calling this again
+/// changes the level. Python's own logging configuration still decides what happens to the records.
+#[pyfunction]
+#[pyo3(signature = (level = "info"))]
+pub(crate) fn enable_logging(level: &str) -> PyResult<()> {
+    let level = match level.to_lowercase().as_str() {
+        "trace" => Level::TRACE,
+        "debug" => Level::DEBUG,
+        "info" => Level::INFO,
+        "warn" | "warning" => Level::WARN,
+        "error" => Level::ERROR,
+        _ => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid log level {:?}, expected one of trace, debug, info, warning or error",
+                level
+            )))
+        }
+    };
+    INSTALLED
+        .get_or_init(install)
+        .clone()
+        .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+    LEVEL.store(verbosity(&level), Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Stop forwarding logs to Python.
+#[pyfunction]
+pub(crate) fn disable_logging() {
+    LEVEL.store(0, Ordering::Relaxed);
+}
@@ -0,0 +1,261 @@
+//! Counting what is in an exported mesh, without handing the whole file to Python.
+
+use std::collections::{HashMap, HashSet};
+
+use pyo3::{pyclass, pyfunction, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    connection, defaults, export, pickle, program::Code, progress, reject_module_files, run_code, spawn,
+    with_reconnect, ExportFile, FileExportFormat, RunOptions, Units,
+};
+
+/// The size of a mesh in an exported file.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct MeshStats {
+    /// The name of the file.
+    #[pyo3(get)]
+    pub name: String,
+    /// The number of triangles in the mesh.
+    #[pyo3(get)]
+    pub triangle_count: usize,
+    /// The number of distinct vertices in the mesh.
+    #[pyo3(get)]
+    pub vertex_count: usize,
+    /// The size of the file in bytes.
+    #[pyo3(get)]
+    pub byte_size: usize,
+}
+
+#[pymethods]
+impl MeshStats {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Triangle and vertex counts of an STL, binary or ASCII.
+fn stl_counts(contents: &[u8]) -> Result<(usize, usize), String> {
+    // A binary STL is an 80 byte header, a triangle count, then 50 bytes per triangle.
+    if let Some(count) = contents.get(80..84) {
+        let triangles = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+        if contents.len() == 84 + 50 * triangles {
+            let vertices: HashSet<&[u8]> = contents[84..]
+                .chunks_exact(50)
+                .flat_map(|facet| facet[12..48].chunks_exact(12))
+                .collect();
+            return Ok((triangles, vertices.len()));
+        }
+    }
+
+    let text = std::str::from_utf8(contents).map_err(|_| "not a binary or ASCII STL".to_string())?;
+    if !text.trim_start().starts_with("solid") {
+        return Err("not a binary or ASCII STL".to_string());
+    }
+    let mut triangles = 0;
+    let mut vertices = HashSet::new();
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("facet") => triangles += 1,
+            Some("vertex") => {
+                vertices.insert(words.collect::<Vec<_>>());
+            }
+            _ => {}
+        }
+    }
+
+    Ok((triangles, vertices.len()))
+}
+
+/// Triangle and vertex counts of the meshes described by a glTF document, from its accessors.
+fn gltf_counts(document: &serde_json::Value) -> Result<(usize, usize), String> {
+    let accessor_count = |index: &serde_json::Value| {
+        index
+            .as_u64()
+            .and_then(|index| document["accessors"].get(index as usize))
+            .and_then(|accessor| accessor["count"].as_u64())
+            .map(|count| count as usize)
+            .ok_or_else(|| format!("missing accessor {}", index))
+    };
+
+    let (mut triangles, mut vertices) = (0, 0);
+    let primitives = document["meshes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|mesh| mesh["primitives"].as_array().into_iter().flatten());
+    for primitive in primitives {
+        let positions = accessor_count(&primitive["attributes"]["POSITION"])?;
+        let indices = match primitive.get("indices") {
+            Some(indices) => accessor_count(indices)?,
+            None => positions,
+        };
+        vertices += positions;
+        // Triangles are the default mode, then triangle strips and fans. Points and lines have no triangles.
+        triangles += match primitive["mode"].as_u64().unwrap_or(4) {
+            4 => indices / 3,
+            5 | 6 => indices.saturating_sub(2),
+            _ => 0,
+        };
+    }
+
+    Ok((triangles, vertices))
+}
+
+/// Triangle and vertex counts of a GLB, from the glTF document in its first chunk.
+fn glb_counts(contents: &[u8]) -> Result<(usize, usize), String> {
+    if contents.get(0..4) != Some(b"glTF") || contents.get(16..20) != Some(b"JSON") {
+        return Err("not a GLB".to_string());
+    }
+    let length = u32::from_le_bytes([contents[12], contents[13], contents[14], contents[15]]) as usize;
+    let json = contents.get(20..20 + length).ok_or("the JSON chunk is truncated")?;
+    let document = serde_json::from_slice(json).map_err(|err| err.to_string())?;
+
+    gltf_counts(&document)
+}
+
+/// Triangle and vertex counts of an OBJ. Faces with more than three vertices count as a fan of triangles.
+fn obj_counts(contents: &[u8]) -> Result<(usize, usize), String> {
+    let text = std::str::from_utf8(contents).map_err(|err| err.to_string())?;
+    let (mut triangles, mut vertices) = (0, 0);
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => vertices += 1,
+            Some("f") => triangles += words.count().saturating_sub(2),
+            _ => {}
+        }
+    }
+
+    Ok((triangles, vertices))
+}
+
+/// Triangle and vertex counts of an ASCII PLY. Faces with more than three vertices count as a fan of triangles.
+pub(crate) fn ply_counts(contents: &[u8]) -> Result<(usize, usize), String> {
+    let text = std::str::from_utf8(contents).map_err(|_| "only ASCII PLY files can be read".to_string())?;
+    let mut lines = text.lines();
+    let mut elements = Vec::new();
+    for line in lines.by_ref() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", format, ..] if *format != "ascii" => return Err("only ASCII PLY files can be read".to_string()),
+            ["element", name, count] => {
+                elements.push((*name, count.parse::<usize>().map_err(|err| err.to_string())?));
+            }
+            ["end_header"] => break,
+            _ => {}
+        }
+    }
+
+    let (mut triangles, mut vertices) = (0, 0);
+    for (name, count) in elements {
+        for _ in 0..count {
+            let line = lines
+                .next()
+                .ok_or_else(|| format!("fewer {} lines than the header says", name))?;
+            match name {
+                "vertex" => vertices += 1,
+                "face" => {
+                    let corners = line.split_whitespace().next().and_then(|n| n.parse::<usize>().ok());
+                    triangles += corners.ok_or("a face has no vertex count")?.saturating_sub(2);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok((triangles, vertices))
+}
+
+/// Count the triangles and vertices in an exported file, or `None` if it isn't a mesh, e.g. the materials of an OBJ.
+pub(crate) fn mesh_stats(file: &ExportFile) -> PyResult<Option<MeshStats>> {
+    let extension = std::path::Path::new(&file.name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    let counts = match extension.as_deref() {
+        Some("stl") => stl_counts(&file.contents),
+        Some("glb") => glb_counts(&file.contents),
+        Some("gltf") => serde_json::from_slice(&file.contents)
+            .map_err(|err| err.to_string())
+            .and_then(|document| gltf_counts(&document)),
+        Some("obj") => obj_counts(&file.contents),
+        Some("ply") => ply_counts(&file.contents),
+        _ => return Ok(None),
+    };
+    let (triangle_count, vertex_count) = counts.map_err(|err| {
+        pyo3::exceptions::PyValueError::new_err(format!("Couldn't read the mesh in {}: {}", file.name, err))
+    })?;
+
+    Ok(Some(MeshStats {
+        name: file.name.clone(),
+        triangle_count,
+        vertex_count,
+        byte_size: file.contents.len(),
+    }))
+}
+
+/// Execute the kcl code, export it to a mesh format and count the triangles and vertices in each file.
+///
+/// The counts are read from the exported files, which are not returned. Files that hold no mesh, like the materials
+/// of an OBJ, are left out. Raises `ValueError` for formats that aren't meshes, like STEP.
+#[pyfunction]
+#[pyo3(signature = (
+    code,
+    units = None,
+    export_format = FileExportFormat::Glb,
+    warnings_as_errors = false,
+    files = None,
+    on_progress = None,
+    attachments = None,
+))]
+pub(crate) async fn execute_and_get_mesh_stats(
+    code: Code,
+    units: Option<Units>,
+    export_format: FileExportFormat,
+    warnings_as_errors: bool,
+    files: Option<HashMap<String, String>>,
+    on_progress: Option<PyObject>,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+) -> PyResult<Vec<MeshStats>> {
+    reject_module_files(files, attachments)?;
+    connection::require_token(None)?;
+    if matches!(export_format, FileExportFormat::Fbx | FileExportFormat::Step) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Mesh statistics can't be read from {:?} files, use Glb, Gltf, Obj, Ply or Stl",
+            export_format
+        )));
+    }
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        warnings_as_errors,
+        progress: progress::Progress::new(on_progress),
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options, export_format) = (&code, &options, &export_format);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            options.progress.report(progress::ProgressPhase::Exporting, None)?;
+            let files = export(&executed.ctx, executed.units, export_format.clone()).await?;
+            let mut stats = Vec::new();
+            for file in &files {
+                stats.extend(mesh_stats(file)?);
+            }
+
+            Ok(stats)
+        })
+        .await
+    })
+    .await
+}
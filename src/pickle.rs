@@ -0,0 +1,78 @@
+//! Pickling the classes we return, so they can be passed between processes.
+
+use pyo3::{prelude::PyAnyMethods, pyfunction, IntoPy, PyObject, PyResult, PyTypeInfo, Python};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    batch::BatchError,
+    connection::ConnectionStatus,
+    mesh::MeshStats,
+    progress::ProgressEvent,
+    session::SessionOutcome,
+    snapshot::SnapshotResult,
+    stats::RunStats,
+    trace::TraceEntry,
+    variables::{Variable, Variables},
+    Appearance, AppliedFix, CameraPose, Diagnostic, ExecutionReport, ExportSelection, FormatOptions, ObjectHandle,
+    RenderSettings, ReportEntry,
+};
+
+/// What `__reduce__` returns for a variant of an enum: it is looked up again by name on its class when unpickled.
+pub(crate) fn reduce_variant<T: PyTypeInfo>(
+    py: Python<'_>,
+    variant: String,
+) -> PyResult<(PyObject, (PyObject, String))> {
+    let getattr = py.import_bound("builtins")?.getattr("getattr")?;
+    let class = T::type_object_bound(py).into_any();
+
+    Ok((getattr.unbind(), (class.unbind(), variant)))
+}
+
+/// What `__reduce__` returns for a class that can be serialized: it is rebuilt from its JSON by `_unpickle`.
+pub(crate) fn reduce_json<T: PyTypeInfo + Serialize>(
+    py: Python<'_>,
+    value: &T,
+) -> PyResult<(PyObject, (String, String))> {
+    let unpickle = py.import_bound("kcl")?.getattr("_unpickle")?;
+    let json = serde_json::to_string(value).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+
+    Ok((unpickle.unbind(), (T::NAME.to_string(), json)))
+}
+
+/// Rebuild a pickled object from the name of its class and its JSON.
+#[pyfunction]
+#[pyo3(name = "_unpickle")]
+pub(crate) fn unpickle(py: Python<'_>, class: &str, json: &str) -> PyResult<PyObject> {
+    fn load<T: DeserializeOwned + IntoPy<PyObject>>(py: Python<'_>, json: &str) -> PyResult<PyObject> {
+        let value: T =
+            serde_json::from_str(json).map_err(|err| pyo3::exceptions::PyValueError::new_err(err.to_string()))?;
+        Ok(value.into_py(py))
+    }
+
+    match class {
+        "Appearance" => load::<Appearance>(py, json),
+        "AppliedFix" => load::<AppliedFix>(py, json),
+        "BatchError" => load::<BatchError>(py, json),
+        "CameraPose" => load::<CameraPose>(py, json),
+        "ConnectionStatus" => load::<ConnectionStatus>(py, json),
+        "Diagnostic" => load::<Diagnostic>(py, json),
+        "ExecutionReport" => load::<ExecutionReport>(py, json),
+        "ExportSelection" => load::<ExportSelection>(py, json),
+        "FormatOptions" => load::<FormatOptions>(py, json),
+        "MeshStats" => load::<MeshStats>(py, json),
+        "ObjectHandle" => load::<ObjectHandle>(py, json),
+        "ProgressEvent" => load::<ProgressEvent>(py, json),
+        "RenderSettings" => load::<RenderSettings>(py, json),
+        "ReportEntry" => load::<ReportEntry>(py, json),
+        "RunStats" => load::<RunStats>(py, json),
+        "SessionOutcome" => load::<SessionOutcome>(py, json),
+        "SnapshotResult" => load::<SnapshotResult>(py, json),
+        "TraceEntry" => load::<TraceEntry>(py, json),
+        "Variable" => load::<Variable>(py, json),
+        "Variables" => load::<Variables>(py, json),
+        _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Can't unpickle an object of class {:?}",
+            class
+        ))),
+    }
+}
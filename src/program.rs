@@ -0,0 +1,105 @@
+//! Parsed kcl programs, so code used for several things is only parsed once.
+
+use std::sync::Arc;
+
+use pyo3::{prelude::PyAnyMethods, pyclass, pymethods, types::PyAny, Bound, FromPyObject, PyResult};
+
+use crate::{parse_code, Diagnostic, FormatOptions};
+
+/// A parsed kcl program, which can be given anywhere kcl code can, e.g. to `lint`, `format` or `execute`, without
+/// being parsed again.
+#[derive(Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct Program {
+    source: String,
+    /// Shared rather than copied, since the AST of a large program is large.
+    program: Arc<kcl_lib::Program>,
+    /// Non-fatal diagnostics from parsing the program.
+    warnings: Vec<Diagnostic>,
+}
+
+#[pymethods]
+impl Program {
+    /// Parse the kcl code, raising the same errors as executing it would for code that doesn't parse.
+    #[staticmethod]
+    fn parse(code: String) -> PyResult<Self> {
+        let (program, warnings) = parse_code(&code)?;
+        Ok(Program {
+            source: code,
+            program: Arc::new(program),
+            warnings,
+        })
+    }
+
+    /// The kcl code the program was parsed from.
+    fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    /// The program formatted as kcl code.
+    #[pyo3(signature = (options = None))]
+    fn recast(&self, options: Option<FormatOptions>) -> String {
+        let options: kcl_lib::FormatOptions = options.unwrap_or_default().into();
+        self.program.ast.recast(&options, 0)
+    }
+
+    /// The digest of the program, as `program_digest` gives it.
+    fn digest(&self) -> String {
+        let mut ast = self.program.ast.clone();
+        ast.compute_digest()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<(Bound<'py, PyAny>, (String,))> {
+        Ok((slf.get_type().getattr("parse")?, (slf.borrow().source.clone(),)))
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Kcl code given from python, either as a string or a `Program` that has already been parsed.
+#[derive(FromPyObject, Debug, Clone)]
+pub enum Code {
+    Text(String),
+    Program(Program),
+}
+
+impl From<String> for Code {
+    fn from(code: String) -> Self {
+        Code::Text(code)
+    }
+}
+
+impl Code {
+    /// The kcl code itself.
+    pub(crate) fn source(&self) -> &str {
+        match self {
+            Code::Text(code) => code,
+            Code::Program(program) => &program.source,
+        }
+    }
+
+    /// The parsed program, along with any non-fatal diagnostics, parsing the code only if it hasn't been already.
+    pub(crate) fn parse(&self) -> PyResult<(kcl_lib::Program, Vec<Diagnostic>)> {
+        match self {
+            Code::Text(code) => parse_code(code),
+            Code::Program(program) => Ok((kcl_lib::Program::clone(&program.program), program.warnings.clone())),
+        }
+    }
+
+    /// The parsed program, for things that only take code without any errors, e.g. `lint`.
+    pub(crate) fn parse_no_errs(&self) -> PyResult<kcl_lib::Program> {
+        match self {
+            Code::Text(code) => kcl_lib::Program::parse_no_errs(code).map_err(pyo3::PyErr::from),
+            Code::Program(program) => Ok(kcl_lib::Program::clone(&program.program)),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Reporting the progress of long running calls back to Python.
+
+use std::sync::Arc;
+
+use pyo3::{pyclass, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::pickle;
+
+/// The step a call is at.
+#[derive(Serialize, Deserialize, PartialEq, Hash, Debug, Clone, Copy)]
+#[pyclass(eq, eq_int, hash, frozen, module = "kcl")]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressPhase {
+    /// Parsing the kcl code.
+    Parsing,
+    /// Connecting to the engine.
+    Connecting,
+    /// Executing the program.
+    Executing,
+    /// Taking snapshots.
+    Snapshotting,
+    /// Exporting the model.
+    Exporting,
+}
+
+#[pymethods]
+impl ProgressPhase {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject, String))> {
+        pickle::reduce_variant::<Self>(py, format!("{:?}", self))
+    }
+
+    fn __copy__(&self) -> Self {
+        *self
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        *self
+    }
+}
+
+/// An update on the progress of a call.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct ProgressEvent {
+    /// The step the call is at.
+    #[pyo3(get)]
+    pub phase: ProgressPhase,
+    /// Which of `operation_total` operations in this step is starting, counting from 0, when that is known.
+    #[pyo3(get)]
+    pub operation_index: Option<usize>,
+    /// How many operations this step has, when that is known.
+    #[pyo3(get)]
+    pub operation_total: Option<usize>,
+    /// More detail about what is happening.
+    #[pyo3(get)]
+    pub message: Option<String>,
+}
+
+#[pymethods]
+impl ProgressEvent {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// The Python callable progress is reported to, if there is one.
+#[derive(Default, Debug, Clone)]
+pub(crate) struct Progress(Option<Arc<PyObject>>);
+
+impl Progress {
+    pub(crate) fn new(on_progress: Option<PyObject>) -> Self {
+        Progress(on_progress.map(Arc::new))
+    }
+
+    /// Report that a step has started. An exception raised by the callback is returned, so it aborts the call.
+    pub(crate) fn report(&self, phase: ProgressPhase, message: Option<String>) -> PyResult<()> {
+        self.report_event(ProgressEvent {
+            phase,
+            operation_index: None,
+            operation_total: None,
+            message,
+        })
+    }
+
+    /// Report that an operation of a step has started.
+    pub(crate) fn report_operation(&self, phase: ProgressPhase, index: usize, total: usize) -> PyResult<()> {
+        self.report_event(ProgressEvent {
+            phase,
+            operation_index: Some(index),
+            operation_total: Some(total),
+            message: None,
+        })
+    }
+
+    fn report_event(&self, event: ProgressEvent) -> PyResult<()> {
+        let Some(on_progress) = &self.0 else {
+            return Ok(());
+        };
+        Python::with_gil(|py| on_progress.call1(py, (event,)))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+//! Executing kcl projects: directories with an entry file and a `project.toml` of settings.
+
+use std::path::{Path, PathBuf};
+
+use pyo3::{pyfunction, PyObject, PyResult};
+use serde::Deserialize;
+
+use crate::{
+    connection, defaults, export, get_background_color,
+    program::Code,
+    progress::{Progress, ProgressPhase},
+    run_code, snapshot, spawn, with_reconnect, Color, Diagnostic, ExportFile, FileExportFormat, ImageFormat,
+    RenderSettings, RunOptions, UnitLength,
+};
+
+/// The name of the file project settings are read from.
+const PROJECT_SETTINGS_FILE: &str = "project.toml";
+
+/// The entry file used when none is given.
+const DEFAULT_ENTRY: &str = "main.kcl";
+
+/// The parts of `project.toml` we care about.
+#[derive(Deserialize, Default, Debug)]
+struct ProjectSettings {
+    #[serde(default)]
+    settings: Settings,
+}
+
+#[derive(Deserialize, Default, Debug)]
+struct Settings {
+    #[serde(default)]
+    modeling: ModelingSettings,
+}
+
+#[derive(Deserialize, Default, Debug)]
+struct ModelingSettings {
+    /// The default length unit, e.g. `"mm"`.
+    base_unit: Option<String>,
+}
+
+/// A kcl project loaded from disk.
+#[derive(Debug)]
+struct Project {
+    directory: PathBuf,
+    code: Code,
+    units: Option<UnitLength>,
+}
+
+/// Load the entry file and settings of the project in `directory`.
+fn load_project(directory: &Path, entry: Option<&str>) -> PyResult<Project> {
+    if !directory.is_dir() {
+        return Err(pyo3::exceptions::PyNotADirectoryError::new_err(format!(
+            "Project directory {} does not exist or is not a directory",
+            directory.display()
+        )));
+    }
+
+    let entry = directory.join(entry.unwrap_or(DEFAULT_ENTRY));
+    if !entry.is_file() {
+        return Err(pyo3::exceptions::PyFileNotFoundError::new_err(format!(
+            "Project entry file {} does not exist, pass `entry` to use a different file",
+            entry.display()
+        )));
+    }
+    let code = std::fs::read_to_string(&entry)?;
+
+    let settings_path = directory.join(PROJECT_SETTINGS_FILE);
+    let settings = if settings_path.is_file() {
+        let contents = std::fs::read_to_string(&settings_path)?;
+        toml::from_str::<ProjectSettings>(&contents).map_err(|err| {
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid project settings in {}: {}",
+                settings_path.display(),
+                err
+            ))
+        })?
+    } else {
+        ProjectSettings::default()
+    };
+    let units = settings
+        .settings
+        .modeling
+        .base_unit
+        .map(|unit| {
+            unit.parse::<UnitLength>().map_err(|err| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "Invalid project settings in {}: {}",
+                    settings_path.display(),
+                    err
+                ))
+            })
+        })
+        .transpose()?;
+
+    Ok(Project {
+        directory: directory.to_path_buf(),
+        code: Code::Text(code),
+        units,
+    })
+}
+
+impl Project {
+    fn run_options(
+        &self,
+        render_settings: Option<RenderSettings>,
+        warnings_as_errors: bool,
+        on_progress: Option<PyObject>,
+    ) -> RunOptions {
+        RunOptions {
+            units: self.units,
+            render_settings: render_settings.unwrap_or_default(),
+            warnings_as_errors,
+            project_directory: Some(self.directory.clone()),
+            progress: Progress::new(on_progress),
+            ..Default::default()
+        }
+    }
+}
+
+/// Execute the kcl project in a directory, returning any non-fatal diagnostics.
+///
+/// The project's entry file is `main.kcl` unless another is given, and its settings are read from `project.toml`.
+/// Imports are resolved relative to the project directory.
+#[pyfunction]
+#[pyo3(signature = (path, entry = None, warnings_as_errors = false, on_progress = None))]
+pub(crate) async fn execute_project(
+    path: PathBuf,
+    entry: Option<String>,
+    warnings_as_errors: bool,
+    on_progress: Option<PyObject>,
+) -> PyResult<Vec<Diagnostic>> {
+    connection::require_token(None)?;
+    let project = load_project(&path, entry.as_deref())?;
+    let options = project.run_options(None, warnings_as_errors, on_progress);
+    spawn(async move {
+        let (code, options) = (&project.code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+
+            Ok(executed.warnings)
+        })
+        .await
+    })
+    .await
+}
+
+/// Execute the kcl project in a directory and snapshot it in a specific format.
+#[pyfunction]
+#[pyo3(signature = (
+    path,
+    image_format = None,
+    entry = None,
+    render_settings = None,
+    background_color = None,
+    transparent = false,
+    warnings_as_errors = false,
+    on_progress = None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn execute_project_and_snapshot(
+    path: PathBuf,
+    image_format: Option<ImageFormat>,
+    entry: Option<String>,
+    render_settings: Option<RenderSettings>,
+    background_color: Option<Color>,
+    transparent: bool,
+    warnings_as_errors: bool,
+    on_progress: Option<PyObject>,
+) -> PyResult<Vec<u8>> {
+    connection::require_token(None)?;
+    let image_format = defaults::image_format(image_format)?;
+    let project = load_project(&path, entry.as_deref())?;
+    let background_color = get_background_color(image_format, background_color, transparent)?;
+    let options = project.run_options(render_settings, warnings_as_errors, on_progress);
+    spawn(async move {
+        let (code, options) = (&project.code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            options.progress.report(ProgressPhase::Snapshotting, None)?;
+            snapshot(
+                &executed.ctx,
+                image_format,
+                background_color,
+                options.render_settings.settle(true),
+            )
+            .await
+        })
+        .await
+    })
+    .await
+}
+
+/// Execute the kcl project in a directory and export it to a specific file format.
+#[pyfunction]
+#[pyo3(signature = (path, export_format = None, entry = None, warnings_as_errors = false, on_progress = None))]
+pub(crate) async fn execute_project_and_export(
+    path: PathBuf,
+    export_format: Option<FileExportFormat>,
+    entry: Option<String>,
+    warnings_as_errors: bool,
+    on_progress: Option<PyObject>,
+) -> PyResult<Vec<ExportFile>> {
+    connection::require_token(None)?;
+    let export_format = defaults::export_format(export_format)?;
+    let project = load_project(&path, entry.as_deref())?;
+    let options = project.run_options(None, warnings_as_errors, on_progress);
+    spawn(async move {
+        let (code, options, export_format) = (&project.code, &options, &export_format);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            options.progress.report(ProgressPhase::Exporting, None)?;
+            export(&executed.ctx, executed.units, export_format.clone()).await
+        })
+        .await
+    })
+    .await
+}
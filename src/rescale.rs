@@ -0,0 +1,77 @@
+//! Converting the lengths in exported files from one unit to another.
+
+use pyo3::PyResult;
+
+use crate::{ExportFile, UnitLength};
+
+/// Scale the numbers of the lines starting with `keyword`, keeping the rest of each line as it is.
+///
+/// Only the first three numbers are scaled, since those are the position, e.g. the optional weight after the
+/// position of an OBJ vertex isn't a length.
+fn scale_lines(text: &str, keyword: &str, factor: f64) -> PyResult<String> {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let Some(rest) = line
+                .trim_start()
+                .strip_prefix(keyword)
+                .filter(|rest| rest.starts_with([' ', '\t']))
+            else {
+                return Ok(line.to_string());
+            };
+            let ending = &rest[rest.trim_end().len()..];
+            let mut values: Vec<String> = rest.split_whitespace().map(str::to_string).collect();
+            for value in values.iter_mut().take(3) {
+                let number: f64 = value.parse().map_err(|_| {
+                    pyo3::exceptions::PyValueError::new_err(format!("Could not read {:?} as a number", value))
+                })?;
+                *value = (number * factor).to_string();
+            }
+            Ok(format!("{}{} {}{}", indent, keyword, values.join(" "), ending))
+        })
+        .collect()
+}
+
+/// A copy of an exported file with its lengths converted to `units`.
+pub(crate) fn scale_to(file: &ExportFile, units: UnitLength) -> PyResult<ExportFile> {
+    let Some(from) = file.units else {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "The units of {} aren't known, so it can't be scaled",
+            file.name
+        )));
+    };
+    let factor = from.in_mm() / units.in_mm();
+    let text = || {
+        std::str::from_utf8(&file.contents).map_err(|_| {
+            pyo3::exceptions::PyNotImplementedError::new_err(format!(
+                "{} is a binary file, only text files can be scaled so far",
+                file.name
+            ))
+        })
+    };
+    let contents = match file.format.as_deref() {
+        // Binary STL files start with an 80 byte header, which can happen to be text, so the vertices are checked for.
+        Some("stl") if text().is_ok_and(|text| text.trim_start().starts_with("solid") && text.contains("vertex")) => {
+            scale_lines(text()?, "vertex", factor)?
+        }
+        Some("stl") => {
+            return Err(pyo3::exceptions::PyNotImplementedError::new_err(
+                "Only ASCII STL files can be scaled so far",
+            ))
+        }
+        Some("obj") => scale_lines(text()?, "v", factor)?,
+        format => {
+            return Err(pyo3::exceptions::PyNotImplementedError::new_err(format!(
+                "Files in the {} format can't be scaled yet, only ASCII STL and OBJ files can",
+                format.unwrap_or("unknown")
+            )))
+        }
+    };
+
+    Ok(ExportFile {
+        contents: contents.into_bytes(),
+        name: file.name.clone(),
+        units: Some(units),
+        format: file.format.clone(),
+    })
+}
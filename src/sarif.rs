@@ -0,0 +1,224 @@
+//! Lint findings as a SARIF document, for code scanning tools like GitHub's.
+
+use kcl_lib::lint::{checks, Finding};
+use pyo3::{pyfunction, FromPyObject, PyResult};
+use serde_json::{json, Value};
+
+use crate::{
+    file_checks, lint_program,
+    lints::{LintFinding, LintSeverity},
+    program::Code,
+    version, Diagnostic,
+};
+
+/// The version of SARIF the documents are in.
+const SARIF_VERSION: &str = "2.1.0";
+
+/// Where the schema for the version of SARIF the documents are in is.
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// The path kcl code given directly, rather than in a file, is reported at.
+const CODE_URI: &str = "main.kcl";
+
+/// What to lint from python: the paths of kcl files, or kcl code itself.
+#[derive(FromPyObject, Debug, Clone)]
+pub enum PathsOrCode {
+    Paths(Vec<String>),
+    Code(Code),
+}
+
+/// The lint rules kcl-lib has.
+fn known_rules() -> [Finding; 3] {
+    [checks::Z0001, checks::Z0002, checks::Z0003]
+}
+
+/// The SARIF level for a severity.
+fn level(severity: LintSeverity) -> &'static str {
+    match severity {
+        LintSeverity::Info => "note",
+        LintSeverity::Warning => "warning",
+        LintSeverity::Error => "error",
+    }
+}
+
+/// The region of the code between two byte offsets, with 1-based lines and columns counted in characters, as the
+/// run's `columnKind` says.
+fn region(code: &str, start: usize, end: usize) -> Value {
+    let position = |offset: usize| {
+        let before = code.get(..offset.min(code.len())).unwrap_or(code);
+        let line_start = before.rfind('\n').map(|newline| newline + 1).unwrap_or_default();
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    };
+    let ((start_line, start_column), (end_line, end_column)) = (position(start), position(end.max(start)));
+
+    json!({
+        "startLine": start_line,
+        "startColumn": start_column,
+        "endLine": end_line,
+        "endColumn": end_column,
+        "byteOffset": start,
+        "byteLength": end.saturating_sub(start),
+    })
+}
+
+/// Where something is in a file, or the whole file if it couldn't be read.
+fn location(uri: &str, index: usize, code: Option<&str>, start: usize, end: usize) -> Value {
+    let mut physical = json!({ "artifactLocation": { "uri": uri, "index": index } });
+    if let Some(code) = code {
+        physical["region"] = region(code, start, end);
+    }
+
+    json!({ "physicalLocation": physical })
+}
+
+/// A file that was linted, or that couldn't be.
+struct Linted {
+    uri: String,
+    code: Option<String>,
+    findings: Result<Vec<LintFinding>, Diagnostic>,
+}
+
+impl Linted {
+    fn new(uri: String, code: Result<String, Diagnostic>) -> PyResult<Self> {
+        let code = match code {
+            Ok(code) => code,
+            Err(err) => {
+                return Ok(Linted {
+                    uri,
+                    code: None,
+                    findings: Err(err),
+                })
+            }
+        };
+        let findings = match kcl_lib::Program::parse_no_errs(&code) {
+            Ok(program) => Ok(lint_program(&program, None)?),
+            Err(err) => Err(Diagnostic::from(err)),
+        };
+
+        Ok(Linted {
+            uri,
+            code: Some(code),
+            findings,
+        })
+    }
+}
+
+/// Lint kcl files, or kcl code, and return the findings as a SARIF 2.1.0 JSON document, e.g. to upload to GitHub
+/// code scanning.
+///
+/// `paths_or_code` is a list of paths to kcl files, which are reported as given, or kcl code, which is reported as
+/// `main.kcl`. With `rules`, a list of rule codes like `Z0001`, only those rules are reported. Files that can't be
+/// read or parsed are reported as errors in the run's tool execution notifications, rather than raising.
+#[pyfunction]
+#[pyo3(signature = (paths_or_code, rules = None))]
+pub(crate) fn lint_sarif(paths_or_code: PathsOrCode, rules: Option<Vec<String>>) -> PyResult<String> {
+    let known = known_rules();
+    if let Some(unknown) = rules
+        .iter()
+        .flatten()
+        .find(|code| !known.iter().any(|rule| rule.code == code.as_str()))
+    {
+        let valid = known.map(|rule| rule.code).join(", ");
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Unknown lint rule {:?}, expected one of: {}",
+            unknown, valid
+        )));
+    }
+    let rules: Vec<Finding> = known
+        .into_iter()
+        .filter(|rule| {
+            rules
+                .as_ref()
+                .is_none_or(|rules| rules.iter().any(|code| code == rule.code))
+        })
+        .collect();
+
+    let files = match paths_or_code {
+        PathsOrCode::Paths(paths) => paths
+            .into_iter()
+            .map(|path| {
+                let code = file_checks::read(&path);
+                Linted::new(path, code)
+            })
+            .collect::<PyResult<Vec<_>>>()?,
+        PathsOrCode::Code(code) => vec![Linted::new(CODE_URI.to_string(), Ok(code.source().to_string()))?],
+    };
+
+    let mut results = Vec::new();
+    let mut notifications = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let code = file.code.as_deref();
+        let findings = match &file.findings {
+            Ok(findings) => findings,
+            Err(err) => {
+                notifications.push(json!({
+                    "level": "error",
+                    "message": { "text": err.message },
+                    "locations": [location(&file.uri, index, code, err.start, err.end)],
+                }));
+                continue;
+            }
+        };
+        for finding in findings {
+            let discovered = &finding.discovered;
+            let Some(rule_index) = rules.iter().position(|rule| rule.code == discovered.finding.code) else {
+                continue;
+            };
+            let mut result = json!({
+                "ruleId": discovered.finding.code,
+                "ruleIndex": rule_index,
+                "level": level(finding.severity),
+                "message": { "text": discovered.description },
+                "locations": [location(&file.uri, index, code, discovered.pos.start(), discovered.pos.end())],
+            });
+            if discovered.overridden {
+                result["suppressions"] = json!([{ "kind": "inSource" }]);
+            }
+            results.push(result);
+        }
+    }
+
+    let rules: Vec<Value> = rules
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.code,
+                "shortDescription": { "text": rule.title },
+                "fullDescription": { "text": rule.description },
+                "defaultConfiguration": { "level": level(LintSeverity::of_rule(rule.code)) },
+                "properties": { "experimental": rule.experimental },
+            })
+        })
+        .collect();
+    let artifacts: Vec<Value> = files
+        .iter()
+        .map(|file| json!({ "location": { "uri": file.uri } }))
+        .collect();
+    let sarif = json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": version::NAME,
+                    "version": version::VERSION,
+                    "informationUri": "https://github.com/KittyCAD/kcl.py",
+                    "rules": rules,
+                },
+                "extensions": [{ "name": "kcl-lib", "version": version::KCL_LIB_VERSION }],
+            },
+            "columnKind": "unicodeCodePoints",
+            "artifacts": artifacts,
+            "results": results,
+            "invocations": [{
+                "executionSuccessful": true,
+                "toolExecutionNotifications": notifications,
+            }],
+        }],
+    });
+
+    serde_json::to_string_pretty(&sarif).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))
+}
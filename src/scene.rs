@@ -0,0 +1,399 @@
+//! The objects a program made, as a tree of where each came from, for seeing what is in the scene while debugging.
+
+use std::collections::BTreeMap;
+
+use pyo3::{pyclass, pyfunction, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    artifact_graph, connection, default_planes, defaults, pickle, program::Code, run_code, spawn, tags, trace,
+    with_reconnect, RunOptions, Units,
+};
+
+/// An object in the scene, and the objects that came from it.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct SceneObject {
+    /// The id of the object in the engine, or None for a pattern, which only groups the objects it made.
+    #[pyo3(get)]
+    pub id: Option<String>,
+    /// What the object is: `plane`, `sketch`, `segment`, `solid`, `face`, `fillet`, `chamfer`, `helix` or `pattern`.
+    #[pyo3(get)]
+    pub kind: String,
+    /// The name to show for the object: the variable or tag it was given, or else the function that made it, e.g.
+    /// `extrude`.
+    #[pyo3(get)]
+    pub name: String,
+    /// The source range of the code that made the object, as a `(start, end)` pair of byte offsets, or None if it
+    /// wasn't made by the program's own code, like a default plane or something made in an imported module.
+    #[pyo3(get)]
+    pub source_range: Option<(usize, usize)>,
+    /// The objects that came from this one, in the order the code made them. A sketch is under the plane or face it
+    /// is on, a solid under the sketch it was made from, and the objects a pattern made under the pattern.
+    #[pyo3(get)]
+    pub children: Vec<SceneObject>,
+}
+
+impl SceneObject {
+    /// Write the object and everything under it, one per line, indented by depth.
+    fn render(&self, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!("{}{} ({})", "  ".repeat(depth), self.name, self.kind));
+        for child in &self.children {
+            child.render(depth + 1, lines);
+        }
+    }
+
+    /// The object with the given name, looking at this object and then everything under it in order.
+    fn find(&self, name: &str) -> Option<&SceneObject> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(name))
+    }
+
+    /// Where the object appears in the code, for ordering. Objects the code didn't make appear where the first object
+    /// under them does.
+    fn position(&self) -> usize {
+        match self.source_range {
+            Some((start, _)) => start,
+            None => self
+                .children
+                .iter()
+                .map(SceneObject::position)
+                .min()
+                .unwrap_or(usize::MAX),
+        }
+    }
+}
+
+#[pymethods]
+impl SceneObject {
+    fn __str__(&self) -> String {
+        let mut lines = Vec::new();
+        self.render(0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// The objects a program made, as a tree.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct Scene {
+    /// The objects that didn't come from any other, usually planes, in the order the code first used them.
+    #[pyo3(get)]
+    pub objects: Vec<SceneObject>,
+}
+
+#[pymethods]
+impl Scene {
+    /// The first object with the given name, in the order `str` writes them, or None if there isn't one.
+    fn find(&self, name: &str) -> Option<SceneObject> {
+        self.objects.iter().find_map(|object| object.find(name)).cloned()
+    }
+
+    /// The tree, one object per line as `name (kind)`, indented under the object it came from.
+    fn __str__(&self) -> String {
+        let mut lines = Vec::new();
+        for object in &self.objects {
+            object.render(0, &mut lines);
+        }
+        lines.join("\n")
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// An object before it is put in the tree.
+struct Node {
+    /// The id of the object, or a made up one for a pattern.
+    key: String,
+    parent: Option<String>,
+    object: SceneObject,
+}
+
+/// The kind of object an artifact is, or None for artifacts that are part of another object, like the edges of a
+/// solid.
+fn kind(artifact: &Value) -> Option<String> {
+    let kind = match artifact["type"].as_str()? {
+        "plane" => "plane",
+        "path" => "sketch",
+        "segment" => "segment",
+        "sweep" => "solid",
+        "wall" | "cap" => "face",
+        "edgeCut" => artifact["subType"].as_str().unwrap_or("edgeCut"),
+        "helix" => "helix",
+        _ => return None,
+    };
+    Some(kind.to_string())
+}
+
+/// The source range of the code that made an artifact, if it is in the program's own code.
+pub(crate) fn source_range(artifact: &Value) -> Option<(usize, usize)> {
+    let range = &artifact.get("codeRef").or(artifact.get("faceCodeRef"))?["range"];
+    let (start, end) = (range[0].as_u64()? as usize, range[1].as_u64()? as usize);
+    // The third number is the module, and the program's own code is module 0.
+    let module = range[2].as_u64().unwrap_or_default();
+    (module == 0 && end > 0).then_some((start, end))
+}
+
+/// The name of the function called by the code in a source range, e.g. `extrude` for `extrude(5, %)`.
+fn call_name(source: &str, (start, end): (usize, usize)) -> Option<String> {
+    let (callee, _) = source.get(start..end)?.split_once('(')?;
+    let name = callee
+        .trim()
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()?;
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// The id of the object an artifact came from.
+fn parent(artifact: &Value) -> Option<String> {
+    let field = |artifact: &Value, key: &str| artifact[key].as_str().map(str::to_string);
+    match artifact["type"].as_str()? {
+        // A sketch on a face has the face as its plane, and so does the solid made by extruding it.
+        "path" | "sweep" => field(artifact, "planeId"),
+        "segment" => field(artifact, "pathId"),
+        // A fillet or chamfer is under the solid whose edge it replaced.
+        "wall" | "cap" | "edgeCut" => field(artifact, "sweepId"),
+        _ => None,
+    }
+}
+
+/// The geometry in a top level value, as the kind of object and id of each, with arrays flattened.
+fn geometry(value: &Value) -> Vec<(String, String)> {
+    let kind = value["type"].as_str().unwrap_or_default();
+    let inner = &value["value"];
+    let items = || inner.as_array().into_iter().flatten();
+    match kind {
+        "Sketch" | "Solid" | "Plane" | "Helix" => inner["id"]
+            .as_str()
+            .map(|id| vec![(kind.to_lowercase(), id.to_string())])
+            .unwrap_or_default(),
+        // Some versions of kcl-lib keep groups of sketches and solids without the type of each.
+        "Sketches" | "Solids" => items()
+            .filter_map(|item| Some((kind[..kind.len() - 1].to_lowercase(), item["id"].as_str()?.to_string())))
+            .collect(),
+        "Array" | "MixedArray" | "HomArray" | "Tuple" => items().flat_map(geometry).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Make a tree of nodes, with each node's children ordered by where they appear in the code.
+fn assemble(parent: Option<&str>, nodes: &[Node]) -> Vec<SceneObject> {
+    let mut objects: Vec<SceneObject> = nodes
+        .iter()
+        .filter(|node| node.parent.as_deref() == parent)
+        .map(|node| SceneObject {
+            children: assemble(Some(&node.key), nodes),
+            ..node.object.clone()
+        })
+        .collect();
+    // The sort is stable, so objects made by the same code keep the order they were made in.
+    objects.sort_by_key(SceneObject::position);
+    objects
+}
+
+/// Execute the kcl code and return the objects it made as a tree, for seeing what is in the scene.
+///
+/// Each object has its id, its kind, a name to show for it and where in the code it was made, with the objects that
+/// came from it as its children: planes hold the sketches on them, sketches their segments and the solids made from
+/// them, solids their faces, fillets and chamfers, and faces the sketches on them. The objects a pattern made are
+/// under a `pattern` object, under the object that was patterned. `str` of the result is the tree, one object per
+/// line.
+///
+/// The tree is built from what execution recorded, without asking the engine anything more, and its order follows
+/// the code, so the tree of a program is the same every time it runs.
+#[pyfunction]
+#[pyo3(signature = (code, units = None))]
+pub(crate) async fn execute_and_get_scene(code: Code, units: Option<Units>) -> PyResult<Scene> {
+    connection::require_token(None)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options) = (&code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            let source = code.source();
+            let graph = artifact_graph(&executed.exec_state)?;
+
+            // Geometry is named after the variable it was assigned to, and faces and segments after their tags.
+            let values = trace::top_level_values(&executed.program, &executed.exec_state)?;
+            let mut names = BTreeMap::new();
+            for value in &values {
+                let items = geometry(&value.value);
+                let single = items.len() == 1 && value.value["value"]["id"].is_string();
+                for (index, (_, id)) in items.iter().enumerate() {
+                    let name = if single {
+                        value.name.clone()
+                    } else {
+                        format!("{}[{}]", value.name, index)
+                    };
+                    names.entry(id.clone()).or_insert(name);
+                }
+            }
+            for value in trace::root_bindings(&executed.exec_state)?.values() {
+                tags::names_by_id(value, &mut names);
+            }
+
+            let mut nodes: Vec<Node> = Vec::new();
+            for artifact in graph["nodes"].as_array().into_iter().flatten() {
+                let (Some(id), Some(kind)) = (artifact["id"].as_str(), kind(artifact)) else {
+                    continue;
+                };
+                let source_range = source_range(artifact);
+                let name = names
+                    .get(id)
+                    .cloned()
+                    .or_else(|| {
+                        let side = artifact["subType"].as_str().filter(|_| artifact["type"] == "cap")?;
+                        Some(format!("{} cap", side))
+                    })
+                    .or_else(|| source_range.and_then(|range| call_name(source, range)))
+                    .unwrap_or_else(|| kind.clone());
+                nodes.push(Node {
+                    key: id.to_string(),
+                    parent: parent(artifact),
+                    object: SceneObject {
+                        id: Some(id.to_string()),
+                        kind,
+                        name,
+                        source_range,
+                        children: Vec::new(),
+                    },
+                });
+            }
+
+            // Sketches on a default plane refer to it, but it was made before the program ran, so it isn't in the
+            // graph. The planes are already made, so looking up their ids doesn't ask the engine again.
+            let has_node = |nodes: &[Node], key: &str| nodes.iter().any(|node| node.key == key);
+            let missing_parent = nodes
+                .iter()
+                .any(|node| node.parent.as_ref().is_some_and(|parent| !has_node(&nodes, parent)));
+            if missing_parent {
+                for (name, id) in default_planes::ids(&executed.ctx).await? {
+                    let id = id.to_string();
+                    if let Some(node) = nodes.iter_mut().find(|node| node.key == id) {
+                        node.object.name = name;
+                    } else if nodes.iter().any(|node| node.parent.as_ref() == Some(&id)) {
+                        nodes.push(Node {
+                            key: id.clone(),
+                            parent: None,
+                            object: SceneObject {
+                                id: Some(id),
+                                kind: "plane".to_string(),
+                                name,
+                                source_range: None,
+                                children: Vec::new(),
+                            },
+                        });
+                    }
+                }
+            }
+
+            // Patterns copy geometry without recording the copies in the graph, so the copies are found in memory.
+            // A pattern gives back the original first and then its copies.
+            for value in &values {
+                let items = geometry(&value.value);
+                let (known, copies): (Vec<_>, Vec<_>) =
+                    items.iter().enumerate().partition(|(_, (_, id))| has_node(&nodes, id));
+                if copies.is_empty() {
+                    continue;
+                }
+                let single = items.len() == 1 && value.value["value"]["id"].is_string();
+                let parent = if single {
+                    None
+                } else {
+                    let key = format!("pattern {}", value.name);
+                    nodes.push(Node {
+                        key: key.clone(),
+                        parent: known
+                            .first()
+                            .filter(|(index, _)| *index == 0)
+                            .map(|(_, (_, id))| id.clone()),
+                        object: SceneObject {
+                            id: None,
+                            kind: "pattern".to_string(),
+                            name: value.name.clone(),
+                            source_range: Some(value.source_range),
+                            children: Vec::new(),
+                        },
+                    });
+                    Some(key)
+                };
+                for (index, (kind, id)) in copies {
+                    nodes.push(Node {
+                        key: id.clone(),
+                        parent: parent.clone(),
+                        object: SceneObject {
+                            id: Some(id.clone()),
+                            kind: kind.clone(),
+                            name: names
+                                .get(id)
+                                .cloned()
+                                .unwrap_or_else(|| format!("{}[{}]", value.name, index)),
+                            source_range: Some(value.source_range),
+                            children: Vec::new(),
+                        },
+                    });
+                }
+            }
+
+            // An object whose parent isn't in the scene is at the top, as is anything in a loop of parents, which the
+            // graph shouldn't have but would otherwise leave the object out.
+            let keys: Vec<String> = nodes.iter().map(|node| node.key.clone()).collect();
+            for index in 0..nodes.len() {
+                let mut parent = nodes[index].parent.clone();
+                let mut steps = 0;
+                while let Some(key) = parent.filter(|key| keys.contains(key)) {
+                    if key == nodes[index].key || steps > nodes.len() {
+                        nodes[index].parent = None;
+                        break;
+                    }
+                    parent = nodes
+                        .iter()
+                        .find(|node| node.key == key)
+                        .and_then(|node| node.parent.clone());
+                    steps += 1;
+                }
+                if nodes[index]
+                    .parent
+                    .as_ref()
+                    .is_some_and(|parent| !keys.contains(parent))
+                {
+                    nodes[index].parent = None;
+                }
+            }
+
+            Ok(Scene {
+                objects: assemble(None, &nodes),
+            })
+        })
+        .await
+    })
+    .await
+}
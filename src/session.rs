@@ -0,0 +1,430 @@
+//! A session with the engine that lives across many calls.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use kcl_lib::ExecutorContext;
+use pyo3::{pyclass, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    connection::{self, ClientConfig},
+    default_planes, defaults, empty_scene_error, export_entities,
+    export_options::{ExportOptions, OutputOptions},
+    find_objects, get_background_color, kcl_error, new_context_with, pickle,
+    program::Code,
+    snapshot_framing, snapshot_from, spawn,
+    trace::{self, TraceEntry},
+    CameraPose, Color, Diagnostic, ExportFile, ExportSelection, FileExportFormat, ImageFormat, ObjectHandle,
+    RenderSettings, UnitLength, Units,
+};
+
+/// What happened when a session executed some kcl code.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct SessionOutcome {
+    /// Whether the program was unchanged since the previous execution, so nothing ran again and its results were
+    /// reused. Any change to the program runs all of it again.
+    #[pyo3(get)]
+    pub cache_hit: bool,
+    /// Non-fatal diagnostics from parsing the program. kcl-lib doesn't report warnings while executing, so only
+    /// parse-time ones appear here.
+    #[pyo3(get)]
+    pub warnings: Vec<Diagnostic>,
+    /// Wall-clock duration of the execution in milliseconds, not including parsing or connecting to the engine.
+    #[pyo3(get)]
+    pub execution_ms: f64,
+    /// The solids, sketches and planes the program created, for use in later calls like `export`.
+    #[pyo3(get)]
+    pub objects: Vec<ObjectHandle>,
+    /// The value of each top level variable, for debugging what the program computed.
+    #[pyo3(get)]
+    pub trace: Vec<TraceEntry>,
+    /// The ids of the default planes, by name, e.g. `XY` or `-XZ`, to hide or leave out of exports. Empty if the
+    /// session doesn't create them.
+    #[pyo3(get)]
+    pub default_planes: BTreeMap<String, String>,
+}
+
+#[pymethods]
+impl SessionOutcome {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+#[derive(Default)]
+struct SessionState {
+    /// The connection to the engine, made the first time something is executed.
+    ctx: Option<ExecutorContext>,
+    /// The last program executed and the state it left behind, for reuse by the next execution.
+    last: Option<(kcl_lib::Program, kcl_lib::ExecState)>,
+}
+
+/// A spare connection in a session's pool, with the scene from one of the session's executions.
+#[derive(Default)]
+struct Replica {
+    ctx: Option<ExecutorContext>,
+    /// Which execution of the session the scene is from.
+    generation: u64,
+}
+
+/// The latest program a session executed, if it succeeded, and which execution it was.
+#[derive(Default)]
+struct Latest {
+    generation: u64,
+    program: Option<kcl_lib::Program>,
+    /// How many solids and sketches the program created.
+    object_count: usize,
+}
+
+impl Latest {
+    /// The latest program, if the scene it made has anything in it or `allow_empty` is set.
+    fn program(&self, allow_empty: bool) -> PyResult<&kcl_lib::Program> {
+        let Some(program) = &self.program else {
+            return Err(pyo3::exceptions::PyException::new_err(
+                "Nothing has been executed in this session yet",
+            ));
+        };
+        if self.object_count == 0 && !allow_empty {
+            return Err(empty_scene_error(program, self.object_count));
+        }
+
+        Ok(program)
+    }
+}
+
+/// A session with the engine that keeps its connection, and the results of the last execution, between calls.
+///
+/// Executing the same program as the previous execution reuses its results rather than running it again. The
+/// connection is made with `config` if one is given, e.g. for a `token_provider`, otherwise from the environment.
+///
+/// With a `pool_size` above 1 the session keeps that many connections, so exports of the whole scene can run at the
+/// same time, each on its own connection with its own copy of the scene. Everything else, like executing and
+/// exporting particular objects, uses the session's main connection.
+///
+/// With `create_default_planes=False` the default planes are left out of the scene, so they aren't in exports, and
+/// code that uses one raises a semantic error pointing at where it does.
+#[pyclass]
+pub struct Session {
+    units: UnitLength,
+    render_settings: RenderSettings,
+    config: Option<ClientConfig>,
+    create_default_planes: bool,
+    state: Arc<tokio::sync::Mutex<SessionState>>,
+    /// Executions hold this for writing, so nothing is exported from a scene while it is changing.
+    latest: Arc<tokio::sync::RwLock<Latest>>,
+    /// The connections in the pool besides the main one, which copy its scene when they are first used after it
+    /// changes.
+    replicas: Vec<Arc<tokio::sync::Mutex<Replica>>>,
+    /// How many connections to the engine the session can use at once.
+    #[pyo3(get)]
+    pub pool_size: usize,
+    /// Whether to reuse the results of the previous execution where the program hasn't changed.
+    #[pyo3(get, set)]
+    pub use_cache: bool,
+}
+
+/// Bring a connection in the pool up to date with the latest execution, connecting first if it isn't yet.
+async fn replay(
+    replica: &mut Replica,
+    latest: &Latest,
+    program: &kcl_lib::Program,
+    config: Option<&ClientConfig>,
+    units: UnitLength,
+    render_settings: &RenderSettings,
+    create_default_planes: bool,
+) -> PyResult<ExecutorContext> {
+    if replica.ctx.is_none() {
+        replica.ctx = Some(new_context_with(config, units, render_settings, None).await?);
+    }
+    let ctx = replica.ctx.clone().expect("the context was just created");
+    if replica.generation != latest.generation {
+        ctx.run(program.clone().into(), &mut kcl_lib::ExecState::default())
+            .await
+            .map_err(kcl_error)?;
+        if !create_default_planes {
+            default_planes::remove(&ctx).await?;
+        }
+        replica.generation = latest.generation;
+    }
+
+    Ok(ctx)
+}
+
+#[pymethods]
+impl Session {
+    #[new]
+    #[pyo3(signature = (
+        units,
+        render_settings = None,
+        use_cache = true,
+        config = None,
+        create_default_planes = true,
+        pool_size = 1,
+    ))]
+    fn new(
+        units: Units,
+        render_settings: Option<RenderSettings>,
+        use_cache: bool,
+        config: Option<ClientConfig>,
+        create_default_planes: bool,
+        pool_size: usize,
+    ) -> PyResult<Self> {
+        if pool_size == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("pool_size must be at least 1"));
+        }
+        Ok(Session {
+            units: units.try_into()?,
+            render_settings: render_settings.unwrap_or_default(),
+            config,
+            create_default_planes,
+            state: Default::default(),
+            latest: Default::default(),
+            replicas: (1..pool_size).map(|_| Default::default()).collect(),
+            pool_size,
+            use_cache,
+        })
+    }
+
+    /// Execute the kcl code, reusing what it can from the previous execution.
+    async fn execute(&self, code: Code) -> PyResult<SessionOutcome> {
+        connection::require_token(self.config.as_ref())?;
+        let (units, render_settings, use_cache) = (self.units, self.render_settings.clone(), self.use_cache);
+        let (config, state, latest) = (self.config.clone(), self.state.clone(), self.latest.clone());
+        let create_default_planes = self.create_default_planes;
+        spawn(async move {
+            let mut latest = latest.write().await;
+            latest.generation += 1;
+            latest.program = None;
+            let mut state = state.lock().await;
+            let (program, warnings) = code.parse()?;
+            if !create_default_planes {
+                default_planes::check_unused(&program)?;
+            }
+            if state.ctx.is_none() {
+                state.ctx = Some(new_context_with(config.as_ref(), units, &render_settings, None).await?);
+            }
+            let ctx = state.ctx.clone().expect("the context was just created");
+
+            let last = if use_cache { state.last.take() } else { None };
+            let cache_info = kcl_lib::CacheInformation {
+                old: last.map(|(last_program, exec_state)| kcl_lib::OldAstState {
+                    ast: last_program.ast,
+                    exec_state,
+                    settings: ctx.settings.clone(),
+                }),
+                new_ast: program.ast.clone(),
+            };
+
+            // kcl-lib either reuses everything, when nothing that matters has changed, or clears the scene and runs
+            // the whole program again. When it reuses everything it leaves the state it is given untouched, so the
+            // previous execution's state is kept rather than an empty one.
+            let cache_hit = ctx.get_changed_program(cache_info.clone()).await.is_none();
+            let start = std::time::Instant::now();
+            let exec_state = match cache_info.old {
+                Some(old) if cache_hit => old.exec_state,
+                _ => {
+                    let mut exec_state = kcl_lib::ExecState::default();
+                    ctx.run(cache_info, &mut exec_state).await.map_err(kcl_error)?;
+                    exec_state
+                }
+            };
+            let execution_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+            let objects = find_objects(&exec_state)?;
+            let trace = trace::trace(&program, &exec_state)?;
+            let default_planes = if create_default_planes {
+                default_planes::ids(&ctx)
+                    .await?
+                    .into_iter()
+                    .map(|(name, id)| (name, id.to_string()))
+                    .collect()
+            } else {
+                default_planes::remove(&ctx).await?;
+                BTreeMap::new()
+            };
+            latest.program = Some(program.clone());
+            latest.object_count = objects.iter().filter(|object| object.kind != "plane").count();
+            state.last = Some((program, exec_state));
+
+            Ok(SessionOutcome {
+                cache_hit,
+                warnings,
+                execution_ms,
+                objects,
+                trace,
+                default_planes,
+            })
+        })
+        .await
+    }
+
+    /// Snapshot the scene the last execution left, without running anything again, e.g. to try different views.
+    ///
+    /// The camera fits `frame_objects` if there are any, otherwise the whole scene, unless a `camera` pose is given.
+    /// `background_color`, `transparent` and `deterministic` work as they do for `execute_and_snapshot`. Raises
+    /// `KclEmptySceneError` if the scene has no solids or sketches, unless `allow_empty` is set.
+    #[pyo3(signature = (
+        image_format = None,
+        camera = None,
+        frame_objects = None,
+        background_color = None,
+        transparent = false,
+        deterministic = true,
+        allow_empty = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    async fn snapshot(
+        &self,
+        image_format: Option<ImageFormat>,
+        camera: Option<CameraPose>,
+        frame_objects: Option<Vec<ObjectHandle>>,
+        background_color: Option<Color>,
+        transparent: bool,
+        deterministic: bool,
+        allow_empty: bool,
+    ) -> PyResult<Vec<u8>> {
+        let image_format = defaults::image_format(image_format)?;
+        let frame = frame_objects
+            .unwrap_or_default()
+            .iter()
+            .map(ObjectHandle::uuid)
+            .collect::<PyResult<Vec<_>>>()?;
+        if !frame.is_empty() && camera.is_some() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Give either a camera or frame_objects, not both",
+            ));
+        }
+        let background_color = get_background_color(image_format, background_color, transparent)?;
+        let settle = self.render_settings.settle(deterministic);
+        let (state, latest) = (self.state.clone(), self.latest.clone());
+        spawn(async move {
+            let latest = latest.read().await;
+            latest.program(allow_empty)?;
+            // The camera is only moved on the main connection, so the scene is snapshotted where it was executed.
+            let state = state.lock().await;
+            let ctx = state.ctx.as_ref().expect("the session has executed something");
+            match camera {
+                Some(camera) => snapshot_from(ctx, &camera, image_format, background_color, settle).await,
+                None => snapshot_framing(ctx, frame, image_format, background_color, settle).await,
+            }
+        })
+        .await
+    }
+
+    /// Export what the last execution created to a specific file format.
+    ///
+    /// Only the given objects are exported if there are any, otherwise the whole scene is. STL and PLY exports can
+    /// also be given a `selection`, which must be `ExportSelection.objects()` or the default scene if objects are.
+    /// `format_options` work as they do for `execute_and_export`. Raises `KclEmptySceneError` if the scene has no
+    /// solids or sketches, unless `allow_empty` is set.
+    #[pyo3(signature = (export_format = None, objects = None, selection = None, format_options = None, allow_empty = false))]
+    async fn export(
+        &self,
+        export_format: Option<FileExportFormat>,
+        objects: Option<Vec<ObjectHandle>>,
+        selection: Option<ExportSelection>,
+        format_options: Option<ExportOptions>,
+        allow_empty: bool,
+    ) -> PyResult<Vec<ExportFile>> {
+        let export_format = defaults::export_format(export_format)?;
+        let entity_ids = objects
+            .unwrap_or_default()
+            .iter()
+            .map(ObjectHandle::uuid)
+            .collect::<PyResult<Vec<_>>>()?;
+        let output_options = OutputOptions::new(&export_format, format_options, selection, &entity_ids)?;
+        let (units, render_settings, config) = (self.units, self.render_settings.clone(), self.config.clone());
+        let (state, latest, replicas) = (self.state.clone(), self.latest.clone(), self.replicas.clone());
+        let create_default_planes = self.create_default_planes;
+        spawn(async move {
+            let latest = latest.read().await;
+            let program = latest.program(allow_empty)?;
+
+            // Objects are exported by their ids, which only the main connection's copy of the scene has, but the
+            // whole scene can be exported from any connection that isn't busy.
+            if entity_ids.is_empty() && state.try_lock().is_err() {
+                if let Some(mut replica) = replicas.iter().find_map(|replica| replica.try_lock().ok()) {
+                    let ctx = replay(
+                        &mut replica,
+                        &latest,
+                        program,
+                        config.as_ref(),
+                        units,
+                        &render_settings,
+                        create_default_planes,
+                    )
+                    .await?;
+                    return export_entities(&ctx, units, export_format, entity_ids, output_options).await;
+                }
+            }
+
+            let state = state.lock().await;
+            let ctx = state.ctx.as_ref().expect("the session has executed something");
+            export_entities(ctx, units, export_format, entity_ids, output_options).await
+        })
+        .await
+    }
+
+    /// Clear the scene and forget the previous execution, so the next execution starts from an empty scene.
+    ///
+    /// Until something is executed again, snapshots and exports are of the empty scene, so they raise
+    /// `KclEmptySceneError` unless `allow_empty` is set. The connections to the engine are kept.
+    async fn reset(&self) -> PyResult<()> {
+        let (state, latest) = (self.state.clone(), self.latest.clone());
+        let create_default_planes = self.create_default_planes;
+        spawn(async move {
+            let mut latest = latest.write().await;
+            let mut state = state.lock().await;
+            state.last = None;
+            if let Some(ctx) = &state.ctx {
+                ctx.reset_scene(&mut kcl_lib::ExecState::default(), Default::default())
+                    .await
+                    .map_err(kcl_error)?;
+                if !create_default_planes {
+                    default_planes::remove(ctx).await?;
+                }
+            }
+            // The connections in the pool copy the empty program, which clears their scenes too.
+            let empty = kcl_lib::Program::parse_no_errs("")?;
+            *latest = Latest {
+                generation: latest.generation + 1,
+                program: state.ctx.is_some().then_some(empty),
+                object_count: 0,
+            };
+            Ok(())
+        })
+        .await
+    }
+
+    /// Close every connection the session has to the engine, and forget the previous execution.
+    ///
+    /// The session can still be used afterwards, connecting again the next time something is executed.
+    async fn close(&self) -> PyResult<()> {
+        let (state, latest, replicas) = (self.state.clone(), self.latest.clone(), self.replicas.clone());
+        spawn(async move {
+            let mut latest = latest.write().await;
+            latest.generation += 1;
+            latest.program = None;
+            *state.lock().await = Default::default();
+            for replica in replicas {
+                *replica.lock().await = Default::default();
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Forget the previous execution, so the next one runs the whole program from scratch.
+    fn bust_cache(&self, py: Python<'_>) {
+        py.allow_threads(|| self.state.blocking_lock().last = None);
+    }
+}
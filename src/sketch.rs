@@ -0,0 +1,418 @@
+//! Exporting the 2D sketches of a program as vector drawings, for laser cutters and the like.
+
+use std::{collections::HashMap, f64::consts::TAU, fmt::Write};
+
+use pyo3::{pyclass, pyfunction, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    connection, defaults, pickle, program::Code, progress, reject_module_files, run_code, spawn, trace, with_reconnect,
+    ExportFile, RunOptions, UnitLength, Units,
+};
+
+/// The 2D file formats sketches can be exported to.
+#[derive(Serialize, Deserialize, PartialEq, Hash, Debug, Clone, Copy)]
+#[pyclass(eq, eq_int, hash, frozen, module = "kcl")]
+#[serde(rename_all = "lowercase")]
+pub enum SketchExportFormat {
+    /// .svg format
+    Svg,
+    /// .dxf format
+    Dxf,
+}
+
+#[pymethods]
+impl SketchExportFormat {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject, String))> {
+        pickle::reduce_variant::<Self>(py, format!("{:?}", self))
+    }
+
+    fn __copy__(&self) -> Self {
+        *self
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        *self
+    }
+}
+
+impl SketchExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            SketchExportFormat::Svg => "svg",
+            SketchExportFormat::Dxf => "dxf",
+        }
+    }
+}
+
+/// A path of a sketch, as kcl-lib serializes it. Only the fields needed to draw it are kept.
+#[derive(Deserialize)]
+struct PathJson {
+    #[serde(rename = "type")]
+    kind: String,
+    from: [f64; 2],
+    to: [f64; 2],
+    center: Option<[f64; 2]>,
+    radius: Option<f64>,
+    ccw: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct SketchJson {
+    paths: Vec<PathJson>,
+}
+
+/// One piece of a sketch, in the sketch's own 2D coordinates and the units of the program.
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Line {
+        from: [f64; 2],
+        to: [f64; 2],
+    },
+    Arc {
+        from: [f64; 2],
+        to: [f64; 2],
+        center: [f64; 2],
+        ccw: bool,
+    },
+    Circle {
+        center: [f64; 2],
+        radius: f64,
+    },
+}
+
+impl Segment {
+    fn from_json(path: PathJson) -> Option<Self> {
+        match (path.kind.as_str(), path.center, path.radius, path.ccw) {
+            // The first path only records where the sketch starts.
+            ("Base", ..) => None,
+            ("Circle", Some(center), Some(radius), _) => Some(Segment::Circle { center, radius }),
+            (_, Some(center), _, Some(ccw)) => Some(Segment::Arc {
+                from: path.from,
+                to: path.to,
+                center,
+                ccw,
+            }),
+            _ => Some(Segment::Line {
+                from: path.from,
+                to: path.to,
+            }),
+        }
+    }
+
+    /// The points to fit in the drawing for this segment to be fully visible.
+    fn extremes(&self) -> Vec<[f64; 2]> {
+        match *self {
+            Segment::Line { from, to } => vec![from, to],
+            Segment::Circle { center, radius } => vec![
+                [center[0] - radius, center[1] - radius],
+                [center[0] + radius, center[1] + radius],
+            ],
+            Segment::Arc { from, to, center, ccw } => {
+                let radius = distance(from, center);
+                let (start, sweep) = arc_angles(from, to, center, ccw);
+                let mut points = vec![from, to];
+                // Wherever the arc crosses an axis through its center is as far as it goes in that direction.
+                for quadrant in 0..4 {
+                    let angle = quadrant as f64 * TAU / 4.0;
+                    if (angle - start).rem_euclid(TAU) <= sweep {
+                        points.push([center[0] + radius * angle.cos(), center[1] + radius * angle.sin()]);
+                    }
+                }
+                points
+            }
+        }
+    }
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+/// The angle an arc starts at when drawn counterclockwise, and how far it sweeps, both in radians.
+fn arc_angles(from: [f64; 2], to: [f64; 2], center: [f64; 2], ccw: bool) -> (f64, f64) {
+    let angle = |point: [f64; 2]| (point[1] - center[1]).atan2(point[0] - center[0]);
+    let (start, end) = if ccw {
+        (angle(from), angle(to))
+    } else {
+        (angle(to), angle(from))
+    };
+    let sweep = (end - start).rem_euclid(TAU);
+    // An arc that ends where it starts goes all the way around.
+    (start, if sweep < 1e-9 { TAU } else { sweep })
+}
+
+/// A sketch bound to a top level variable.
+struct NamedSketch {
+    name: String,
+    start: usize,
+    segments: Vec<Segment>,
+}
+
+/// Find every sketch bound to a variable at the top level of the program, in the order they appear in the code.
+///
+/// Sketches that were extruded are now solids, so they aren't found.
+fn find_sketches(exec_state: &kcl_lib::ExecState) -> PyResult<Vec<NamedSketch>> {
+    let mut sketches = Vec::new();
+    for (name, value) in trace::root_values(exec_state)? {
+        let found = match value {
+            kcl_lib::exec::KclValue::Sketch { value } => vec![value],
+            kcl_lib::exec::KclValue::Sketches { value } => value.iter().collect(),
+            _ => continue,
+        };
+        let count = found.len();
+        for (index, sketch) in found.into_iter().enumerate() {
+            let json = serde_json::to_value(sketch)
+                .and_then(serde_json::from_value::<SketchJson>)
+                .map_err(|err| {
+                    pyo3::exceptions::PyException::new_err(format!(
+                        "Couldn't read the paths of sketch {}: {}",
+                        name, err
+                    ))
+                })?;
+            sketches.push(NamedSketch {
+                name: if count == 1 {
+                    name.clone()
+                } else {
+                    format!("{}_{}", name, index)
+                },
+                start: sketch
+                    .meta
+                    .first()
+                    .map(|meta| meta.source_range.start())
+                    .unwrap_or_default(),
+                segments: json.paths.into_iter().filter_map(Segment::from_json).collect(),
+            });
+        }
+    }
+    sketches.sort_by_key(|sketch| sketch.start);
+
+    Ok(sketches)
+}
+
+/// The unit to give the size of an SVG in, and how many of it there are in one of the program's units.
+fn svg_unit(units: UnitLength) -> (&'static str, f64) {
+    match units {
+        UnitLength::Mm => ("mm", 1.0),
+        UnitLength::Cm => ("cm", 1.0),
+        UnitLength::M => ("mm", 1000.0),
+        UnitLength::In => ("in", 1.0),
+        UnitLength::Ft => ("in", 12.0),
+        UnitLength::Yd => ("in", 36.0),
+    }
+}
+
+/// Draw the segments as an SVG, at their real size.
+///
+/// SVG's y axis points down, so y is negated to keep the drawing the same way up as the sketch.
+fn to_svg(segments: &[Segment], units: UnitLength) -> String {
+    let points: Vec<[f64; 2]> = segments.iter().flat_map(Segment::extremes).collect();
+    let min_x = points.iter().map(|point| point[0]).fold(f64::INFINITY, f64::min);
+    let max_x = points.iter().map(|point| point[0]).fold(f64::NEG_INFINITY, f64::max);
+    let min_y = points.iter().map(|point| point[1]).fold(f64::INFINITY, f64::min);
+    let max_y = points.iter().map(|point| point[1]).fold(f64::NEG_INFINITY, f64::max);
+    let (width, height) = (max_x - min_x, max_y - min_y);
+    let (unit, scale) = svg_unit(units);
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}{unit}" height="{}{unit}" viewBox="{} {} {} {}">"#,
+        width * scale,
+        height * scale,
+        min_x,
+        -max_y,
+        width,
+        height,
+    );
+    let style = r#"fill="none" stroke="black" stroke-width="1" vector-effect="non-scaling-stroke""#;
+    let mut path = String::new();
+    let mut position = None;
+    for segment in segments {
+        match *segment {
+            Segment::Circle { center, radius } => {
+                let _ = writeln!(
+                    svg,
+                    r#"  <circle cx="{}" cy="{}" r="{}" {style}/>"#,
+                    center[0], -center[1], radius
+                );
+            }
+            Segment::Line { from, to } | Segment::Arc { from, to, .. } => {
+                if position != Some(from) {
+                    let _ = write!(path, "M {} {} ", from[0], -from[1]);
+                }
+                if let Segment::Arc { center, ccw, .. } = *segment {
+                    // With y negated, counterclockwise in the sketch is a sweep flag of 0.
+                    let radius = distance(from, center);
+                    let (_, sweep) = arc_angles(from, to, center, ccw);
+                    // A single arc command can't go all the way around, so go halfway first.
+                    if sweep >= TAU - 1e-9 {
+                        let opposite = [2.0 * center[0] - from[0], 2.0 * center[1] - from[1]];
+                        let _ = write!(
+                            path,
+                            "A {r} {r} 0 0 {} {} {} ",
+                            !ccw as u8,
+                            opposite[0],
+                            -opposite[1],
+                            r = radius
+                        );
+                    }
+                    let large = sweep > TAU / 2.0 && sweep < TAU - 1e-9;
+                    let _ = write!(
+                        path,
+                        "A {r} {r} 0 {} {} {} {} ",
+                        large as u8,
+                        !ccw as u8,
+                        to[0],
+                        -to[1],
+                        r = radius
+                    );
+                } else {
+                    let _ = write!(path, "L {} {} ", to[0], -to[1]);
+                }
+                position = Some(to);
+            }
+        }
+    }
+    if !path.is_empty() {
+        let _ = writeln!(svg, r#"  <path d="{}" {style}/>"#, path.trim_end());
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// The code of the units in the `$INSUNITS` header variable of a DXF.
+fn dxf_units(units: UnitLength) -> u8 {
+    match units {
+        UnitLength::In => 1,
+        UnitLength::Ft => 2,
+        UnitLength::Mm => 4,
+        UnitLength::Cm => 5,
+        UnitLength::M => 6,
+        UnitLength::Yd => 10,
+    }
+}
+
+/// Draw the segments as the entities of an ASCII DXF, in the units of the program.
+fn to_dxf(segments: &[Segment], units: UnitLength) -> String {
+    let metric = matches!(units, UnitLength::Mm | UnitLength::Cm | UnitLength::M);
+    let mut dxf = String::new();
+    let mut pair = |code: u16, value: String| {
+        let _ = write!(dxf, "{}\n{}\n", code, value);
+    };
+    for (code, value) in [
+        (0, "SECTION"),
+        (2, "HEADER"),
+        (9, "$ACADVER"),
+        (1, "AC1009"),
+        (9, "$INSUNITS"),
+    ] {
+        pair(code, value.to_string());
+    }
+    pair(70, dxf_units(units).to_string());
+    pair(9, "$MEASUREMENT".to_string());
+    pair(70, (metric as u8).to_string());
+    for (code, value) in [(0, "ENDSEC"), (0, "SECTION"), (2, "ENTITIES")] {
+        pair(code, value.to_string());
+    }
+    for segment in segments {
+        match *segment {
+            Segment::Line { from, to } => {
+                pair(0, "LINE".to_string());
+                pair(8, "0".to_string());
+                pair(10, from[0].to_string());
+                pair(20, from[1].to_string());
+                pair(11, to[0].to_string());
+                pair(21, to[1].to_string());
+            }
+            Segment::Circle { center, radius } => {
+                pair(0, "CIRCLE".to_string());
+                pair(8, "0".to_string());
+                pair(10, center[0].to_string());
+                pair(20, center[1].to_string());
+                pair(40, radius.to_string());
+            }
+            Segment::Arc { from, to, center, ccw } => {
+                let (start, sweep) = arc_angles(from, to, center, ccw);
+                let full = sweep >= TAU - 1e-9;
+                pair(0, if full { "CIRCLE" } else { "ARC" }.to_string());
+                pair(8, "0".to_string());
+                pair(10, center[0].to_string());
+                pair(20, center[1].to_string());
+                pair(40, distance(from, center).to_string());
+                // Arcs in a DXF always go counterclockwise.
+                if !full {
+                    pair(50, start.to_degrees().to_string());
+                    pair(51, (start + sweep).to_degrees().to_string());
+                }
+            }
+        }
+    }
+    for (code, value) in [(0, "ENDSEC"), (0, "EOF")] {
+        pair(code, value.to_string());
+    }
+
+    dxf
+}
+
+/// Execute the kcl code and export each of its sketches as a 2D drawing, in the units of the program.
+///
+/// Only sketches bound to top level variables and not extruded are exported, one file per sketch, named after its
+/// variable. Each drawing is in the sketch's own coordinates, at real size. Raises `ValueError` if there are no
+/// such sketches.
+#[pyfunction]
+#[pyo3(signature = (code, units, export_format, warnings_as_errors = false, files = None, on_progress = None, attachments = None))]
+pub(crate) async fn execute_and_export_sketch(
+    code: Code,
+    units: Option<Units>,
+    export_format: SketchExportFormat,
+    warnings_as_errors: bool,
+    files: Option<HashMap<String, String>>,
+    on_progress: Option<PyObject>,
+    attachments: Option<HashMap<String, Vec<u8>>>,
+) -> PyResult<Vec<ExportFile>> {
+    reject_module_files(files, attachments)?;
+    connection::require_token(None)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        warnings_as_errors,
+        progress: progress::Progress::new(on_progress),
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options) = (&code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            options.progress.report(progress::ProgressPhase::Exporting, None)?;
+            let sketches = find_sketches(&executed.exec_state)?;
+            if sketches.iter().all(|sketch| sketch.segments.is_empty()) {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "The program has no sketches to export to {}, only sketches that are bound to a top level variable \
+                     and haven't been extruded can be",
+                    export_format.extension()
+                )));
+            }
+
+            Ok(sketches
+                .into_iter()
+                .filter(|sketch| !sketch.segments.is_empty())
+                .map(|sketch| {
+                    let contents = match export_format {
+                        SketchExportFormat::Svg => to_svg(&sketch.segments, executed.units),
+                        SketchExportFormat::Dxf => to_dxf(&sketch.segments, executed.units),
+                    };
+                    ExportFile {
+                        contents: contents.into_bytes(),
+                        name: format!("{}.{}", sketch.name, export_format.extension()),
+                        units: Some(executed.units),
+                        format: Some(export_format.extension().to_string()),
+                    }
+                })
+                .collect())
+        })
+        .await
+    })
+    .await
+}
@@ -0,0 +1,255 @@
+//! Snapshots along with what is known about how they were taken.
+
+use std::collections::HashMap;
+
+use pyo3::{pyclass, pymethods, types::PyAny, types::PyBytes, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    pickle, progress::Progress, stats::RunStats, Appearance, CameraPose, Color, ImageFormat, RenderMode,
+    RenderSettings, UnitLength, Units,
+};
+
+/// How to execute kcl code and snapshot it, for `execute_and_snapshot_ex`.
+///
+/// The options are the same as the arguments of `execute_and_snapshot`, and mean the same.
+#[derive(Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct SnapshotOptions {
+    /// The units to execute the code in, or the default units if not set.
+    #[pyo3(get)]
+    pub units: Option<UnitLength>,
+    /// The format to encode the image in, or the default format if not set.
+    #[pyo3(get)]
+    pub image_format: Option<ImageFormat>,
+    /// How to render the scene, or the defaults if not set.
+    #[pyo3(get)]
+    pub render_settings: Option<RenderSettings>,
+    /// The color behind the model, or the engine's default if not set.
+    pub background_color: Option<Color>,
+    /// Whether the background is transparent, if no `background_color` is given.
+    #[pyo3(get)]
+    pub transparent: bool,
+    /// The appearance to give objects that don't set their own.
+    #[pyo3(get)]
+    pub appearance: Option<Appearance>,
+    /// Raise a `KclWarningError` if there were any non-fatal diagnostics.
+    #[pyo3(get)]
+    pub warnings_as_errors: bool,
+    /// In-memory kcl modules, by path, that the code can import.
+    #[pyo3(get)]
+    pub files: Option<HashMap<String, String>>,
+    /// Where to report progress to.
+    pub(crate) progress: Progress,
+    /// The top level variables whose objects alone are shown, or everything if not set.
+    #[pyo3(get)]
+    pub entity_names: Option<Vec<String>>,
+    /// Whether edges are drawn, or the engine's default if not set.
+    #[pyo3(get)]
+    pub render_mode: Option<RenderMode>,
+    /// Where to take the snapshot from, instead of zooming to fit.
+    #[pyo3(get)]
+    pub camera: Option<CameraPose>,
+    /// Let the camera settle before the snapshot, so the same program gives the same image.
+    #[pyo3(get)]
+    pub deterministic: bool,
+    /// In-memory files, by path, that the code can import, like STEP files.
+    pub attachments: Option<HashMap<String, Vec<u8>>>,
+    /// The top level variables for the camera to fit, with everything else still shown.
+    #[pyo3(get)]
+    pub frame_objects: Option<Vec<String>>,
+    /// Snapshot the empty scene, rather than raising `KclEmptySceneError`, if the code creates no objects.
+    #[pyo3(get)]
+    pub allow_empty: bool,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        SnapshotOptions {
+            units: None,
+            image_format: None,
+            render_settings: None,
+            background_color: None,
+            transparent: false,
+            appearance: None,
+            warnings_as_errors: false,
+            files: None,
+            progress: Progress::default(),
+            entity_names: None,
+            render_mode: None,
+            camera: None,
+            deterministic: true,
+            attachments: None,
+            frame_objects: None,
+            allow_empty: false,
+        }
+    }
+}
+
+#[pymethods]
+impl SnapshotOptions {
+    #[new]
+    #[pyo3(signature = (
+        units = None,
+        image_format = None,
+        render_settings = None,
+        background_color = None,
+        transparent = false,
+        appearance = None,
+        warnings_as_errors = false,
+        files = None,
+        on_progress = None,
+        entity_names = None,
+        render_mode = None,
+        camera = None,
+        deterministic = true,
+        attachments = None,
+        frame_objects = None,
+        allow_empty = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        units: Option<Units>,
+        image_format: Option<ImageFormat>,
+        render_settings: Option<RenderSettings>,
+        background_color: Option<Color>,
+        transparent: bool,
+        appearance: Option<Appearance>,
+        warnings_as_errors: bool,
+        files: Option<HashMap<String, String>>,
+        on_progress: Option<PyObject>,
+        entity_names: Option<Vec<String>>,
+        render_mode: Option<RenderMode>,
+        camera: Option<CameraPose>,
+        deterministic: bool,
+        attachments: Option<HashMap<String, Vec<u8>>>,
+        frame_objects: Option<Vec<String>>,
+        allow_empty: bool,
+    ) -> PyResult<Self> {
+        Ok(SnapshotOptions {
+            units: units.map(UnitLength::try_from).transpose()?,
+            image_format,
+            render_settings,
+            background_color,
+            transparent,
+            appearance,
+            warnings_as_errors,
+            files,
+            progress: Progress::new(on_progress),
+            entity_names,
+            render_mode,
+            camera,
+            deterministic,
+            attachments,
+            frame_objects,
+            allow_empty,
+        })
+    }
+}
+
+/// A snapshot of a model, and how it was taken.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct SnapshotResult {
+    /// The encoded image.
+    pub data: Vec<u8>,
+    /// The format the image is encoded in.
+    #[pyo3(get)]
+    pub format: ImageFormat,
+    /// The width of the image in pixels.
+    #[pyo3(get)]
+    pub width: u32,
+    /// The height of the image in pixels.
+    #[pyo3(get)]
+    pub height: u32,
+    /// Where the camera was when the snapshot was taken.
+    #[pyo3(get)]
+    pub camera: CameraPose,
+    /// How long parsing and executing the program took, including connecting to the engine, in milliseconds.
+    #[pyo3(get)]
+    pub execution_ms: f64,
+    /// How long each phase took and how many commands were sent, including those for the snapshot.
+    #[pyo3(get)]
+    pub stats: RunStats,
+}
+
+impl SnapshotResult {
+    /// Describe an image the engine returned, reading its size from its header.
+    pub(crate) fn new(
+        data: Vec<u8>,
+        format: ImageFormat,
+        camera: CameraPose,
+        execution_ms: f64,
+        stats: RunStats,
+    ) -> PyResult<Self> {
+        let (width, height) = image_dimensions(&data).ok_or_else(|| {
+            pyo3::exceptions::PyException::new_err(format!(
+                "Couldn't read the size of the {} snapshot returned by the engine",
+                format
+            ))
+        })?;
+
+        Ok(SnapshotResult {
+            data,
+            format,
+            width,
+            height,
+            camera,
+            execution_ms,
+            stats,
+        })
+    }
+}
+
+#[pymethods]
+impl SnapshotResult {
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.data)
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+fn u16_be(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?).into())
+}
+
+/// The width and height of a PNG or JPEG image, read from its header.
+pub(crate) fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // The IHDR chunk always comes first.
+        let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+        let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+        return Some((width, height));
+    }
+
+    if data.starts_with(b"\xff\xd8") {
+        // Walk the segments until the start of frame, which holds the size.
+        let mut at = 2;
+        while at + 4 <= data.len() {
+            if data[at] != 0xff {
+                return None;
+            }
+            let marker = data[at + 1];
+            let length = u16_be(data, at + 2)? as usize;
+            if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+                return Some((u16_be(data, at + 7)?, u16_be(data, at + 5)?));
+            }
+            at += 2 + length;
+        }
+        return None;
+    }
+
+    None
+}
@@ -0,0 +1,165 @@
+//! Timings and counts for a call, cheap enough to collect on every one.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use indexmap::IndexMap;
+use kcl_lib::{
+    exec::{DefaultPlanes, IdGenerator},
+    EngineManager, ExecutionKind, ExecutorContext, KclError, SourceRange,
+};
+use kittycad_modeling_cmds::websocket::{ModelingSessionData, WebSocketRequest, WebSocketResponse};
+use pyo3::{pyclass, pymethods, types::PyAny, Bound, IntoPy, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::pickle;
+
+/// How long each phase of a call took, and how many commands it sent to the engine.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+#[pyclass(eq, module = "kcl")]
+pub struct RunStats {
+    /// How long parsing the program took, in milliseconds.
+    #[pyo3(get)]
+    pub parse_ms: f64,
+    /// How long connecting to the engine took, in milliseconds, or 0 if an existing connection was used.
+    #[pyo3(get)]
+    pub connect_ms: f64,
+    /// How long executing the program took, in milliseconds.
+    #[pyo3(get)]
+    pub execution_ms: f64,
+    /// How many modeling commands were sent to the engine, both while executing the program and for any snapshots and
+    /// exports after it. Commands sent in a batch are each counted.
+    #[pyo3(get)]
+    pub command_count: usize,
+}
+
+#[pymethods]
+impl RunStats {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// An engine connection that counts the modeling commands sent over it, so a call can report how many it sent, and
+/// notices when it drops.
+///
+/// Sending a command only fails when the connection is gone: kcl-lib gives the engine's answer back even when it is an
+/// error, and only turns that into an error afterwards. So any error here marks the call as disconnected.
+///
+/// Each call wraps the connection it uses in a fresh one, so the count covers that call alone: the commands kcl-lib
+/// sends while executing the program as well as the ones this library sends for snapshots and exports. Batched
+/// commands are counted one by one rather than as a single request.
+#[derive(Debug)]
+pub(crate) struct CountingEngine {
+    inner: Arc<Box<dyn EngineManager>>,
+    sent: Arc<AtomicUsize>,
+}
+
+impl CountingEngine {
+    /// Wrap the context's engine in one that counts the commands sent over it, and return the count.
+    pub(crate) fn install(ctx: &mut ExecutorContext) -> Arc<AtomicUsize> {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let engine = CountingEngine {
+            inner: ctx.engine.clone(),
+            sent: sent.clone(),
+        };
+        ctx.engine = Arc::new(Box::new(engine));
+        sent
+    }
+}
+
+#[async_trait::async_trait]
+impl EngineManager for CountingEngine {
+    fn batch(&self) -> Arc<std::sync::Mutex<Vec<(WebSocketRequest, SourceRange)>>> {
+        self.inner.batch()
+    }
+
+    fn batch_end(&self) -> Arc<std::sync::Mutex<IndexMap<uuid::Uuid, (WebSocketRequest, SourceRange)>>> {
+        self.inner.batch_end()
+    }
+
+    fn execution_kind(&self) -> ExecutionKind {
+        self.inner.execution_kind()
+    }
+
+    fn replace_execution_kind(&self, execution_kind: ExecutionKind) -> ExecutionKind {
+        self.inner.replace_execution_kind(execution_kind)
+    }
+
+    async fn default_planes(
+        &self,
+        id_generator: &mut IdGenerator,
+        source_range: SourceRange,
+    ) -> Result<DefaultPlanes, KclError> {
+        self.inner.default_planes(id_generator, source_range).await
+    }
+
+    async fn clear_scene_post_hook(
+        &self,
+        id_generator: &mut IdGenerator,
+        source_range: SourceRange,
+    ) -> Result<(), KclError> {
+        self.inner.clear_scene_post_hook(id_generator, source_range).await
+    }
+
+    async fn inner_send_modeling_cmd(
+        &self,
+        id: uuid::Uuid,
+        source_range: SourceRange,
+        cmd: WebSocketRequest,
+        id_to_source_range: HashMap<uuid::Uuid, SourceRange>,
+    ) -> Result<WebSocketResponse, KclError> {
+        let count = match &cmd {
+            WebSocketRequest::ModelingCmdBatchReq(batch) => batch.requests.len(),
+            WebSocketRequest::ModelingCmdReq(_) => 1,
+            _ => 0,
+        };
+        self.sent.fetch_add(count, Ordering::Relaxed);
+        self.inner
+            .inner_send_modeling_cmd(id, source_range, cmd, id_to_source_range)
+            .await
+            .inspect_err(|_| crate::mark_disconnected())
+    }
+
+    fn get_session_data(&self) -> Option<ModelingSessionData> {
+        self.inner.get_session_data()
+    }
+}
+
+/// What a call returns: just its result, or with `return_stats` a `(result, RunStats)` tuple.
+pub(crate) enum WithStats<T> {
+    Plain(T),
+    Stats(T, RunStats),
+}
+
+impl<T> WithStats<T> {
+    pub(crate) fn new(value: T, stats: RunStats, return_stats: bool) -> Self {
+        if return_stats {
+            WithStats::Stats(value, stats)
+        } else {
+            WithStats::Plain(value)
+        }
+    }
+}
+
+impl<T: IntoPy<PyObject>> IntoPy<PyObject> for WithStats<T> {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            WithStats::Plain(value) => value.into_py(py),
+            WithStats::Stats(value, stats) => (value, stats).into_py(py),
+        }
+    }
+}
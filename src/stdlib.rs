@@ -0,0 +1,132 @@
+//! Documentation for the kcl standard library.
+
+use std::sync::OnceLock;
+
+use pyo3::{pyclass, pyfunction, pymethods, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::{to_py_object, tokio};
+
+/// A parameter of a standard library function.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass]
+pub struct StdlibParameter {
+    /// The name of the parameter.
+    #[pyo3(get)]
+    pub name: String,
+    /// The kcl type of the parameter.
+    #[pyo3(get, name = "type")]
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// Whether the parameter must be given.
+    #[pyo3(get)]
+    pub required: bool,
+}
+
+/// A function in the kcl standard library.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass]
+pub struct StdlibFunction {
+    /// The name of the function.
+    #[pyo3(get)]
+    pub name: String,
+    /// A one line summary of what the function does.
+    #[pyo3(get)]
+    pub summary: String,
+    /// A longer description of the function.
+    #[pyo3(get)]
+    pub description: String,
+    /// The parameters of the function, in order.
+    #[pyo3(get)]
+    pub parameters: Vec<StdlibParameter>,
+    /// The kcl type the function returns, if it returns anything.
+    #[pyo3(get)]
+    pub return_type: Option<String>,
+    /// Tags grouping the function with related ones, e.g. `sketch`.
+    #[pyo3(get)]
+    pub tags: Vec<String>,
+    /// Whether the function is deprecated.
+    #[pyo3(get)]
+    pub deprecated: bool,
+}
+
+/// The documentation of a standard library function, as kcl-lib serializes it.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FunctionData {
+    name: String,
+    summary: String,
+    description: String,
+    tags: Vec<String>,
+    args: Vec<StdlibParameter>,
+    return_value: Option<ReturnValue>,
+    unpublished: bool,
+    deprecated: bool,
+}
+
+/// What a standard library function returns, as kcl-lib serializes it.
+#[derive(Deserialize)]
+struct ReturnValue {
+    #[serde(rename = "type")]
+    type_: String,
+}
+
+impl From<FunctionData> for StdlibFunction {
+    fn from(data: FunctionData) -> Self {
+        StdlibFunction {
+            name: data.name,
+            summary: data.summary,
+            description: data.description,
+            parameters: data.args,
+            return_type: data.return_value.map(|value| value.type_),
+            tags: data.tags,
+            deprecated: data.deprecated,
+        }
+    }
+}
+
+#[pymethods]
+impl StdlibFunction {
+    /// The function as a dict, ready to be serialized to JSON.
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_py_object(py, self)
+    }
+}
+
+/// Every published function in the standard library, sorted by name.
+pub(crate) fn functions() -> PyResult<Vec<StdlibFunction>> {
+    static FUNCTIONS: OnceLock<Vec<StdlibFunction>> = OnceLock::new();
+    if let Some(functions) = FUNCTIONS.get() {
+        return Ok(functions.clone());
+    }
+
+    // kcl-lib only makes its standard library public as part of an executor, which a mock one is enough for.
+    let ctx = tokio().block_on(kcl_lib::ExecutorContext::new_mock());
+    let mut functions = Vec::new();
+    for function in ctx.stdlib.fns.values() {
+        let data: FunctionData = serde_json::to_value(function)
+            .and_then(serde_json::from_value)
+            .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+        if !data.unpublished {
+            functions.push(StdlibFunction::from(data));
+        }
+    }
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(FUNCTIONS.get_or_init(|| functions).clone())
+}
+
+/// Get the documentation for every function in the kcl standard library.
+#[pyfunction]
+pub(crate) fn stdlib_docs() -> PyResult<Vec<StdlibFunction>> {
+    functions()
+}
+
+/// Get the documentation for a single function in the kcl standard library.
+#[pyfunction]
+pub(crate) fn stdlib_function(name: &str) -> PyResult<StdlibFunction> {
+    functions()?
+        .into_iter()
+        .find(|function| function.name == name)
+        .ok_or_else(|| pyo3::exceptions::PyKeyError::new_err(name.to_string()))
+}
@@ -0,0 +1,131 @@
+//! Snapshots of a program as it is built up, one top level statement at a time.
+
+use pyo3::{pyfunction, PyObject, PyResult};
+
+use crate::{
+    connection, defaults, find_objects, get_background_color, get_camera, kcl_error, program::Code, progress, run_code,
+    set_background_color, snapshot_from, spawn, with_reconnect, zoom_to_fit, CameraPose, Color, ImageFormat,
+    RenderSettings, RunOptions, Units,
+};
+
+/// The top level statements that model something, as their index in the program and their source range.
+///
+/// A declaration counts if it is bound to geometry once the whole program has run, and a statement that isn't bound
+/// to anything, like a bare `extrude`, always counts. Imports and declarations of plain values don't.
+fn modeling_steps(
+    program: &kcl_lib::Program,
+    exec_state: &kcl_lib::ExecState,
+) -> PyResult<Vec<(usize, (usize, usize))>> {
+    let ast =
+        serde_json::to_value(&program.ast).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+    let objects = find_objects(exec_state)?;
+
+    let mut steps = Vec::new();
+    for (index, item) in ast["body"].as_array().into_iter().flatten().enumerate() {
+        let is_step = match item["type"].as_str() {
+            Some("ExpressionStatement" | "ReturnStatement") => true,
+            Some("VariableDeclaration") => {
+                // Both the singular and plural forms have been used for declarations.
+                let declarations = item["declarations"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_else(|| vec![item["declaration"].clone()]);
+                declarations.iter().any(|declaration| {
+                    let name = declaration["id"]["name"].as_str();
+                    objects.iter().any(|object| object.name.as_deref() == name)
+                })
+            }
+            _ => false,
+        };
+        if is_step {
+            let offset = |key: &str| item[key].as_u64().unwrap_or_default() as usize;
+            steps.push((index, (offset("start"), offset("end"))));
+        }
+    }
+
+    Ok(steps)
+}
+
+/// Execute the kcl code and snapshot it after each top level statement that models something, like a storyboard.
+///
+/// Returns a `(source_range, image)` pair for each of those statements, in order, where the source range is a
+/// `(start, end)` pair of byte offsets. Every image is taken from where zoom to fit puts the camera for the whole
+/// program, so the sequence doesn't jump around. The program is run again up to each statement on the same engine
+/// session, reusing what was already executed.
+#[pyfunction]
+#[pyo3(signature = (
+    code,
+    units = None,
+    image_format = None,
+    render_settings = None,
+    background_color = None,
+    transparent = false,
+    warnings_as_errors = false,
+    on_progress = None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn execute_and_snapshot_steps(
+    code: Code,
+    units: Option<Units>,
+    image_format: Option<ImageFormat>,
+    render_settings: Option<RenderSettings>,
+    background_color: Option<Color>,
+    transparent: bool,
+    warnings_as_errors: bool,
+    on_progress: Option<PyObject>,
+) -> PyResult<Vec<((usize, usize), Vec<u8>)>> {
+    connection::require_token(None)?;
+    let image_format = defaults::image_format(image_format)?;
+    let background_color = get_background_color(image_format, background_color, transparent)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        render_settings: render_settings.unwrap_or_default(),
+        warnings_as_errors,
+        progress: progress::Progress::new(on_progress),
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options) = (&code, &options);
+        with_reconnect(|| async move {
+            // The whole program is run first, for the camera framing and to know which statements to stop after.
+            let executed = run_code(code, options).await?;
+            let steps = modeling_steps(&executed.program, &executed.exec_state)?;
+            let ctx = &executed.ctx;
+            if let Some(color) = background_color {
+                set_background_color(ctx, color).await?;
+            }
+            zoom_to_fit(ctx).await?;
+            let camera = CameraPose::from(get_camera(ctx).await?);
+
+            let mut images = Vec::with_capacity(steps.len());
+            let mut last: Option<(kcl_lib::Program, kcl_lib::ExecState)> = None;
+            for (step, (index, source_range)) in steps.iter().enumerate() {
+                options
+                    .progress
+                    .report_operation(progress::ProgressPhase::Snapshotting, step, steps.len())?;
+                let mut program = executed.program.clone();
+                program.ast.body.truncate(index + 1);
+                // Each prefix starts the same as the last one, so only the new statements are executed.
+                let cache_info = kcl_lib::CacheInformation {
+                    old: last.take().map(|(last_program, exec_state)| kcl_lib::OldAstState {
+                        ast: last_program.ast,
+                        exec_state,
+                        settings: ctx.settings.clone(),
+                    }),
+                    new_ast: program.ast.clone(),
+                };
+                let mut exec_state = kcl_lib::ExecState::default();
+                ctx.run(cache_info, &mut exec_state).await.map_err(kcl_error)?;
+                last = Some((program, exec_state));
+
+                let image =
+                    snapshot_from(ctx, &camera, image_format, None, options.render_settings.settle(true)).await?;
+                images.push((*source_range, image));
+            }
+
+            Ok(images)
+        })
+        .await
+    })
+    .await
+}
@@ -0,0 +1,644 @@
+//! Type stubs for the module, so type checkers know what it has.
+//!
+//! The names come from the module itself: its classes and functions, their parameters and defaults, and the members
+//! and attributes of its classes. Python can't see the Rust types of parameters, attributes or return values, nor
+//! which functions return a coroutine, so those come from the tables here, and generating the stubs fails for
+//! anything they miss.
+
+use pyo3::{
+    exceptions::{PyBaseException, PyRuntimeError},
+    prelude::{PyAnyMethods, PyDictMethods, PyTypeMethods},
+    pyfunction,
+    types::{PyAny, PyDict, PyType},
+    Bound, PyResult, Python,
+};
+
+/// Python keywords, which pyo3 allows as parameter names since they aren't Rust keywords.
+const KEYWORDS: &[&str] = &[
+    "class", "def", "from", "global", "import", "in", "is", "lambda", "nonlocal", "not", "or", "and", "pass", "raise",
+    "with", "yield", "del", "elif", "except", "finally", "try", "assert", "None", "True", "False",
+];
+
+/// Parameters that take the same type wherever they appear, unless [`TYPES`] says otherwise.
+const PARAMETERS: &[(&str, &str)] = &[
+    ("allow_empty", "bool"),
+    ("appearance", "Appearance"),
+    ("attachments", "dict[str, bytes]"),
+    ("background_color", "Union[str, tuple[float, float, float, float]]"),
+    ("camera", "CameraPose"),
+    ("client_config", "ClientConfig"),
+    ("code", "Union[str, Program]"),
+    ("column", "int"),
+    ("config", "ClientConfig"),
+    ("context_lines", "int"),
+    ("deterministic", "bool"),
+    ("entity_names", "list[str]"),
+    ("entry", "str"),
+    ("export_format", "FileExportFormat"),
+    ("files", "dict[str, str]"),
+    (
+        "format_options",
+        "Union[FbxOptions, StlOptions, PlyOptions, GltfOptions]",
+    ),
+    ("frame_objects", "list[str]"),
+    ("image_format", "ImageFormat"),
+    ("index", "int"),
+    ("items", "list[Union[str, Program, tuple[str]]]"),
+    ("line", "int"),
+    ("max_concurrency", "int"),
+    ("min_severity", "Union[LintSeverity, str]"),
+    ("name", "str"),
+    ("on_progress", "Callable[[ProgressEvent], Any]"),
+    ("options", "FormatOptions"),
+    ("path", "Union[str, PathLike[str]]"),
+    ("paths", "list[str]"),
+    ("render_mode", "RenderMode"),
+    ("render_settings", "RenderSettings"),
+    ("return_stats", "bool"),
+    ("rules", "list[str]"),
+    ("selection", "ExportSelection"),
+    ("source_range", "tuple[int, int]"),
+    ("timeout_seconds", "float"),
+    ("transparent", "bool"),
+    ("units", "Union[UnitLength, str]"),
+    ("warnings_as_errors", "bool"),
+];
+
+/// The types of everything else: `function` is a function's return type and `function.param` one of its
+/// parameters, `Class.attribute` an attribute, and `Class.method` and `Class.method.param` the same for a method.
+/// A constructor parameter without an entry here or in [`PARAMETERS`] has the type of the attribute it sets.
+const TYPES: &[(&str, &str)] = &[
+    // Functions.
+    ("check", "CheckReport"),
+    ("check.code", "str"),
+    ("check.format_options", "FormatOptions"),
+    ("check_connection", "ConnectionStatus"),
+    ("completions", "list[CompletionItem]"),
+    ("completions.code", "str"),
+    ("disable_logging", "None"),
+    ("enable_logging", "None"),
+    ("enable_logging.level", "str"),
+    ("engine_version", "dict[str, str]"),
+    ("execute", "Union[list[Diagnostic], tuple[list[Diagnostic], RunStats]]"),
+    ("execute_and_check_geometry", "GeometryReport"),
+    (
+        "execute_and_export",
+        "Union[list[ExportFile], tuple[list[ExportFile], RunStats]]",
+    ),
+    ("execute_and_export.base_name", "str"),
+    ("execute_and_export.validate", "bool"),
+    ("execute_and_export_sketch", "list[ExportFile]"),
+    ("execute_and_export_sketch.export_format", "SketchExportFormat"),
+    ("execute_and_export_sketch.units", "Optional[Union[UnitLength, str]]"),
+    ("execute_and_get_artifact_graph", "dict[str, Any]"),
+    ("execute_and_get_mesh_stats", "list[MeshStats]"),
+    ("execute_and_get_scene", "Scene"),
+    ("execute_and_get_tags", "dict[str, Tag]"),
+    ("execute_and_get_variables", "Variables"),
+    ("execute_and_get_variables.names", "list[str]"),
+    ("execute_and_snapshot", "Union[bytes, tuple[bytes, RunStats]]"),
+    ("execute_and_snapshot_and_export", "tuple[bytes, list[ExportFile]]"),
+    ("execute_and_snapshot_batch", "list[Union[bytes, BatchError]]"),
+    ("execute_and_snapshot_ex", "SnapshotResult"),
+    ("execute_and_snapshot_ex.options", "SnapshotOptions"),
+    ("execute_and_snapshot_iter", "BatchIterator"),
+    ("execute_and_snapshot_steps", "list[tuple[tuple[int, int], bytes]]"),
+    ("execute_and_snapshot_turntable", "list[bytes]"),
+    ("execute_and_snapshot_turntable.frames", "int"),
+    ("execute_project", "list[Diagnostic]"),
+    ("execute_project_and_export", "list[ExportFile]"),
+    ("execute_project_and_snapshot", "bytes"),
+    ("execute_with_report", "ExecutionReport"),
+    ("format", "str"),
+    ("format_check_paths", "PathsReport"),
+    ("format_dir", "FormatDirResult"),
+    ("format_dir.check", "bool"),
+    ("get_defaults", "Defaults"),
+    ("get_retry_settings", "RetrySettings"),
+    ("hover", "Optional[Hover]"),
+    ("hover.code", "str"),
+    ("lint", "list[LintFinding]"),
+    ("lint_and_fix", "tuple[str, list[LintFinding], list[AppliedFix]]"),
+    ("lint_and_fix.code", "str"),
+    ("lint_paths", "PathsReport"),
+    ("lint_sarif", "str"),
+    ("lint_sarif.paths_or_code", "Union[list[str], str, Program]"),
+    ("lint_strict", "list[LintFinding]"),
+    ("lint_strict.fail_on", "Union[LintSeverity, str]"),
+    ("parse", "dict[str, Any]"),
+    ("parse.code", "str"),
+    ("parse_to_json", "str"),
+    ("parse_to_json.code", "str"),
+    ("program_digest", "str"),
+    ("recast", "str"),
+    ("recast.ast", "dict[str, Any]"),
+    ("reset_defaults", "None"),
+    ("semantic_tokens", "list[tuple[int, int, str, list[str]]]"),
+    ("semantic_tokens.code", "str"),
+    ("set_defaults", "None"),
+    ("set_retry_settings", "None"),
+    ("set_retry_settings.settings", "RetrySettings"),
+    ("signature_help", "Optional[SignatureHelp]"),
+    ("signature_help.code", "str"),
+    ("snippet", "Snippet"),
+    ("snippet.code", "str"),
+    ("stdlib_docs", "list[StdlibFunction]"),
+    ("stdlib_function", "StdlibFunction"),
+    ("tokenize", "list[Token]"),
+    ("tokenize.code", "str"),
+    ("validate", "list[Diagnostic]"),
+    ("validate.code", "str"),
+    ("validate_export", "list[str]"),
+    ("validate_export.file", "ExportFile"),
+    ("version_info", "dict[str, str]"),
+    // Classes.
+    ("AppliedFix.end", "int"),
+    ("AppliedFix.replacement", "str"),
+    ("AppliedFix.rule", "str"),
+    ("AppliedFix.start", "int"),
+    ("Appearance.color", "str"),
+    ("Appearance.metalness", "float"),
+    ("Appearance.roughness", "float"),
+    ("BatchError.index", "int"),
+    ("BatchError.kind", "str"),
+    ("BatchError.message", "str"),
+    ("BatchIterator.__aiter__", "BatchIterator"),
+    (
+        "BatchIterator.__anext__",
+        "Awaitable[tuple[int, Union[bytes, BatchError]]]",
+    ),
+    ("BatchIterator.next", "tuple[int, Union[bytes, BatchError]]"),
+    ("CameraPose.center", "tuple[float, float, float]"),
+    ("CameraPose.eye", "tuple[float, float, float]"),
+    ("CameraPose.fov_degrees", "Optional[float]"),
+    ("CameraPose.up", "tuple[float, float, float]"),
+    ("CheckReport.format_diff", "str"),
+    ("CheckReport.lint_findings", "list[LintFinding]"),
+    ("CheckReport.ok", "bool"),
+    ("CheckReport.parse_errors", "list[Diagnostic]"),
+    ("ClientConfig.api_token", "Optional[str]"),
+    ("ClientConfig.headers", "Optional[dict[str, str]]"),
+    ("ClientConfig.host", "Optional[str]"),
+    ("ClientConfig.token_provider", "Optional[Callable[[], str]]"),
+    ("ClientConfig.user_agent", "Optional[str]"),
+    ("CompletionItem.detail", "Optional[str]"),
+    ("CompletionItem.end", "int"),
+    ("CompletionItem.kind", "str"),
+    ("CompletionItem.label", "str"),
+    ("CompletionItem.start", "int"),
+    ("ConnectionStatus.latency_ms", "float"),
+    ("ConnectionStatus.ok", "bool"),
+    ("ConnectionStatus.org", "Optional[str]"),
+    ("ConnectionStatus.user", "Optional[str]"),
+    ("Defaults.client_config", "Optional[ClientConfig]"),
+    ("Defaults.export_format", "Optional[FileExportFormat]"),
+    ("Defaults.image_format", "Optional[ImageFormat]"),
+    ("Defaults.timeout_seconds", "Optional[float]"),
+    ("Defaults.units", "Optional[UnitLength]"),
+    ("Diagnostic.end", "int"),
+    ("Diagnostic.message", "str"),
+    ("Diagnostic.severity", "str"),
+    ("Diagnostic.snippet", "Snippet"),
+    ("Diagnostic.snippet.code", "str"),
+    ("Diagnostic.start", "int"),
+    ("Discovered.description", "str"),
+    ("Discovered.finding", "Finding"),
+    ("Discovered.overridden", "bool"),
+    ("Discovered.pos", "tuple[int, int]"),
+    ("ExecutionReport.entries", "list[ReportEntry]"),
+    ("ExecutionReport.files", "list[ExportFile]"),
+    ("ExecutionReport.image", "Optional[bytes]"),
+    ("ExecutionReport.to_dict", "dict[str, Any]"),
+    ("ExecutionReport.total_ms", "float"),
+    ("ExportFile.__init__.contents", "bytes"),
+    ("ExportFile.__init__.format", "str"),
+    ("ExportFile.contents", "memoryview"),
+    ("ExportFile.format", "Optional[str]"),
+    ("ExportFile.name", "str"),
+    ("ExportFile.save", "None"),
+    ("ExportFile.scale_to", "ExportFile"),
+    ("ExportFile.units", "Optional[UnitLength]"),
+    ("ExportSelection.default_scene", "ExportSelection"),
+    ("ExportSelection.index", "Optional[int]"),
+    ("ExportSelection.kind", "ExportSelectionKind"),
+    ("ExportSelection.mesh_by_index", "ExportSelection"),
+    ("ExportSelection.mesh_by_name", "ExportSelection"),
+    ("ExportSelection.name", "Optional[str]"),
+    ("ExportSelection.objects", "ExportSelection"),
+    ("ExportSelection.scene_by_index", "ExportSelection"),
+    ("ExportSelection.scene_by_name", "ExportSelection"),
+    ("FbxOptions.storage", "str"),
+    ("FileReport.error", "Optional[Diagnostic]"),
+    ("FileReport.findings", "list[LintFinding]"),
+    ("FileReport.formatted", "Optional[bool]"),
+    ("FileReport.ok", "bool"),
+    ("FileReport.path", "str"),
+    ("Finding.code", "str"),
+    ("Finding.description", "str"),
+    ("Finding.experimental", "bool"),
+    ("Finding.title", "str"),
+    ("FormatDirResult.changed", "list[str]"),
+    ("FormatDirResult.errors", "dict[str, Diagnostic]"),
+    ("FormatDirResult.ok", "bool"),
+    ("FormatOptions.insert_final_newline", "bool"),
+    ("FormatOptions.tab_size", "int"),
+    ("FormatOptions.use_tabs", "bool"),
+    ("GeometryCheck.details", "list[str]"),
+    ("GeometryCheck.name", "str"),
+    ("GeometryCheck.reason", "Optional[str]"),
+    ("GeometryCheck.status", "str"),
+    ("GeometryReport.passed", "bool"),
+    ("GeometryReport.solids", "list[SolidReport]"),
+    ("GltfOptions.presentation", "Optional[str]"),
+    ("GltfOptions.storage", "Optional[str]"),
+    ("Hover.contents", "str"),
+    ("Hover.end", "int"),
+    ("Hover.start", "int"),
+    ("ImageFormat.from_str", "ImageFormat"),
+    ("LintFinding.description", "str"),
+    ("LintFinding.finding", "Finding"),
+    ("LintFinding.overridden", "bool"),
+    ("LintFinding.pos", "tuple[int, int]"),
+    ("LintFinding.severity", "LintSeverity"),
+    ("LintSeverity.from_str", "LintSeverity"),
+    ("MeshStats.byte_size", "int"),
+    ("MeshStats.name", "str"),
+    ("MeshStats.triangle_count", "int"),
+    ("MeshStats.vertex_count", "int"),
+    ("ObjectHandle.end", "int"),
+    ("ObjectHandle.id", "str"),
+    ("ObjectHandle.kind", "str"),
+    ("ObjectHandle.name", "Optional[str]"),
+    ("ObjectHandle.start", "int"),
+    ("PathsReport.__getitem__", "FileReport"),
+    ("PathsReport.__getitem__.key", "str"),
+    ("PathsReport.failed", "list[FileReport]"),
+    ("PathsReport.files", "list[FileReport]"),
+    ("PathsReport.ok", "bool"),
+    ("PlyOptions.selection", "Optional[ExportSelection]"),
+    ("PlyOptions.storage", "str"),
+    ("Program.digest", "str"),
+    ("Program.parse", "Program"),
+    ("Program.parse.code", "str"),
+    ("Program.recast", "str"),
+    ("Program.source", "str"),
+    ("ProgressEvent.message", "Optional[str]"),
+    ("ProgressEvent.operation_index", "Optional[int]"),
+    ("ProgressEvent.operation_total", "Optional[int]"),
+    ("ProgressEvent.phase", "ProgressPhase"),
+    ("RenderSettings.deterministic", "RenderSettings"),
+    ("RenderSettings.enable_ssao", "bool"),
+    ("RenderSettings.highlight_edges", "bool"),
+    ("RenderSettings.settle_frames", "int"),
+    ("RenderSettings.show_grid", "bool"),
+    ("ReportEntry.duration_ms", "float"),
+    ("ReportEntry.label", "str"),
+    ("ReportEntry.source_range", "Optional[tuple[int, int]]"),
+    ("RetrySettings.connect_attempts", "int"),
+    ("RetrySettings.disabled", "RetrySettings"),
+    ("RetrySettings.initial_delay", "float"),
+    ("RetrySettings.max_delay", "float"),
+    ("RetrySettings.rerun_on_disconnect", "bool"),
+    ("RunStats.command_count", "int"),
+    ("RunStats.connect_ms", "float"),
+    ("RunStats.execution_ms", "float"),
+    ("RunStats.parse_ms", "float"),
+    ("Scene.find", "Optional[SceneObject]"),
+    ("Scene.objects", "list[SceneObject]"),
+    ("SceneObject.children", "list[SceneObject]"),
+    ("SceneObject.id", "Optional[str]"),
+    ("SceneObject.kind", "str"),
+    ("SceneObject.name", "str"),
+    ("SceneObject.source_range", "Optional[tuple[int, int]]"),
+    ("Session.__init__.create_default_planes", "bool"),
+    ("Session.bust_cache", "None"),
+    ("Session.close", "None"),
+    ("Session.execute", "SessionOutcome"),
+    ("Session.export", "list[ExportFile]"),
+    ("Session.export.objects", "list[ObjectHandle]"),
+    ("Session.pool_size", "int"),
+    ("Session.reset", "None"),
+    ("Session.snapshot", "bytes"),
+    ("Session.snapshot.frame_objects", "list[ObjectHandle]"),
+    ("Session.use_cache", "bool"),
+    ("SessionOutcome.cache_hit", "bool"),
+    ("SessionOutcome.default_planes", "dict[str, str]"),
+    ("SessionOutcome.execution_ms", "float"),
+    ("SessionOutcome.objects", "list[ObjectHandle]"),
+    ("SessionOutcome.trace", "list[TraceEntry]"),
+    ("SessionOutcome.warnings", "list[Diagnostic]"),
+    ("SignatureHelp.active_parameter", "int"),
+    ("SignatureHelp.label", "str"),
+    ("SignatureHelp.parameters", "list[str]"),
+    ("Snippet.end_column", "int"),
+    ("Snippet.end_line", "int"),
+    ("Snippet.first_line", "int"),
+    ("Snippet.lines", "list[str]"),
+    ("Snippet.start_column", "int"),
+    ("Snippet.start_line", "int"),
+    ("Snippet.underline", "str"),
+    ("SnapshotOptions.allow_empty", "bool"),
+    ("SnapshotOptions.appearance", "Optional[Appearance]"),
+    ("SnapshotOptions.camera", "Optional[CameraPose]"),
+    ("SnapshotOptions.deterministic", "bool"),
+    ("SnapshotOptions.entity_names", "Optional[list[str]]"),
+    ("SnapshotOptions.files", "Optional[dict[str, str]]"),
+    ("SnapshotOptions.frame_objects", "Optional[list[str]]"),
+    ("SnapshotOptions.image_format", "Optional[ImageFormat]"),
+    ("SnapshotOptions.render_mode", "Optional[RenderMode]"),
+    ("SnapshotOptions.render_settings", "Optional[RenderSettings]"),
+    ("SnapshotOptions.transparent", "bool"),
+    ("SnapshotOptions.units", "Optional[UnitLength]"),
+    ("SnapshotOptions.warnings_as_errors", "bool"),
+    ("SnapshotResult.camera", "CameraPose"),
+    ("SnapshotResult.data", "bytes"),
+    ("SnapshotResult.execution_ms", "float"),
+    ("SnapshotResult.format", "ImageFormat"),
+    ("SnapshotResult.height", "int"),
+    ("SnapshotResult.stats", "RunStats"),
+    ("SnapshotResult.width", "int"),
+    ("SolidReport.check", "Optional[GeometryCheck]"),
+    ("SolidReport.checks", "list[GeometryCheck]"),
+    ("SolidReport.id", "str"),
+    ("SolidReport.name", "str"),
+    ("SolidReport.passed", "bool"),
+    ("SolidReport.source_range", "Optional[tuple[int, int]]"),
+    ("StdlibFunction.deprecated", "bool"),
+    ("StdlibFunction.description", "str"),
+    ("StdlibFunction.name", "str"),
+    ("StdlibFunction.parameters", "list[StdlibParameter]"),
+    ("StdlibFunction.return_type", "Optional[str]"),
+    ("StdlibFunction.summary", "str"),
+    ("StdlibFunction.tags", "list[str]"),
+    ("StdlibFunction.to_dict", "dict[str, Any]"),
+    ("StdlibParameter.name", "str"),
+    ("StdlibParameter.required", "bool"),
+    ("StdlibParameter.type", "str"),
+    ("StlOptions.selection", "Optional[ExportSelection]"),
+    ("StlOptions.storage", "str"),
+    ("Tag.area", "Optional[float]"),
+    ("Tag.center", "Optional[tuple[float, float, float]]"),
+    ("Tag.entity_id", "str"),
+    ("Tag.index", "int"),
+    ("Tag.kind", "str"),
+    ("Tag.length", "Optional[float]"),
+    ("Tag.name", "str"),
+    ("Tag.normal", "Optional[tuple[float, float, float]]"),
+    ("Tag.source_range", "tuple[int, int]"),
+    ("Token.end", "int"),
+    ("Token.is_error", "bool"),
+    ("Token.kind", "str"),
+    ("Token.start", "int"),
+    ("Token.value", "str"),
+    ("TraceEntry.name", "str"),
+    ("TraceEntry.source_range", "tuple[int, int]"),
+    ("TraceEntry.value", "str"),
+    ("UnitLength.from_str", "UnitLength"),
+    ("UnitLength.from_str.s", "str"),
+    ("Variable.name", "str"),
+    ("Variable.source_range", "tuple[int, int]"),
+    ("Variable.type_name", "str"),
+    // A variable's value is whatever KCL value it holds, converted to Python.
+    ("Variable.value", "Any"),
+    ("Variables.get", "Optional[Variable]"),
+    ("Variables.missing", "list[str]"),
+    ("Variables.variables", "list[Variable]"),
+];
+
+/// The functions and methods that are `async` in Rust, so return a coroutine.
+const COROUTINES: &[&str] = &[
+    "check_connection",
+    "engine_version",
+    "execute",
+    "execute_and_check_geometry",
+    "execute_and_export",
+    "execute_and_export_sketch",
+    "execute_and_get_artifact_graph",
+    "execute_and_get_mesh_stats",
+    "execute_and_get_scene",
+    "execute_and_get_tags",
+    "execute_and_get_variables",
+    "execute_and_snapshot",
+    "execute_and_snapshot_and_export",
+    "execute_and_snapshot_batch",
+    "execute_and_snapshot_ex",
+    "execute_and_snapshot_steps",
+    "execute_and_snapshot_turntable",
+    "execute_project",
+    "execute_project_and_export",
+    "execute_project_and_snapshot",
+    "execute_with_report",
+    "validate",
+    "BatchIterator.next",
+    "Session.close",
+    "Session.execute",
+    "Session.export",
+    "Session.reset",
+    "Session.snapshot",
+];
+
+/// How a function is called, which decides its first parameter and decorator.
+#[derive(PartialEq)]
+enum Kind {
+    Function,
+    Method,
+    Static,
+    Class,
+}
+
+/// Look up a type in one of the tables.
+fn lookup(table: &[(&str, &'static str)], key: &str) -> Option<&'static str> {
+    table.iter().find(|(name, _)| *name == key).map(|(_, ty)| *ty)
+}
+
+/// The type of `key` from [`TYPES`], or an error saying it's missing.
+fn require(key: &str) -> PyResult<&'static str> {
+    lookup(TYPES, key).ok_or_else(|| PyRuntimeError::new_err(format!("The stubs have no type for `{}`", key)))
+}
+
+/// The return type of a dunder method that is the same for every class, e.g. `bool` for `__eq__`.
+fn dunder_type(name: &str, class: &str) -> Option<String> {
+    match name {
+        "__init__" => Some("None".to_string()),
+        "__repr__" | "__str__" => Some("str".to_string()),
+        "__hash__" | "__int__" | "__len__" => Some("int".to_string()),
+        "__lt__" | "__le__" | "__eq__" | "__ne__" | "__gt__" | "__ge__" => Some("bool".to_string()),
+        "__reduce__" => Some("tuple[Any, ...]".to_string()),
+        "__copy__" | "__deepcopy__" => Some(class.to_string()),
+        _ => None,
+    }
+}
+
+/// The type of a parameter of the function at `path`, e.g. `execute` or `Session.export`.
+fn parameter_type(path: &str, param: &str, default: Option<&str>) -> PyResult<String> {
+    let (class, method) = match path.split_once('.') {
+        Some((class, method)) => (Some(class), method),
+        None => (None, path),
+    };
+    let ty = match (method, param) {
+        ("__lt__" | "__le__" | "__eq__" | "__ne__" | "__gt__" | "__ge__", _) => "object",
+        ("__deepcopy__", _) => "dict[int, Any]",
+        _ => match lookup(TYPES, &format!("{}.{}", path, param)).or_else(|| lookup(PARAMETERS, param)) {
+            Some(ty) => ty,
+            None if method == "__init__" => require(&format!("{}.{}", class.unwrap_or_default(), param))?,
+            None => require(&format!("{}.{}", path, param))?,
+        },
+    };
+    // Leaving out a parameter that defaults to `None` is the same as passing `None`.
+    if default == Some("None") && !ty.starts_with("Optional[") && ty != "Any" {
+        Ok(format!("Optional[{}]", ty))
+    } else {
+        Ok(ty.to_string())
+    }
+}
+
+/// The stub for the function at `path`, from its `__text_signature__`, e.g. `(code, units=None)`.
+fn function_stub(path: &str, text_signature: Option<String>, kind: Kind, indent: &str) -> PyResult<String> {
+    let (class, name) = match path.split_once('.') {
+        Some((class, name)) => (class, name),
+        None => ("", path),
+    };
+
+    let mut params = Vec::new();
+    match &text_signature {
+        Some(text) => {
+            let text = text.trim().trim_start_matches('(').trim_end_matches(')');
+            for param in text.split(',').map(str::trim).filter(|param| !param.is_empty()) {
+                let (param, default) = match param.split_once('=') {
+                    Some((param, default)) => (param, Some(default.trim())),
+                    None => (param, None),
+                };
+                let param = param.trim_start_matches('$');
+                if ["self", "cls", "/", "*"].contains(&param) || param.starts_with('*') {
+                    params.push(param.to_string());
+                    continue;
+                }
+                let ty = parameter_type(path, param, default)?;
+                // A keyword can't be passed by name anyway, so it is written as positional only.
+                let param = if KEYWORDS.contains(&param) {
+                    format!("__{}", param)
+                } else {
+                    param.to_string()
+                };
+                let default = if default.is_some() { " = ..." } else { "" };
+                params.push(format!("{}: {}{}", param, ty, default));
+            }
+        }
+        None => params.extend(["*args: Any".to_string(), "**kwargs: Any".to_string()]),
+    }
+    let first = match kind {
+        Kind::Method => Some("self"),
+        Kind::Class => Some("cls"),
+        Kind::Function | Kind::Static => None,
+    };
+    if let Some(first) = first {
+        if params.first().map(String::as_str) != Some(first) {
+            params.insert(0, first.to_string());
+        }
+    }
+
+    let returns = match dunder_type(name, class) {
+        Some(returns) => returns,
+        None => require(path)?.to_string(),
+    };
+    let decorator = match kind {
+        Kind::Static => format!("{}@staticmethod\n", indent),
+        Kind::Class => format!("{}@classmethod\n", indent),
+        Kind::Function | Kind::Method => String::new(),
+    };
+    let def = if COROUTINES.contains(&path) { "async def" } else { "def" };
+
+    Ok(format!(
+        "{}{}{} {}({}) -> {}: ...\n",
+        decorator,
+        indent,
+        def,
+        name,
+        params.join(", "),
+        returns
+    ))
+}
+
+/// The text signature of a function or method, if pyo3 gave it one.
+fn text_signature(value: &Bound<'_, PyAny>) -> Option<String> {
+    value
+        .getattr("__text_signature__")
+        .ok()
+        .and_then(|text| text.extract::<Option<String>>().ok().flatten())
+}
+
+/// The stub for a class: its enum members, attributes, constructor and methods.
+fn class_stub(name: &str, class: &Bound<'_, PyType>) -> PyResult<String> {
+    if class.is_subclass_of::<PyBaseException>()? {
+        let base = class.getattr("__base__")?.getattr("__name__")?.extract::<String>()?;
+        return Ok(format!("class {}({}): ...\n", name, base));
+    }
+
+    let (mut members, mut attributes, mut methods) = (Vec::new(), Vec::new(), Vec::new());
+    if let Some(text) = text_signature(class.as_any()) {
+        let path = format!("{}.__init__", name);
+        methods.push(function_stub(&path, Some(text), Kind::Method, "    ")?);
+    }
+    let dict = class
+        .getattr("__dict__")?
+        .call_method0("copy")?
+        .downcast_into::<PyDict>()?;
+    for (key, value) in dict.iter() {
+        let key: String = key.extract()?;
+        let path = format!("{}.{}", name, key);
+        let kind = value.get_type().name()?.to_string();
+        if value.is_instance(class)? {
+            members.push(format!("    {}: ClassVar[{}]\n", key, name));
+        } else if kind == "getset_descriptor" {
+            attributes.push(format!("    {}: {}\n", key, require(&path)?));
+        } else if key == "__new__" || key == "__init__" {
+            // The constructor comes from the class's own signature.
+        } else if kind == "staticmethod" {
+            let function = value.getattr("__func__")?;
+            methods.push(function_stub(&path, text_signature(&function), Kind::Static, "    ")?);
+        } else if kind == "classmethod_descriptor" || kind == "classmethod" {
+            methods.push(function_stub(&path, text_signature(&value), Kind::Class, "    ")?);
+        } else if value.is_callable() {
+            methods.push(function_stub(&path, text_signature(&value), Kind::Method, "    ")?);
+        }
+    }
+    // pyo3 doesn't keep attributes in the order they're declared, so they're sorted for stubs that don't change
+    // from one build to the next.
+    attributes.sort();
+    let mut body = [members, attributes, methods].concat().concat();
+    if body.is_empty() {
+        body.push_str("    ...\n");
+    }
+
+    Ok(format!("class {}:\n{}", name, body))
+}
+
+/// Write type stubs for the whole module, the contents of `kcl.pyi`.
+///
+/// The stubs follow whatever this build of the module has, so they are regenerated rather than edited, with
+/// `make stubs`. They cover what is in `__all__`, which leaves out this function and the pickling helper.
+#[pyfunction]
+pub(crate) fn generate_stubs(py: Python<'_>) -> PyResult<String> {
+    let module = py.import_bound("kcl")?;
+    let names: Vec<String> = module.getattr("__all__")?.extract()?;
+
+    let mut stubs = vec![concat!(
+        "# Generated by `kcl.generate_stubs()`, do not edit.\n\n",
+        "from os import PathLike\n",
+        "from typing import Any, Awaitable, Callable, ClassVar, Optional, Union\n",
+    )
+    .to_string()];
+    for name in &names {
+        let value = module.getattr(name.as_str())?;
+        let stub = if let Ok(class) = value.downcast::<PyType>() {
+            class_stub(name, class)?
+        } else if value.is_callable() {
+            function_stub(name, text_signature(&value), Kind::Function, "")?
+        } else {
+            format!("{}: {}\n", name, value.get_type().name()?)
+        };
+        stubs.push(stub);
+    }
+
+    Ok(stubs.join("\n"))
+}
@@ -0,0 +1,286 @@
+//! The faces and edges a program tagged, and what the engine can tell us about them, e.g. for fixturing.
+
+use std::collections::BTreeMap;
+
+use kcl_lib::ExecutorContext;
+use kittycad_modeling_cmds::{ok_response::OkModelingCmdResponse, websocket::OkWebSocketResponseData, ModelingCmd};
+use pyo3::{pyclass, pyfunction, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    connection, defaults, pickle, program::Code, run_code, send_modeling_cmd, spawn, trace, with_reconnect, RunOptions,
+    UnitLength, Units,
+};
+
+/// A face or edge a program tagged, e.g. with `$side`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct Tag {
+    /// The name of the tag, without the `$`.
+    #[pyo3(get)]
+    pub name: String,
+    /// Which of the tags with this name it is, in the order the program made them. Always 0 if the name is unique.
+    #[pyo3(get)]
+    pub index: usize,
+    /// The id of the face or edge in the engine.
+    #[pyo3(get)]
+    pub entity_id: String,
+    /// Whether the tag is on a `face` or an `edge`.
+    #[pyo3(get)]
+    pub kind: String,
+    /// The source range of the tag's declaration, as a `(start, end)` pair of byte offsets.
+    #[pyo3(get)]
+    pub source_range: (usize, usize),
+    /// The area of a face, in the square of the units the program was executed in.
+    #[pyo3(get)]
+    pub area: Option<f64>,
+    /// The center of mass of a face.
+    #[pyo3(get)]
+    pub center: Option<(f64, f64, f64)>,
+    /// The unit normal of a face, at the middle of its surface.
+    #[pyo3(get)]
+    pub normal: Option<(f64, f64, f64)>,
+    /// The length of a straight edge. Curved edges have no length.
+    #[pyo3(get)]
+    pub length: Option<f64>,
+}
+
+#[pymethods]
+impl Tag {
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// A tag found in memory, before asking the engine about it.
+struct Found {
+    name: String,
+    /// The id of the segment the tag was declared on, which stays the same when the segment is extruded into a face.
+    path_id: Option<String>,
+    entity_id: String,
+    is_face: bool,
+    source_range: (usize, usize),
+}
+
+impl Found {
+    /// A tag from a `TagIdentifier` as kcl-lib serializes it, if it has been put on anything.
+    fn new(tag: &Value) -> Option<Self> {
+        let info = tag.get("info").filter(|info| !info.is_null())?;
+        let face_id = info["surface"]["faceId"].as_str();
+        let range = &tag["__meta"][0]["sourceRange"];
+        let offset = |index: usize| range[index].as_u64().unwrap_or_default() as usize;
+
+        Some(Found {
+            name: tag["value"].as_str()?.to_string(),
+            path_id: info["path"]["__geoMeta"]["id"].as_str().map(str::to_string),
+            entity_id: face_id.or(info["id"].as_str())?.to_string(),
+            is_face: face_id.is_some(),
+            source_range: (offset(0), offset(1)),
+        })
+    }
+
+    /// Whether two tags are the same one, e.g. seen on a sketch and again on the solid extruded from it.
+    fn same_as(&self, other: &Found) -> bool {
+        self.name == other.name
+            && match (&self.path_id, &other.path_id) {
+                (Some(path), Some(other_path)) => path == other_path,
+                _ => self.entity_id == other.entity_id,
+            }
+    }
+}
+
+/// Add the tags in a value, and in everything it contains, that haven't been seen yet. A tag seen again on a face
+/// replaces the edge it was on before the segment was extruded.
+fn collect(value: &Value, found: &mut Vec<Found>) {
+    match value {
+        Value::Object(fields) => {
+            if value["type"] == "TagIdentifier" {
+                if let Some(tag) = Found::new(value) {
+                    match found.iter_mut().find(|seen| seen.same_as(&tag)) {
+                        Some(seen) if tag.is_face && !seen.is_face => *seen = tag,
+                        Some(_) => {}
+                        None => found.push(tag),
+                    }
+                }
+                return;
+            }
+            fields.values().for_each(|field| collect(field, found));
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect(item, found)),
+        _ => {}
+    }
+}
+
+/// Add the name of each tag in a value, and in everything it contains, by the id of what it is on. A tag on a segment
+/// that was extruded names both the segment and the face it made. The first tag seen on an id names it.
+pub(crate) fn names_by_id(value: &Value, names: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(fields) => {
+            if value["type"] == "TagIdentifier" {
+                let info = &value["info"];
+                if let Some(name) = value["value"].as_str() {
+                    for id in [info["id"].as_str(), info["surface"]["faceId"].as_str()]
+                        .into_iter()
+                        .flatten()
+                    {
+                        names.entry(id.to_string()).or_insert_with(|| name.to_string());
+                    }
+                }
+                return;
+            }
+            fields.values().for_each(|field| names_by_id(field, names));
+        }
+        Value::Array(items) => items.iter().for_each(|item| names_by_id(item, names)),
+        _ => {}
+    }
+}
+
+/// Send a query to the engine, returning None if the engine can't answer it for this entity.
+pub(crate) async fn query(ctx: &ExecutorContext, cmd: ModelingCmd) -> Option<OkModelingCmdResponse> {
+    match send_modeling_cmd(ctx, cmd).await.ok()? {
+        OkWebSocketResponseData::Modeling { modeling_response } => Some(modeling_response),
+        _ => None,
+    }
+}
+
+/// Ask the engine about a tagged face or edge.
+async fn summarize(ctx: &ExecutorContext, found: Found, index: usize, units: UnitLength) -> PyResult<Tag> {
+    let id = uuid::Uuid::parse_str(&found.entity_id)
+        .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+    let mut tag = Tag {
+        name: found.name,
+        index,
+        entity_id: found.entity_id,
+        kind: if found.is_face { "face" } else { "edge" }.to_string(),
+        source_range: found.source_range,
+        area: None,
+        center: None,
+        normal: None,
+        length: None,
+    };
+
+    if found.is_face {
+        let cmd = ModelingCmd::SurfaceArea(kittycad_modeling_cmds::SurfaceArea {
+            entity_ids: vec![id],
+            output_unit: area_unit(units),
+        });
+        if let Some(OkModelingCmdResponse::SurfaceArea(data)) = query(ctx, cmd).await {
+            tag.area = Some(data.surface_area);
+        }
+        let cmd = ModelingCmd::FaceGetCenter(kittycad_modeling_cmds::FaceGetCenter { object_id: id });
+        if let Some(OkModelingCmdResponse::FaceGetCenter(data)) = query(ctx, cmd).await {
+            tag.center = Some((data.pos.x.0, data.pos.y.0, data.pos.z.0));
+        }
+        let cmd = ModelingCmd::FaceGetGradient(kittycad_modeling_cmds::FaceGetGradient {
+            object_id: id,
+            uv: kittycad_modeling_cmds::shared::Point2d { x: 0.5, y: 0.5 },
+        });
+        if let Some(OkModelingCmdResponse::FaceGetGradient(data)) = query(ctx, cmd).await {
+            tag.normal = Some((data.normal.x, data.normal.y, data.normal.z));
+        }
+    } else {
+        let cmd = ModelingCmd::CurveGetType(kittycad_modeling_cmds::CurveGetType { curve_id: id });
+        let is_line = matches!(
+            query(ctx, cmd).await,
+            Some(OkModelingCmdResponse::CurveGetType(data))
+                if matches!(data.curve_type, kittycad_modeling_cmds::shared::CurveType::Line)
+        );
+        if !is_line {
+            return Ok(tag);
+        }
+        let cmd = ModelingCmd::CurveGetEndPoints(kittycad_modeling_cmds::CurveGetEndPoints { curve_id: id });
+        if let Some(OkModelingCmdResponse::CurveGetEndPoints(data)) = query(ctx, cmd).await {
+            let (start, end) = (data.start, data.end);
+            tag.length = Some(
+                ((end.x.0 - start.x.0).powi(2) + (end.y.0 - start.y.0).powi(2) + (end.z.0 - start.z.0).powi(2)).sqrt(),
+            );
+        }
+    }
+
+    Ok(tag)
+}
+
+/// The unit of area for a unit of length.
+pub(crate) fn area_unit(units: UnitLength) -> kittycad_modeling_cmds::units::UnitArea {
+    use kittycad_modeling_cmds::units::UnitArea;
+
+    match units {
+        UnitLength::Mm => UnitArea::SquareMillimeters,
+        UnitLength::Cm => UnitArea::SquareCentimeters,
+        UnitLength::M => UnitArea::SquareMeters,
+        UnitLength::In => UnitArea::SquareInches,
+        UnitLength::Ft => UnitArea::SquareFeet,
+        UnitLength::Yd => UnitArea::SquareYards,
+    }
+}
+
+/// Execute the kcl code and return the faces and edges it tagged, by the name of the tag.
+///
+/// Each tag has the id of what it is on, whether that is a face or an edge, where it was declared, and what the
+/// engine could measure of it: the area, center and normal of a face, or the length of a straight edge. Anything
+/// the engine couldn't measure is None. A tag on a segment that was extruded is on the face the segment made.
+///
+/// When more than one tag has the same name, e.g. because a function that tags a face was called twice, each is
+/// keyed by its name and index, like `side[0]` and `side[1]`, rather than one replacing the other.
+#[pyfunction]
+#[pyo3(signature = (code, units = None))]
+pub(crate) async fn execute_and_get_tags(code: Code, units: Option<Units>) -> PyResult<BTreeMap<String, Tag>> {
+    connection::require_token(None)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options) = (&code, &options);
+        with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+
+            // Tags are found through the values declared at the top level, in the order they are declared, and then
+            // through any tags bound on their own, e.g. on geometry that wasn't assigned to a variable.
+            let mut found = Vec::new();
+            for value in trace::top_level_values(&executed.program, &executed.exec_state)? {
+                collect(&value.value, &mut found);
+            }
+            let bindings = trace::root_bindings(&executed.exec_state)?;
+            let mut tags: Vec<&Value> = bindings
+                .values()
+                .filter(|value| value["type"] == "TagIdentifier")
+                .collect();
+            tags.sort_by_key(|tag| tag["__meta"][0]["sourceRange"][0].as_u64());
+            for tag in tags {
+                collect(tag, &mut found);
+            }
+
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for tag in &found {
+                *counts.entry(tag.name.clone()).or_default() += 1;
+            }
+            let mut indexes: BTreeMap<String, usize> = BTreeMap::new();
+            let mut result = BTreeMap::new();
+            for tag in found {
+                let index = indexes.entry(tag.name.clone()).or_default();
+                let key = if counts[&tag.name] > 1 {
+                    format!("{}[{}]", tag.name, index)
+                } else {
+                    tag.name.clone()
+                };
+                let tag = summarize(&executed.ctx, tag, *index, executed.units).await?;
+                *index += 1;
+                result.insert(key, tag);
+            }
+
+            Ok(result)
+        })
+        .await
+    })
+    .await
+}
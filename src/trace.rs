@@ -0,0 +1,160 @@
+//! The values a program computed, for seeing why it made what it did.
+//!
+//! kcl has no statement for printing values, so the trace is the value of each top level variable.
+
+use pyo3::{pyclass, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::pickle;
+
+/// The value a top level variable was given.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct TraceEntry {
+    /// The name of the variable.
+    #[pyo3(get)]
+    pub name: String,
+    /// The source range of the declaration, as a `(start, end)` pair of byte offsets.
+    #[pyo3(get)]
+    pub source_range: (usize, usize),
+    /// The value, written like kcl would, e.g. `[1, 2.5]`. Geometry is written as its kind, e.g. `<Solid>`.
+    #[pyo3(get)]
+    pub value: String,
+}
+
+#[pymethods]
+impl TraceEntry {
+    fn __str__(&self) -> String {
+        format!("{} = {}", self.name, self.value)
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Write a value from memory, as kcl-lib serializes it, like kcl would.
+fn render(value: &serde_json::Value) -> String {
+    let inner = &value["value"];
+    match value["type"].as_str() {
+        Some("Number" | "Int" | "Bool" | "String" | "Uuid") => inner.to_string(),
+        Some("KclNone") => "none".to_string(),
+        Some("Array" | "MixedArray" | "HomArray" | "Tuple") => {
+            let items: Vec<String> = inner.as_array().into_iter().flatten().map(render).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Some("Object") => {
+            let fields: Vec<String> = inner
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| format!("{}: {}", key, render(value)))
+                .collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        Some(kind) => format!("<{}>", kind),
+        None => inner.to_string(),
+    }
+}
+
+/// A variable declared at the top level of a program.
+pub(crate) struct TopLevelValue {
+    pub name: String,
+    /// The byte offsets of the start and end of the declaration.
+    pub source_range: (usize, usize),
+    /// The value, as kcl-lib serializes it.
+    pub value: serde_json::Value,
+}
+
+/// Everything bound at the top level of the program, by name in the order it was bound, as kcl-lib serializes it. This
+/// includes tags, which are bound where they are declared, e.g. `$side`, rather than by a variable declaration.
+pub(crate) fn root_bindings(exec_state: &kcl_lib::ExecState) -> PyResult<serde_json::Map<String, serde_json::Value>> {
+    // The root environment is the first, and kcl-lib only lets its bindings be read one name at a time.
+    let Some(root) = exec_state.memory.environments.first() else {
+        return Ok(Default::default());
+    };
+    match serde_json::to_value(root) {
+        Ok(mut root) => match root["bindings"].take() {
+            serde_json::Value::Object(bindings) => Ok(bindings),
+            _ => Ok(Default::default()),
+        },
+        Err(err) => Err(pyo3::exceptions::PyException::new_err(err.to_string())),
+    }
+}
+
+/// Everything bound at the top level of the program, by name in the order it was bound.
+pub(crate) fn root_values(exec_state: &kcl_lib::ExecState) -> PyResult<Vec<(String, &kcl_lib::exec::KclValue)>> {
+    let Some(root) = exec_state.memory.environments.first() else {
+        return Ok(Vec::new());
+    };
+
+    Ok(root_bindings(exec_state)?
+        .keys()
+        .filter_map(|name| Some((name.clone(), root.get(name, Default::default()).ok()?)))
+        .collect())
+}
+
+/// Each variable declared at the top level of the program, in the order they are declared.
+pub(crate) fn top_level_values(
+    program: &kcl_lib::Program,
+    exec_state: &kcl_lib::ExecState,
+) -> PyResult<Vec<TopLevelValue>> {
+    let ast =
+        serde_json::to_value(&program.ast).map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))?;
+    let body = ast
+        .get("body")
+        .and_then(|body| body.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let bindings = root_bindings(exec_state)?;
+
+    let mut values = Vec::new();
+    for item in &body {
+        // Both the singular and plural forms have been used for declarations.
+        let declarations = item
+            .get("declarations")
+            .and_then(|d| d.as_array().cloned())
+            .or_else(|| item.get("declaration").map(|d| vec![d.clone()]))
+            .unwrap_or_default();
+        for declaration in declarations {
+            let Some(name) = declaration
+                .get("id")
+                .and_then(|id| id.get("name"))
+                .and_then(|name| name.as_str())
+            else {
+                continue;
+            };
+            let Some(value) = bindings.get(name).cloned() else {
+                continue;
+            };
+            let offset = |key: &str| item.get(key).and_then(|offset| offset.as_u64()).unwrap_or_default() as usize;
+            values.push(TopLevelValue {
+                name: name.to_string(),
+                source_range: (offset("start"), offset("end")),
+                value,
+            });
+        }
+    }
+
+    Ok(values)
+}
+
+/// The value of each variable declared at the top level of the program, in the order they are declared.
+pub(crate) fn trace(program: &kcl_lib::Program, exec_state: &kcl_lib::ExecState) -> PyResult<Vec<TraceEntry>> {
+    Ok(top_level_values(program, exec_state)?
+        .into_iter()
+        .map(|value| TraceEntry {
+            value: render(&value.value),
+            name: value.name,
+            source_range: value.source_range,
+        })
+        .collect())
+}
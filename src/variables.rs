@@ -0,0 +1,167 @@
+//! Getting the values of a program's top level variables, e.g. for a bill of materials.
+
+use pyo3::{pyclass, pyfunction, pymethods, types::PyAny, Bound, PyObject, PyResult, Python};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    connection, defaults, pickle, program::Code, run_code, spawn, to_py_object, trace, with_reconnect, RunOptions,
+    Units,
+};
+
+/// A top level variable of a program and the value it was given.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct Variable {
+    /// The name of the variable.
+    #[pyo3(get)]
+    pub name: String,
+    /// The source range of the declaration, as a `(start, end)` pair of byte offsets.
+    #[pyo3(get)]
+    pub source_range: (usize, usize),
+    /// The kind of value, as kcl-lib names it, e.g. `Number`, `Array` or `Solid`.
+    #[pyo3(get)]
+    pub type_name: String,
+    value: Value,
+}
+
+#[pymethods]
+impl Variable {
+    /// The value as python numbers, strings, lists and dicts, or None for geometry like a solid.
+    #[getter]
+    fn value(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_py_object(py, &self.value)
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// The variables a program declared, and the names asked for that it didn't.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[pyclass(module = "kcl")]
+pub struct Variables {
+    /// The variables, in the order they are declared.
+    #[pyo3(get)]
+    pub variables: Vec<Variable>,
+    /// The names or patterns asked for that matched no variable.
+    #[pyo3(get)]
+    pub missing: Vec<String>,
+}
+
+#[pymethods]
+impl Variables {
+    /// The variable with the given name, or None if there isn't one.
+    fn get(&self, name: &str) -> Option<Variable> {
+        self.variables.iter().find(|variable| variable.name == name).cloned()
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (String, String))> {
+        pickle::reduce_json(py, self)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// A value from memory, as kcl-lib serializes it, as plain data. Geometry, functions and the like become null.
+fn plain(value: &Value) -> Value {
+    let inner = &value["value"];
+    match value["type"].as_str() {
+        Some("Number" | "Int" | "Bool" | "String" | "Uuid") => inner.clone(),
+        Some("Array" | "MixedArray" | "HomArray" | "Tuple") => {
+            Value::Array(inner.as_array().into_iter().flatten().map(plain).collect())
+        }
+        Some("Object") => Value::Object(
+            inner
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| (key.clone(), plain(value)))
+                .collect(),
+        ),
+        _ => Value::Null,
+    }
+}
+
+/// Whether a name matches a pattern, where `*` matches any run of characters and `?` any one character.
+fn matches(pattern: &str, name: &str) -> bool {
+    fn matches_chars(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.split_first(), name.split_first()) {
+            (None, _) => name.is_empty(),
+            (Some(('*', rest)), _) => {
+                matches_chars(rest, name) || (!name.is_empty() && matches_chars(pattern, &name[1..]))
+            }
+            (Some(('?', rest)), Some((_, name))) => matches_chars(rest, name),
+            (Some((c, rest)), Some((n, name))) if c == n => matches_chars(rest, name),
+            _ => false,
+        }
+    }
+
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    matches_chars(&pattern, &name)
+}
+
+/// Execute the kcl code and return its top level variables with their values, source ranges and kinds.
+///
+/// If `names` is given only the variables matching them are returned, where a name may be a pattern like `bom_*`.
+/// Names that match nothing are listed in `missing` rather than raising, so optional variables can be asked for.
+#[pyfunction]
+#[pyo3(signature = (code, units = None, names = None))]
+pub(crate) async fn execute_and_get_variables(
+    code: Code,
+    units: Option<Units>,
+    names: Option<Vec<String>>,
+) -> PyResult<Variables> {
+    connection::require_token(None)?;
+    let options = RunOptions {
+        units: defaults::units(units)?,
+        ..Default::default()
+    };
+    spawn(async move {
+        let (code, options) = (&code, &options);
+        let values = with_reconnect(|| async move {
+            let executed = run_code(code, options).await?;
+            trace::top_level_values(&executed.program, &executed.exec_state)
+        })
+        .await?;
+
+        let wanted = |name: &str| {
+            names
+                .as_ref()
+                .is_none_or(|names| names.iter().any(|pattern| matches(pattern, name)))
+        };
+        let variables: Vec<Variable> = values
+            .into_iter()
+            .filter(|value| wanted(&value.name))
+            .map(|value| Variable {
+                type_name: value.value["type"].as_str().unwrap_or_default().to_string(),
+                value: plain(&value.value),
+                name: value.name,
+                source_range: value.source_range,
+            })
+            .collect();
+        let missing = names
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|pattern| !variables.iter().any(|variable| matches(pattern, &variable.name)))
+            .collect();
+
+        Ok(Variables { variables, missing })
+    })
+    .await
+}
@@ -0,0 +1,54 @@
+//! Version information for the bindings and what they were built against.
+
+use pyo3::{pyfunction, PyObject, PyResult, Python};
+
+use crate::{connection, defaults, spawn, to_py_object};
+
+/// The name of the crate the bindings are built from.
+pub(crate) const NAME: &str = env!("CARGO_PKG_NAME");
+
+/// The version of the bindings.
+pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The version of kcl-lib the bindings were built against.
+pub(crate) const KCL_LIB_VERSION: &str = env!("KCL_LIB_VERSION");
+
+/// The versions of the bindings, kcl-lib and kittycad-modeling-cmds, as a dict.
+///
+/// This doesn't need a connection to the engine or any credentials.
+#[pyfunction]
+pub(crate) fn version_info(py: Python<'_>) -> PyResult<PyObject> {
+    to_py_object(
+        py,
+        &serde_json::json!({
+            "kcl": VERSION,
+            "kcl_lib": KCL_LIB_VERSION,
+            "kittycad_modeling_cmds": env!("KITTYCAD_MODELING_CMDS_VERSION"),
+        }),
+    )
+}
+
+/// Ask the API which version it is running, as a dict with an `api_version`.
+///
+/// This needs a valid API token, from the default `ClientConfig` set with `set_defaults` or else the environment.
+#[pyfunction]
+pub(crate) async fn engine_version() -> PyResult<PyObject> {
+    connection::require_token(None)?;
+    let client = defaults::client_config(None).unwrap_or_default().client()?;
+    let schema = spawn(async move {
+        client
+            .meta()
+            .get_schema()
+            .await
+            .map_err(|err| pyo3::exceptions::PyException::new_err(err.to_string()))
+    })
+    .await?;
+    let api_version = schema
+        .get("info")
+        .and_then(|info| info.get("version"))
+        .and_then(|version| version.as_str())
+        .ok_or_else(|| pyo3::exceptions::PyException::new_err("The API did not report its version"))?
+        .to_string();
+
+    Python::with_gil(|py| to_py_object(py, &serde_json::json!({ "api_version": api_version })))
+}
@@ -1,5 +1,18 @@
 #!/usr/bin/env python3
+import ast
+import asyncio
+import copy
+import json
 import os
+import pickle
+import signal
+import socket
+import subprocess
+import sys
+import threading
+import time
+import tracemalloc
+import urllib.request
 
 import kcl
 import pytest
@@ -17,7 +30,29 @@ async def test_kcl_execute():
         code = str(f.read())
         assert code is not None
         assert len(code) > 0
-        await kcl.execute(code, kcl.UnitLength.Mm)
+        warnings = await kcl.execute(code, kcl.UnitLength.Mm)
+        assert warnings is not None
+        for warning in warnings:
+            assert warning.severity == "warning"
+            assert len(warning.message) > 0
+            assert warning.end >= warning.start
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_warnings_as_errors():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        warnings = await kcl.execute(code, kcl.UnitLength.Mm)
+        if len(warnings) == 0:
+            # Nothing to raise about.
+            await kcl.execute(code, kcl.UnitLength.Mm, warnings_as_errors=True)
+        else:
+            with pytest.raises(kcl.KclWarningError) as err:
+                await kcl.execute(code, kcl.UnitLength.Mm, warnings_as_errors=True)
+            assert len(err.value.args[1]) == len(warnings)
 
 
 @pytest.mark.asyncio
@@ -34,6 +69,113 @@ async def test_kcl_execute_and_snapshot():
         assert len(image_bytes) > 0
 
 
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_with_render_settings():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        settings = kcl.RenderSettings(
+            highlight_edges=False, enable_ssao=False, show_grid=False
+        )
+        assert settings.highlight_edges is False
+        image_bytes = await kcl.execute_and_snapshot(
+            code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, settings
+        )
+        assert image_bytes is not None
+        assert len(image_bytes) > 0
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_with_background_color():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        image_bytes = await kcl.execute_and_snapshot(
+            code, kcl.UnitLength.Mm, kcl.ImageFormat.Jpeg, background_color="#ffffff"
+        )
+        assert len(image_bytes) > 0
+        image_bytes = await kcl.execute_and_snapshot(
+            code,
+            kcl.UnitLength.Mm,
+            kcl.ImageFormat.Png,
+            background_color=(1.0, 1.0, 1.0, 0.5),
+        )
+        assert len(image_bytes) > 0
+        image_bytes = await kcl.execute_and_snapshot(
+            code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, transparent=True
+        )
+        assert len(image_bytes) > 0
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_invalid_background_color():
+    with pytest.raises(ValueError):
+        await kcl.execute_and_snapshot(
+            "", kcl.UnitLength.Mm, kcl.ImageFormat.Jpeg, transparent=True
+        )
+    with pytest.raises(ValueError):
+        await kcl.execute_and_snapshot(
+            "", kcl.UnitLength.Mm, kcl.ImageFormat.Jpeg, background_color="#ffffff80"
+        )
+    with pytest.raises(ValueError):
+        await kcl.execute_and_snapshot(
+            "", kcl.UnitLength.Mm, kcl.ImageFormat.Png, background_color="#fffff"
+        )
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_with_appearance():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        appearance = kcl.Appearance("#ff6600", metalness=0.5, roughness=0.2)
+        assert appearance.color == "#ff6600ff"
+        image_bytes = await kcl.execute_and_snapshot(
+            code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, appearance=appearance
+        )
+        assert len(image_bytes) > 0
+
+
+def test_kcl_appearance_invalid():
+    with pytest.raises(ValueError):
+        kcl.Appearance("orange")
+    with pytest.raises(ValueError):
+        kcl.Appearance("#ff66")
+    with pytest.raises(ValueError):
+        kcl.Appearance("#ff6600", metalness=2.0)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_turntable():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        images = await kcl.execute_and_snapshot_turntable(
+            code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, frames=4
+        )
+        assert len(images) == 4
+        for image_bytes in images:
+            assert len(image_bytes) > 0
+        # The camera moved between frames.
+        assert images[0] != images[1]
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_turntable_too_few_frames():
+    with pytest.raises(ValueError):
+        await kcl.execute_and_snapshot_turntable(
+            "", kcl.UnitLength.Mm, kcl.ImageFormat.Png, frames=1
+        )
+
+
 @pytest.mark.asyncio
 async def test_kcl_execute_and_export():
     # Read from a file.
@@ -55,6 +197,379 @@ async def test_kcl_execute_and_export():
         assert len(contents) > 0
 
 
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_and_export():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        image_bytes, files = await kcl.execute_and_snapshot_and_export(
+            code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, kcl.FileExportFormat.Step
+        )
+        assert image_bytes is not None
+        assert len(image_bytes) > 0
+        assert files is not None
+        assert len(files) > 0
+        assert len(files[0].name) > 0
+        assert len(files[0].contents) > 0
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_get_artifact_graph():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        graph = await kcl.execute_and_get_artifact_graph(code, kcl.UnitLength.Mm)
+        assert len(graph["nodes"]) > 0
+        assert len(graph["edges"]) > 0
+        ids = {node["id"] for node in graph["nodes"]}
+        for node in graph["nodes"]:
+            assert len(node["type"]) > 0
+        for edge in graph["edges"]:
+            assert edge["from"] in ids
+            assert edge["to"] in ids
+            assert len(edge["kind"]) > 0
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_get_artifact_graph_empty():
+    graph = await kcl.execute_and_get_artifact_graph(
+        "const x = 1", kcl.UnitLength.Mm
+    )
+    assert graph == {"nodes": [], "edges": []}
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_with_report():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        report = await kcl.execute_with_report(
+            code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, kcl.FileExportFormat.Step
+        )
+        labels = [entry.label for entry in report.entries]
+        assert labels == [
+            "parse",
+            "connect",
+            "execute",
+            "zoom_to_fit",
+            "snapshot",
+            "export",
+        ]
+        assert report.entries[0].source_range == (0, len(code))
+        assert all(entry.duration_ms >= 0 for entry in report.entries)
+        assert report.total_ms >= sum(entry.duration_ms for entry in report.entries)
+        assert len(report.image) > 0
+        assert len(report.files) > 0
+
+        report_dict = report.to_dict()
+        assert json.loads(json.dumps(report_dict)) == report_dict
+        assert len(report_dict["entries"]) == len(labels)
+
+
+@pytest.mark.asyncio
+async def test_kcl_session_cache():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+
+    session = kcl.Session(kcl.UnitLength.Mm)
+    assert session.use_cache
+
+    first = await session.execute(code)
+    assert not first.cache_hit
+
+    second = await session.execute(code)
+    assert second.cache_hit
+    assert second.execution_ms < first.execution_ms
+    # The results of the first execution are kept, so the scene can still be used.
+    assert [o.id for o in second.objects] == [o.id for o in first.objects]
+    assert [str(entry) for entry in second.trace] == [str(entry) for entry in first.trace]
+    assert len(await session.snapshot()) > 0
+
+    # Any change runs the whole program again.
+    edited = await session.execute(code + "\nconst extra = 1\n")
+    assert not edited.cache_hit
+
+    session.bust_cache()
+    busted = await session.execute(code)
+    assert not busted.cache_hit
+
+    session.use_cache = False
+    uncached = await session.execute(code)
+    assert not uncached.cache_hit
+
+
+def stl_extents(contents):
+    vertices = [
+        [float(v) for v in line.split()[1:]]
+        for line in bytes(contents).decode().splitlines()
+        if line.strip().startswith("vertex")
+    ]
+    return [max(v[i] for v in vertices) - min(v[i] for v in vertices) for i in range(3)]
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_export_default_units():
+    cube = """
+cube = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([1, 0], %)
+  |> line([0, 1], %)
+  |> line([-1, 0], %)
+  |> close(%)
+  |> extrude(1, %)
+"""
+    # Without units, millimeters are the default.
+    files = await kcl.execute_and_export(cube, None, kcl.FileExportFormat.Stl)
+    for extent in stl_extents(files[0].contents):
+        assert extent == pytest.approx(1.0)
+    assert await kcl.execute(cube) == []
+
+
+def write_project(directory, settings=None):
+    (directory / "cube.kcl").write_text(
+        """export fn cube = (size) => {
+  return startSketchOn('XY')
+    |> startProfileAt([0, 0], %)
+    |> line([size, 0], %)
+    |> line([0, size], %)
+    |> line([-size, 0], %)
+    |> close(%)
+    |> extrude(size, %)
+}
+"""
+    )
+    (directory / "main.kcl").write_text('import cube from "cube.kcl"\n\nmyCube = cube(1)\n')
+    if settings is not None:
+        (directory / "project.toml").write_text(settings)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_project(tmp_path):
+    write_project(tmp_path, '[settings.modeling]\nbase_unit = "in"\n')
+
+    await kcl.execute_project(str(tmp_path))
+
+    files = await kcl.execute_project_and_export(
+        str(tmp_path), kcl.FileExportFormat.Stl
+    )
+    for extent in stl_extents(files[0].contents):
+        assert extent == pytest.approx(1.0)
+
+    image_bytes = await kcl.execute_project_and_snapshot(
+        str(tmp_path), kcl.ImageFormat.Png
+    )
+    assert len(image_bytes) > 0
+
+    # A different entry file.
+    (tmp_path / "other.kcl").write_text('import cube from "cube.kcl"\n\nbig = cube(2)\n')
+    await kcl.execute_project(str(tmp_path), entry="other.kcl")
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_project_errors(tmp_path):
+    with pytest.raises(FileNotFoundError) as err:
+        await kcl.execute_project(str(tmp_path))
+    assert "main.kcl" in str(err.value)
+
+    write_project(tmp_path, "[settings.modeling\nbase_unit = ")
+    with pytest.raises(ValueError) as err:
+        await kcl.execute_project(str(tmp_path))
+    assert "project.toml" in str(err.value)
+
+    write_project(tmp_path, '[settings.modeling]\nbase_unit = "furlong"\n')
+    with pytest.raises(ValueError) as err:
+        await kcl.execute_project(str(tmp_path))
+    assert "project.toml" in str(err.value)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_with_module_files(tmp_path):
+    write_project(tmp_path)
+    files = {"cube.kcl": (tmp_path / "cube.kcl").read_text()}
+    code = (tmp_path / "main.kcl").read_text()
+
+    # kcl-lib can only import from disk, so in-memory files are refused rather than written out.
+    with pytest.raises(NotImplementedError) as err:
+        await kcl.execute(code, kcl.UnitLength.Mm, files=files)
+    assert "cube.kcl" in str(err.value)
+
+    with pytest.raises(NotImplementedError):
+        await kcl.execute_and_export(
+            code, kcl.UnitLength.Mm, kcl.FileExportFormat.Stl, files=files
+        )
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_with_attachments():
+    with open(os.path.join(kcl_dir_file_path, "cube.step"), "rb") as f:
+        attachments = {"cube.step": f.read()}
+    code = 'model = import("cube.step")\n'
+
+    with pytest.raises(NotImplementedError) as err:
+        await kcl.execute(code, kcl.UnitLength.Mm, attachments=attachments)
+    assert "cube.step" in str(err.value)
+
+
+def test_kcl_execute_and_export_releases_gil():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+
+    done = threading.Event()
+    ticks = []
+
+    def count():
+        while not done.is_set():
+            ticks.append(time.monotonic())
+            time.sleep(0.01)
+
+    counter = threading.Thread(target=count)
+    counter.start()
+    try:
+        start = time.monotonic()
+        files = asyncio.run(
+            kcl.execute_and_export(code, kcl.UnitLength.Mm, kcl.FileExportFormat.Step)
+        )
+        end = time.monotonic()
+    finally:
+        done.set()
+        counter.join()
+
+    assert files is not None
+    assert len(files) > 0
+    # The counter thread must have kept running while the export was in flight.
+    during = [t for t in ticks if start <= t <= end]
+    assert len(during) > 1
+
+
+def test_kcl_retry_settings():
+    default = kcl.get_retry_settings()
+    assert default == kcl.RetrySettings()
+    assert default.connect_attempts > 1
+    assert default.rerun_on_disconnect
+
+    try:
+        kcl.set_retry_settings(kcl.RetrySettings.disabled())
+        settings = kcl.get_retry_settings()
+        assert settings.connect_attempts == 1
+        assert not settings.rerun_on_disconnect
+    finally:
+        kcl.set_retry_settings(default)
+    assert kcl.get_retry_settings() == default
+
+    with pytest.raises(ValueError):
+        kcl.RetrySettings(connect_attempts=0)
+    with pytest.raises(ValueError):
+        kcl.RetrySettings(initial_delay=-1.0)
+
+
+def test_kcl_defaults():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    kcl.reset_defaults()
+    assert kcl.get_defaults().units is None
+    assert kcl.get_defaults().image_format is None
+
+    try:
+        kcl.set_defaults(units="in", image_format=kcl.ImageFormat.Png, timeout_seconds=120)
+        # Only what is given changes.
+        kcl.set_defaults(export_format=kcl.FileExportFormat.Step)
+        defaults = kcl.get_defaults()
+        assert defaults.units == kcl.UnitLength.In
+        assert defaults.image_format == kcl.ImageFormat.Png
+        assert defaults.export_format == kcl.FileExportFormat.Step
+        assert defaults.timeout_seconds == 120
+        assert defaults.client_config is None
+
+        with pytest.raises(ValueError):
+            kcl.set_defaults(timeout_seconds=0)
+        assert kcl.get_defaults().timeout_seconds == 120
+
+        # The defaults are used when a call leaves the parameter out, and the call's own arguments win.
+        image = asyncio.run(kcl.execute_and_snapshot(code))
+        assert bytes(image[:4]) == b"\x89PNG"
+        image = asyncio.run(kcl.execute_and_snapshot(code, image_format=kcl.ImageFormat.Jpeg))
+        assert bytes(image[:2]) == b"\xff\xd8"
+    finally:
+        kcl.reset_defaults()
+    assert kcl.get_defaults().units is None
+
+    with pytest.raises(TypeError, match="image_format"):
+        asyncio.run(kcl.execute_and_snapshot(code))
+
+
+def test_kcl_defaults_change_while_executing():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+
+    done = threading.Event()
+    seen = []
+
+    def flip():
+        while not done.is_set():
+            kcl.set_defaults(units=kcl.UnitLength.Mm, image_format=kcl.ImageFormat.Png)
+            kcl.set_defaults(units=kcl.UnitLength.In, image_format=kcl.ImageFormat.Jpeg)
+            defaults = kcl.get_defaults()
+            seen.append((defaults.units, defaults.image_format))
+
+    flipper = threading.Thread(target=flip)
+    flipper.start()
+    try:
+        for _ in range(3):
+            image = asyncio.run(kcl.execute_and_snapshot(code, image_format=kcl.ImageFormat.Png))
+            assert bytes(image[:4]) == b"\x89PNG"
+            image = asyncio.run(kcl.execute_and_snapshot(code))
+            assert bytes(image[:4]) == b"\x89PNG" or bytes(image[:2]) == b"\xff\xd8"
+    finally:
+        done.set()
+        flipper.join()
+        kcl.reset_defaults()
+
+    # Every read saw a whole set of defaults, never half of one and half of the other.
+    assert len(seen) > 0
+    assert set(seen) <= {
+        (kcl.UnitLength.Mm, kcl.ImageFormat.Png),
+        (kcl.UnitLength.In, kcl.ImageFormat.Jpeg),
+    }
+
+
+def test_kcl_unit_length():
+    assert kcl.UnitLength.from_str("mm") == kcl.UnitLength.Mm
+    assert kcl.UnitLength.from_str("IN") == kcl.UnitLength.In
+    assert kcl.UnitLength.from_str("inch") == kcl.UnitLength.In
+    assert kcl.UnitLength.from_str(" yd ") == kcl.UnitLength.Yd
+    for unit in ["mm", "cm", "m", "in", "ft", "yd"]:
+        assert str(kcl.UnitLength.from_str(unit)) == unit
+
+    with pytest.raises(ValueError) as err:
+        kcl.UnitLength.from_str("furlong")
+    assert "mm" in str(err.value)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_units_as_string():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        await kcl.execute(code, "mm")
+
+    with pytest.raises(ValueError):
+        await kcl.execute(code, "furlong")
+
+
 def test_kcl_format():
     # Read from a file.
     with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
@@ -66,6 +581,139 @@ def test_kcl_format():
         assert len(formatted_code) > 0
 
 
+def test_kcl_format_with_options():
+    code = "fn cube = (size) => {\nreturn startSketchOn('XY')\n}\n"
+
+    default = kcl.format(code)
+    assert default == kcl.format(code, kcl.FormatOptions())
+
+    two_spaces = kcl.format(code, kcl.FormatOptions(tab_size=2))
+    assert "\n  return" in two_spaces
+
+    four_spaces = kcl.format(code, kcl.FormatOptions(tab_size=4))
+    assert "\n    return" in four_spaces
+
+    tabs = kcl.format(code, kcl.FormatOptions(use_tabs=True))
+    assert "\n\treturn" in tabs
+
+    no_newline = kcl.format(code, kcl.FormatOptions(insert_final_newline=False))
+    assert not no_newline.endswith("\n")
+
+
+def test_kcl_format_dir(tmp_path):
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+    formatted_code = kcl.format(code)
+
+    (tmp_path / "nested").mkdir()
+    formatted = tmp_path / "formatted.kcl"
+    formatted.write_text(formatted_code)
+    unformatted = tmp_path / "nested" / "unformatted.kcl"
+    unformatted.write_text(formatted_code.replace("  ", "    "))
+    ignored = tmp_path / "notes.txt"
+    ignored.write_text("not kcl")
+
+    # Checking must not write anything.
+    result = kcl.format_dir(str(tmp_path), check=True)
+    assert result.changed == [str(unformatted)]
+    assert result.errors == {}
+    assert result.ok
+    assert unformatted.read_text() != formatted_code
+
+    result = kcl.format_dir(str(tmp_path))
+    assert result.changed == [str(unformatted)]
+    assert unformatted.read_text() == formatted_code
+    assert ignored.read_text() == "not kcl"
+
+    assert kcl.format_dir(str(tmp_path), check=True).changed == []
+
+
+def test_kcl_format_dir_reports_unparseable_files(tmp_path):
+    broken = tmp_path / "broken.kcl"
+    broken.write_text("const x = (")
+    binary = tmp_path / "binary.kcl"
+    binary.write_bytes(b"\xff\xfe")
+    unformatted = tmp_path / "unformatted.kcl"
+    unformatted.write_text("const   x = 1")
+
+    result = kcl.format_dir(str(tmp_path))
+    assert not result.ok
+    assert sorted(result.errors) == [str(binary), str(broken)]
+    assert "UTF-8" in result.errors[str(binary)].message
+    # The other files were still formatted, and reported alongside the errors.
+    assert result.changed == [str(unformatted)]
+    assert unformatted.read_text() == kcl.format("const x = 1")
+
+    with pytest.raises(OSError):
+        kcl.format_dir(str(tmp_path / "missing"))
+
+
+def test_kcl_parse():
+    code = """const width = 10
+
+fn double = (x) => {
+  return x * 2
+}
+"""
+    ast = kcl.parse(code)
+    assert ast is not None
+    body = ast["body"]
+    assert len(body) == 2
+    assert all(item["type"] == "VariableDeclaration" for item in body)
+    assert body[0]["start"] == 0
+    assert body[0]["end"] > body[0]["start"]
+
+    def names(node):
+        if isinstance(node, dict):
+            if node.get("type") == "Identifier":
+                yield node["name"]
+            for value in node.values():
+                yield from names(value)
+        elif isinstance(node, list):
+            for value in node:
+                yield from names(value)
+
+    declared = list(names(body))
+    assert "width" in declared
+    assert "double" in declared
+
+    ast_json = kcl.parse_to_json(code)
+    assert json.loads(ast_json) == ast
+
+
+def test_kcl_parse_error():
+    with pytest.raises(Exception):
+        kcl.parse("const x = (")
+
+
+def test_kcl_program_digest():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        digest = kcl.program_digest(code)
+        assert len(digest) == 64
+        int(digest, 16)
+
+        # Formatting changes must not change the digest.
+        assert kcl.program_digest(kcl.format(code)) == digest
+        assert (
+            kcl.program_digest(kcl.format(code, kcl.FormatOptions(tab_size=4)))
+            == digest
+        )
+
+
+def test_kcl_program_digest_changes():
+    digest = kcl.program_digest("const width = 10\nconst height = width * 2\n")
+    assert kcl.program_digest("const width   =   10\n\n\nconst height = width * 2") == digest
+    assert kcl.program_digest("const width = 11\nconst height = width * 2\n") != digest
+    assert kcl.program_digest("const size = 10\nconst height = size * 2\n") != digest
+
+
 def test_kcl_lint():
     # Read from a file.
     with open(os.path.join(kcl_dir_file_path, "box_with_linter_errors.kcl"), "r") as f:
@@ -83,3 +731,1651 @@ def test_kcl_lint():
         finding_title = finding.title
         assert finding_title is not None
         assert len(finding_title) > 0
+
+
+def test_kcl_stdlib_docs():
+    functions = kcl.stdlib_docs()
+    assert len(functions) > 0
+    names = [function.name for function in functions]
+    assert names == sorted(names)
+
+    extrude = kcl.stdlib_function("extrude")
+    assert extrude.name in names
+    assert len(extrude.summary) > 0
+    parameters = [parameter.name for parameter in extrude.parameters]
+    assert "length" in parameters
+    assert all(len(parameter.type) > 0 for parameter in extrude.parameters)
+
+    # The docs can be serialized to JSON as is.
+    assert json.loads(json.dumps(extrude.to_dict()))["name"] == "extrude"
+
+    with pytest.raises(KeyError):
+        kcl.stdlib_function("notAFunction")
+
+
+def test_kcl_completions():
+    code = """width = 10
+fn box(size) {
+  height = size * 2
+  return ex
+}
+wi"""
+    items = kcl.completions(code, 3, 11)
+    labels = [item.label for item in items]
+    assert "extrude" in labels
+    assert all(label.startswith("ex") for label in labels)
+    extrude = next(item for item in items if item.label == "extrude")
+    assert extrude.kind == "function"
+    assert extrude.detail.startswith("extrude(")
+    assert code[extrude.start : extrude.end] == "ex"
+
+    kinds = {item.label: item.kind for item in kcl.completions(code, 5, 0)}
+    assert kinds["box"] == "function"
+    assert kinds["width"] == "constant"
+    assert kinds["fn"] == "keyword"
+    assert kinds["|>"] == "operator"
+    assert [item.label for item in kcl.completions(code, 5, 2)] == ["width"]
+
+    # Code that doesn't parse yet still gets completions from the lines before.
+    items = kcl.completions("width = 10\nbody = extrude(wi", 1, 17)
+    assert [(item.label, item.kind) for item in items] == [("width", "constant")]
+
+    with pytest.raises(ValueError):
+        kcl.completions(code, 10, 0)
+    with pytest.raises(ValueError):
+        kcl.completions(code, 0, 50)
+
+
+def test_kcl_hover():
+    code = """width = 10
+fn box(size) {
+  return extrude(size, width)
+}"""
+    hover = kcl.hover(code, 2, 12)
+    assert code[hover.start : hover.end] == "extrude"
+    assert "extrude(" in hover.contents
+
+    # Only the standard library has docs to show.
+    assert kcl.hover(code, 2, 24) is None
+    assert kcl.hover(code, 2, 1) is None
+
+
+def test_kcl_signature_help():
+    # The rest of the call doesn't need to be there yet.
+    code = "width = 10\nbody = extrude(width, "
+    help = kcl.signature_help(code, 1, 22)
+    assert help.label == "extrude"
+    assert help.parameters == ["length", "sketch_set"]
+    assert help.active_parameter == 1
+
+    help = kcl.signature_help("width = 10\nbody = extrude(", 1, 15)
+    assert help.active_parameter == 0
+
+    assert kcl.signature_help("width = 10", 0, 5) is None
+
+
+def test_kcl_semantic_tokens():
+    code = "fn double(x) {\n  return x * 2 // twice\n}\nsize = double(5)\n"
+    assert kcl.semantic_tokens(code) == [
+        (0, 2, "keyword", []),
+        (3, 6, "function", ["declaration", "definition"]),
+        (10, 1, "parameter", []),
+        (17, 6, "keyword", []),
+        (24, 1, "variable", []),
+        (26, 1, "operator", []),
+        (28, 1, "number", []),
+        (30, 8, "comment", []),
+        (41, 4, "variable", ["declaration", "definition"]),
+        (46, 1, "operator", []),
+        (48, 6, "function", []),
+        (55, 1, "number", []),
+    ]
+
+
+def test_kcl_semantic_tokens_invalid():
+    # Code that doesn't parse still gets tokens, just without modifiers.
+    assert kcl.semantic_tokens("width = (10 + 3") == [
+        (0, 5, "variable", []),
+        (6, 1, "operator", []),
+        (9, 2, "number", []),
+        (12, 1, "operator", []),
+        (14, 1, "number", []),
+    ]
+
+    # Code that can't be tokenized gets none.
+    assert kcl.semantic_tokens('width = "unterminated') == []
+
+
+def test_kcl_tokenize():
+    # Read from a file.
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+        assert code is not None
+        assert len(code) > 0
+        tokens = kcl.tokenize(code)
+        assert len(tokens) > 0
+        assert "".join(token.value for token in tokens) == code
+        assert not any(token.is_error for token in tokens)
+        for token in tokens:
+            assert len(token.kind) > 0
+            assert code[token.start : token.end] == token.value
+
+    assert [(token.kind, token.value) for token in kcl.tokenize("x = [1, a.b]")] == [
+        ("variable", "x"),
+        ("whitespace", " "),
+        ("operator", "="),
+        ("whitespace", " "),
+        ("brace", "["),
+        ("number", "1"),
+        ("comma", ","),
+        ("whitespace", " "),
+        ("variable", "a"),
+        ("period", "."),
+        ("property", "b"),
+        ("brace", "]"),
+    ]
+
+
+def test_kcl_tokenize_invalid():
+    # Text kcl-lib can't make sense of is an error token, but the rest is still tokenized.
+    tokens = kcl.tokenize("x = 1 ~ 2")
+    assert [token.value for token in tokens if token.is_error] == ["~"]
+    assert tokens[-1].kind == "number"
+
+    # Tokens up to a string that is never closed are kept, and the rest is one error token.
+    code = 'const x = 1 $ "unterminated'
+    tokens = kcl.tokenize(code)
+    assert "".join(token.value for token in tokens) == code
+    assert tokens[0].value == "const"
+    assert tokens[-1].is_error
+    assert tokens[-1].value == '"unterminated'
+
+
+def test_kcl_editor_features_from_threads():
+    # The threads share one language server, so they must each get the answer for their own code.
+    codes = ["width = 10 + 3", "width = (10 + 3", "fn f = (a) => { return a }", 'x = "s"']
+    expected = [kcl.semantic_tokens(code) for code in codes]
+    results = {}
+
+    def run(i):
+        for _ in range(10):
+            results.setdefault(i, []).append(kcl.semantic_tokens(codes[i % len(codes)]))
+
+    threads = [threading.Thread(target=run, args=(i,)) for i in range(8)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    for i, tokens in results.items():
+        assert tokens == [expected[i % len(codes)]] * 10
+
+
+def test_kcl_lint_and_fix():
+    with open(os.path.join(kcl_dir_file_path, "box_with_linter_errors.kcl"), "r") as f:
+        code = str(f.read())
+    fixed, remaining, fixes = kcl.lint_and_fix(code)
+    assert len(fixes) > 0
+    assert all(fix.rule == "Z0001" for fix in fixes)
+    assert [fix.start for fix in fixes] == sorted(fix.start for fix in fixes)
+    assert "box_width" not in fixed
+    assert "xLine(boxWidth, %, $line1)" in fixed
+    assert not any(finding.finding.code == "Z0001" for finding in remaining)
+
+    # The fixed code still parses, and fixing it again changes nothing.
+    kcl.parse(fixed)
+    assert kcl.lint_and_fix(fixed)[0] == fixed
+    again, _, again_fixes = kcl.lint_and_fix(code)
+    assert again == fixed
+    assert [(fix.start, fix.end) for fix in again_fixes] == [
+        (fix.start, fix.end) for fix in fixes
+    ]
+
+    # Rules that weren't asked for are left alone.
+    unchanged, _, fixes = kcl.lint_and_fix(code, rules=["Z9999"])
+    assert unchanged == code
+    assert fixes == []
+
+
+def test_kcl_lint_and_fix_shadowing():
+    # The parameter shadows the variable, so only the variable and what refers to it are renamed.
+    code = """box_width = 1
+fn double = (box_width) => {
+  return box_width * 2
+}
+doubled = double(box_width)
+"""
+    fixed, _, fixes = kcl.lint_and_fix(code)
+    assert fixed == """boxWidth = 1
+fn double = (box_width) => {
+  return box_width * 2
+}
+doubled = double(boxWidth)
+"""
+    assert [code[fix.start : fix.end] for fix in fixes] == ["box_width"] * 2
+
+
+@pytest.mark.asyncio
+async def test_kcl_validate(monkeypatch):
+    # Validating never needs to talk to the engine.
+    monkeypatch.delenv("ZOO_API_TOKEN", raising=False)
+    monkeypatch.delenv("KITTYCAD_API_TOKEN", raising=False)
+
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    diagnostics = await kcl.validate(code)
+    assert not any(d.severity == "error" for d in diagnostics)
+    assert await kcl.validate("width = 10\n") == []
+
+    code = "width = 10\nheight = widht * 2\n"
+    diagnostics = await kcl.validate(code)
+    assert len(diagnostics) == 1
+    assert diagnostics[0].severity == "error"
+    assert "widht" in code[diagnostics[0].start : diagnostics[0].end]
+
+    diagnostics = await kcl.validate("width = (10 +\n")
+    assert len(diagnostics) > 0
+    assert diagnostics[0].severity == "error"
+
+    # kcl-lib can't read the units a settings annotation sets, so that is an error naming them.
+    code = "@settings(defaultLengthUnit = in)\nwidth = 10\n"
+    diagnostics = await kcl.validate(code)
+    assert [d.severity for d in diagnostics] == ["error"]
+    assert 'units="in"' in diagnostics[0].message
+    assert code[diagnostics[0].start : diagnostics[0].end] == "@settings(defaultLengthUnit = in)"
+
+
+def test_kcl_settings_annotation():
+    # Code that sets its own units isn't run in any others.
+    with pytest.raises(ValueError) as err:
+        kcl.Program.parse("@settings(defaultLengthUnit = in)\nwidth = 10\n")
+    assert 'units="in"' in str(err.value)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_batch():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    events = []
+    results = await kcl.execute_and_snapshot_batch(
+        [code, ("width = undefinedThing * 2",), (code,)],
+        kcl.UnitLength.Mm,
+        kcl.ImageFormat.Png,
+        max_concurrency=2,
+        on_progress=lambda index, status: events.append((index, status)),
+    )
+    assert len(results) == 3
+    assert len(results[0]) > 0
+    assert len(results[2]) > 0
+
+    # One bad program doesn't fail the others.
+    assert isinstance(results[1], kcl.BatchError)
+    assert results[1].index == 1
+    assert "undefinedThing" in results[1].message
+
+    assert sorted(events) == sorted(
+        [(0, "started"), (0, "succeeded")]
+        + [(1, "started"), (1, "failed")]
+        + [(2, "started"), (2, "succeeded")]
+    )
+
+    with pytest.raises(ValueError):
+        await kcl.execute_and_snapshot_batch(
+            [code], kcl.UnitLength.Mm, kcl.ImageFormat.Png, max_concurrency=0
+        )
+    # Anything after the code would be ignored, so it is refused.
+    with pytest.raises(ValueError) as err:
+        await kcl.execute_and_snapshot_batch(
+            [(code, "lego")], kcl.UnitLength.Mm, kcl.ImageFormat.Png
+        )
+    assert "2 elements" in str(err.value)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_with_progress():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    events = []
+    await kcl.execute_and_snapshot_and_export(
+        code,
+        kcl.UnitLength.Mm,
+        kcl.ImageFormat.Png,
+        kcl.FileExportFormat.Step,
+        on_progress=events.append,
+    )
+    assert [event.phase for event in events] == [
+        kcl.ProgressPhase.Parsing,
+        kcl.ProgressPhase.Connecting,
+        kcl.ProgressPhase.Executing,
+        kcl.ProgressPhase.Snapshotting,
+        kcl.ProgressPhase.Exporting,
+    ]
+
+    events = []
+    await kcl.execute_and_snapshot_turntable(
+        code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, frames=4, on_progress=events.append
+    )
+    frames = [e for e in events if e.phase == kcl.ProgressPhase.Snapshotting]
+    assert [(e.operation_index, e.operation_total) for e in frames] == [
+        (0, 4),
+        (1, 4),
+        (2, 4),
+        (3, 4),
+    ]
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_progress_callback_error():
+    def on_progress(event):
+        if event.phase == kcl.ProgressPhase.Executing:
+            raise RuntimeError("cancelled")
+
+    with pytest.raises(RuntimeError, match="cancelled"):
+        await kcl.execute("width = 10", on_progress=on_progress)
+
+
+@pytest.mark.asyncio
+async def test_kcl_enable_logging(caplog):
+    caplog.set_level(5, logger="kcl")
+    kcl.enable_logging("debug")
+    # Calling it again only changes the level.
+    kcl.enable_logging("trace")
+    try:
+        await kcl.execute("width = 10")
+        # Records are handed to Python on another thread.
+        for _ in range(50):
+            if any(record.name == "kcl" for record in caplog.records):
+                break
+            await asyncio.sleep(0.1)
+        assert any(record.name == "kcl" for record in caplog.records)
+    finally:
+        kcl.disable_logging()
+
+    with pytest.raises(ValueError):
+        kcl.enable_logging("loud")
+
+
+TWO_BODIES = """fn cube = (x, size) => {
+  return startSketchOn('XY')
+    |> startProfileAt([x, 0], %)
+    |> line([size, 0], %)
+    |> line([0, size], %)
+    |> line([-size, 0], %)
+    |> close(%)
+    |> extrude(size, %)
+}
+
+small = cube(0, 1)
+big = cube(10, 4)
+"""
+
+
+@pytest.mark.asyncio
+async def test_kcl_session_objects():
+    session = kcl.Session(kcl.UnitLength.Mm)
+    outcome = await session.execute(TWO_BODIES)
+    solids = [obj for obj in outcome.objects if obj.kind == "solid"]
+    assert [solid.name for solid in solids] == ["small", "big"]
+    assert solids[0].id != solids[1].id
+    assert all(solid.end > solid.start for solid in solids)
+
+    # Export just one of the bodies.
+    files = await session.export(kcl.FileExportFormat.Stl, objects=[solids[1]])
+    for extent in stl_extents(files[0].contents):
+        assert extent == pytest.approx(4.0)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_entity_names():
+    image = await kcl.execute_and_snapshot(
+        TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png, entity_names=["big"]
+    )
+    assert len(image) > 0
+    full = await kcl.execute_and_snapshot(
+        TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png
+    )
+    assert image != full
+
+    with pytest.raises(KeyError, match="medium"):
+        await kcl.execute_and_snapshot(
+            TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png, entity_names=["medium"]
+        )
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_render_mode():
+    shaded = await kcl.execute_and_snapshot(
+        TWO_BODIES,
+        kcl.UnitLength.Mm,
+        kcl.ImageFormat.Png,
+        render_mode=kcl.RenderMode.Shaded,
+    )
+    edges = await kcl.execute_and_snapshot(
+        TWO_BODIES,
+        kcl.UnitLength.Mm,
+        kcl.ImageFormat.Png,
+        render_mode=kcl.RenderMode.ShadedWithEdges,
+    )
+    assert len(shaded) > 0
+    assert shaded != edges
+
+    with pytest.raises(NotImplementedError, match="Wireframe"):
+        await kcl.execute_and_snapshot(
+            TWO_BODIES,
+            kcl.UnitLength.Mm,
+            kcl.ImageFormat.Png,
+            render_mode=kcl.RenderMode.Wireframe,
+        )
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_camera():
+    camera = kcl.CameraPose(eye=(50, -50, 40), center=(7, 2, 2), fov_degrees=30)
+    assert camera.up == (0, 0, 1)
+    image = await kcl.execute_and_snapshot(
+        TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png, camera=camera
+    )
+    assert len(image) > 0
+    fitted = await kcl.execute_and_snapshot(
+        TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png
+    )
+    assert image != fitted
+
+    with pytest.raises(ValueError):
+        kcl.CameraPose(eye=(1, 1, 1), center=(1, 1, 1))
+    with pytest.raises(ValueError):
+        kcl.CameraPose(eye=(1, 1, 1), center=(0, 0, 0), fov_degrees=180)
+
+
+def test_kcl_image_format_names():
+    for image_format in [kcl.ImageFormat.Png, kcl.ImageFormat.Jpeg]:
+        assert kcl.ImageFormat.from_str(str(image_format)) == image_format
+    assert str(kcl.ImageFormat.Jpeg) == "jpeg"
+    assert kcl.ImageFormat.from_str("PNG") == kcl.ImageFormat.Png
+    with pytest.raises(ValueError):
+        kcl.ImageFormat.from_str("webp")
+
+
+def test_kcl_version_info():
+    # This must work without any credentials.
+    info = kcl.version_info()
+    assert info["kcl"] == kcl.__version__
+    assert info["kcl_lib"] != "unknown"
+    assert info["kittycad_modeling_cmds"] != "unknown"
+
+
+@pytest.mark.asyncio
+async def test_kcl_engine_version():
+    version = await kcl.engine_version()
+    assert len(version["api_version"]) > 0
+
+
+@pytest.mark.asyncio
+async def test_kcl_check_connection():
+    status = await kcl.check_connection()
+    assert status.ok
+    assert status.latency_ms > 0
+    assert status.user is not None
+
+
+@pytest.mark.asyncio
+async def test_kcl_check_connection_failures():
+    with pytest.raises(kcl.KclAuthError) as err:
+        await kcl.check_connection(kcl.ClientConfig(api_token="not-a-real-token"))
+    assert err.value.status in (401, 403)
+    with pytest.raises(kcl.HostNotFoundError):
+        await kcl.check_connection(kcl.ClientConfig(host="https://kcl-py.invalid"))
+    # A host that never answers.
+    with pytest.raises(TimeoutError):
+        await kcl.check_connection(
+            kcl.ClientConfig(host="http://10.255.255.1"), timeout_seconds=0.5
+        )
+    with pytest.raises(ValueError):
+        await kcl.check_connection(timeout_seconds=0)
+
+
+@pytest.mark.asyncio
+async def test_kcl_missing_token(monkeypatch):
+    monkeypatch.delenv("ZOO_API_TOKEN", raising=False)
+    monkeypatch.delenv("KITTYCAD_API_TOKEN", raising=False)
+    # A host that never answers, so trying to connect would hang rather than fail.
+    monkeypatch.setenv("KITTYCAD_HOST", "http://10.255.255.1")
+    monkeypatch.setenv("ZOO_HOST", "http://10.255.255.1")
+    calls = [
+        lambda: kcl.execute("cube = startSketchOn('XY')"),
+        lambda: kcl.execute_and_snapshot("x = 1", None, kcl.ImageFormat.Png),
+        lambda: kcl.execute_and_export("x = 1", None, kcl.FileExportFormat.Step),
+        lambda: kcl.check_connection(),
+        lambda: kcl.Session(kcl.UnitLength.Mm).execute("x = 1"),
+    ]
+    for call in calls:
+        with pytest.raises(kcl.KclAuthError) as err:
+            await asyncio.wait_for(call(), timeout=2)
+        assert err.value.status is None
+        assert "KITTYCAD_API_TOKEN" in str(err.value)
+        assert "ZOO_API_TOKEN" in str(err.value)
+        assert "ClientConfig" in err.value.hint
+
+
+def test_kcl_concurrent_event_loops():
+    # Every thread runs its own event loop, and they all share the one runtime underneath.
+    errors = []
+
+    async def run_many():
+        for i in range(20):
+            diagnostics = await kcl.validate(f"width = {i}\nheight = width * 2")
+            assert diagnostics == []
+
+    def run_thread():
+        try:
+            asyncio.run(run_many())
+        except Exception as err:
+            errors.append(err)
+
+    threads = [threading.Thread(target=run_thread) for _ in range(8)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+    assert errors == []
+
+
+def test_kcl_pickle_export_file():
+    contents = bytes(range(256)) * 4
+    file = kcl.ExportFile(contents, "model.stl")
+    for copied in [pickle.loads(pickle.dumps(file)), copy.copy(file), copy.deepcopy(file)]:
+        assert bytes(copied.contents) == contents
+        assert copied.name == "model.stl"
+
+
+def test_kcl_pickle_enums():
+    for value in [
+        kcl.ImageFormat.Png,
+        kcl.FileExportFormat.Step,
+        kcl.UnitLength.In,
+        kcl.RenderMode.ShadedWithEdges,
+        kcl.ProgressPhase.Exporting,
+        kcl.ExportSelectionKind.MeshByName,
+    ]:
+        assert pickle.loads(pickle.dumps(value)) == value
+        assert copy.deepcopy(value) == value
+    formats = {kcl.ImageFormat.Png: "png", kcl.ImageFormat.Jpeg: "jpeg"}
+    assert formats[kcl.ImageFormat.Png] == "png"
+
+
+def test_kcl_pickle_options():
+    for value in [
+        kcl.FormatOptions(tab_size=4),
+        kcl.RenderSettings(show_grid=True),
+        kcl.CameraPose((10.0, 0.0, 0.0), (0.0, 0.0, 0.0), fov_degrees=30.0),
+        kcl.ExportSelection.scene_by_name("parts"),
+    ]:
+        for copied in [pickle.loads(pickle.dumps(value)), copy.copy(value), copy.deepcopy(value)]:
+            assert copied == value
+            assert type(copied).__module__ == "kcl"
+
+    appearance = kcl.Appearance("#ff000080", metalness=0.5)
+    for copied in [pickle.loads(pickle.dumps(appearance)), copy.deepcopy(appearance)]:
+        assert copied.color == appearance.color
+        assert copied.metalness == 0.5
+
+    _, _, fixes = kcl.lint_and_fix("box_width = 1\n")
+    copied = pickle.loads(pickle.dumps(fixes))
+    assert [(f.rule, f.start, f.end, f.replacement) for f in copied] == [
+        (f.rule, f.start, f.end, f.replacement) for f in fixes
+    ]
+
+    event = kcl._unpickle(
+        "ProgressEvent",
+        '{"phase": "executing", "operation_index": 1, "operation_total": 3, "message": null}',
+    )
+    copied = pickle.loads(pickle.dumps(event))
+    assert (copied.phase, copied.operation_index, copied.operation_total) == (
+        kcl.ProgressPhase.Executing,
+        1,
+        3,
+    )
+
+
+@pytest.mark.asyncio
+async def test_kcl_pickle_diagnostics():
+    diagnostics = await kcl.validate("width = height")
+    assert len(diagnostics) > 0
+    copied = pickle.loads(pickle.dumps(diagnostics))
+    assert [str(d) for d in copied] == [str(d) for d in diagnostics]
+
+
+def test_kcl_snapshot_options():
+    options = kcl.SnapshotOptions()
+    assert options.units is None
+    assert options.deterministic
+    assert not options.allow_empty
+
+    options = kcl.SnapshotOptions(units="in", entity_names=["body"])
+    assert options.units == kcl.UnitLength.In
+    assert options.entity_names == ["body"]
+    with pytest.raises(ValueError):
+        kcl.SnapshotOptions(units="furlong")
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_ex():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    options = kcl.SnapshotOptions(units=kcl.UnitLength.Mm, image_format=kcl.ImageFormat.Png)
+    result = await kcl.execute_and_snapshot_ex(code, options)
+    assert isinstance(result.data, bytes)
+    assert result.data.startswith(b"\x89PNG")
+    assert result.format == kcl.ImageFormat.Png
+    assert result.width > 0 and result.height > 0
+    assert result.camera.eye != result.camera.center
+    assert result.execution_ms > 0
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_export_sketch():
+    square = """
+square = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([100, 0], %)
+  |> line([0, 100], %)
+  |> line([-100, 0], %)
+  |> close(%)
+"""
+    files = await kcl.execute_and_export_sketch(
+        square, kcl.UnitLength.Mm, kcl.SketchExportFormat.Svg
+    )
+    assert [file.name for file in files] == ["square.svg"]
+    svg = bytes(files[0].contents).decode()
+    assert 'width="100mm"' in svg and 'height="100mm"' in svg
+
+    files = await kcl.execute_and_export_sketch(
+        square, kcl.UnitLength.Mm, kcl.SketchExportFormat.Dxf
+    )
+    dxf = bytes(files[0].contents).decode().splitlines()
+    assert dxf[dxf.index("$INSUNITS") + 2] == "4"
+    assert dxf.count("LINE") == 4
+
+    with pytest.raises(ValueError, match="no sketches"):
+        await kcl.execute_and_export_sketch(
+            square + "  |> extrude(10, %)\n",
+            kcl.UnitLength.Mm,
+            kcl.SketchExportFormat.Svg,
+        )
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_get_mesh_stats():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    for export_format in [kcl.FileExportFormat.Glb, kcl.FileExportFormat.Stl]:
+        stats = await kcl.execute_and_get_mesh_stats(
+            code, kcl.UnitLength.Mm, export_format
+        )
+        assert len(stats) == 1
+        assert stats[0].triangle_count > 0
+        assert 0 < stats[0].vertex_count <= 3 * stats[0].triangle_count
+        assert stats[0].byte_size > 0
+
+    with pytest.raises(ValueError):
+        await kcl.execute_and_get_mesh_stats(
+            code, kcl.UnitLength.Mm, kcl.FileExportFormat.Step
+        )
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_deterministic():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    first = await kcl.execute_and_snapshot(
+        code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, deterministic=True
+    )
+    second = await kcl.execute_and_snapshot(
+        code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, deterministic=True
+    )
+    assert bytes(first) == bytes(second)
+
+
+def test_kcl_snippet():
+    code = 'a = 1\r\nnäme = "ü" + y\r\nc = 3\r\n'
+    start = code.encode().index(b"y")
+    snippet = kcl.snippet(code, (start, start + 1))
+    assert snippet.lines == ["a = 1", 'näme = "ü" + y', "c = 3"]
+    assert snippet.first_line == 0
+    assert (snippet.start_line, snippet.start_column) == (1, 13)
+    assert (snippet.end_line, snippet.end_column) == (1, 14)
+    assert snippet.underline == " " * 13 + "^"
+
+    with pytest.raises(ValueError):
+        kcl.snippet(code, (0, len(code.encode()) + 1))
+
+
+@pytest.mark.asyncio
+async def test_kcl_diagnostic_snippet():
+    code = "x = 1\n\ty = zz\n"
+    diagnostics = await kcl.validate(code)
+    snippet = diagnostics[0].snippet(code, context_lines=0)
+    assert snippet.lines == ["\ty = zz"]
+    assert snippet.underline == "\t    ^^"
+
+
+@pytest.mark.asyncio
+async def test_kcl_session_trace():
+    code = """width = 10
+height = width * 2 + 0.5
+size = [width, height]
+box = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([width, 0], %)
+  |> line([0, height], %)
+  |> close(%)
+  |> extrude(1, %)
+"""
+    session = kcl.Session(kcl.UnitLength.Mm)
+    outcome = await session.execute(code)
+    trace = {entry.name: entry for entry in outcome.trace}
+    assert list(trace) == ["width", "height", "size", "box"]
+    assert float(trace["height"].value) == 20.5
+    assert trace["size"].value.startswith("[")
+    assert trace["box"].value == "<Solid>"
+    start, end = trace["height"].source_range
+    assert code.encode()[start:end].decode().startswith("height")
+
+
+@pytest.mark.asyncio
+async def test_kcl_export_selection():
+    session = kcl.Session(kcl.UnitLength.Mm)
+    outcome = await session.execute(TWO_BODIES)
+    big = [obj for obj in outcome.objects if obj.name == "big"]
+
+    files = await session.export(
+        kcl.FileExportFormat.Ply,
+        objects=big,
+        selection=kcl.ExportSelection.objects(),
+    )
+    assert len(files) > 0
+
+    # These are all rejected before anything is sent to the engine.
+    with pytest.raises(ValueError):
+        await session.export(
+            kcl.FileExportFormat.Stl, selection=kcl.ExportSelection.objects()
+        )
+    with pytest.raises(ValueError):
+        await session.export(
+            kcl.FileExportFormat.Stl,
+            objects=big,
+            selection=kcl.ExportSelection.scene_by_index(0),
+        )
+    with pytest.raises(ValueError):
+        await kcl.execute_and_export(
+            TWO_BODIES,
+            kcl.UnitLength.Mm,
+            kcl.FileExportFormat.Step,
+            selection=kcl.ExportSelection.default_scene(),
+        )
+
+
+def test_kcl_export_selection_kind():
+    selection = kcl.ExportSelection.scene_by_index(2)
+    assert selection.kind == kcl.ExportSelectionKind.SceneByIndex
+    assert str(selection.kind) == "scene_by_index"
+    assert selection.index == 2
+    assert selection.name is None
+    assert kcl.ExportSelection.mesh_by_name("m").kind == kcl.ExportSelectionKind.MeshByName
+    assert kcl.ExportSelection.objects().kind == kcl.ExportSelectionKind.Objects
+
+
+@pytest.mark.asyncio
+async def test_kcl_export_format_options():
+    files = await kcl.execute_and_export(
+        TWO_BODIES,
+        kcl.UnitLength.Mm,
+        kcl.FileExportFormat.Fbx,
+        format_options=kcl.FbxOptions(storage="ascii"),
+    )
+    assert len(files) > 0
+    assert b"FBXHeaderExtension" in bytes(files[0].contents)
+
+    with pytest.raises(ValueError):
+        kcl.FbxOptions(storage="text")
+    with pytest.raises(ValueError):
+        await kcl.execute_and_export(
+            TWO_BODIES,
+            kcl.UnitLength.Mm,
+            kcl.FileExportFormat.Step,
+            format_options=kcl.StlOptions(storage="binary"),
+        )
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_steps():
+    steps = await kcl.execute_and_snapshot_steps(
+        TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png
+    )
+    assert len(steps) > 1
+    starts = [start for (start, _), _ in steps]
+    assert starts == sorted(starts)
+    for (start, end), image_bytes in steps:
+        assert TWO_BODIES[start:end].strip() != ""
+        assert len(image_bytes) > 0
+    # Each step adds to the scene, from the same camera.
+    assert steps[0][1] != steps[-1][1]
+
+
+def test_kcl_type_stubs():
+    stubs_path = os.path.join(os.path.dirname(os.path.realpath(__file__)), "..", "kcl.pyi")
+    with open(stubs_path, "r") as f:
+        stubs = f.read()
+    # The shipped stubs are what this build of the module generates, so they can't fall behind it.
+    assert stubs == kcl.generate_stubs()
+
+    tree = ast.parse(stubs)
+    names = {node.target.id for node in tree.body if isinstance(node, ast.AnnAssign)}
+    functions = {
+        node.name: node for node in tree.body if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef))
+    }
+    names |= {node.name for node in tree.body if isinstance(node, ast.ClassDef)} | set(functions)
+    assert names == set(kcl.__all__)
+    # The pickling helper and the stub generator are left out of `__all__`, and so of the stubs.
+    helpers = {"_unpickle", "generate_stubs"}
+    assert not helpers & names
+    assert {name for name in dir(kcl) if not name.startswith("__")} - helpers <= names
+
+    execute = functions["execute"]
+    assert isinstance(execute, ast.AsyncFunctionDef)
+    assert [arg.arg for arg in execute.args.args][:2] == ["code", "units"]
+    assert ast.unparse(execute.args.args[0].annotation) == "Union[str, Program]"
+    assert ast.unparse(execute.args.args[1].annotation) == "Optional[Union[UnitLength, str]]"
+    assert ast.unparse(execute.returns) == "Union[list[Diagnostic], tuple[list[Diagnostic], RunStats]]"
+    assert isinstance(functions["format"], ast.FunctionDef)
+    assert ast.unparse(functions["format"].returns) == "str"
+    assert "Any]" not in ast.unparse(functions["lint"])
+    unit_length = next(node for node in tree.body if getattr(node, "name", "") == "UnitLength")
+    members = {node.target.id for node in unit_length.body if isinstance(node, ast.AnnAssign)}
+    assert {"Mm", "In"} <= members
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_return_stats():
+    diagnostics = await kcl.execute(TWO_BODIES, kcl.UnitLength.Mm)
+    assert isinstance(diagnostics, list)
+
+    diagnostics, stats = await kcl.execute(TWO_BODIES, kcl.UnitLength.Mm, return_stats=True)
+    assert isinstance(diagnostics, list)
+    assert stats.parse_ms >= 0
+    assert stats.connect_ms >= 0
+    assert stats.execution_ms >= 0
+    # Executing the program sends commands to the engine, which are counted too.
+    assert stats.command_count > 0
+    assert pickle.loads(pickle.dumps(stats)) == stats
+
+    _, export_stats = await kcl.execute_and_export(
+        TWO_BODIES, kcl.UnitLength.Mm, kcl.FileExportFormat.Step, return_stats=True
+    )
+    # Exporting sends more commands than just executing.
+    assert export_stats.command_count > stats.command_count
+
+
+@pytest.mark.asyncio
+async def test_kcl_engine_error():
+    code = """sketch = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([10, 0], %, $side)
+  |> line([0, 10], %)
+  |> line([-10, 0], %)
+  |> close(%)
+  |> extrude(10, %)
+  |> fillet({ radius = 50, tags = [getOppositeEdge(side)] }, %)
+"""
+    with pytest.raises(kcl.KclEngineError) as err:
+        await kcl.execute(code, kcl.UnitLength.Mm)
+    assert err.value.message
+    assert err.value.start is not None and err.value.end is not None
+    assert "fillet" in code[err.value.start : err.value.end]
+    assert issubclass(kcl.UnexpectedResponseError, kcl.KclEngineError)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_export_base_name():
+    files = await kcl.execute_and_export(
+        TWO_BODIES, kcl.UnitLength.Mm, kcl.FileExportFormat.Step, base_name="part-r2"
+    )
+    assert [file.name for file in files] == ["part-r2.step"]
+
+    files = await kcl.execute_and_export(
+        TWO_BODIES,
+        kcl.UnitLength.Mm,
+        kcl.FileExportFormat.Gltf,
+        format_options=kcl.GltfOptions(storage="standard"),
+        base_name="part-r2",
+    )
+    names = {file.name for file in files}
+    assert "part-r2.gltf" in names
+    gltf = next(file for file in files if file.name == "part-r2.gltf")
+    for buffer in json.loads(bytes(gltf.contents))["buffers"]:
+        assert buffer["uri"] in names
+
+    for base_name in ["", "a/b", "..\\part"]:
+        with pytest.raises(ValueError):
+            await kcl.execute_and_export(
+                TWO_BODIES, kcl.UnitLength.Mm, kcl.FileExportFormat.Step, base_name=base_name
+            )
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_frame_objects():
+    whole = await kcl.execute_and_snapshot(TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png)
+    framed = await kcl.execute_and_snapshot(
+        TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png, frame_objects=["small"]
+    )
+    assert len(framed) > 0
+    assert framed != whole
+    assert (
+        await kcl.execute_and_snapshot(
+            TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png, frame_objects=[]
+        )
+        == whole
+    )
+
+    with pytest.raises(KeyError):
+        await kcl.execute_and_snapshot(
+            TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png, frame_objects=["missing"]
+        )
+    camera = kcl.CameraPose(eye=(10, 10, 10), center=(0, 0, 0), up=(0, 0, 1))
+    with pytest.raises(ValueError):
+        await kcl.execute_and_snapshot(
+            TWO_BODIES, kcl.UnitLength.Mm, kcl.ImageFormat.Png, camera=camera, frame_objects=["small"]
+        )
+
+
+@pytest.mark.asyncio
+async def test_kcl_recast_edited_ast():
+    code = """size = 4
+box = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([size, 0], %)
+  |> line([0, size], %)
+  |> line([-size, 0], %)
+  |> close(%)
+  |> extrude(size, %)
+"""
+    ast = kcl.parse(code)
+    assert kcl.recast(ast) == kcl.format(code)
+
+    ast["body"][0]["declaration"]["init"]["value"] = 6
+    ast["body"][0]["declaration"]["init"]["raw"] = "6"
+    edited = kcl.recast(ast)
+    assert edited.startswith("size = 6\n")
+    await kcl.execute(edited, kcl.UnitLength.Mm)
+
+    ast["body"][0]["declaration"]["init"]["value"] = [6]
+    with pytest.raises(kcl.InvalidAstError) as err:
+        kcl.recast(ast)
+    assert err.value.path == "body[0].declaration.init"
+    assert isinstance(err.value, ValueError)
+
+
+def test_kcl_lint_severity():
+    with open(os.path.join(kcl_dir_file_path, "box_with_linter_errors.kcl"), "r") as f:
+        code = f.read()
+    lints = kcl.lint(code)
+    assert len(lints) > 0
+    # Names that aren't lowerCamelCase go against convention, but aren't mistakes.
+    assert all(lint.severity == kcl.LintSeverity.Warning for lint in lints)
+    assert kcl.LintSeverity.Info < kcl.LintSeverity.Warning < kcl.LintSeverity.Error
+    assert len(kcl.lint(code, min_severity="warning")) == len(lints)
+    assert kcl.lint(code, min_severity=kcl.LintSeverity.Error) == []
+
+    assert len(kcl.lint_strict(code, fail_on="error")) == len(lints)
+    with pytest.raises(kcl.KclLintError) as err:
+        kcl.lint_strict(code)
+    message, findings = err.value.args
+    assert "Z0001" in message
+    assert [finding.pos for finding in findings] == [lint.pos for lint in lints]
+    assert all(isinstance(finding, kcl.LintFinding) for finding in findings)
+
+    with pytest.raises(ValueError):
+        kcl.lint(code, min_severity="loud")
+
+
+@pytest.mark.asyncio
+async def test_kcl_token_provider():
+    token = os.environ.get("KITTYCAD_API_TOKEN") or os.environ.get("ZOO_API_TOKEN")
+    tokens = iter(["expired-token", token])
+    calls = []
+
+    def provider():
+        calls.append(1)
+        return next(tokens)
+
+    # The first token is rejected, so the provider is asked for another and the connection is tried again.
+    session = kcl.Session(kcl.UnitLength.Mm, config=kcl.ClientConfig(token_provider=provider))
+    outcome = await session.execute(TWO_BODIES)
+    assert len(outcome.objects) > 0
+    assert len(calls) == 2
+
+    def failing_provider():
+        raise RuntimeError("sidecar is down")
+
+    with pytest.raises(kcl.KclAuthError, match="sidecar is down"):
+        await kcl.check_connection(kcl.ClientConfig(token_provider=failing_provider))
+    with pytest.raises(TypeError):
+        kcl.ClientConfig(token_provider="not-callable")
+
+
+@pytest.mark.asyncio
+async def test_kcl_session_default_planes():
+    session = kcl.Session(kcl.UnitLength.Mm)
+    outcome = await session.execute(TWO_BODIES)
+    assert sorted(outcome.default_planes) == ["-XY", "-XZ", "-YZ", "XY", "XZ", "YZ"]
+    assert len(set(outcome.default_planes.values())) == 6
+
+    session = kcl.Session(kcl.UnitLength.Mm, create_default_planes=False)
+    outcome = await session.execute("size = 1\n")
+    assert outcome.default_planes == {}
+
+    # The error points at where the plane is used.
+    with pytest.raises(Exception) as err:
+        await session.execute(TWO_BODIES)
+    assert "XY" in str(err.value)
+    assert "create_default_planes" in str(err.value)
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_snapshot_iter():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    results = {}
+    async for index, result in kcl.execute_and_snapshot_iter(
+        [code, "width = undefinedThing * 2", code],
+        kcl.UnitLength.Mm,
+        kcl.ImageFormat.Png,
+        max_concurrency=2,
+    ):
+        results[index] = result
+    assert sorted(results) == [0, 1, 2]
+    assert len(results[0]) > 0
+    assert isinstance(results[1], kcl.BatchError)
+    assert "undefinedThing" in results[1].message
+
+    # Stopping early leaves the rest to be cancelled.
+    async for index, result in kcl.execute_and_snapshot_iter(
+        [code] * 4, kcl.UnitLength.Mm, kcl.ImageFormat.Png, max_concurrency=1
+    ):
+        assert len(result) > 0
+        break
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_get_variables():
+    code = """bom_width = 10
+bom_parts = [1, 2.5]
+other = "not wanted"
+""" + TWO_BODIES
+    result = await kcl.execute_and_get_variables(
+        code, kcl.UnitLength.Mm, names=["bom_*", "big", "bom_color"]
+    )
+    assert [variable.name for variable in result.variables] == [
+        "bom_width",
+        "bom_parts",
+        "big",
+    ]
+    assert result.missing == ["bom_color"]
+
+    width = result.get("bom_width")
+    assert width.value == 10
+    assert width.type_name == "Number"
+    assert code[width.source_range[0] : width.source_range[1]] == "bom_width = 10"
+    assert result.get("bom_parts").value == [1, 2.5]
+    assert result.get("big").value is None
+    assert result.get("big").type_name == "Solid"
+
+    everything = await kcl.execute_and_get_variables(code, kcl.UnitLength.Mm)
+    assert len(everything.variables) > len(result.variables)
+    assert everything.missing == []
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_get_tags():
+    code = """fn post(x) {
+  return startSketchOn('XY')
+    |> startProfileAt([x, 0], %)
+    |> line([1, 0], %, $post_side)
+    |> line([0, 1], %)
+    |> line([-1, 0], %)
+    |> close(%)
+    |> extrude(3, %)
+}
+
+base = startSketchOn('XY')
+  |> startProfileAt([0, -10], %)
+  |> line([4, 0], %, $front)
+  |> line([0, 2], %)
+  |> line([-4, 0], %)
+  |> close(%)
+  |> extrude(2, %)
+posts = [post(0), post(5)]
+outline = startSketchOn('XZ')
+  |> startProfileAt([0, 20], %)
+  |> line([5, 0], %, $rail)
+"""
+    tags = await kcl.execute_and_get_tags(code, kcl.UnitLength.Mm)
+
+    front = tags["front"]
+    assert front.kind == "face"
+    assert front.index == 0
+    assert code[front.source_range[0] : front.source_range[1]].endswith("front")
+    assert front.area == pytest.approx(8)
+    assert front.normal is not None
+    assert abs(front.normal[1]) == pytest.approx(1)
+
+    rail = tags["rail"]
+    assert rail.kind == "edge"
+    assert rail.length == pytest.approx(5)
+    assert rail.area is None
+
+    # The function was called twice, so there are two tags with the same name and neither is dropped.
+    assert "post_side" not in tags
+    sides = [tags["post_side[0]"], tags["post_side[1]"]]
+    assert [side.index for side in sides] == [0, 1]
+    assert sides[0].entity_id != sides[1].entity_id
+    assert all(side.kind == "face" for side in sides)
+
+    restored = pickle.loads(pickle.dumps(front))
+    assert restored.entity_id == front.entity_id
+    assert restored.normal == front.normal
+
+
+@pytest.mark.asyncio
+async def test_kcl_session_pool():
+    session = kcl.Session(kcl.UnitLength.Mm, pool_size=2)
+    assert session.pool_size == 2
+    await session.execute(TWO_BODIES)
+
+    # Both exports of the whole scene run at once, each on its own connection.
+    step, stl = await asyncio.gather(
+        session.export(kcl.FileExportFormat.Step),
+        session.export(kcl.FileExportFormat.Stl),
+    )
+    assert len(step) == 1 and len(step[0].contents) > 0
+    assert len(stl) == 1 and len(stl[0].contents) > 0
+
+    await session.close()
+    with pytest.raises(Exception, match="Nothing has been executed"):
+        await session.export(kcl.FileExportFormat.Stl)
+
+    with pytest.raises(ValueError):
+        kcl.Session(kcl.UnitLength.Mm, pool_size=0)
+
+
+@pytest.mark.asyncio
+async def test_kcl_export_file_units():
+    files = await kcl.execute_and_export(
+        TWO_BODIES, kcl.UnitLength.In, kcl.FileExportFormat.Stl
+    )
+    assert files[0].units == kcl.UnitLength.In
+    assert files[0].format == "stl"
+
+    scaled = files[0].scale_to(kcl.UnitLength.Mm)
+    assert scaled.units == kcl.UnitLength.Mm
+    assert max(stl_extents(scaled.contents)) == pytest.approx(
+        max(stl_extents(files[0].contents)) * 25.4
+    )
+
+    step = await kcl.execute_and_export(
+        TWO_BODIES, kcl.UnitLength.In, kcl.FileExportFormat.Step
+    )
+    assert step[0].format == "step"
+    with pytest.raises(NotImplementedError):
+        step[0].scale_to(kcl.UnitLength.Mm)
+
+
+@pytest.mark.asyncio
+async def test_kcl_empty_scene():
+    code = "width = 10\nheight = width * 2\n"
+    with pytest.raises(kcl.KclEmptySceneError) as err:
+        await kcl.execute_and_snapshot(code, kcl.UnitLength.Mm, kcl.ImageFormat.Png)
+    assert err.value.object_count == 0
+    assert code[err.value.start : err.value.end] == "height = width * 2"
+
+    with pytest.raises(kcl.KclEmptySceneError):
+        await kcl.execute_and_export(code, kcl.UnitLength.Mm, kcl.FileExportFormat.Stl)
+
+    image = await kcl.execute_and_snapshot(
+        code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, allow_empty=True
+    )
+    assert len(image) > 0
+
+
+def test_kcl_program():
+    code = "width   = 10\n"
+    program = kcl.Program.parse(code)
+    assert program.source() == code
+    assert program.recast() == kcl.format(code)
+    assert kcl.format(program) == kcl.format(code)
+    assert program.digest() == kcl.program_digest(code)
+    assert kcl.program_digest(program) == program.digest()
+    assert kcl.lint(program) == []
+    assert pickle.loads(pickle.dumps(program)).source() == code
+
+    with pytest.raises(Exception):
+        kcl.Program.parse("width = (")
+
+    # Digesting the parsed program skips parsing, which dominates for a large one.
+    big = "\n".join(f"v{i} = {i} * 2 + 1" for i in range(5000))
+    parsed = kcl.Program.parse(big)
+    start = time.perf_counter()
+    kcl.program_digest(big)
+    from_code = time.perf_counter() - start
+    start = time.perf_counter()
+    kcl.program_digest(parsed)
+    from_program = time.perf_counter() - start
+    assert from_program < from_code
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_program():
+    program = kcl.Program.parse(TWO_BODIES)
+    assert await kcl.execute(program, kcl.UnitLength.Mm) == []
+    files = await kcl.execute_and_export(
+        program, kcl.UnitLength.Mm, kcl.FileExportFormat.Stl
+    )
+    assert len(files) > 0
+
+
+def test_kcl_check_paths_reports_every_file(tmp_path):
+    good = tmp_path / "good.kcl"
+    good.write_text("x = 1\n")
+    unformatted = tmp_path / "unformatted.kcl"
+    unformatted.write_text("x   =  1\n")
+    broken = tmp_path / "broken.kcl"
+    broken.write_text("x = (")
+    binary = tmp_path / "binary.kcl"
+    binary.write_bytes(b"\xff\xfe")
+    missing = tmp_path / "missing.kcl"
+    paths = [str(p) for p in [unformatted, missing, good, broken, binary]]
+
+    report = kcl.format_check_paths(paths)
+    assert [file.path for file in report.files] == paths
+    assert report[str(good)].formatted is True
+    assert report[str(unformatted)].formatted is False
+    for path in [missing, broken, binary]:
+        assert report[str(path)].formatted is None
+        assert report[str(path)].error is not None
+    assert not report.ok
+    assert [file.path for file in report.failed()] == [
+        str(unformatted),
+        str(missing),
+        str(broken),
+        str(binary),
+    ]
+
+    report = kcl.lint_paths(paths)
+    assert len(report) == len(paths)
+    assert report[str(good)].ok
+    assert report[str(unformatted)].ok
+    assert "UTF-8" in report[str(binary)].error.message
+    with pytest.raises(KeyError):
+        report[str(tmp_path / "other.kcl")]
+
+
+@pytest.mark.asyncio
+async def test_kcl_deterministic_snapshots_are_identical():
+    settings = kcl.RenderSettings.deterministic()
+    assert not settings.enable_ssao
+    assert settings.settle_frames > 0
+
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    # PNG, since JPEG compression could hide differences between the images.
+    first = await kcl.execute_and_snapshot(
+        code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, render_settings=settings
+    )
+    second = await kcl.execute_and_snapshot(
+        code, kcl.UnitLength.Mm, kcl.ImageFormat.Png, render_settings=settings
+    )
+    assert first == second
+
+
+@pytest.mark.asyncio
+async def test_kcl_session_snapshot_and_reset():
+    session = kcl.Session(kcl.UnitLength.Mm)
+    with pytest.raises(Exception, match="Nothing has been executed"):
+        await session.snapshot(kcl.ImageFormat.Png)
+
+    # Execute once, then look at it from different views without running it again.
+    outcome = await session.execute(TWO_BODIES)
+    fitted = await session.snapshot(kcl.ImageFormat.Png)
+    side = await session.snapshot(
+        kcl.ImageFormat.Png, camera=kcl.CameraPose(eye=(0, -100, 0), center=(0, 0, 0))
+    )
+    assert len(fitted) > 0 and len(side) > 0
+    assert fitted != side
+    small = [obj for obj in outcome.objects if obj.name == "small"]
+    assert len(await session.snapshot(kcl.ImageFormat.Png, frame_objects=small)) > 0
+    files = await session.export(kcl.FileExportFormat.Stl)
+    assert len(files) > 0 and len(files[0].contents) > 0
+
+    # After a reset the scene is empty until something is executed again.
+    await session.reset()
+    with pytest.raises(kcl.KclEmptySceneError):
+        await session.snapshot(kcl.ImageFormat.Png)
+    with pytest.raises(kcl.KclEmptySceneError):
+        await session.export(kcl.FileExportFormat.Stl)
+    outcome = await session.execute(TWO_BODIES)
+    assert not outcome.cache_hit
+    assert len(await session.snapshot(kcl.ImageFormat.Png)) > 0
+
+
+def test_kcl_lint_sarif(tmp_path):
+    jsonschema = pytest.importorskip("jsonschema")
+    with open(os.path.join(kcl_dir_file_path, "box_with_linter_errors.kcl"), "r") as f:
+        code = str(f.read())
+    broken = tmp_path / "broken.kcl"
+    broken.write_text("x = (")
+    paths = [os.path.join(kcl_dir_file_path, "box_with_linter_errors.kcl"), str(broken)]
+
+    for document in [kcl.lint_sarif(code), kcl.lint_sarif(paths, rules=["Z0001"])]:
+        sarif = json.loads(document)
+        with urllib.request.urlopen(sarif["$schema"]) as response:
+            schema = json.load(response)
+        jsonschema.validate(sarif, schema)
+        assert sarif["version"] == "2.1.0"
+
+    run = json.loads(kcl.lint_sarif(code))["runs"][0]
+    driver = run["tool"]["driver"]
+    assert driver["version"] == kcl.version_info()["kcl"]
+    assert [rule["id"] for rule in driver["rules"]] == ["Z0001", "Z0002", "Z0003"]
+    findings = kcl.lint(code)
+    assert len(run["results"]) == len(findings)
+    result = run["results"][0]
+    assert result["ruleId"] == findings[0].finding.code
+    region = result["locations"][0]["physicalLocation"]["region"]
+    start, end = findings[0].pos
+    assert region["byteOffset"] == start
+    assert region["startLine"] == code[:start].count("\n") + 1
+
+    # Files that can't be parsed are reported, not raised.
+    run = json.loads(kcl.lint_sarif(paths, rules=["Z0001"]))["runs"][0]
+    assert [rule["id"] for rule in run["tool"]["driver"]["rules"]] == ["Z0001"]
+    notifications = run["invocations"][0]["toolExecutionNotifications"]
+    assert len(notifications) == 1
+    assert notifications[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"] == str(broken)
+
+    with pytest.raises(ValueError):
+        kcl.lint_sarif(code, rules=["Z9999"])
+
+
+def test_kcl_export_file_contents_are_not_copied(tmp_path):
+    data = os.urandom(16 * 1024 * 1024)
+    file = kcl.ExportFile(data, "model.step")
+
+    # Reading the contents gives a view of the file's own buffer, however many times it's done.
+    tracemalloc.start()
+    views = [file.contents for _ in range(10)] + [memoryview(file)]
+    _, peak = tracemalloc.get_traced_memory()
+    tracemalloc.stop()
+    assert peak < len(data)
+    for view in views:
+        assert view.readonly
+        assert view.obj is file
+        assert sys.getsizeof(view) < 1024
+    assert views[0] == data
+    with pytest.raises(TypeError):
+        views[0][0] = 0
+
+    # The view keeps the file alive.
+    del file, views[1:]
+    assert bytes(views[0][:16]) == data[:16]
+
+    path = tmp_path / "model.step"
+    kcl.ExportFile(data, "model.step").save(str(path))
+    assert path.read_bytes() == data
+
+
+@pytest.mark.asyncio
+@pytest.mark.skipif(
+    not os.environ.get("KCL_TEST_LARGE_EXPORT"),
+    reason="set KCL_TEST_LARGE_EXPORT to export a very large model",
+)
+async def test_kcl_large_export(tmp_path):
+    code = TWO_BODIES + "".join(f"c{i} = cube({i * 2}, 1)\n" for i in range(2000))
+    files = await kcl.execute_and_export(
+        code, kcl.UnitLength.Mm, kcl.FileExportFormat.Step
+    )
+    path = tmp_path / files[0].name
+    files[0].save(str(path))
+    assert path.stat().st_size == len(files[0].contents)
+
+
+def test_kcl_validate_export_finds_broken_files():
+    # A binary STL whose header counts more triangles than the file has.
+    stl = bytes(80) + (2).to_bytes(4, "little") + bytes(50)
+    problems = kcl.validate_export(kcl.ExportFile(stl, "model.stl"))
+    assert len(problems) == 1
+    assert "2 triangles" in problems[0]
+
+    # A GLB cut short in its BIN chunk.
+    document = json.dumps(
+        {
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 36}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3}
+            ],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+        }
+    ).encode()
+    document += b" " * (-len(document) % 4)
+    chunks = (
+        len(document).to_bytes(4, "little")
+        + b"JSON"
+        + document
+        + (36).to_bytes(4, "little")
+        + b"BIN\0"
+        + bytes(36)
+    )
+    glb = (
+        b"glTF"
+        + (2).to_bytes(4, "little")
+        + (12 + len(chunks)).to_bytes(4, "little")
+        + chunks
+    )
+    assert kcl.validate_export(kcl.ExportFile(glb, "model.glb")) == []
+    problems = kcl.validate_export(kcl.ExportFile(glb[:-8], "model.glb"))
+    assert any("header says the file is" in problem for problem in problems)
+    assert any("BIN chunk" in problem for problem in problems)
+
+    step = b"ISO-10303-21;\nHEADER;\nFILE_SCHEMA(('AP214'));\nENDSEC;\nDATA;\n"
+    problems = kcl.validate_export(kcl.ExportFile(step, "model.step"))
+    assert len(problems) == 1
+    assert "END-ISO-10303-21;" in problems[0]
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_export_validate():
+    with open(os.path.join(kcl_dir_file_path, "lego.kcl"), "r") as f:
+        code = str(f.read())
+    for export_format in [
+        kcl.FileExportFormat.Glb,
+        kcl.FileExportFormat.Gltf,
+        kcl.FileExportFormat.Stl,
+        kcl.FileExportFormat.Step,
+    ]:
+        files = await kcl.execute_and_export(
+            code, kcl.UnitLength.Mm, export_format, validate=True
+        )
+        for file in files:
+            assert kcl.validate_export(file) == []
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="needs SIGINT")
+def test_kcl_execute_and_export_keyboard_interrupt(tmp_path):
+    code = TWO_BODIES + "".join(f"c{i} = cube({i * 2}, 1)\n" for i in range(2000))
+    (tmp_path / "main.kcl").write_text(code)
+    script = tmp_path / "export.py"
+    script.write_text(
+        "import asyncio, sys, kcl\n"
+        "code = open(sys.argv[1]).read()\n"
+        "print('started', flush=True)\n"
+        "asyncio.run(kcl.execute_and_export(code, 'mm', kcl.FileExportFormat.Step))\n"
+    )
+    process = subprocess.Popen(
+        [sys.executable, str(script), str(tmp_path / "main.kcl")],
+        stdout=subprocess.PIPE,
+        stderr=subprocess.PIPE,
+    )
+    assert process.stdout.readline().strip() == b"started"
+    time.sleep(2)
+
+    process.send_signal(signal.SIGINT)
+    try:
+        _, stderr = process.communicate(timeout=5)
+    except subprocess.TimeoutExpired:
+        process.kill()
+        raise
+    assert process.returncode != 0
+    assert b"KeyboardInterrupt" in stderr
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_get_scene():
+    code = """base = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([4, 0], %, $front)
+  |> line([0, 4], %)
+  |> line([-4, 0], %)
+  |> close(%)
+  |> extrude(2, %)
+  |> fillet({ radius: 0.5, tags: [getNextAdjacentEdge(front)] }, %)
+copies = patternLinear3d({ axis: [1, 0, 0], repetitions: 2, distance: 6 }, base)
+"""
+    scene = await kcl.execute_and_get_scene(code, kcl.UnitLength.Mm)
+
+    plane = scene.find("XY")
+    assert plane.kind == "plane"
+    [sketch] = plane.children
+    assert sketch.kind == "sketch"
+    assert [child.kind for child in sketch.children].count("segment") >= 4
+
+    base = scene.find("base")
+    assert base.kind == "solid"
+    assert "base" in [child.name for child in sketch.children]
+    assert code[base.source_range[0] : base.source_range[1]].startswith("extrude")
+    kinds = [child.kind for child in base.children]
+    assert "face" in kinds and "fillet" in kinds
+    assert scene.find("front").kind in ("segment", "face")
+    assert any(child.name == "start cap" for child in base.children)
+
+    pattern = scene.find("copies")
+    assert pattern.kind == "pattern"
+    assert pattern.id is None
+    assert [child.name for child in pattern.children] == ["copies[1]", "copies[2]"]
+    assert all(child.kind == "solid" for child in pattern.children)
+
+    # The tree comes out the same every time, so it can be compared against a saved copy.
+    again = await kcl.execute_and_get_scene(code, kcl.UnitLength.Mm)
+    assert str(again) == str(scene)
+    assert str(scene).splitlines()[0] == "XY (plane)"
+    assert pickle.loads(pickle.dumps(scene)).find("base").name == "base"
+
+
+@pytest.mark.asyncio
+async def test_kcl_client_config_headers():
+    # A server that records each request and turns it away, standing in for the API behind a proxy.
+    server = socket.socket()
+    server.bind(("127.0.0.1", 0))
+    server.listen(8)
+    requests = []
+
+    def serve():
+        while True:
+            connection, _ = server.accept()
+            with connection:
+                data = b""
+                while b"\r\n\r\n" not in data:
+                    chunk = connection.recv(4096)
+                    if not chunk:
+                        break
+                    data += chunk
+                requests.append(data.decode("latin-1").lower())
+                connection.sendall(
+                    b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+
+    threading.Thread(target=serve, daemon=True).start()
+    config = kcl.ClientConfig(
+        api_token="not-a-token",
+        host="http://127.0.0.1:%d" % server.getsockname()[1],
+        user_agent="corp-agent/1.0",
+        headers={"X-Corp-Proxy": "yes"},
+    )
+    with pytest.raises(kcl.KclAuthError) as err:
+        await kcl.Session(kcl.UnitLength.Mm, config=config).execute("x = 1")
+    # Read from the response to the websocket handshake, not the message.
+    assert err.value.status == 401
+
+    upgrades = [request for request in requests if "upgrade: websocket" in request]
+    assert upgrades
+    for request in upgrades:
+        assert "x-corp-proxy: yes" in request
+        assert "user-agent: corp-agent/1.0" in request
+        assert "authorization: bearer not-a-token" in request
+
+    for reserved in ["Authorization", "host"]:
+        with pytest.raises(ValueError):
+            kcl.ClientConfig(headers={reserved: "x"})
+    with pytest.raises(ValueError):
+        config.headers = {"Host": "example.com"}
+    assert config.headers == {"X-Corp-Proxy": "yes"}
+
+
+def test_kcl_check():
+    formatted = kcl.format("my_var = 1\n")
+    report = kcl.check(formatted)
+    assert report.parse_errors == []
+    assert report.format_diff == ""
+    assert len(report.lint_findings) == 1
+    assert not report.ok
+    assert kcl.check(formatted, rules=["Z9999"]).ok
+    assert kcl.check(formatted.replace("\n", "\r\n"), rules=[]).format_diff == ""
+
+    report = kcl.check("x = 1\ny=2\n", rules=[])
+    assert report.format_diff == "--- original\n+++ formatted\n@@ -1,2 +1,2 @@\n x = 1\n-y=2\n+y = 2\n"
+    assert not report.ok
+
+    report = kcl.check("x = (\ny = 2")
+    assert len(report.parse_errors) == 1
+    assert report.format_diff == ""
+    assert report.lint_findings == []
+    assert not report.ok
+
+
+@pytest.mark.asyncio
+async def test_kcl_execute_and_check_geometry():
+    code = """cube = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([4, 0], %, $front)
+  |> line([0, 4], %)
+  |> line([-4, 0], %)
+  |> close(%)
+  |> extrude(4, %)
+"""
+    report = await kcl.execute_and_check_geometry(code, kcl.UnitLength.Mm)
+    assert report.passed
+
+    [cube] = report.solids
+    assert cube.name == "cube"
+    assert cube.passed
+    assert [check.name for check in cube.checks] == ["watertight", "degenerate_faces", "volume", "self_intersection"]
+    for check in cube.checks:
+        assert check.status in ("passed", "skipped")
+        assert (check.status == "skipped") == (check.reason is not None)
+    assert cube.check("volume").status == "passed"
+    assert cube.check("self_intersection").status == "skipped"
+    assert cube.check("missing") is None
+
+    [copied] = pickle.loads(pickle.dumps(report)).solids
+    assert copied.id == cube.id
+    assert [check.status for check in copied.checks] == [check.status for check in cube.checks]